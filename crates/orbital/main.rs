@@ -27,7 +27,7 @@ pub use self::window::Window;
 
 use self::bmp::BmpFile;
 use self::config::Config;
-use self::event::{EVENT_KEY, EVENT_MOUSE, QuitEvent};
+use self::event::{EVENT_KEY, EVENT_MOUSE, MOUSE_BUTTONS, QuitEvent};
 
 pub mod bmp;
 pub mod color;
@@ -205,7 +205,7 @@ impl OrbitalScheme {
             }
 
             if self.dragging {
-                if event.c > 0 {
+                if event.c & MOUSE_BUTTONS != 0 {
                     if let Some(id) = self.order.front() {
                         if let Some(mut window) = self.windows.get_mut(&id) {
                             if self.drag_x != self.cursor_x || self.drag_y != self.cursor_y {
@@ -237,12 +237,12 @@ impl OrbitalScheme {
                             window_event.a -= window.x as i64;
                             window_event.b -= window.y as i64;
                             window.event(window_event);
-                            if event.c > 0 {
+                            if event.c & MOUSE_BUTTONS != 0 {
                                 focus = i;
                             }
                             break;
                         } else if window.title_rect().contains(event.a as i32, event.b as i32) {
-                            if event.c > 0 {
+                            if event.c & MOUSE_BUTTONS != 0 {
                                 focus = i;
                                 if window.exit_contains(event.a as i32, event.b as i32) {
                                     window.event(QuitEvent.to_event());
@@ -1,5 +1,5 @@
 use std::cell::UnsafeCell;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::{Read, Write, Result, Seek, SeekFrom};
 use std::mem;
 use std::path::PathBuf;
@@ -15,7 +15,7 @@ unsafe impl Sync for Socket {}
 
 impl Socket {
     pub fn open(path: &str) -> Result<Socket> {
-        let file = try!(File::open(path));
+        let file = try!(OpenOptions::new().read(true).write(true).open(path));
         Ok(Socket {
             file: UnsafeCell::new(file)
         })
@@ -3,7 +3,8 @@
 #![feature(slice_concat_ext)]
 
 use std::env;
-use std::io::{Write, stdin, stdout};
+use std::io::{Read, Write, stdin, stdout};
+use std::net::TcpStream;
 use std::rand;
 use std::process;
 use std::ptr;
@@ -70,8 +71,29 @@ fn command(args: Vec<String>){
             unsafe {
                 asm!("int 3" : : : : "intel", "volatile");
             }
+        } else if command == "tcp" {
+            // Send a line to a TCP server and print its answer, checking that streams are writable
+            match args.get(1).map(|addr| TcpStream::connect(addr.as_str())) {
+                Some(Ok(mut stream)) => {
+                    let line = args[2..].join(" ") + "\n";
+                    match stream.write(line.as_bytes()) {
+                        Ok(count) if count == line.len() => {
+                            println!("Sent {} bytes", count);
+                            let mut buf = [0; 4096];
+                            match stream.read(&mut buf) {
+                                Ok(count) => println!("Received {} bytes: {}", count, String::from_utf8_lossy(&buf[..count])),
+                                Err(err) => println!("Failed to receive: {}", err),
+                            }
+                        },
+                        Ok(count) => println!("Sent {} of {} bytes", count, line.len()),
+                        Err(err) => println!("Failed to send: {}", err),
+                    }
+                },
+                Some(Err(err)) => println!("Failed to connect: {}", err),
+                None => println!("Usage: tcp address:port [message]"),
+            }
         } else {
-            println!("Commands: exit panic ptr_write reboot halt clone leak_test int3");
+            println!("Commands: exit panic ptr_write reboot halt clone leak_test int3 tcp");
         }
     }
 }
@@ -40,6 +40,7 @@ pub trait Scheme {
             SYS_RMDIR => self.rmdir(c_string_to_str(packet.b as *const u8)),
             SYS_STAT => self.stat(c_string_to_str(packet.b as *const u8), unsafe { &mut *(packet.c as *mut Stat) }),
             SYS_UNLINK => self.unlink(c_string_to_str(packet.b as *const u8)),
+            SYS_UTIMENS => self.utimens(c_string_to_str(packet.b as *const u8), unsafe { &*(packet.c as *const [TimeSpec; 2]) }),
 
             SYS_READ => self.read(packet.b, unsafe { slice::from_raw_parts_mut(packet.c as *mut u8, packet.d) }),
             SYS_WRITE => self.write(packet.b, unsafe { slice::from_raw_parts(packet.c as *const u8, packet.d) }),
@@ -48,6 +49,7 @@ pub trait Scheme {
             SYS_FSTAT => self.fstat(packet.b, unsafe { &mut *(packet.c as *mut Stat) }),
             SYS_FSYNC => self.fsync(packet.b),
             SYS_FTRUNCATE => self.ftruncate(packet.b, packet.c),
+            SYS_FUTIMENS => self.futimens(packet.b, unsafe { &*(packet.c as *const [TimeSpec; 2]) }),
             SYS_CLOSE => self.close(packet.b),
 
             _ => Err(Error::new(ENOSYS))
@@ -81,6 +83,11 @@ pub trait Scheme {
         Err(Error::new(ENOENT))
     }
 
+    #[allow(unused_variables)]
+    fn utimens(&mut self, path: &str, times: &[TimeSpec; 2]) -> Result<usize> {
+        Err(Error::new(ENOENT))
+    }
+
     /* Resource operations */
     #[allow(unused_variables)]
     fn read(&mut self, id: usize, buf: &mut [u8]) -> Result<usize> {
@@ -117,6 +124,11 @@ pub trait Scheme {
         Err(Error::new(EBADF))
     }
 
+    #[allow(unused_variables)]
+    fn futimens(&mut self, id: usize, times: &[TimeSpec; 2]) -> Result<usize> {
+        Err(Error::new(EBADF))
+    }
+
     #[allow(unused_variables)]
     fn close(&mut self, id: usize) -> Result<usize> {
         Err(Error::new(EBADF))
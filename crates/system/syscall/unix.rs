@@ -1,6 +1,11 @@
-use syscall::arch::{syscall0, syscall1, syscall2, syscall3};
+use syscall::arch::{syscall0, syscall1, syscall2, syscall3, syscall4, syscall5};
 use error::Result;
 
+pub const SYS_ACCESS: usize = 33;
+    pub const F_OK: usize = 0;
+    pub const X_OK: usize = 1;
+    pub const W_OK: usize = 2;
+    pub const R_OK: usize = 4;
 pub const SYS_BRK: usize = 45;
 pub const SYS_CHDIR: usize = 12;
 pub const SYS_CLONE: usize = 120;
@@ -19,28 +24,67 @@ pub const SYS_CLONE: usize = 120;
     pub const CLONE_SUPERVISE: usize = 0x400000;
 pub const SYS_CLOSE: usize = 6;
 pub const SYS_CLOCK_GETTIME: usize = 265;
+pub const SYS_CLOCK_GETRES: usize = 266;
     pub const CLOCK_REALTIME: usize = 1;
     pub const CLOCK_MONOTONIC: usize = 4;
 pub const SYS_DUP: usize = 41;
+pub const SYS_DUP2: usize = 63;
 pub const SYS_EXECVE: usize = 11;
 pub const SYS_EXIT: usize = 1;
+pub const SYS_FCNTL: usize = 55;
+    pub const F_DUPFD: usize = 0;
+    pub const F_GETFD: usize = 1;
+    pub const F_SETFD: usize = 2;
+    pub const F_GETFL: usize = 3;
+    pub const F_SETFL: usize = 4;
+    pub const FD_CLOEXEC: usize = 1;
+pub const SYS_FLOCK: usize = 143;
+    pub const LOCK_SH: usize = 1;
+    pub const LOCK_EX: usize = 2;
+    pub const LOCK_NB: usize = 4;
+    pub const LOCK_UN: usize = 8;
+pub const SYS_FORK: usize = 2;
 pub const SYS_FPATH: usize = 928;
 pub const SYS_FSTAT: usize = 28;
+pub const SYS_FSTATVFS: usize = 100;
 pub const SYS_FSYNC: usize = 118;
 pub const SYS_FTRUNCATE: usize = 93;
+pub const SYS_FUTIMENS: usize = 321;
+pub const SYS_GETCWD: usize = 183;
+pub const SYS_GETDENTS: usize = 141;
 pub const SYS_GETPID: usize = 20;
+pub const SYS_GETPPID: usize = 64;
 pub const SYS_IOPL: usize = 110;
+pub const SYS_KILL: usize = 37;
+    pub const SIGINT: usize = 2;
+    pub const SIGKILL: usize = 9;
+    pub const SIGSEGV: usize = 11;
+    pub const SIGTERM: usize = 15;
+    pub const SIGCHLD: usize = 17;
+    pub const NSIG: usize = 32;
 pub const SYS_LINK: usize = 9;
 pub const SYS_LSEEK: usize = 19;
     pub const SEEK_SET: usize = 0;
     pub const SEEK_CUR: usize = 1;
     pub const SEEK_END: usize = 2;
+pub const SYS_LSTAT: usize = 107;
 pub const SYS_MKDIR: usize = 39;
+pub const SYS_MMAP: usize = 90;
+    pub const PROT_NONE: usize = 0;
+    pub const PROT_READ: usize = 1;
+    pub const PROT_WRITE: usize = 2;
+    pub const PROT_EXEC: usize = 4;
+    pub const MAP_SHARED: usize = 1;
+    pub const MAP_PRIVATE: usize = 2;
+    pub const MAP_FIXED: usize = 0x10;
+    pub const MAP_ANONYMOUS: usize = 0x20;
+pub const SYS_MUNMAP: usize = 91;
 pub const SYS_NANOSLEEP: usize = 162;
 pub const SYS_OPEN: usize = 5;
     pub const O_RDONLY: usize = 0;
     pub const O_WRONLY: usize = 1;
     pub const O_RDWR: usize = 2;
+    pub const O_ACCMODE: usize = 3;
     pub const O_NONBLOCK: usize = 4;
     pub const O_APPEND: usize = 8;
     pub const O_SHLOCK: usize = 0x10;
@@ -50,22 +94,101 @@ pub const SYS_OPEN: usize = 5;
     pub const O_CREAT: usize = 0x200;
     pub const O_TRUNC: usize = 0x400;
     pub const O_EXCL: usize = 0x800;
+    pub const O_CLOEXEC: usize = 0x1000;
+    pub const O_DIRECTORY: usize = 0x100000;
 pub const SYS_PIPE2: usize = 331;
+pub const SYS_POLL: usize = 168;
+    pub const POLLIN: usize = 0x1;
+    pub const POLLOUT: usize = 0x4;
+    pub const POLLERR: usize = 0x8;
+    pub const POLLHUP: usize = 0x10;
+    pub const POLLNVAL: usize = 0x20;
+pub const SYS_PREAD: usize = 180;
+pub const SYS_PWRITE: usize = 181;
 pub const SYS_READ: usize = 3;
+pub const SYS_READLINK: usize = 85;
+pub const SYS_READV: usize = 145;
+    pub const IOV_MAX: usize = 1024;
+pub const SYS_RENAME: usize = 38;
 pub const SYS_RMDIR: usize = 84;
+pub const SYS_SENDFILE: usize = 187;
+pub const SYS_SIGNAL: usize = 48;
+    pub const SIG_DFL: usize = 0;
+    pub const SIG_IGN: usize = 1;
+pub const SYS_SETPRIORITY: usize = 97;
+pub const SYS_SIGRETURN: usize = 119;
 pub const SYS_STAT: usize = 18;
     pub const MODE_DIR: u16 = 0x4000;
     pub const MODE_FILE: u16 = 0x8000;
+    pub const MODE_SYMLINK: u16 = 0xA000;
+    pub const MODE_TYPE: u16 = 0xF000;
+    pub const MODE_PERM: u16 = 0x1FF;
+pub const SYS_STATVFS: usize = 99;
+pub const SYS_SYMLINK: usize = 83;
+pub const SYS_TIMES: usize = 43;
+pub const SYS_TRUNCATE: usize = 92;
 pub const SYS_UNLINK: usize = 10;
+pub const SYS_UTIMENS: usize = 320;
 pub const SYS_WAITPID: usize = 7;
+    pub const WNOHANG: usize = 1;
 pub const SYS_WRITE: usize = 4;
+pub const SYS_WRITEV: usize = 146;
 pub const SYS_YIELD: usize = 158;
 
 #[derive(Copy, Clone, Debug, Default)]
 #[repr(packed)]
 pub struct Stat {
     pub st_mode: u16,
-    pub st_size: u64
+    pub st_size: u64,
+    pub st_atime: TimeSpec,
+    pub st_mtime: TimeSpec,
+    pub st_ctime: TimeSpec,
+}
+
+/// Filesystem statistics, as returned by statvfs
+///
+/// Sizes are counted in blocks of `f_bsize` bytes. Filesystems without inodes count their files
+/// instead.
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(packed)]
+pub struct StatVfs {
+    pub f_bsize: u64,
+    pub f_blocks: u64,
+    pub f_bfree: u64,
+    pub f_files: u64,
+    pub f_ffree: u64,
+}
+
+/// A directory entry, as returned by getdents
+///
+/// Each entry is followed by `d_namlen` bytes of name and a NUL terminator. `d_reclen` is the
+/// total length of the record, including this header.
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(packed)]
+pub struct Dirent {
+    pub d_ino: u64,
+    pub d_reclen: u16,
+    pub d_namlen: u16,
+}
+
+/// A file descriptor to poll, and the events it is polled for
+///
+/// `revents` is set by poll to the events that are ready. POLLERR, POLLHUP and POLLNVAL are always
+/// reported, even if they are not requested in `events`.
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(packed)]
+pub struct PollFd {
+    pub fd: usize,
+    pub events: usize,
+    pub revents: usize,
+}
+
+/// A buffer for readv and writev
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(packed)]
+pub struct IoVec {
+    pub iov_base: usize,
+    pub iov_len: usize,
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -75,6 +198,20 @@ pub struct TimeSpec {
     pub tv_nsec: i32,
 }
 
+/// CPU time used by a process and its waited for children, as returned by times
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(packed)]
+pub struct Tms {
+    pub tms_utime: TimeSpec,
+    pub tms_stime: TimeSpec,
+    pub tms_cutime: TimeSpec,
+    pub tms_cstime: TimeSpec,
+}
+
+pub unsafe fn sys_access(path: *const u8, mode: usize) -> Result<usize> {
+    syscall2(SYS_ACCESS, path as usize, mode)
+}
+
 pub unsafe fn sys_brk(addr: usize) -> Result<usize> {
     syscall1(SYS_BRK, addr)
 }
@@ -95,10 +232,18 @@ pub fn sys_clock_gettime(clock: usize, tp: &mut TimeSpec) -> Result<usize> {
     unsafe { syscall2(SYS_CLOCK_GETTIME, clock, tp as *mut TimeSpec as usize) }
 }
 
+pub fn sys_clock_getres(clock: usize, res: &mut TimeSpec) -> Result<usize> {
+    unsafe { syscall2(SYS_CLOCK_GETRES, clock, res as *mut TimeSpec as usize) }
+}
+
 pub fn sys_dup(fd: usize) -> Result<usize> {
     unsafe { syscall1(SYS_DUP, fd) }
 }
 
+pub fn sys_dup2(fd: usize, new_fd: usize) -> Result<usize> {
+    unsafe { syscall2(SYS_DUP2, fd, new_fd) }
+}
+
 pub unsafe fn sys_execve(path: *const u8, args: *const *const u8) -> Result<usize> {
     syscall2(SYS_EXECVE, path as usize, args as usize)
 }
@@ -107,6 +252,18 @@ pub fn sys_exit(status: usize) -> Result<usize> {
     unsafe { syscall1(SYS_EXIT, status) }
 }
 
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> Result<usize> {
+    unsafe { syscall3(SYS_FCNTL, fd, cmd, arg) }
+}
+
+pub fn sys_flock(fd: usize, operation: usize) -> Result<usize> {
+    unsafe { syscall2(SYS_FLOCK, fd, operation) }
+}
+
+pub unsafe fn sys_fork() -> Result<usize> {
+    syscall0(SYS_FORK)
+}
+
 pub fn sys_fpath(fd: usize, buf: &mut [u8]) -> Result<usize> {
     unsafe { syscall3(SYS_FPATH, fd, buf.as_mut_ptr() as usize, buf.len()) }
 }
@@ -115,6 +272,10 @@ pub fn sys_fstat(fd: usize, stat: &mut Stat) -> Result<usize> {
     unsafe { syscall2(SYS_FSTAT, fd, stat as *mut Stat as usize) }
 }
 
+pub fn sys_fstatvfs(fd: usize, stat: &mut StatVfs) -> Result<usize> {
+    unsafe { syscall2(SYS_FSTATVFS, fd, stat as *mut StatVfs as usize) }
+}
+
 pub fn sys_fsync(fd: usize) -> Result<usize> {
     unsafe { syscall1(SYS_FSYNC, fd) }
 }
@@ -123,14 +284,34 @@ pub fn sys_ftruncate(fd: usize, len: usize) -> Result<usize> {
     unsafe { syscall2(SYS_FTRUNCATE, fd, len) }
 }
 
+pub fn sys_futimens(fd: usize, times: &[TimeSpec; 2]) -> Result<usize> {
+    unsafe { syscall2(SYS_FUTIMENS, fd, times.as_ptr() as usize) }
+}
+
+pub fn sys_getcwd(buf: &mut [u8]) -> Result<usize> {
+    unsafe { syscall2(SYS_GETCWD, buf.as_mut_ptr() as usize, buf.len()) }
+}
+
+pub fn sys_getdents(fd: usize, buf: &mut [u8]) -> Result<usize> {
+    unsafe { syscall3(SYS_GETDENTS, fd, buf.as_mut_ptr() as usize, buf.len()) }
+}
+
 pub fn sys_getpid() -> Result<usize> {
     unsafe { syscall0(SYS_GETPID) }
 }
 
+pub fn sys_getppid() -> Result<usize> {
+    unsafe { syscall0(SYS_GETPPID) }
+}
+
 pub unsafe fn sys_iopl(level: usize) -> Result<usize> {
     syscall1(SYS_IOPL, level)
 }
 
+pub fn sys_kill(pid: usize, sig: usize) -> Result<usize> {
+    unsafe { syscall2(SYS_KILL, pid, sig) }
+}
+
 pub unsafe fn sys_link(old: *const u8, new: *const u8) -> Result<usize> {
     syscall2(SYS_LINK, old as usize, new as usize)
 }
@@ -139,10 +320,23 @@ pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> Result<usize> {
     unsafe { syscall3(SYS_LSEEK, fd, offset as usize, whence) }
 }
 
+pub unsafe fn sys_lstat(path: *const u8, stat: &mut Stat) -> Result<usize> {
+    syscall2(SYS_LSTAT, path as usize, stat as *mut Stat as usize)
+}
+
 pub unsafe fn sys_mkdir(path: *const u8, mode: usize) -> Result<usize> {
     syscall2(SYS_MKDIR, path as usize, mode)
 }
 
+/// Map memory. There is no offset argument, as only anonymous mappings are supported
+pub unsafe fn sys_mmap(addr: usize, len: usize, prot: usize, flags: usize, fd: usize) -> Result<usize> {
+    syscall5(SYS_MMAP, addr, len, prot, flags, fd)
+}
+
+pub unsafe fn sys_munmap(addr: usize, len: usize) -> Result<usize> {
+    syscall2(SYS_MUNMAP, addr, len)
+}
+
 pub fn sys_nanosleep(req: &TimeSpec, rem: &mut TimeSpec) -> Result<usize> {
     unsafe { syscall2(SYS_NANOSLEEP, req as *const TimeSpec as usize, rem as *mut TimeSpec as usize) }
 }
@@ -155,22 +349,87 @@ pub unsafe fn sys_pipe2(fds: *mut usize, flags: usize) -> Result<usize> {
     syscall2(SYS_PIPE2, fds as usize, flags)
 }
 
+pub fn sys_poll(fds: &mut [PollFd], timeout: isize) -> Result<usize> {
+    unsafe { syscall3(SYS_POLL, fds.as_mut_ptr() as usize, fds.len(), timeout as usize) }
+}
+
+pub fn sys_pread(fd: usize, buf: &mut [u8], offset: usize) -> Result<usize> {
+    unsafe { syscall4(SYS_PREAD, fd, buf.as_mut_ptr() as usize, buf.len(), offset) }
+}
+
+pub fn sys_pwrite(fd: usize, buf: &[u8], offset: usize) -> Result<usize> {
+    unsafe { syscall4(SYS_PWRITE, fd, buf.as_ptr() as usize, buf.len(), offset) }
+}
+
 pub fn sys_read(fd: usize, buf: &mut [u8]) -> Result<usize> {
     unsafe { syscall3(SYS_READ, fd, buf.as_mut_ptr() as usize, buf.len()) }
 }
 
+pub unsafe fn sys_readlink(path: *const u8, buf: &mut [u8]) -> Result<usize> {
+    syscall3(SYS_READLINK, path as usize, buf.as_mut_ptr() as usize, buf.len())
+}
+
+pub unsafe fn sys_readv(fd: usize, iov: &[IoVec]) -> Result<usize> {
+    syscall3(SYS_READV, fd, iov.as_ptr() as usize, iov.len())
+}
+
+pub unsafe fn sys_rename(old: *const u8, new: *const u8) -> Result<usize> {
+    syscall2(SYS_RENAME, old as usize, new as usize)
+}
+
 pub unsafe fn sys_rmdir(path: *const u8) -> Result<usize> {
     syscall1(SYS_RMDIR, path as usize)
 }
 
+pub fn sys_sendfile(out_fd: usize, in_fd: usize, offset: Option<&mut usize>, count: usize) -> Result<usize> {
+    let offset_ptr = match offset {
+        Some(offset) => offset as *mut usize as usize,
+        None => 0,
+    };
+    unsafe { syscall4(SYS_SENDFILE, out_fd, in_fd, offset_ptr, count) }
+}
+
+/// Set the handler of a signal to SIG_DFL, SIG_IGN, or a function called with the signal number.
+/// The handler returns to restorer, which has to call sys_sigreturn. Returns the previous handler
+pub unsafe fn sys_signal(sig: usize, handler: usize, restorer: usize) -> Result<usize> {
+    syscall3(SYS_SIGNAL, sig, handler, restorer)
+}
+
+/// Return from a signal handler, restoring the registers of the interrupted code
+pub unsafe fn sys_sigreturn() -> Result<usize> {
+    syscall0(SYS_SIGRETURN)
+}
+
 pub unsafe fn sys_stat(path: *const u8, stat: &mut Stat) -> Result<usize> {
     syscall2(SYS_STAT, path as usize, stat as *mut Stat as usize)
 }
 
+pub unsafe fn sys_statvfs(path: *const u8, stat: &mut StatVfs) -> Result<usize> {
+    syscall2(SYS_STATVFS, path as usize, stat as *mut StatVfs as usize)
+}
+
+pub unsafe fn sys_symlink(target: *const u8, path: *const u8) -> Result<usize> {
+    syscall2(SYS_SYMLINK, target as usize, path as usize)
+}
+
+pub unsafe fn sys_truncate(path: *const u8, len: usize) -> Result<usize> {
+    syscall2(SYS_TRUNCATE, path as usize, len)
+}
+
+pub fn sys_times(buf: &mut Tms) -> Result<usize> {
+    unsafe { syscall1(SYS_TIMES, buf as *mut Tms as usize) }
+}
+
 pub unsafe fn sys_unlink(path: *const u8) -> Result<usize> {
     syscall1(SYS_UNLINK, path as usize)
 }
 
+pub unsafe fn sys_utimens(path: *const u8, times: &[TimeSpec; 2]) -> Result<usize> {
+    syscall2(SYS_UTIMENS, path as usize, times.as_ptr() as usize)
+}
+
+/// Wait for a child to exit. The status holds the exit code in its second byte, or the number of
+/// the signal that terminated the child in its first byte
 pub fn sys_waitpid(pid: usize, status: &mut usize, options: usize) -> Result<usize> {
     unsafe { syscall3(SYS_WAITPID, pid, status as *mut usize as usize, options) }
 }
@@ -179,6 +438,14 @@ pub fn sys_write(fd: usize, buf: &[u8]) -> Result<usize> {
     unsafe { syscall3(SYS_WRITE, fd, buf.as_ptr() as usize, buf.len()) }
 }
 
+pub unsafe fn sys_writev(fd: usize, iov: &[IoVec]) -> Result<usize> {
+    syscall3(SYS_WRITEV, fd, iov.as_ptr() as usize, iov.len())
+}
+
+pub fn sys_setpriority(pid: usize, priority: isize) -> Result<usize> {
+    unsafe { syscall2(SYS_SETPRIORITY, pid, priority as usize) }
+}
+
 pub fn sys_yield() -> Result<usize> {
     unsafe { syscall0(SYS_YIELD) }
 }
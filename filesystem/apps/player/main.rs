@@ -1,6 +1,6 @@
 extern crate orbclient;
 
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::env;
 use std::io::{Read, Write};
 
@@ -27,7 +27,7 @@ fn main() {
     let wav = WavFile::from_data(&vec);
 
     if !wav.data.is_empty() {
-        if let Ok(mut audio) = File::open("audio://") {
+        if let Ok(mut audio) = OpenOptions::new().write(true).open("audio://") {
             audio.write(&wav.data);
         }
     }
@@ -0,0 +1,53 @@
+use super::SDTHeader;
+
+use core::mem::size_of;
+use core::ptr;
+
+/// The address space of a generic address in system memory
+pub const ADDRESS_SPACE_MEMORY: u8 = 0;
+
+#[repr(packed)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GenericAddress {
+    pub address_space: u8,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+#[repr(packed)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HpetData {
+    pub hardware_rev_id: u8,
+    pub comparator_info: u8,
+    pub pci_vendor_id: u16,
+    pub base_address: GenericAddress,
+    pub hpet_number: u8,
+    pub minimum_tick: u16,
+    pub page_protection: u8,
+}
+
+/// The High Precision Event Timer description table
+#[repr(packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct HPET {
+    pub header: &'static SDTHeader,
+    pub data: HpetData,
+}
+
+impl HPET {
+    pub fn new(header: &'static SDTHeader) -> Option<Self> {
+        if header.valid("HPET") {
+            let data: &'static [u8] = header.data();
+            if data.len() >= size_of::<HpetData>() {
+                return Some(HPET {
+                    header: header,
+                    data: unsafe { ptr::read(data.as_ptr() as *const HpetData) },
+                });
+            }
+        }
+
+        None
+    }
+}
@@ -4,6 +4,7 @@ use system::error::{Error, Result, ENOENT};
 use system::syscall::O_CREAT;
 pub use self::dsdt::DSDT;
 pub use self::fadt::FADT;
+pub use self::hpet::HPET;
 pub use self::madt::MADT;
 pub use self::rsdt::RSDT;
 pub use self::sdt::SDTHeader;
@@ -12,6 +13,7 @@ pub use self::ssdt::SSDT;
 pub mod aml;
 pub mod dsdt;
 pub mod fadt;
+pub mod hpet;
 pub mod madt;
 pub mod rsdt;
 pub mod sdt;
@@ -24,6 +26,7 @@ pub struct Acpi {
     dsdt: Option<DSDT>,
     ssdt: Option<SSDT>,
     madt: Option<MADT>,
+    hpet: Option<HPET>,
 }
 
 impl Acpi {
@@ -38,6 +41,7 @@ impl Acpi {
                     dsdt: None,
                     ssdt: None,
                     madt: None,
+                    hpet: None,
                 };
 
                 for addr in acpi.rsdt.addrs.iter() {
@@ -58,6 +62,8 @@ impl Acpi {
                         acpi.ssdt = Some(ssdt);
                     } else if let Some(madt) = MADT::new(header) {
                         acpi.madt = Some(madt);
+                    } else if let Some(hpet) = HPET::new(header) {
+                        acpi.hpet = Some(hpet);
                     } else {
                         for b in header.signature.iter() {
                             debug!("{}", *b as char);
@@ -74,6 +80,11 @@ impl Acpi {
             }
         }
     }
+
+    /// The HPET description, if the firmware reports one
+    pub fn hpet(&self) -> Option<HPET> {
+        self.hpet
+    }
 }
 
 impl KScheme for Acpi {
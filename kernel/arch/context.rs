@@ -1,29 +1,31 @@
-use common::slice::GetSlice;
-
 use alloc::arc::Arc;
 use alloc::boxed::{Box, FnBox};
 
+use arch::intex::intex_pid;
 use arch::memory;
 use arch::paging::Page;
 use arch::regs::Regs;
 
+use collections::BTreeMap;
 use collections::string::{String, ToString};
 use collections::vec::Vec;
 
+use common::parse_path;
 use common::time::Duration;
 
 use core::cell::UnsafeCell;
 use core::slice::{Iter, IterMut};
-use core::{mem, ptr};
+use core::{cmp, mem, ptr};
 use core::ops::DerefMut;
 
 use fs::Resource;
 
-use syscall::{do_sys_exit, CLONE_FILES, CLONE_FS, CLONE_VM, CLONE_VFORK, CLONE_SUPERVISE};
+use syscall::{do_sys_exit, CLONE_FILES, CLONE_FS, CLONE_VM, CLONE_VFORK, CLONE_SUPERVISE,
+              O_ACCMODE, O_WRONLY};
 
 use system::error::{Error, Result, EBADF, EFAULT, ENOMEM, ESRCH, ENOENT, EINVAL};
 
-use sync::WaitMap;
+use sync::WaitCondition;
 
 pub const CONTEXT_IMAGE_ADDR: usize = 0x8048000;
 pub const CONTEXT_IMAGE_SIZE: usize = 0x10000000;
@@ -34,14 +36,24 @@ pub const CONTEXT_HEAP_SIZE: usize = 0x40000000;
 pub const CONTEXT_MMAP_ADDR: usize = CONTEXT_HEAP_ADDR + CONTEXT_HEAP_SIZE + memory::CLUSTER_SIZE;
 pub const CONTEXT_MMAP_SIZE: usize = 0x20000000;
 
-pub const CONTEXT_STACK_ADDR: usize = CONTEXT_MMAP_ADDR + CONTEXT_MMAP_SIZE + memory::CLUSTER_SIZE;
+pub const CONTEXT_STACK_ADDR: usize = CONTEXT_MMAP_ADDR + CONTEXT_MMAP_SIZE + CONTEXT_STACK_GUARD;
 pub const CONTEXT_STACK_SIZE: usize = 0x100000;
 
+/// The size of the unmapped guard page below kernel and userspace stacks, so that an overflow
+/// faults instead of corrupting the memory below
+pub const CONTEXT_STACK_GUARD: usize = memory::CLUSTER_SIZE;
+
+/// The pid of the root context, the parent of kernel contexts and orphans. It never waits for
+/// them, so they are removed as soon as they exit
+pub const CONTEXT_ROOT_PID: usize = 1;
+
 pub struct ContextManager {
     pub inner: Vec<Box<Context>>,
     pub enabled: bool,
     pub i: usize,
     pub next_pid: usize,
+    /// The scheduled time of the last context chosen to run
+    pub sched_floor: usize,
 }
 
 impl ContextManager {
@@ -51,6 +63,7 @@ impl ContextManager {
             enabled: false,
             i: 0,
             next_pid: 1,
+            sched_floor: 0,
         }
     }
 
@@ -107,6 +120,34 @@ impl ContextManager {
         self.inner.push(context);
     }
 
+    /// Remove a context, keeping the current index on the current context
+    pub unsafe fn remove(&mut self, pid: usize) -> Option<Box<Context>> {
+        match self.inner.iter().position(|context| context.pid == pid) {
+            Some(i) => {
+                if i < self.i {
+                    self.i -= 1;
+                }
+                Some(self.inner.remove(i))
+            },
+            None => None,
+        }
+    }
+
+    /// Unblock the sleeping contexts that are due to wake up
+    pub fn wake(&mut self, now: Duration) {
+        for mut context in self.inner.iter_mut() {
+            if context.blocked {
+                if let Some(wake) = context.wake {
+                    if wake <= now {
+                        context.blocked = false;
+                        context.wake = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remove the exited contexts at the current index that have no parent to wait for them
     pub unsafe fn clean(&mut self) {
         loop {
             if self.i >= self.len() {
@@ -115,7 +156,7 @@ impl ContextManager {
 
             let mut remove = false;
             if let Ok(next) = self.current() {
-                if next.exited {
+                if next.exited && (next.ppid == CONTEXT_ROOT_PID || self.find(next.ppid).is_err()) {
                     remove = true;
                 }
             }
@@ -134,6 +175,36 @@ impl ContextManager {
     }
 }
 
+/// The lowest priority of a context
+pub const PRIO_MIN: isize = -4;
+/// The highest priority of a context
+pub const PRIO_MAX: isize = 4;
+/// The priority of kernel services, like karp and kicmp
+pub const PRIO_KERNEL: isize = 2;
+
+/// The scheduled time a time slice costs at the default priority
+pub const SCHED_UNIT: usize = 16;
+/// The most time slices a context can get ahead of the others by being blocked or new
+pub const SCHED_LAG: usize = 10;
+
+/// The scheduled time a time slice costs at a priority. Each step up halves the cost, so that a
+/// context gets twice as many slices as one a step below it, and each step down doubles it.
+pub fn sched_cost(priority: isize) -> usize {
+    let priority = cmp::max(PRIO_MIN, cmp::min(PRIO_MAX, priority));
+    if priority >= 0 {
+        SCHED_UNIT >> priority as usize
+    } else {
+        SCHED_UNIT << (-priority) as usize
+    }
+}
+
+/// The scheduled time a context competes with, given the scheduled time of the last context
+/// chosen. A context that was blocked or is new only gets `SCHED_LAG` slices ahead, so that it
+/// does not run alone while it catches up.
+pub fn sched_time(sched_time: usize, floor: usize) -> usize {
+    cmp::max(sched_time, floor.saturating_sub(SCHED_LAG * SCHED_UNIT))
+}
+
 /// Switch context
 ///
 /// Unsafe due to interrupt disabling, raw pointers, and unsafe Context functions
@@ -145,20 +216,43 @@ pub unsafe fn context_switch() {
         let mut contexts = ::env().contexts.lock();
         if contexts.enabled {
             let current_i = contexts.i;
+            let floor = contexts.sched_floor;
+            let now = Duration::monotonic();
+
+            // Choose the runnable context with the least scheduled time, so that a busy context
+            // cannot starve the ones that often block, and a context with a higher priority,
+            // whose time slices cost less, runs for more of them. Ties go to the first in round
+            // robin order, and the idle context only runs if nothing else can
+            let mut best: Option<(usize, usize)> = None;
+            let mut idle = false;
             'searching: loop {
                 contexts.i += 1;
                 contexts.clean();
                 if let Ok(mut next) = contexts.current_mut() {
-                    if next.blocked {
+                    let mut runnable = false;
+                    if next.exited {
+                        // Zombies are never switched to
+                    } else if next.blocked {
                         if let Some(wake) = next.wake {
-                            if wake <= Duration::monotonic() {
+                            if wake <= now {
                                 next.blocked = false;
                                 next.wake = None;
-                                break 'searching;
+                                runnable = true;
                             }
                         }
                     } else {
-                        break 'searching;
+                        runnable = true;
+                    }
+
+                    if runnable {
+                        if next.pid == CONTEXT_ROOT_PID {
+                            idle = true;
+                        } else {
+                            next.sched_time = sched_time(next.sched_time, floor);
+                            if best.map_or(true, |(_, best_time)| next.sched_time < best_time) {
+                                best = Some((next.pid, next.sched_time));
+                            }
+                        }
                     }
                 }
                 if contexts.i == current_i {
@@ -166,6 +260,24 @@ pub unsafe fn context_switch() {
                 }
             }
 
+            // Contexts may have been removed while searching, so the chosen one is found again
+            let chosen = match best {
+                Some((pid, best_time)) => {
+                    contexts.sched_floor = cmp::max(floor, best_time);
+                    Some(pid)
+                },
+                None => if idle {
+                    Some(CONTEXT_ROOT_PID)
+                } else {
+                    None
+                },
+            };
+            if let Some(pid) = chosen {
+                if let Some(i) = contexts.inner.iter().position(|context| context.pid == pid) {
+                    contexts.i = i;
+                }
+            }
+
             if contexts.i != current_i {
                 if let Ok(mut current) = contexts.get_mut(current_i) {
                     current.unmap();
@@ -193,15 +305,33 @@ pub unsafe fn context_switch() {
     }
 
     if current_ptr as usize > 0 && next_ptr as usize > 0 {
+        intex_pid = (*next_ptr).pid;
         (*current_ptr).switch_to(&mut *next_ptr);
     }
 }
 
-pub unsafe fn context_clone(regs: &Regs) -> Result<usize> {
+/// Allocate a kernel stack, with room for the FX area above it and an unmapped guard page below it
+unsafe fn kernel_stack_alloc() -> usize {
+    let guard = memory::alloc_aligned(CONTEXT_STACK_GUARD + CONTEXT_STACK_SIZE + 512, 4096);
+    if guard > 0 {
+        Page::new(guard).unmap();
+        guard + CONTEXT_STACK_GUARD
+    } else {
+        0
+    }
+}
+
+/// Free a kernel stack allocated by `kernel_stack_alloc`, along with its guard page
+unsafe fn kernel_stack_unalloc(kernel_stack: usize) {
+    let guard = kernel_stack - CONTEXT_STACK_GUARD;
+    Page::new(guard).map_kernel_write(guard);
+    memory::unalloc(guard);
+}
+
+pub unsafe fn context_clone(regs: &Regs, flags: usize) -> Result<usize> {
     let mut contexts = ::env().contexts.lock();
-    let flags = regs.bx;
 
-    let kernel_stack = memory::alloc(CONTEXT_STACK_SIZE + 512);
+    let kernel_stack = kernel_stack_alloc();
     if kernel_stack > 0 {
         let clone_pid = Context::next_pid();
 
@@ -227,6 +357,23 @@ pub unsafe fn context_clone(regs: &Regs) -> Result<usize> {
             let fx = kernel_stack + CONTEXT_STACK_SIZE;
             ::memcpy(fx as *mut u8, parent.fx as *const u8, 512);
 
+            let (image, heap, mmap) = if flags & CLONE_VM == CLONE_VM {
+                //debugln!("{}: {}: clone memory for {}", parent.pid, parent.name, clone_pid);
+
+                (parent.image.clone(), parent.heap.clone(), parent.mmap.clone())
+            } else {
+                let zones = (Arc::new(UnsafeCell::new((*parent.image.get()).share())),
+                             Arc::new(UnsafeCell::new((*parent.heap.get()).share())),
+                             Arc::new(UnsafeCell::new((*parent.mmap.get()).share())));
+
+                // The memory of the parent is read only until it is written to, like the child
+                (*parent.image.get()).map();
+                (*parent.heap.get()).map();
+                (*parent.mmap.get()).map();
+
+                zones
+            };
+
             box Context {
                 pid: clone_pid,
                 ppid: parent.pid,
@@ -234,8 +381,14 @@ pub unsafe fn context_clone(regs: &Regs) -> Result<usize> {
                 iopl: parent.iopl,
                 blocked: false,
                 exited: false,
+                status: 0,
                 switch: 0,
                 time: 0,
+                sched_time: parent.sched_time,
+                priority: parent.priority,
+                user_time: 0,
+                child_time: 0,
+                child_user_time: 0,
                 vfork: if flags & CLONE_VFORK == CLONE_VFORK {
                     parent.blocked = true;
                     Some(parent.deref_mut())
@@ -243,6 +396,7 @@ pub unsafe fn context_clone(regs: &Regs) -> Result<usize> {
                     None
                 },
                 wake: None,
+                signals: 0,
 
                 supervised: flags & CLONE_SUPERVISE == CLONE_SUPERVISE,
                 blocked_syscall: false,
@@ -271,27 +425,9 @@ pub unsafe fn context_clone(regs: &Regs) -> Result<usize> {
                 },
                 loadable: parent.loadable,
 
-                image: if flags & CLONE_VM == CLONE_VM {
-                    //debugln!("{}: {}: clone memory for {}", parent.pid, parent.name, clone_pid);
-
-                    parent.image.clone()
-                } else {
-                    Arc::new(UnsafeCell::new((*parent.image.get()).dup()))
-                },
-                heap: if flags & CLONE_VM == CLONE_VM {
-                    //debugln!("{}: {}: clone memory for {}", parent.pid, parent.name, clone_pid);
-
-                    parent.heap.clone()
-                } else {
-                    Arc::new(UnsafeCell::new((*parent.heap.get()).dup()))
-                },
-                mmap: if flags & CLONE_VM == CLONE_VM {
-                    //debugln!("{}: {}: clone memory for {}", parent.pid, parent.name, clone_pid);
-
-                    parent.mmap.clone()
-                } else {
-                    Arc::new(UnsafeCell::new((*parent.mmap.get()).dup()))
-                },
+                image: image,
+                heap: heap,
+                mmap: mmap,
                 env_vars: if flags & CLONE_VM == CLONE_VM {  // is CLONE_VM the good flag ?
                     parent.env_vars.clone()
                 } else {
@@ -315,6 +451,8 @@ pub unsafe fn context_clone(regs: &Regs) -> Result<usize> {
 
                                 files.push(ContextFile {
                                     fd: file.fd,
+                                    flags: file.flags,
+                                    cloexec: file.cloexec,
                                     resource: resource,
                                 });
                             },
@@ -323,11 +461,31 @@ pub unsafe fn context_clone(regs: &Regs) -> Result<usize> {
                     }
                     Arc::new(UnsafeCell::new(files))
                 },
+                signal_handlers: if flags & CLONE_VM == CLONE_VM {
+                    parent.signal_handlers.clone()
+                } else {
+                    Arc::new(UnsafeCell::new((*parent.signal_handlers.get()).clone()))
+                },
 
-                statuses: WaitMap::new(),
+                child_exit: WaitCondition::new(),
+                signal_regs: if flags & CLONE_VM == CLONE_VM {
+                    Vec::new()
+                } else {
+                    parent.signal_regs.clone()
+                },
             }
         };
 
+        // A child process of the foreground context takes its place, until it exits
+        if flags & CLONE_VM != CLONE_VM {
+            let mut console = ::env().console.lock();
+            if let Ok(parent) = contexts.current() {
+                if console.foreground_pid == parent.pid {
+                    console.foreground_pid = clone_pid;
+                }
+            }
+        }
+
         contexts.push(context);
 
         if flags & CLONE_VFORK == CLONE_VFORK {
@@ -396,6 +554,11 @@ unsafe extern "cdecl" fn context_box(/*Throw away extra params from ABI*/ _rdi:
     do_sys_exit(0);
 }
 
+/// A mapping of physical memory into a context
+///
+/// Allocated memory can be shared with other contexts by fork, in which case it is counted in
+/// `shared_memory` of the environment, and freed when the last mapping of it is dropped. Shared
+/// memory is mapped read only, and copied by the page fault handler when it is written to.
 pub struct ContextMemory {
     pub physical_address: usize,
     pub virtual_address: usize,
@@ -405,9 +568,76 @@ pub struct ContextMemory {
 }
 
 impl ContextMemory {
+    /// Check if the memory is shared with another context
+    pub fn shared(&self) -> bool {
+        self.allocated && ::env().shared_memory.lock().contains_key(&self.physical_address)
+    }
+
+    /// Check if a buffer is inside of the mapping. A buffer that wraps around the end of the
+    /// address space is not
+    pub fn contains(&self, ptr: usize, len: usize) -> bool {
+        match (ptr.checked_add(len), self.virtual_address.checked_add(self.virtual_size)) {
+            (Some(end), Some(mem_end)) => ptr >= self.virtual_address && end <= mem_end,
+            _ => false,
+        }
+    }
+
+    /// Make a mapping of the same memory at the same address, sharing it until it is written to.
+    /// The memory has to be mapped again, so that it becomes read only
+    pub fn share(&self) -> ContextMemory {
+        if self.allocated {
+            *::env().shared_memory.lock().entry(self.physical_address).or_insert(1) += 1;
+        }
+
+        ContextMemory {
+            physical_address: self.physical_address,
+            virtual_address: self.virtual_address,
+            virtual_size: self.virtual_size,
+            writeable: self.writeable,
+            allocated: self.allocated,
+        }
+    }
+
+    /// Give the mapping its own copy of shared memory. Returns false if no memory is available for
+    /// the copy. The memory has to be mapped again, so that it becomes writeable
+    pub unsafe fn unshare(&mut self) -> bool {
+        if ! self.shared() {
+            return true;
+        }
+
+        let physical_address = memory::alloc_aligned(self.virtual_size, 4096);
+        if physical_address == 0 {
+            return false;
+        }
+        ::memcpy(physical_address as *mut u8, self.physical_address as *const u8, self.virtual_size);
+
+        ContextMemory::release(self.physical_address);
+        self.physical_address = physical_address;
+        true
+    }
+
+    /// Drop a mapping of shared memory. Returns false if the memory is not shared, and should be
+    /// freed by the caller
+    fn release(physical_address: usize) -> bool {
+        let mut shared_memory = ::env().shared_memory.lock();
+        let count = match shared_memory.get(&physical_address) {
+            Some(&count) => count,
+            None => return false,
+        };
+
+        // Once a single mapping is left, it owns the memory again
+        if count > 2 {
+            shared_memory.insert(physical_address, count - 1);
+        } else {
+            shared_memory.remove(&physical_address);
+        }
+        true
+    }
+
     pub unsafe fn map(&mut self) {
+        let writeable = self.writeable && ! self.shared();
         for i in 0..(self.virtual_size + 4095) / 4096 {
-            if self.writeable {
+            if writeable {
                 Page::new(self.virtual_address + i * 4096)
                     .map_user_write(self.physical_address + i * 4096);
             } else {
@@ -427,7 +657,7 @@ impl ContextMemory {
 
 impl Drop for ContextMemory {
     fn drop(&mut self) {
-        if self.allocated {
+        if self.allocated && ! ContextMemory::release(self.physical_address) {
             unsafe { memory::unalloc(self.physical_address) };
         }
     }
@@ -435,9 +665,29 @@ impl Drop for ContextMemory {
 
 pub struct ContextFile {
     pub fd: usize,
+    /// The file status flags, including the access mode
+    pub flags: usize,
+    /// Close this descriptor when executing
+    pub cloexec: bool,
     pub resource: Box<Resource>,
 }
 
+impl ContextFile {
+    /// Check if the access mode allows reading
+    pub fn readable(&self) -> bool {
+        self.flags & O_ACCMODE != O_WRONLY
+    }
+
+    /// Check if the access mode allows writing
+    ///
+    /// Descriptors opened read only are not refused yet, as programs built outside of this tree,
+    /// like redoxfsd writing to `disk:/0` and orbclient writing to `orbital:` windows, open files
+    /// they write to with `File::open`. Resources that cannot be written refuse writes themselves
+    pub fn writeable(&self) -> bool {
+        true
+    }
+}
+
 pub struct ContextZone {
     pub address: usize,
     pub size: usize,
@@ -453,37 +703,32 @@ impl ContextZone {
         }
     }
 
-    pub fn dup(&self) -> ContextZone {
-        let mut mem: Vec<ContextMemory> = Vec::new();
-        for entry in self.memory.iter() {
-            let physical_address = unsafe { memory::alloc(entry.virtual_size) };
-            if physical_address > 0 {
-                //TODO: Remap pages during memcpy
-                unsafe {
-                    ::memcpy(physical_address as *mut u8,
-                             entry.physical_address as *const u8,
-                             entry.virtual_size);
-                }
-
-                //debugln!("{}: {}: dup memory {:X}:{:X} for {}", parent.pid, parent.name, entry.virtual_address, entry.virtual_address + entry.virtual_size, clone_pid);
-
-                mem.push(ContextMemory {
-                    physical_address: physical_address,
-                    virtual_address: entry.virtual_address,
-                    virtual_size: entry.virtual_size,
-                    writeable: entry.writeable,
-                    allocated: true,
-                });
-            } else {
-                //debugln!("{}: {}: failed to dup memory {:X}:{:X} for {}", parent.pid, parent.name, entry.virtual_address, entry.virtual_address + entry.virtual_size, clone_pid);
-            }
-        }
-
+    /// Make a copy of the zone that shares its memory until it is written to. The zone has to be
+    /// mapped again, so that its memory becomes read only
+    pub fn share(&self) -> ContextZone {
         ContextZone {
             address: self.address,
             size: self.size,
-            memory: mem
+            memory: self.memory.iter().map(|entry| entry.share()).collect(),
+        }
+    }
+
+    /// Give the memory containing an address its own copy, if it is writeable and shared. Returns
+    /// Ok(false) if the address is not inside of such memory, and `ENOMEM` if no memory is
+    /// available for the copy. The zone must be mapped
+    pub unsafe fn unshare(&mut self, address: usize) -> Result<bool> {
+        for mem in self.memory.iter_mut() {
+            let end = mem.virtual_address + (mem.virtual_size + 4095) / 4096 * 4096;
+            if mem.writeable && address >= mem.virtual_address && address < end && mem.shared() {
+                if ! mem.unshare() {
+                    return Err(Error::new(ENOMEM));
+                }
+                mem.map();
+                return Ok(true);
+            }
         }
+
+        Ok(false)
     }
 
     pub fn size(&self) -> usize {
@@ -511,10 +756,97 @@ impl ContextZone {
         return next_mem;
     }
 
+    /// Find the lowest free, page aligned range of the given size inside of the zone
+    pub fn next_free(&self, size: usize) -> Option<usize> {
+        let size = (size + 4095) / 4096 * 4096;
+
+        let mut address = self.address;
+        'search: while address + size <= self.address + self.size {
+            for mem in self.memory.iter() {
+                let pages = (mem.virtual_size + 4095) / 4096;
+                let end = mem.virtual_address + pages * 4096;
+                if address < end && mem.virtual_address < address + size {
+                    address = end;
+                    continue 'search;
+                }
+            }
+
+            return Some(address);
+        }
+
+        None
+    }
+
+    /// Unmap and free the allocated pages inside of a page aligned range, splitting the memory
+    /// that is only partially covered. Returns the number of bytes that were unmapped
+    pub unsafe fn unmap_range(&mut self, address: usize, size: usize) -> usize {
+        let size = (size + 4095) / 4096 * 4096;
+
+        let mut unmapped = 0;
+        let mut memory = Vec::new();
+        for mut mem in self.memory.drain(..) {
+            let start = mem.virtual_address;
+            let end = start + (mem.virtual_size + 4095) / 4096 * 4096;
+            let low = cmp::max(address, start);
+            let high = cmp::min(address.saturating_add(size), end);
+            if ! mem.allocated || low >= high {
+                memory.push(mem);
+                continue;
+            }
+
+            // Shared memory is copied, so that the pieces can be split and freed
+            if mem.shared() {
+                if ! mem.unshare() {
+                    memory.push(mem);
+                    continue;
+                }
+                mem.map();
+            }
+
+            if low > start {
+                memory::split(mem.physical_address + low - start);
+            }
+            if high < end {
+                memory::split(mem.physical_address + high - start);
+            }
+
+            for i in 0..(high - low) / 4096 {
+                Page::new(low + i * 4096).map_kernel_write(low + i * 4096);
+            }
+            memory::unalloc(mem.physical_address + low - start);
+            unmapped += high - low;
+
+            if low > start {
+                memory.push(ContextMemory {
+                    physical_address: mem.physical_address,
+                    virtual_address: start,
+                    virtual_size: low - start,
+                    writeable: mem.writeable,
+                    allocated: true,
+                });
+            }
+            if high < end {
+                memory.push(ContextMemory {
+                    physical_address: mem.physical_address + high - start,
+                    virtual_address: high,
+                    virtual_size: end - high,
+                    writeable: mem.writeable,
+                    allocated: true,
+                });
+            }
+
+            // The pieces were freed or moved above
+            mem.allocated = false;
+        }
+        self.memory = memory;
+
+        unmapped
+    }
+
     /// Translate to physical if a ptr is inside of the mapped memory
     pub fn translate(&self, ptr: usize, len: usize) -> Option<usize> {
         for mem in self.memory.iter() {
-            if ptr >= mem.virtual_address && ptr + len <= mem.virtual_address + mem.virtual_size {
+            if mem.contains(ptr, len) {
                 return Some(ptr - mem.virtual_address + mem.physical_address);
             }
         }
@@ -522,6 +854,17 @@ impl ContextZone {
         None
     }
 
+    /// Check if a buffer is inside of writeable mapped memory
+    pub fn writeable(&self, ptr: usize, len: usize) -> bool {
+        for mem in self.memory.iter() {
+            if mem.contains(ptr, len) {
+                return mem.writeable;
+            }
+        }
+
+        false
+    }
+
     /// Get a memory map from a pointer
     pub fn get_mem<'a>(&'a self, ptr: usize) -> Result<&'a ContextMemory> {
         for mem in self.memory.iter() {
@@ -568,6 +911,15 @@ pub struct EnvironmentVariable {
     value: String
 }
 
+/// A signal handler registered with signal
+#[derive(Copy, Clone)]
+pub struct SignalHandler {
+    /// The function called with the signal number, or SIG_IGN
+    pub handler: usize,
+    /// The function the handler returns to, which calls sigreturn
+    pub restorer: usize,
+}
+
 pub struct Context {
     // These members are used for control purposes by the scheduler {
     /// The PID of the context
@@ -580,16 +932,31 @@ pub struct Context {
     pub iopl: usize,
     /// Indicates that the context is blocked, and should not be switched to
     pub blocked: bool,
-    /// Indicates that the context exited
+    /// Indicates that the context exited. It stays as a zombie until its parent waits for it
     pub exited: bool,
+    /// The wait status of the context, once it exited
+    pub status: usize,
     /// How many times was the context switched to
     pub switch: usize,
     /// The number of time slices used
     pub time: usize,
+    /// The time counted by the scheduler, which runs the runnable context with the least. Each
+    /// time slice adds `sched_cost` of the priority
+    pub sched_time: usize,
+    /// The priority, from `PRIO_MIN` to `PRIO_MAX`. Higher priorities get more time slices
+    pub priority: isize,
+    /// The number of time slices used in user mode
+    pub user_time: usize,
+    /// The time slices used by children that were waited for, and by their children
+    pub child_time: usize,
+    /// The time slices used in user mode by children that were waited for, and by their children
+    pub child_user_time: usize,
     /// Indicates that the context needs to unblock parent
     pub vfork: Option<*mut Context>,
     /// When to wake up
     pub wake: Option<Duration>,
+    /// Pending signals, a bit for each signal number
+    pub signals: usize,
     // }
 
     /// Is this process supervised?
@@ -629,10 +996,14 @@ pub struct Context {
     pub cwd: Arc<UnsafeCell<String>>,
     /// Program files, cloned for threads, copied or created for processes. Modified by file operations
     pub files: Arc<UnsafeCell<Vec<ContextFile>>>,
+    /// Signal handlers, cloned for threads, copied for processes. Reset by exec
+    pub signal_handlers: Arc<UnsafeCell<BTreeMap<usize, SignalHandler>>>,
     // }
 
-    /// Exit statuses of children
-    pub statuses: WaitMap<usize, usize>,
+    /// Notified when a child exits, waking the context if it is waiting for it
+    pub child_exit: WaitCondition,
+    /// Registers of the code interrupted by signal handlers, restored by sigreturn
+    pub signal_regs: Vec<Regs>,
 }
 
 impl Context {
@@ -669,16 +1040,23 @@ impl Context {
         let fx = memory::alloc(512);
 
         box Context {
-            pid: Context::next_pid(),
+            pid: CONTEXT_ROOT_PID,
             ppid: 0,
             name: "kidle".to_string(),
             iopl: 3,
             blocked: false,
             exited: false,
+            status: 0,
             switch: 0,
             time: 0,
+            sched_time: 0,
+            priority: 0,
+            user_time: 0,
+            child_time: 0,
+            child_user_time: 0,
             vfork: None,
             wake: None,
+            signals: 0,
 
             supervised: false,
             blocked_syscall: false,
@@ -696,13 +1074,15 @@ impl Context {
 
             cwd: Arc::new(UnsafeCell::new(String::new())),
             files: Arc::new(UnsafeCell::new(Vec::new())),
+            signal_handlers: Arc::new(UnsafeCell::new(BTreeMap::new())),
 
-            statuses: WaitMap::new(),
+            child_exit: WaitCondition::new(),
+            signal_regs: Vec::new(),
         }
     }
 
     pub unsafe fn new(name: String, call: usize, args: &Vec<usize>) -> Box<Self> {
-        let kernel_stack = memory::alloc(CONTEXT_STACK_SIZE + 512);
+        let kernel_stack = kernel_stack_alloc();
 
         let mut regs = Regs::default();
         regs.sp = kernel_stack + CONTEXT_STACK_SIZE - 128;
@@ -711,15 +1091,22 @@ impl Context {
 
         let mut ret = box Context {
             pid: Context::next_pid(),
-            ppid: 0,
+            ppid: CONTEXT_ROOT_PID,
             name: name,
             iopl: 3,
             blocked: false,
             exited: false,
+            status: 0,
             switch: 0,
             time: 0,
+            sched_time: 0,
+            priority: 0,
+            user_time: 0,
+            child_time: 0,
+            child_user_time: 0,
             vfork: None,
             wake: None,
+            signals: 0,
 
             supervised: false,
             blocked_syscall: false,
@@ -737,8 +1124,10 @@ impl Context {
 
             cwd: Arc::new(UnsafeCell::new(String::new())),
             files: Arc::new(UnsafeCell::new(Vec::new())),
+            signal_handlers: Arc::new(UnsafeCell::new(BTreeMap::new())),
 
-            statuses: WaitMap::new(),
+            child_exit: WaitCondition::new(),
+            signal_regs: Vec::new(),
         };
 
         for arg in args.iter() {
@@ -751,6 +1140,11 @@ impl Context {
     }
 
     pub fn spawn(name: String, box_fn: Box<FnBox()>) -> usize {
+        Context::spawn_priority(name, 0, box_fn)
+    }
+
+    /// Spawn a kernel context running `box_fn`, with a priority
+    pub fn spawn_priority(name: String, priority: isize, box_fn: Box<FnBox()>) -> usize {
         let ret;
 
         unsafe {
@@ -761,7 +1155,8 @@ impl Context {
             context_box_args.push(box_fn_ptr as usize);
             context_box_args.push(0); // Return address, 0 catches bad code
 
-            let context = Context::new(name, context_box as usize, &context_box_args);
+            let mut context = Context::new(name, context_box as usize, &context_box_args);
+            context.priority = priority;
 
             ret = context.pid;
 
@@ -771,36 +1166,19 @@ impl Context {
         ret
     }
 
+    /// Resolve a path against the working directory
     pub fn canonicalize(&self, path: &str) -> String {
-        if path.find(':').is_none() {
-            let cwd = unsafe { &*self.cwd.get() };
-            if path == "." {
-                cwd.to_string()
-            } else if path == ".." {
-                cwd.get_slice(..cwd.get_slice(..cwd.len() - 1)
-                                   .rfind('/')
-                                   .map_or(cwd.len(), |i| i + 1))
-                   .to_string()
-            } else if path.starts_with("./") {
-                cwd.to_string() + &path.get_slice(2..)
-            } else if path.starts_with("../") {
-                cwd.get_slice(..cwd.get_slice(..cwd.len() - 1)
-                                   .rfind('/')
-                                   .map_or(cwd.len(), |i| i + 1))
-                   .to_string() + &path.get_slice(3..)
-            } else if path.starts_with('/') {
-                cwd.get_slice(..cwd.find(':').map_or(1, |i| i + 1)).to_string() + &path
-            } else {
-                cwd.to_string() + &path
-            }
-        } else {
-            path.to_string()
-        }
+        parse_path::canonicalize(unsafe { &*self.cwd.get() }, path)
     }
 
     /// Get the next available file descriptor
     pub fn next_fd(&self) -> usize {
-        let mut next_fd = 0;
+        self.next_fd_from(0)
+    }
+
+    /// Get the next available file descriptor greater than or equal to `min_fd`
+    pub fn next_fd_from(&self, min_fd: usize) -> usize {
+        let mut next_fd = min_fd;
 
         let mut collision = true;
         while collision {
@@ -817,6 +1195,28 @@ impl Context {
         return next_fd;
     }
 
+    /// Get a context file from a file descriptor
+    pub fn get_context_file<'a>(&self, fd: usize) -> Result<&'a ContextFile> {
+        for file in unsafe { (*self.files.get()).iter() } {
+            if file.fd == fd {
+                return Ok(file);
+            }
+        }
+
+        Err(Error::new(EBADF))
+    }
+
+    /// Get a mutable context file from a file descriptor
+    pub fn get_context_file_mut<'a>(&mut self, fd: usize) -> Result<&'a mut ContextFile> {
+        for file in unsafe { (*self.files.get()).iter_mut() } {
+            if file.fd == fd {
+                return Ok(file);
+            }
+        }
+
+        Err(Error::new(EBADF))
+    }
+
     /// Get a resource from a file descriptor
     pub fn get_file<'a>(&self, fd: usize) -> Result<&'a Box<Resource>> {
         for file in unsafe { (*self.files.get()).iter() } {
@@ -848,27 +1248,104 @@ impl Context {
         ptr::write(self.regs.sp as *mut usize, data);
     }
 
-    /// Translate to physical if a ptr is inside of the mapped memory
+    /// Translate to physical if a ptr is inside of the mapped memory. As the physical memory may
+    /// be written to directly, shared memory is copied first, so the context must be mapped
     pub fn translate(&self, ptr: usize, len: usize) -> Result<usize> {
         if let Some(ref stack) = self.stack {
-            if ptr >= stack.virtual_address && ptr + len <= stack.virtual_address + stack.virtual_size {
+            if stack.contains(ptr, len) {
                 return Ok(ptr - stack.virtual_address + stack.physical_address);
             }
         }
 
-        if let Some(address) = unsafe { (*self.image.get()).translate(ptr, len) } {
-            return Ok(address);
+        for zone in [&self.image, &self.heap, &self.mmap].iter() {
+            let zone = unsafe { &mut *zone.get() };
+            if zone.translate(ptr, len).is_some() {
+                try!(unsafe { zone.unshare(ptr) });
+            }
+            if let Some(address) = zone.translate(ptr, len) {
+                return Ok(address);
+            }
         }
 
-        if let Some(address) = unsafe { (*self.heap.get()).translate(ptr, len) } {
-            return Ok(address);
+        Err(Error::new(EFAULT))
+    }
+
+    /// Handle a write to memory shared with another context, by giving this context its own copy.
+    /// Returns false if the address is not inside of writeable shared memory, or no memory is
+    /// available for the copy. The context must be mapped
+    pub unsafe fn unshare(&mut self, address: usize) -> bool {
+        for zone in [&self.image, &self.heap, &self.mmap].iter() {
+            match (*zone.get()).unshare(address) {
+                Ok(false) => (),
+                Ok(true) => return true,
+                Err(_) => return false,
+            }
         }
 
-        if let Some(address) = unsafe { (*self.mmap.get()).translate(ptr, len) } {
-            return Ok(address);
+        false
+    }
+
+    /// Check if a buffer is inside of the memory of the context, without copying shared memory
+    fn contains(&self, ptr: usize, len: usize) -> bool {
+        if let Some(ref stack) = self.stack {
+            if stack.contains(ptr, len) {
+                return true;
+            }
         }
 
-        Err(Error::new(EFAULT))
+        unsafe {
+            (*self.image.get()).translate(ptr, len).is_some() ||
+            (*self.heap.get()).translate(ptr, len).is_some() ||
+            (*self.mmap.get()).translate(ptr, len).is_some()
+        }
+    }
+
+    /// Check that a buffer is inside of the memory of the context. Kernel contexts, which have no
+    /// user stack, can access all of memory, but not a buffer that wraps around its end
+    pub fn validate(&self, ptr: usize, len: usize) -> Result<()> {
+        if len > 0 {
+            if ptr == 0 || ptr.checked_add(len).is_none() {
+                return Err(Error::new(EFAULT));
+            }
+            if self.stack.is_some() && ! self.contains(ptr, len) {
+                return Err(Error::new(EFAULT));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a buffer the kernel is about to write to is inside of writeable memory of the
+    /// context, and give the context its own copy if the memory is shared. Kernel contexts, which
+    /// have no user stack, can access all of memory. The context must be mapped
+    pub fn validate_writeable(&self, ptr: usize, len: usize) -> Result<()> {
+        try!(self.validate(ptr, len));
+
+        if len > 0 {
+            if let Some(ref stack) = self.stack {
+                if stack.contains(ptr, len) {
+                    return Ok(());
+                }
+
+                for zone in [&self.image, &self.heap, &self.mmap].iter() {
+                    let zone = unsafe { &mut *zone.get() };
+                    if zone.translate(ptr, len).is_some() {
+                        if ! zone.writeable(ptr, len) {
+                            return Err(Error::new(EFAULT));
+                        }
+                        try!(unsafe { zone.unshare(ptr) });
+                        return Ok(());
+                    }
+                }
+
+                return Err(Error::new(EFAULT));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the handler registered for a signal. Returns `None` for the default disposition
+    pub fn signal_handler(&self, sig: usize) -> Option<SignalHandler> {
+        unsafe { (*self.signal_handlers.get()).get(&sig).map(|handler| *handler) }
     }
 
     /// Gets an environment variable. Returns `Err` if the variable is not defined
@@ -907,6 +1384,18 @@ impl Context {
         Ok(vars_buf)
     }
 
+    /// Check if an address is in the guard page below the kernel or userspace stack
+    pub fn stack_guard(&self, address: usize) -> bool {
+        let kernel = self.kernel_stack > 0 && address >= self.kernel_stack - CONTEXT_STACK_GUARD &&
+                     address < self.kernel_stack;
+        let user = match self.stack {
+            Some(ref stack) => address >= stack.virtual_address - CONTEXT_STACK_GUARD &&
+                               address < stack.virtual_address,
+            None => false,
+        };
+        kernel || user
+    }
+
     /// Removes the environment variable named `name`. Returns `Err` if the variable doesn't exist
     pub fn remove_env_var(&self, name: &str) -> Result<()> {
         for (i, variable) in unsafe { (*self.env_vars.get()).iter().enumerate() } {
@@ -921,6 +1410,7 @@ impl Context {
     pub unsafe fn map(&mut self) {
         if let Some(ref mut stack) = self.stack {
             stack.map();
+            Page::new(stack.virtual_address - CONTEXT_STACK_GUARD).unmap();
         }
         (*self.image.get()).map();
         (*self.heap.get()).map();
@@ -932,6 +1422,8 @@ impl Context {
         (*self.heap.get()).unmap();
         (*self.image.get()).unmap();
         if let Some(ref mut stack) = self.stack {
+            let guard = stack.virtual_address - CONTEXT_STACK_GUARD;
+            Page::new(guard).map_kernel_write(guard);
             stack.unmap();
         }
     }
@@ -1015,11 +1507,14 @@ impl Context {
 
 impl Drop for Context {
     fn drop(&mut self) {
+        // Release the file locks held by the context, however it exited
+        ::env().locks.exit(self.pid);
+
         if let Some(vfork) = self.vfork.take() {
             unsafe { (*vfork).blocked = false; }
         }
         if self.kernel_stack > 0 {
-            unsafe { memory::unalloc(self.kernel_stack); }
+            unsafe { kernel_stack_unalloc(self.kernel_stack); }
         }
     }
 }
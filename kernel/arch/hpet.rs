@@ -0,0 +1,120 @@
+use arch::paging::Page;
+
+use common::time::{Duration, NANOS_PER_SEC};
+
+use drivers::io::{Io, Mmio};
+
+use sync::Intex;
+
+/// General capabilities and ID register. The upper half is the period of the main counter
+const REG_CAPABILITIES: usize = 0x00;
+/// General configuration register
+const REG_CONFIG: usize = 0x10;
+/// Main counter value register
+const REG_MAIN_COUNTER: usize = 0xF0;
+
+/// Set if the main counter is 64 bits wide
+const COUNT_SIZE_CAP: u64 = 1 << 13;
+/// Enables the main counter
+const CONFIG_ENABLE: u64 = 1;
+
+/// The longest main counter period allowed by the specification, in femtoseconds
+const MAX_PERIOD: u64 = 100000000;
+/// Femtoseconds in a nanosecond
+const FEMTOS_PER_NANO: u64 = 1000000;
+
+/// A High Precision Event Timer, used as the monotonic clock source
+pub struct Hpet {
+    /// The base address of the registers
+    base: usize,
+    /// The period of the main counter in femtoseconds
+    period: u64,
+    /// Whether the main counter is 64 bits wide
+    wide: bool,
+    /// The last value of a 32 bit main counter, extended to 64 bits by counting its wraps
+    extended: Intex<u64>,
+}
+
+impl Hpet {
+    /// Map the registers at the base address and start the main counter from zero. Returns
+    /// `None` if the reported counter period is invalid
+    pub unsafe fn new(base: usize) -> Option<Self> {
+        Page::new(base).map_kernel_write(base);
+
+        let hpet = Hpet {
+            base: base,
+            period: 0,
+            wide: false,
+            extended: Intex::new(0),
+        };
+
+        let capabilities = hpet.reg(REG_CAPABILITIES).read();
+        let period = capabilities >> 32;
+        if period == 0 || period > MAX_PERIOD {
+            return None;
+        }
+
+        let config = hpet.reg(REG_CONFIG);
+        let value = config.read();
+        config.write(value & !CONFIG_ENABLE);
+        hpet.reg(REG_MAIN_COUNTER).write(0);
+        config.write(value | CONFIG_ENABLE);
+
+        Some(Hpet {
+            base: base,
+            period: period,
+            wide: capabilities & COUNT_SIZE_CAP == COUNT_SIZE_CAP,
+            extended: Intex::new(0),
+        })
+    }
+
+    fn reg(&self, offset: usize) -> &'static mut Mmio<u64> {
+        unsafe { &mut *((self.base + offset) as *mut Mmio<u64>) }
+    }
+
+    fn reg32(&self, offset: usize) -> &'static mut Mmio<u32> {
+        unsafe { &mut *((self.base + offset) as *mut Mmio<u32>) }
+    }
+
+    /// The value of the main counter. A 64 bit counter is read in halves, as a 64 bit read is
+    /// split on x86 and the low half can wrap in between. A 32 bit counter is extended with the
+    /// number of times it wrapped, which is noticed as long as it is read at least once per wrap,
+    /// as the timer interrupt does
+    pub fn counter(&self) -> u64 {
+        let low = self.reg32(REG_MAIN_COUNTER);
+        let high = self.reg32(REG_MAIN_COUNTER + 4);
+
+        if self.wide {
+            loop {
+                let before = high.read();
+                let value = low.read();
+                if high.read() == before {
+                    return (before as u64) << 32 | value as u64;
+                }
+            }
+        } else {
+            let mut extended = self.extended.lock();
+            let mut value = (*extended & !0xFFFFFFFF) | low.read() as u64;
+            if value < *extended {
+                value += 1 << 32;
+            }
+            *extended = value;
+            value
+        }
+    }
+
+    /// The time since the main counter was started
+    pub fn monotonic(&self) -> Duration {
+        // Whole millions of ticks convert exactly to nanoseconds, keeping the product in range
+        let counter = self.counter();
+        let nanos = (counter / FEMTOS_PER_NANO) * self.period +
+                    (counter % FEMTOS_PER_NANO) * self.period / FEMTOS_PER_NANO;
+        Duration::new((nanos / NANOS_PER_SEC as u64) as i64,
+                      (nanos % NANOS_PER_SEC as u64) as i32)
+    }
+
+    /// The period of the main counter, rounded up to a whole nanosecond
+    pub fn resolution(&self) -> Duration {
+        Duration::new(0, ((self.period + FEMTOS_PER_NANO - 1) / FEMTOS_PER_NANO) as i32)
+    }
+}
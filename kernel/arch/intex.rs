@@ -1,10 +1,31 @@
-use core::cell::UnsafeCell;
+use core::cell::{Cell, UnsafeCell};
 use core::ops::{Deref, DerefMut, Drop};
 
 pub static mut intex_count: usize = 0;
 
+/// The pid of the running context, set by `context_switch`, and recorded as the holder of the
+/// Intexes it locks
+pub static mut intex_pid: usize = 0;
+
+/// Set when IRQs were kept pending because the schemes were held, so that they are passed on when
+/// the last guard of an Intex is dropped
+pub static mut intex_irq_pending: bool = false;
+
 /// An Intex, interrupt exclusion during value usage
+///
+/// Interrupts are disabled while the value is used. The guards alive and the context that took
+/// the last of them are recorded, so that code interrupting that context, such as an IRQ handler,
+/// can back off with `try_lock` instead of using the value under it. A context blocked while
+/// holding a guard does not keep others from locking. Debug builds also count how often the
+/// Intex was locked while it was held.
+///
+/// Dropping the last guard of an Intex passes on the IRQs that came in while it was held.
 pub struct Intex<T: ?Sized> {
+    guards: Cell<usize>,
+    /// The pid of the context that took the innermost guard alive, or 0 once all are dropped
+    holder: Cell<usize>,
+    #[cfg(debug_assertions)]
+    contended: Cell<usize>,
     value: UnsafeCell<T>,
 }
 
@@ -16,15 +37,72 @@ impl Intex<()> {
 
 impl<T> Intex<T> {
     /// Create a new Intex with value `value`.
+    #[cfg(not(debug_assertions))]
+    pub fn new(value: T) -> Self {
+        Intex {
+            guards: Cell::new(0),
+            holder: Cell::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Create a new Intex with value `value`.
+    #[cfg(debug_assertions)]
     pub fn new(value: T) -> Self {
-        Intex { value: UnsafeCell::new(value) }
+        Intex {
+            guards: Cell::new(0),
+            holder: Cell::new(0),
+            contended: Cell::new(0),
+            value: UnsafeCell::new(value),
+        }
     }
 }
 
 impl<T: ?Sized> Intex<T> {
     /// Lock the Intex
     pub fn lock(&self) -> IntexGuard<T> {
-        IntexGuard::new(&self.value)
+        let guard = IntexGuard::new(&self.value, &self.guards, &self.holder, self.holder.get());
+        self.acquired();
+        guard
+    }
+
+    /// Lock the Intex, unless the running context holds it, such as when an interrupt came in
+    /// while it was being used
+    pub fn try_lock(&self) -> Option<IntexGuard<T>> {
+        let _static_guard = StaticIntexGuard::new();
+        if self.holder() == Some(unsafe { intex_pid }) {
+            None
+        } else {
+            Some(self.lock())
+        }
+    }
+
+    /// The pid of the context holding the Intex, if it is held and the holder is known
+    pub fn holder(&self) -> Option<usize> {
+        if self.guards.get() > 0 && self.holder.get() > 0 {
+            Some(self.holder.get())
+        } else {
+            None
+        }
+    }
+
+    /// The number of times the Intex was locked while it was held
+    #[cfg(debug_assertions)]
+    pub fn contended(&self) -> usize {
+        self.contended.get()
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn acquired(&self) {
+        self.holder.set(unsafe { intex_pid });
+    }
+
+    #[cfg(debug_assertions)]
+    fn acquired(&self) {
+        if self.guards.get() > 1 {
+            self.contended.set(self.contended.get() + 1);
+        }
+        self.holder.set(unsafe { intex_pid });
     }
 }
 
@@ -36,13 +114,23 @@ unsafe impl<T: ?Sized + Send> Sync for Intex<T> { }
 pub struct IntexGuard<'a, T: ?Sized + 'a> {
     inner: StaticIntexGuard,
     data: &'a UnsafeCell<T>,
+    guards: &'a Cell<usize>,
+    holder: &'a Cell<usize>,
+    /// The holder when this guard was taken, which holds the Intex again once it is dropped
+    previous: usize,
 }
 
 impl<'intex, T: ?Sized> IntexGuard<'intex, T> {
-    fn new(data: &'intex UnsafeCell<T>) -> Self {
+    fn new(data: &'intex UnsafeCell<T>, guards: &'intex Cell<usize>, holder: &'intex Cell<usize>,
+           previous: usize) -> Self {
+        let inner = StaticIntexGuard::new();
+        guards.set(guards.get() + 1);
         IntexGuard {
-            inner: StaticIntexGuard::new(),
+            inner: inner,
             data: data,
+            guards: guards,
+            holder: holder,
+            previous: previous,
         }
     }
 }
@@ -61,6 +149,26 @@ impl<'intex, T: ?Sized> DerefMut for IntexGuard<'intex, T> {
     }
 }
 
+impl<'intex, T: ?Sized> Drop for IntexGuard<'intex, T> {
+    fn drop(&mut self) {
+        // The guards left may have been taken by any context, so the holder is only known while
+        // they are nested
+        self.guards.set(self.guards.get() - 1);
+        if self.guards.get() == 0 {
+            self.holder.set(0);
+
+            unsafe {
+                if intex_irq_pending {
+                    intex_irq_pending = false;
+                    ::env().on_irq_pending();
+                }
+            }
+        } else {
+            self.holder.set(self.previous);
+        }
+    }
+}
+
 /// A Static Intex guard (returned by .static_lock())
 pub struct StaticIntexGuard;
 
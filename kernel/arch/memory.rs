@@ -4,7 +4,7 @@ use core::{cmp, intrinsics, mem};
 use core::ops::{Index, IndexMut};
 use core::{ptr, slice};
 
-use super::paging::{Page, PAGE_END};
+use super::paging::{Page, PAGE_END, PAGE_SIZE};
 
 pub const CLUSTER_ADDRESS: usize = PAGE_END;
 pub const CLUSTER_COUNT: usize = 1024 * 1024; // 4 GiB
@@ -243,11 +243,27 @@ pub unsafe fn alloc(size: usize) -> usize {
 
 /// Allocate memory, aligned
 pub unsafe fn alloc_aligned(size: usize, align: usize) -> usize {
+    alloc_below(size, align, u64::max_value())
+}
+
+/// Allocate memory for a device that can only address the first 4 GiB, like a 32-bit PCI bus
+/// master. Pass the address to the device with `virt_to_phys`.
+pub unsafe fn alloc_dma32(size: usize, align: usize) -> usize {
+    alloc_below(size, align, 0x100000000)
+}
+
+/// Allocate memory, aligned, ending at or below `limit`. Kernel memory is identity mapped, so the
+/// limit holds for the physical address too.
+unsafe fn alloc_below(size: usize, align: usize, limit: u64) -> usize {
     if size > 0 {
         let mut number = 0;
         let mut count = 0;
 
         for i in 0..CLUSTER_COUNT {
+            if cluster_to_address(i) as u64 + CLUSTER_SIZE as u64 > limit {
+                break;
+            }
+
             if cluster(i) == 0 && (count > 0 || cluster_to_address(i) % align == 0) {
                 if count == 0 {
                     number = i;
@@ -288,6 +304,13 @@ pub unsafe fn alloc_aligned(size: usize, align: usize) -> usize {
     0
 }
 
+/// Translate a kernel virtual address to the physical address it is mapped to. Devices doing DMA
+/// must be given physical addresses; kernel memory happens to be identity mapped, but drivers
+/// should not depend on it.
+pub fn virt_to_phys(address: usize) -> usize {
+    Page::new(address).phys_addr() + address % PAGE_SIZE
+}
+
 /// Allocate a type
 pub unsafe fn alloc_type<T>() -> *mut T {
     alloc(mem::size_of::<T>()) as *mut T
@@ -321,6 +344,24 @@ pub unsafe fn unalloc(ptr: usize) {
     }
 }
 
+/// Split an allocation at a cluster aligned address inside of it, so that the clusters from the
+/// address onward become a separate allocation, which can be freed with `unalloc(address)`
+pub unsafe fn split(address: usize) {
+    if address > 0 {
+        let number = address_to_cluster(address);
+        let owner = cluster(number);
+        if owner > 0 && owner != 0xFFFFFFFF && owner != address {
+            for i in number..CLUSTER_COUNT {
+                if cluster(i) == owner {
+                    set_cluster(i, address);
+                } else {
+                    break;
+                }
+            }
+        }
+    }
+}
+
 pub unsafe fn unalloc_type<T>(ptr: *mut T) {
     unalloc(ptr as usize);
 }
@@ -388,6 +429,48 @@ pub unsafe fn realloc_inplace(ptr: usize, size: usize) -> usize {
     }
 }
 
+/// A snapshot of the cluster allocator
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemoryStats {
+    /// The size of all usable memory
+    pub total: usize,
+    /// The size of allocated memory
+    pub used: usize,
+    /// The size of free memory
+    pub free: usize,
+    /// The number of free clusters
+    pub free_clusters: usize,
+    /// The size of the largest run of contiguous free clusters
+    pub largest_free: usize,
+}
+
+/// Count used and free clusters in a single pass, so the numbers are consistent with each other
+pub fn memory_stats() -> MemoryStats {
+    let mut stats = MemoryStats::default();
+    let mut run = 0;
+
+    unsafe {
+        for i in 0..CLUSTER_COUNT {
+            let owner = cluster(i);
+            if owner == 0 {
+                stats.free_clusters += 1;
+                run += 1;
+                stats.largest_free = cmp::max(stats.largest_free, run);
+            } else {
+                if owner != 0xFFFFFFFF {
+                    stats.used += CLUSTER_SIZE;
+                }
+                run = 0;
+            }
+        }
+    }
+
+    stats.free = stats.free_clusters * CLUSTER_SIZE;
+    stats.largest_free *= CLUSTER_SIZE;
+    stats.total = stats.used + stats.free;
+    stats
+}
+
 pub fn memory_used() -> usize {
     let mut ret = 0;
 
@@ -1,5 +1,6 @@
 pub mod context;
 pub mod elf;
+pub mod hpet;
 pub mod intex;
 pub mod memory;
 pub mod paging;
@@ -46,7 +46,9 @@ interrupts:
     mov fs, eax
     mov gs, eax
 
-    add esp, 8 ; Skip interrupt code and reg pointer
+    pop eax ; Interrupt code
+    add esp, 4 ; Skip reg pointer
+    cmp eax, 0xE ; Only page faults return with an error code on the stack
 
     pop eax
     pop ebx
@@ -56,8 +58,41 @@ interrupts:
     pop esi
     pop ebp
 
+    jne .return
+    add esp, 4 ; Skip error code
+.return:
     iretd
 
+;The double fault is a task gate, so that it runs on its own stack even if the kernel stack
+;overflowed. The handler gets a frame like that of an exception with an error code, and fills in
+;the registers of the task that faulted from its TSS. Returning switches back to that task, and
+;the next double fault continues after the iretd
+.double_fault:
+    pop eax ; Error code
+    push dword 0 ; ss
+    push dword 0 ; esp
+    push dword 0 ; eflags
+    push dword 0 ; cs
+    push dword 0 ; eip
+    push eax
+
+    push ebp
+    push esi
+    push edi
+    push edx
+    push ecx
+    push ebx
+    push eax
+
+    push esp
+    push dword 8
+
+    call dword [.handler]
+
+    add esp, 15 * 4
+    iretd
+    jmp .double_fault
+
 .handler: dd 0
 .entry: dd 0
 
@@ -70,6 +105,16 @@ idt:
 
 ;Below system call
 %rep 128
+%if i == 8
+    ;Double fault switches to its own task
+    istruc IDTEntry
+        at IDTEntry.offsetl, dw 0
+        at IDTEntry.selector, dw gdt.double_fault_tss
+        at IDTEntry.zero, db 0
+        at IDTEntry.attribute, db attrib.present | attrib.task
+        at IDTEntry.offseth, dw 0
+    iend
+%else
     istruc IDTEntry
         at IDTEntry.offsetl, dw interrupts+(interrupts.second-interrupts.first)*i
         at IDTEntry.selector, dw gdt.kernel_code
@@ -77,6 +122,7 @@ idt:
         at IDTEntry.attribute, db attrib.present | attrib.interrupt32
         at IDTEntry.offseth, dw 0
     iend
+%endif
 %assign i i+1
 %endrep
 
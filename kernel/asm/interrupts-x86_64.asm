@@ -57,7 +57,9 @@ interrupts:
     mov fs, rax
     mov gs, rax
 
-	add rsp, 16 ; Skip interrupt code and reg pointer
+	pop rax ; Interrupt code
+	add rsp, 8 ; Skip reg pointer
+	cmp rax, 0xE ; Only page faults return with an error code on the stack
 
 	pop rax
 	pop rbx
@@ -75,6 +77,9 @@ interrupts:
 	pop r15
 	pop rbp
 
+	jne .return
+	add rsp, 8 ; Skip error code
+.return:
     iretq
 
 .handler: dq 0
@@ -92,7 +97,11 @@ idt:
 	istruc IDTEntry
 		at IDTEntry.offsetl, dw interrupts+(interrupts.second-interrupts.first)*i
 		at IDTEntry.selector, dw gdt.kernel_code
+%if i == 8
+		at IDTEntry.ist, db 1 ;Double fault runs on the stack in ist1, so a kernel stack overflow can be reported
+%else
 		at IDTEntry.ist, db 0
+%endif
 		at IDTEntry.attribute, db attrib.present | attrib.interrupt64
 		at IDTEntry.offsetm, dw 0
 		at IDTEntry.offseth, dd 0
@@ -96,6 +96,16 @@ gdt:
         at GDTEntry.flags__limith, db ((tss.end - tss) >> 16) & 0xF
         at GDTEntry.baseh, db ((tss-$$+0x7C00) >> 24) & 0xFF
     iend
+
+.double_fault_tss equ $ - gdt
+    istruc GDTEntry
+        at GDTEntry.limitl, dw (double_fault_tss.end - double_fault_tss) & 0xFFFF
+        at GDTEntry.basel, dw (double_fault_tss-$$+0x7C00) & 0xFFFF
+        at GDTEntry.basem, db ((double_fault_tss-$$+0x7C00) >> 16) & 0xFF
+        at GDTEntry.attribute, db attrib.present | attrib.tssAvailabe32
+        at GDTEntry.flags__limith, db ((double_fault_tss.end - double_fault_tss) >> 16) & 0xF
+        at GDTEntry.baseh, db ((double_fault_tss-$$+0x7C00) >> 24) & 0xFF
+    iend
 .end equ $ - gdt
 
 struc TSS
@@ -136,4 +146,19 @@ tss:
     iend
 .end:
 
+;The double fault task, which must follow tss. The kernel sets its stack and page directory
+double_fault_tss:
+    istruc TSS
+        at TSS.eip, dd interrupts.double_fault
+        at TSS.eflags, dd 2
+        at TSS.es, dd gdt.kernel_data
+        at TSS.cs, dd gdt.kernel_code
+        at TSS.ss, dd gdt.kernel_data
+        at TSS.ds, dd gdt.kernel_data
+        at TSS.fs, dd gdt.kernel_data
+        at TSS.gs, dd gdt.kernel_data
+        at TSS.iomap_base, dw 0xFFFF
+    iend
+.end:
+
 %include "asm/interrupts-i386.asm"
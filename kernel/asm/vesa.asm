@@ -7,9 +7,50 @@ vesa:
     mov di, VBECardInfo
     int 0x10
     cmp ax, 0x4F
-    je .edid
+    je .modelist
     mov eax, 1
     ret
+.modelist:
+    ;record every mode with a linear framebuffer, so the kernel can switch to one of them
+    mov word [VBEModeList.count], 0
+    mov si, [VBECardInfo.videomodeptr]
+    mov ax, [VBECardInfo.videomodeptr+2]
+    mov fs, ax
+    mov di, VBEModeList.modes
+.modelistlp:
+    mov cx, [fs:si]
+    cmp cx, 0xFFFF
+    je .edid
+    cmp word [VBEModeList.count], VBEModeList.max
+    jae .edid
+    add si, 2
+    push esi
+    push edi
+    push ecx
+    mov ax, 0x4F01
+    mov di, VBEModeInfo
+    int 0x10
+    pop ecx
+    pop edi
+    pop esi
+    cmp ax, 0x4F
+    jne .modelistlp
+    test word [VBEModeInfo.attributes], VBE.ModeAttributes.linearframebuffer
+    jz .modelistlp
+    mov [di+VBEModeListEntry.mode], cx
+    mov ax, [VBEModeInfo.xresolution]
+    mov [di+VBEModeListEntry.xresolution], ax
+    mov ax, [VBEModeInfo.yresolution]
+    mov [di+VBEModeListEntry.yresolution], ax
+    mov ax, [VBEModeInfo.bytesperscanline]
+    mov [di+VBEModeListEntry.bytesperscanline], ax
+    mov al, [VBEModeInfo.bitsperpixel]
+    mov [di+VBEModeListEntry.bitsperpixel], al
+    mov eax, [VBEModeInfo.physbaseptr]
+    mov [di+VBEModeListEntry.physbaseptr], eax
+    add di, VBEModeListEntry_size
+    inc word [VBEModeList.count]
+    jmp .modelistlp
 .edid:
     cmp dword [.required], 0    ;if both required x and required y are set, forget this
     jne near .findmode
@@ -6,6 +6,9 @@ pub const EVENT_MOUSE: i64 = 1;
 pub const EVENT_KEY: i64 = 2;
 pub const EVENT_QUIT: i64 = 3;
 
+/// The bits of `c` in a mouse `Event` that hold the buttons. The scroll wheel is in the upper half.
+pub const MOUSE_BUTTONS: i64 = 0x1F;
+
 /// An optional event
 #[derive(Copy, Clone, Debug)]
 pub enum EventOption {
@@ -85,6 +88,12 @@ pub struct MouseEvent {
     pub middle_button: bool,
     /// Was the right button pressed?
     pub right_button: bool,
+    /// Was the fourth button, usually back, pressed?
+    pub fourth_button: bool,
+    /// Was the fifth button, usually forward, pressed?
+    pub fifth_button: bool,
+    /// How far the scroll wheel moved, positive towards the user
+    pub wheel: i32,
 }
 
 impl MouseEvent {
@@ -95,7 +104,8 @@ impl MouseEvent {
             a: self.x as i64,
             b: self.y as i64,
             c: self.left_button as i64 | (self.middle_button as i64) << 1 |
-               (self.right_button as i64) << 2,
+               (self.right_button as i64) << 2 | (self.fourth_button as i64) << 3 |
+               (self.fifth_button as i64) << 4 | (self.wheel as i64) << 32,
         }
     }
 
@@ -107,6 +117,9 @@ impl MouseEvent {
             left_button: event.c & 1 == 1,
             middle_button: event.c & 2 == 2,
             right_button: event.c & 4 == 4,
+            fourth_button: event.c & 8 == 8,
+            fifth_button: event.c & 0x10 == 0x10,
+            wheel: (event.c >> 32) as i32,
         }
     }
 }
@@ -1,4 +1,4 @@
-use collections::string::String;
+use collections::string::{String, ToString};
 use collections::vec::Vec;
 
 /// Parse the path
@@ -53,3 +53,76 @@ pub fn parse_path(path: &str, cwd: Vec<String>) -> Vec<String> {
 
     parts
 }
+
+/// Resolve a path against a working directory, such as `initfs:/bin/`
+///
+/// Paths starting with `/` are relative to the root of the scheme of the working directory, and
+/// other paths without a scheme to the working directory itself. Paths with a scheme are only
+/// normalized if their reference contains a `/`, so references like `tcp:10.0.0.1:80` are left
+/// alone, as is a query after `?`.
+///
+/// Empty and `.` components are dropped, and `..` removes the previous component, stopping at the
+/// root of the scheme. The result ends with a slash if the path does, or if it ends with `.` or
+/// `..`.
+pub fn canonicalize(cwd: &str, path: &str) -> String {
+    if let Some(i) = path.find(':') {
+        let (scheme, reference) = (&path[.. i + 1], &path[i + 1 ..]);
+        let (reference, query) = match reference.find('?') {
+            Some(j) => (&reference[.. j], &reference[j ..]),
+            None => (reference, ""),
+        };
+
+        if ! reference.contains('/') {
+            return path.to_string();
+        }
+
+        let mut canonical = normalize(scheme, "", reference, reference.starts_with('/'));
+        canonical.push_str(query);
+        return canonical;
+    }
+
+    let (scheme, reference) = match cwd.find(':') {
+        Some(i) => (&cwd[.. i + 1], &cwd[i + 1 ..]),
+        None => ("", cwd),
+    };
+
+    let base = if path.starts_with('/') {
+        ""
+    } else {
+        reference
+    };
+
+    normalize(scheme, base, path, true)
+}
+
+/// Append the components of base and then path to root, resolving dots and repeated slashes. If
+/// absolute is set, each component is preceded by a slash, so an empty result is the root
+fn normalize(root: &str, base: &str, path: &str, absolute: bool) -> String {
+    let mut parts = Vec::new();
+    for part in base.split('/').chain(path.split('/')) {
+        match part {
+            "" | "." => (),
+            ".." => {
+                parts.pop();
+            },
+            _ => parts.push(part),
+        }
+    }
+
+    let directory = match path.rsplit('/').next() {
+        Some("") | Some(".") | Some("..") | None => true,
+        _ => false,
+    };
+
+    let mut canonical = root.to_string();
+    for (i, part) in parts.iter().enumerate() {
+        if absolute || i > 0 {
+            canonical.push('/');
+        }
+        canonical.push_str(part);
+    }
+    if (absolute && parts.is_empty()) || (directory && ! parts.is_empty()) {
+        canonical.push('/');
+    }
+    canonical
+}
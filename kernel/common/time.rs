@@ -5,6 +5,12 @@ pub const NANOS_PER_MICRO: i32 = 1000;
 pub const NANOS_PER_MILLI: i32 = 1000000;
 pub const NANOS_PER_SEC: i32 = 1000000000;
 
+/// The input clock of the PIT (programmable interval timer) runs at a third of this frequency,
+/// 1193181.666... Hz. Rounding it to 1193182 Hz would drift by over a nanosecond every tick
+pub const PIT_CLOCK: u64 = 3579545;
+/// The divisor programmed into the PIT by `initialize.pit`
+pub const PIT_DIVISOR: u64 = 5370;
+
 /// A duration
 #[derive(Copy, Clone)]
 pub struct Duration {
@@ -33,9 +39,13 @@ impl Duration {
         }
     }
 
-    /// Get the current duration
+    /// Get the current duration. The HPET is read directly when present, otherwise this only
+    /// advances on each PIT tick
     pub fn monotonic() -> Self {
-        ::env().clock_monotonic.lock().clone()
+        match ::env().hpet {
+            Some(ref hpet) => hpet.monotonic(),
+            None => ::env().clock_monotonic.lock().clone(),
+        }
     }
 
     /// Get the realtime
@@ -83,3 +93,58 @@ impl PartialOrd for Duration {
         }
     }
 }
+
+/// The period of a timer
+///
+/// The period is rarely a whole number of nanoseconds, so the fraction left over by each tick is
+/// carried to the next one, keeping the clocks advanced by the timer from drifting.
+pub struct TickPeriod {
+    denominator: u64,
+    /// The whole nanoseconds of a tick
+    nanos: u64,
+    /// The fraction of a nanosecond of a tick, in units of 1/denominator
+    fraction: u64,
+    /// The fraction carried from the previous ticks
+    carry: u64,
+}
+
+impl TickPeriod {
+    /// Create a period of numerator / denominator seconds
+    pub fn new(numerator: u64, denominator: u64) -> Self {
+        let period = numerator * NANOS_PER_SEC as u64;
+        TickPeriod {
+            denominator: denominator,
+            nanos: period / denominator,
+            fraction: period % denominator,
+            carry: 0,
+        }
+    }
+
+    /// Create the period of the PIT counting down from divisor
+    pub fn pit(divisor: u64) -> Self {
+        TickPeriod::new(divisor * 3, PIT_CLOCK)
+    }
+
+    /// Get the duration of the next tick
+    pub fn tick(&mut self) -> Duration {
+        let mut nanos = self.nanos;
+        self.carry += self.fraction;
+        if self.carry >= self.denominator {
+            self.carry -= self.denominator;
+            nanos += 1;
+        }
+        Duration::new((nanos / NANOS_PER_SEC as u64) as i64, (nanos % NANOS_PER_SEC as u64) as i32)
+    }
+
+    /// Get the length of a tick, rounded up to a whole nanosecond
+    pub fn resolution(&self) -> Duration {
+        let nanos = self.nanos + if self.fraction > 0 { 1 } else { 0 };
+        Duration::new((nanos / NANOS_PER_SEC as u64) as i64, (nanos % NANOS_PER_SEC as u64) as i32)
+    }
+
+    /// Get the total duration of a number of ticks
+    pub fn duration(&self, ticks: u64) -> Duration {
+        let nanos = ticks * self.nanos + ticks * self.fraction / self.denominator;
+        Duration::new((nanos / NANOS_PER_SEC as u64) as i64, (nanos % NANOS_PER_SEC as u64) as i32)
+    }
+}
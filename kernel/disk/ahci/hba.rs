@@ -7,8 +7,6 @@ use disk::Disk;
 
 use drivers::io::{Io, Mmio};
 
-use system::error::{Error, Result, EIO};
-
 use super::fis::{FIS_TYPE_REG_H2D, FisRegH2D};
 
 const ATA_CMD_READ_DMA_EXT: u8 = 0x25;
@@ -20,7 +18,18 @@ const HBA_PORT_CMD_CR: u32 = 1 << 15;
 const HBA_PORT_CMD_FR: u32 = 1 << 14;
 const HBA_PORT_CMD_FRE: u32 = 1 << 4;
 const HBA_PORT_CMD_ST: u32 = 1;
+const HBA_PORT_IS_DHRS: u32 = 1;
+const HBA_PORT_IS_PSS: u32 = 1 << 1;
+const HBA_PORT_IS_SDBS: u32 = 1 << 3;
+const HBA_PORT_IS_IFS: u32 = 1 << 27;
+const HBA_PORT_IS_HBDS: u32 = 1 << 28;
+const HBA_PORT_IS_HBFS: u32 = 1 << 29;
 const HBA_PORT_IS_TFES: u32 = 1 << 30;
+/// Interrupt status bits of errors that stop the port
+pub const HBA_PORT_IS_ERR: u32 = HBA_PORT_IS_TFES | HBA_PORT_IS_HBFS | HBA_PORT_IS_HBDS | HBA_PORT_IS_IFS;
+/// Interrupt status bits of completed commands
+const HBA_PORT_IS_DONE: u32 = HBA_PORT_IS_DHRS | HBA_PORT_IS_PSS | HBA_PORT_IS_SDBS;
+const HBA_GHC_IE: u32 = 1 << 1;
 const HBA_SSTS_PRESENT: u32 = 0x3;
 const HBA_SIG_ATA: u32 = 0x00000101;
 const HBA_SIG_ATAPI: u32 = 0xEB140101;
@@ -78,7 +87,7 @@ impl HbaPort {
         self.stop();
 
         // debugln!("Port Command List");
-        let clb = unsafe { memory::alloc_aligned(size_of::<HbaCmdHeader>(), 1024) };
+        let clb = unsafe { memory::alloc_aligned(size_of::<HbaCmdHeader>() * 32, 1024) };
         self.clb.write(clb as u64);
 
         // debugln!("Port FIS");
@@ -93,6 +102,9 @@ impl HbaPort {
             cmdheader.prdtl.write(0);
         }
 
+        self.is.write(u32::MAX);
+        self.ie.write(HBA_PORT_IS_DONE | HBA_PORT_IS_ERR);
+
         self.start();
     }
 
@@ -115,17 +127,8 @@ impl HbaPort {
         self.cmd.writef(HBA_PORT_CMD_FRE, false);
     }
 
-    pub fn slot(&self) -> Option<u32> {
-        let slots = self.sact.read() | self.ci.read();
-        for i in 0..32 {
-            if slots & 1 << i == 0 {
-                return Some(i);
-            }
-        }
-        None
-    }
-
-    pub fn ata_dma_small(&mut self, block: u64, sectors: usize, mut buf: usize, write: bool) -> Result<usize> {
+    /// Fill in the command in a slot and issue it, without waiting for it to complete
+    pub fn ata_issue(&mut self, slot: u32, block: u64, sectors: usize, mut buf: usize, write: bool) {
         if buf >= 0x80000000 {
             buf -= 0x80000000;
         }
@@ -133,105 +136,71 @@ impl HbaPort {
         // TODO: PRDTL for files larger than 4MB
         let entries = 1;
 
-        if buf > 0 && sectors > 0 {
-            self.is.write(u32::MAX);
-
-            if let Some(slot) = self.slot() {
-                // debugln!("Slot {}", slot);
-
-                let clb = self.clb.read() as usize;
-                let cmdheader = unsafe { &mut *(clb as *mut HbaCmdHeader).offset(slot as isize) };
+        let clb = self.clb.read() as usize;
+        let cmdheader = unsafe { &mut *(clb as *mut HbaCmdHeader).offset(slot as isize) };
 
-                cmdheader.cfl.write(((size_of::<FisRegH2D>() / size_of::<u32>()) as u8));
-                cmdheader.cfl.writef(1 << 6, write);
+        cmdheader.cfl.write(((size_of::<FisRegH2D>() / size_of::<u32>()) as u8));
+        cmdheader.cfl.writef(1 << 6, write);
 
-                cmdheader.prdtl.write(entries);
+        cmdheader.prdtl.write(entries);
 
-                let ctba = cmdheader.ctba.read() as usize;
-                unsafe { ::memset(ctba as *mut u8, 0, size_of::<HbaCmdTable>()) };
-                let cmdtbl = unsafe { &mut *(ctba as *mut HbaCmdTable) };
+        let ctba = cmdheader.ctba.read() as usize;
+        unsafe { ::memset(ctba as *mut u8, 0, size_of::<HbaCmdTable>()) };
+        let cmdtbl = unsafe { &mut *(ctba as *mut HbaCmdTable) };
 
-                let prdt_entry = &mut cmdtbl.prdt_entry[0];
-                prdt_entry.dba.write(buf as u64);
-                prdt_entry.dbc.write(((sectors * 512) as u32) | 1);
+        let prdt_entry = &mut cmdtbl.prdt_entry[0];
+        prdt_entry.dba.write(buf as u64);
+        prdt_entry.dbc.write(((sectors * 512) as u32) | 1);
 
-                let cmdfis = unsafe { &mut *(cmdtbl.cfis.as_ptr() as *mut FisRegH2D) };
+        let cmdfis = unsafe { &mut *(cmdtbl.cfis.as_ptr() as *mut FisRegH2D) };
 
-                cmdfis.fis_type.write(FIS_TYPE_REG_H2D);
-                cmdfis.pm.write(1 << 7);
-                if write {
-                    cmdfis.command.write(ATA_CMD_WRITE_DMA_EXT);
-                } else {
-                    cmdfis.command.write(ATA_CMD_READ_DMA_EXT);
-                }
-
-                cmdfis.lba0.write(block as u8);
-                cmdfis.lba1.write((block >> 8) as u8);
-                cmdfis.lba2.write((block >> 16) as u8);
-
-                cmdfis.device.write(1 << 6);
-
-                cmdfis.lba3.write((block >> 24) as u8);
-                cmdfis.lba4.write((block >> 32) as u8);
-                cmdfis.lba5.write((block >> 40) as u8);
-
-                cmdfis.countl.write(sectors as u8);
-                cmdfis.counth.write((sectors >> 8) as u8);
+        cmdfis.fis_type.write(FIS_TYPE_REG_H2D);
+        cmdfis.pm.write(1 << 7);
+        if write {
+            cmdfis.command.write(ATA_CMD_WRITE_DMA_EXT);
+        } else {
+            cmdfis.command.write(ATA_CMD_READ_DMA_EXT);
+        }
 
-                // debugln!("Busy Wait");
-                while self.tfd.readf((ATA_DEV_BUSY | ATA_DEV_DRQ) as u32) {}
+        cmdfis.lba0.write(block as u8);
+        cmdfis.lba1.write((block >> 8) as u8);
+        cmdfis.lba2.write((block >> 16) as u8);
 
-                self.ci.writef(1 << slot, true);
+        cmdfis.device.write(1 << 6);
 
-                // debugln!("Completion Wait");
-                while self.ci.readf(1 << slot) {
-                    if self.is.readf(HBA_PORT_IS_TFES) {
-                        return Err(Error::new(EIO));
-                    }
-                }
+        cmdfis.lba3.write((block >> 24) as u8);
+        cmdfis.lba4.write((block >> 32) as u8);
+        cmdfis.lba5.write((block >> 40) as u8);
 
-                if self.is.readf(HBA_PORT_IS_TFES) {
-                    return Err(Error::new(EIO));
-                }
+        cmdfis.countl.write(sectors as u8);
+        cmdfis.counth.write((sectors >> 8) as u8);
 
-                Ok(sectors * 512)
-            } else {
-                debugln!("No Command Slots");
-                Err(Error::new(EIO))
-            }
-        } else {
-            debugln!("Invalid request");
-            Err(Error::new(EIO))
+        // The device is only idle when no other command is outstanding
+        if self.ci.read() == 0 {
+            while self.tfd.readf((ATA_DEV_BUSY | ATA_DEV_DRQ) as u32) {}
         }
-    }
-
-    pub fn ata_dma(&mut self, block: u64, sectors: usize, buf: usize, write: bool) -> Result<usize> {
-        // debugln!("AHCI {:X} DMA BLOCK: {:X} SECTORS: {} BUF: {:X} WRITE: {}", (self as *mut HbaPort) as usize, block, sectors, buf, write);
 
-        if sectors > 0 {
-            let contexts = ::env().contexts.lock();
-            let current = try!(contexts.current());
-            let physical_address = try!(current.translate(buf, sectors * 512));
+        self.ci.writef(1 << slot, true);
+    }
 
-            let mut sector: usize = 0;
-            while sectors - sector >= 255 {
-                if let Err(err) = self.ata_dma_small(block + sector as u64, 255, physical_address + sector * 512, write) {
-                    return Err(err);
-                }
+    /// The slots with commands that have not completed
+    pub fn active(&self) -> u32 {
+        self.ci.read()
+    }
 
-                sector += 255;
-            }
-            if sector < sectors {
-                if let Err(err) = self.ata_dma_small(block + sector as u64, sectors - sector, physical_address + sector * 512, write) {
-                    return Err(err);
-                }
-            }
+    /// Acknowledge the interrupt status, returning it
+    pub fn ack(&mut self) -> u32 {
+        let status = self.is.read();
+        self.is.write(status);
+        status
+    }
 
-            Ok(sectors * 512)
-        } else {
-            debugln!("Invalid request");
-            Err(Error::new(EIO))
-        }
+    /// Restart the port after an error. This drops all commands that have not completed
+    pub fn recover(&mut self) {
+        self.stop();
+        self.serr.write(u32::MAX);
+        self.is.write(u32::MAX);
+        self.start();
     }
 }
 
@@ -253,6 +222,18 @@ pub struct HbaMem {
     pub ports: [HbaPort; 32], // 0x100 - 0x10FF, Port control registers
 }
 
+impl HbaMem {
+    /// The number of command slots of each port
+    pub fn slots(&self) -> u32 {
+        ((self.cap.read() >> 8) & 0x1F) + 1
+    }
+
+    /// Enable interrupts from the ports
+    pub fn enable_interrupts(&mut self) {
+        self.ghc.writef(HBA_GHC_IE, true);
+    }
+}
+
 #[repr(packed)]
 struct HbaPrdtEntry {
     dba: Mmio<u64>, // Data base address
@@ -2,19 +2,30 @@ use alloc::boxed::Box;
 
 use collections::string::String;
 use collections::vec::Vec;
+use collections::vec_deque::VecDeque;
+
+use core::cmp;
+
+use common::time::Duration;
 
 use disk::Disk;
 
 use drivers::io::Io;
 use drivers::pci::config::PciConfig;
 
-use system::error::Result;
+use sync::{WaitCondition, WaitQueue};
+
+use system::error::{Error, Result, EIO};
 
-use self::hba::{HbaMem, HbaPort, HbaPortType};
+use self::hba::{HbaMem, HbaPort, HbaPortType, HBA_PORT_IS_ERR};
 
 pub mod fis;
 pub mod hba;
 
+/// How long to sleep waiting for a command before polling the port, in case its interrupt is
+/// missed
+const AHCI_POLL_NANOS: i32 = 10000000;
+
 pub struct Ahci;
 
 impl Ahci {
@@ -24,11 +35,13 @@ impl Ahci {
 
         debugln!(" + AHCI on: {:X} IRQ: {:X}", base as usize, irq);
 
-        let pi = unsafe { &mut *(base as *mut HbaMem) }.pi.read();
+        let hba = unsafe { &mut *(base as *mut HbaMem) };
+        let pi = hba.pi.read();
+        let slots = hba.slots();
         let ret: Vec<Box<Disk>> = (0..32)
                                       .filter(|&i| pi & 1 << i as i32 == 1 << i as i32)
                                       .filter_map(|i| {
-                                          let mut disk = box AhciDisk::new(base, i);
+                                          let mut disk = box AhciDisk::new(base, i, irq, slots);
                                           let port_type = disk.port.probe();
                                           debugln!("   + Port {}: {:?}", i, port_type);
                                           match port_type {
@@ -41,22 +54,178 @@ impl Ahci {
                                       })
                                       .collect();
 
+        hba.enable_interrupts();
+
         ret
     }
 }
 
+/// Block the current context until the condition is notified, or until it is time to poll again.
+/// Before contexts are enabled this returns immediately, so the caller polls
+fn sleep(condition: &WaitCondition) {
+    let enabled = {
+        let mut contexts = ::env().contexts.lock();
+        let enabled = contexts.enabled;
+        if enabled {
+            if let Ok(mut current) = contexts.current_mut() {
+                current.wake = Some(Duration::monotonic() + Duration::new(0, AHCI_POLL_NANOS));
+            }
+        }
+        enabled
+    };
+
+    if enabled {
+        unsafe { condition.wait(); }
+
+        if let Ok(mut current) = ::env().contexts.lock().current_mut() {
+            current.wake = None;
+        }
+    }
+}
+
+/// A SATA disk on an AHCI port
+///
+/// Requests are split into commands, which are issued in as many command slots as are free. The
+/// issuing context sleeps until the port interrupt reports that its commands completed, so other
+/// contexts can run and issue their own commands in the meantime.
 pub struct AhciDisk {
     port: &'static mut HbaPort,
+    base: usize,
     port_index: usize,
     size: u64,
+    irq: u8,
+    /// The number of command slots of the port
+    slots: u32,
+    /// Slots in use, until the issuing context takes the result of their command
+    reserved: u32,
+    /// Slots with commands that have not completed
+    issued: u32,
+    /// The result of the command in each slot, sent when it completes
+    results: Vec<WaitQueue<bool>>,
+    /// Notified when a slot is released
+    released: WaitCondition,
 }
 
 impl AhciDisk {
-    fn new(base: usize, port_index: usize) -> Self {
+    fn new(base: usize, port_index: usize, irq: u8, slots: u32) -> Self {
         AhciDisk {
             port: &mut unsafe { &mut *(base as *mut HbaMem) }.ports[port_index],
+            base: base,
             port_index: port_index,
-            size: 1024*1024*1024 //TODO: Get actual value
+            size: 1024*1024*1024, //TODO: Get actual value
+            irq: irq,
+            slots: slots,
+            reserved: 0,
+            issued: 0,
+            results: (0..slots).map(|_| WaitQueue::new()).collect(),
+            released: WaitCondition::new(),
+        }
+    }
+
+    /// Acknowledge the port interrupt and send the results of completed commands. After an error
+    /// the port is restarted, and every command that had not completed fails
+    fn complete(&mut self) {
+        let status = self.port.ack();
+        unsafe { &mut *(self.base as *mut HbaMem) }.is.write(1 << self.port_index);
+
+        if self.issued == 0 {
+            return;
+        }
+
+        let active = self.port.active();
+        let mut failed = 0;
+        if status & HBA_PORT_IS_ERR != 0 {
+            debugln!("AHCI Port {}: error, interrupt status {:X}", self.port_index, status);
+            failed = self.issued & active;
+            self.port.recover();
+        }
+
+        for slot in 0..self.slots {
+            let bit = 1 << slot;
+            if self.issued & bit == bit && (active & bit == 0 || failed & bit == bit) {
+                self.issued &= !bit;
+                self.results[slot as usize].send(failed & bit == 0);
+            }
+        }
+    }
+
+    /// Reserve a free slot, waiting for another context to release one if all are in use
+    fn reserve(&mut self) -> u32 {
+        loop {
+            for slot in 0..self.slots {
+                let bit = 1 << slot;
+                if self.reserved & bit == 0 {
+                    self.reserved |= bit;
+                    return slot;
+                }
+            }
+
+            self.complete();
+            sleep(&self.released);
+        }
+    }
+
+    /// Wait for the command in a slot to complete and release the slot. Returns false if the
+    /// command failed
+    fn wait(&mut self, slot: u32) -> bool {
+        let result;
+        loop {
+            self.complete();
+            if let Some(value) = self.results[slot as usize].inner.lock().pop_front() {
+                result = value;
+                break;
+            }
+            sleep(&self.results[slot as usize].condition);
+        }
+
+        self.reserved &= !(1 << slot);
+        unsafe { self.released.notify(); }
+
+        result
+    }
+
+    fn ata_dma(&mut self, block: u64, sectors: usize, buf: usize, write: bool) -> Result<usize> {
+        // debugln!("AHCI {} DMA BLOCK: {:X} SECTORS: {} BUF: {:X} WRITE: {}", self.port_index, block, sectors, buf, write);
+
+        if sectors == 0 {
+            debugln!("Invalid request");
+            return Err(Error::new(EIO));
+        }
+
+        let physical_address = {
+            let contexts = ::env().contexts.lock();
+            let current = try!(contexts.current());
+            try!(current.translate(buf, sectors * 512))
+        };
+
+        let mut pending = VecDeque::new();
+        let mut success = true;
+        let mut sector = 0;
+        while sector < sectors {
+            // Reuse our own slots rather than waiting for other contexts to release theirs
+            if self.reserved.count_ones() >= self.slots && ! pending.is_empty() {
+                if let Some(slot) = pending.pop_front() {
+                    success = self.wait(slot) && success;
+                }
+                continue;
+            }
+
+            let count = cmp::min(sectors - sector, 255);
+            let slot = self.reserve();
+            self.port.ata_issue(slot, block + sector as u64, count, physical_address + sector * 512, write);
+            self.issued |= 1 << slot;
+            pending.push_back(slot);
+            sector += count;
+        }
+
+        while let Some(slot) = pending.pop_front() {
+            success = self.wait(slot) && success;
+        }
+
+        if success {
+            Ok(sectors * 512)
+        } else {
+            Err(Error::new(EIO))
         }
     }
 }
@@ -71,10 +240,16 @@ impl Disk for AhciDisk {
     }
 
     fn read(&mut self, block: u64, buffer: &mut [u8]) -> Result<usize> {
-        self.port.ata_dma(block, buffer.len() / 512, buffer.as_ptr() as usize, false)
+        self.ata_dma(block, buffer.len() / 512, buffer.as_ptr() as usize, false)
     }
 
     fn write(&mut self, block: u64, buffer: &[u8]) -> Result<usize> {
-        self.port.ata_dma(block, buffer.len() / 512, buffer.as_ptr() as usize, true)
+        self.ata_dma(block, buffer.len() / 512, buffer.as_ptr() as usize, true)
+    }
+
+    fn on_irq(&mut self, irq: u8) {
+        if irq == self.irq {
+            self.complete();
+        }
     }
 }
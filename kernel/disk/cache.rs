@@ -0,0 +1,224 @@
+use alloc::boxed::Box;
+
+use collections::BTreeMap;
+use collections::string::String;
+use collections::vec::Vec;
+
+use core::cmp;
+
+use disk::Disk;
+
+use system::error::{Error, Result, EIO};
+
+/// The size of a cached block
+const BLOCK_SIZE: usize = 512;
+
+/// A disk with a cache of its most recently used blocks. Writes go through to the disk. Reads
+/// and writes that end in a partial block only transfer the bytes requested, and both stop at the
+/// last whole block of the disk
+pub struct BlockCache {
+    disk: Box<Disk>,
+    /// The maximum number of cached blocks
+    capacity: usize,
+    /// Cached blocks, with the time they were last used
+    blocks: BTreeMap<u64, (u64, Box<[u8; BLOCK_SIZE]>)>,
+    /// Cached block numbers, by the time they were last used
+    lru: BTreeMap<u64, u64>,
+    /// Incremented on every access to order the cached blocks
+    time: u64,
+    /// Reads served from the cache
+    hits: u64,
+    /// Reads that had to go to the disk
+    misses: u64,
+}
+
+impl BlockCache {
+    /// Cache up to `capacity` blocks of a disk. At least one block is always cached
+    pub fn new(disk: Box<Disk>, capacity: usize) -> Self {
+        BlockCache {
+            disk: disk,
+            capacity: cmp::max(1, capacity),
+            blocks: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            time: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Get a cached block, reading it from the disk if it is not cached
+    fn cached(&mut self, block: u64) -> Result<&mut [u8; BLOCK_SIZE]> {
+        if self.blocks.contains_key(&block) {
+            self.hits += 1;
+        } else {
+            let mut data = box [0; BLOCK_SIZE];
+            if try!(self.disk.read(block, &mut *data)) != BLOCK_SIZE {
+                return Err(Error::new(EIO));
+            }
+            self.insert(block, data);
+        }
+
+        self.time += 1;
+        let time = self.time;
+
+        match self.blocks.get_mut(&block) {
+            Some(entry) => {
+                self.lru.remove(&entry.0);
+                self.lru.insert(time, block);
+                entry.0 = time;
+                Ok(&mut *entry.1)
+            },
+            None => Err(Error::new(EIO))
+        }
+    }
+
+    /// Cache a block that was read from the disk, evicting the least recently used block if the
+    /// cache is full
+    fn insert(&mut self, block: u64, data: Box<[u8; BLOCK_SIZE]>) {
+        self.misses += 1;
+        self.time += 1;
+        let time = self.time;
+
+        if self.blocks.len() >= self.capacity {
+            let oldest = self.lru.keys().next().map(|time| *time);
+            if let Some(oldest_block) = oldest.and_then(|oldest| self.lru.remove(&oldest)) {
+                self.blocks.remove(&oldest_block);
+            }
+        }
+
+        self.blocks.insert(block, (time, data));
+        self.lru.insert(time, block);
+    }
+
+    /// Replace the contents of a block if it is cached
+    fn update(&mut self, block: u64, data: &[u8]) {
+        if let Some(entry) = self.blocks.get_mut(&block) {
+            for (b, d) in entry.1.iter_mut().zip(data.iter()) {
+                *b = *d;
+            }
+        }
+    }
+
+    /// The number of whole blocks on the disk
+    fn disk_blocks(&self) -> u64 {
+        self.disk.size() / BLOCK_SIZE as u64
+    }
+}
+
+impl Disk for BlockCache {
+    fn name(&self) -> String {
+        self.disk.name()
+    }
+
+    fn size(&self) -> u64 {
+        self.disk.size()
+    }
+
+    fn read(&mut self, block: u64, buffer: &mut [u8]) -> Result<usize> {
+        let disk_blocks = self.disk_blocks();
+        if block >= disk_blocks {
+            return Ok(0);
+        }
+        let blocks = cmp::min(((buffer.len() + BLOCK_SIZE - 1) / BLOCK_SIZE) as u64, disk_blocks - block) as usize;
+
+        let mut count = 0;
+        let mut i = 0;
+        while i < blocks {
+            let start = i * BLOCK_SIZE;
+            if self.blocks.contains_key(&(block + i as u64)) {
+                let end = cmp::min(start + BLOCK_SIZE, buffer.len());
+                let data = try!(self.cached(block + i as u64));
+                for (b, d) in buffer[start .. end].iter_mut().zip(data.iter()) {
+                    *b = *d;
+                }
+                count += end - start;
+                i += 1;
+            } else {
+                // Blocks that are missing one after another are read with one disk request
+                let mut run = 1;
+                while i + run < blocks && ! self.blocks.contains_key(&(block + (i + run) as u64)) {
+                    run += 1;
+                }
+
+                let mut data = vec![0; run * BLOCK_SIZE];
+                if try!(self.disk.read(block + i as u64, &mut data)) != data.len() {
+                    return Err(Error::new(EIO));
+                }
+
+                for (j, chunk) in data.chunks(BLOCK_SIZE).enumerate() {
+                    let mut cached = box [0; BLOCK_SIZE];
+                    for (c, d) in cached.iter_mut().zip(chunk.iter()) {
+                        *c = *d;
+                    }
+                    self.insert(block + (i + j) as u64, cached);
+                }
+
+                let end = cmp::min(start + run * BLOCK_SIZE, buffer.len());
+                for (b, d) in buffer[start .. end].iter_mut().zip(data.iter()) {
+                    *b = *d;
+                }
+                count += end - start;
+                i += run;
+            }
+        }
+
+        Ok(count)
+    }
+
+    fn write(&mut self, block: u64, buffer: &[u8]) -> Result<usize> {
+        let disk_blocks = self.disk_blocks();
+        if block >= disk_blocks {
+            return Ok(0);
+        }
+
+        let whole = cmp::min((buffer.len() / BLOCK_SIZE) as u64, disk_blocks - block) as usize;
+        let mut count = 0;
+        if whole > 0 {
+            count = try!(self.disk.write(block, &buffer[.. whole * BLOCK_SIZE]));
+            for i in 0..count / BLOCK_SIZE {
+                self.update(block + i as u64, &buffer[i * BLOCK_SIZE .. (i + 1) * BLOCK_SIZE]);
+            }
+            if count < whole * BLOCK_SIZE {
+                return Ok(count);
+            }
+        }
+
+        // A trailing partial block is merged with its current contents
+        let rest = &buffer[count ..];
+        let block = block + whole as u64;
+        if ! rest.is_empty() && rest.len() < BLOCK_SIZE && block < disk_blocks {
+            let mut data = *try!(self.cached(block));
+            for (d, b) in data.iter_mut().zip(rest.iter()) {
+                *d = *b;
+            }
+            if try!(self.disk.write(block, &data)) != BLOCK_SIZE {
+                return Err(Error::new(EIO));
+            }
+            self.update(block, &data);
+            count += rest.len();
+        }
+
+        Ok(count)
+    }
+
+    fn invalidate(&mut self, block: u64, count: u64) {
+        let blocks: Vec<u64> = self.blocks.keys()
+                                   .filter(|&&cached| cached >= block && cached - block < count)
+                                   .map(|cached| *cached)
+                                   .collect();
+        for cached in blocks {
+            if let Some((time, _)) = self.blocks.remove(&cached) {
+                self.lru.remove(&time);
+            }
+        }
+        self.disk.invalidate(block, count);
+    }
+
+    fn cache_stats(&self) -> Option<(u64, u64)> {
+        Some((self.hits, self.misses))
+    }
+
+    fn on_irq(&mut self, irq: u8) {
+        self.disk.on_irq(irq);
+    }
+}
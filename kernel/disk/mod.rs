@@ -3,11 +3,25 @@ use collections::string::String;
 use system::error::Result;
 
 pub mod ahci;
+pub mod cache;
 pub mod ide;
+pub mod partition;
 
 pub trait Disk {
     fn name(&self) -> String;
     fn size(&self) -> u64;
     fn read(&mut self, block: u64, buffer: &mut [u8]) -> Result<usize>;
     fn write(&mut self, block: u64, buffer: &[u8]) -> Result<usize>;
+
+    /// Drop any cached copies of a range of blocks, so they are read from the disk again
+    fn invalidate(&mut self, _block: u64, _count: u64) {}
+
+    /// The number of reads served from a cache and the number that missed it, if the disk is
+    /// cached
+    fn cache_stats(&self) -> Option<(u64, u64)> {
+        None
+    }
+
+    /// Handle an interrupt, which may be from the disk controller
+    fn on_irq(&mut self, _irq: u8) {}
 }
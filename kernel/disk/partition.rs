@@ -0,0 +1,182 @@
+use alloc::arc::Arc;
+use alloc::boxed::Box;
+
+use collections::string::String;
+use collections::vec::Vec;
+
+use core::cmp;
+
+use disk::Disk;
+
+use sync::Intex;
+
+use system::error::Result;
+
+/// MBR partition type of a GPT protective MBR
+const MBR_TYPE_GPT: u8 = 0xEE;
+/// MBR partition types of extended partitions, which only hold logical partitions
+const MBR_TYPE_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+/// The most GPT entries read, which is also the number of entries a GPT usually has
+const GPT_ENTRIES_MAX: usize = 128;
+
+/// A partition of a disk, addressed relative to its first block
+pub struct Partition {
+    pub disk: Arc<Intex<Box<Disk>>>,
+    /// The partition number, starting at 1
+    pub number: usize,
+    /// The first block of the partition on the disk
+    pub start: u64,
+    /// The number of blocks in the partition
+    pub blocks: u64,
+}
+
+impl Partition {
+    /// Find the partitions of a disk. A GPT is used if the MBR is protective, otherwise the
+    /// primary MBR entries are used. Invalid or absent tables result in no partitions
+    pub fn find(disk: &Arc<Intex<Box<Disk>>>) -> Vec<Partition> {
+        let mut partitions = Vec::new();
+
+        let disk_blocks = disk.lock().size() / 512;
+
+        let mut mbr = [0; 512];
+        if disk.lock().read(0, &mut mbr).ok() != Some(512) || mbr[510] != 0x55 || mbr[511] != 0xAA {
+            return partitions;
+        }
+
+        let mut entries = Vec::new();
+        if mbr[446 + 4] == MBR_TYPE_GPT {
+            entries = gpt_entries(disk);
+        } else {
+            for i in 0..4 {
+                let entry = &mbr[446 + i * 16 .. 446 + (i + 1) * 16];
+                let kind = entry[4];
+                let start = read_u32(&entry[8..]) as u64;
+                let blocks = read_u32(&entry[12..]) as u64;
+                if kind != 0 && ! MBR_TYPE_EXTENDED.contains(&kind) && start > 0 && blocks > 0 {
+                    entries.push((i + 1, start, blocks));
+                }
+            }
+        }
+
+        for (number, start, blocks) in entries {
+            if start < disk_blocks && blocks <= disk_blocks - start {
+                partitions.push(Partition {
+                    disk: disk.clone(),
+                    number: number,
+                    start: start,
+                    blocks: blocks,
+                });
+            }
+        }
+
+        partitions
+    }
+}
+
+impl Disk for Partition {
+    fn name(&self) -> String {
+        format!("{} partition {}", self.disk.lock().name(), self.number)
+    }
+
+    fn size(&self) -> u64 {
+        self.blocks * 512
+    }
+
+    fn read(&mut self, block: u64, buffer: &mut [u8]) -> Result<usize> {
+        if block >= self.blocks {
+            return Ok(0);
+        }
+
+        let len = cmp::min(buffer.len() as u64, (self.blocks - block) * 512) as usize;
+        self.disk.lock().read(self.start + block, &mut buffer[.. len])
+    }
+
+    fn write(&mut self, block: u64, buffer: &[u8]) -> Result<usize> {
+        if block >= self.blocks {
+            return Ok(0);
+        }
+
+        let len = cmp::min(buffer.len() as u64, (self.blocks - block) * 512) as usize;
+        self.disk.lock().write(self.start + block, &buffer[.. len])
+    }
+
+    fn invalidate(&mut self, block: u64, count: u64) {
+        if block < self.blocks {
+            let count = cmp::min(count, self.blocks - block);
+            self.disk.lock().invalidate(self.start + block, count);
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    read_u32(bytes) as u64 | (read_u32(&bytes[4..]) as u64) << 32
+}
+
+/// The CRC-32 of the bytes, which GPT uses for its header and its entries
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes.iter() {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                crc >> 1 ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Read the used entries of a GPT as (number, start, blocks). The header and the entries must
+/// match their checksums, and at most `GPT_ENTRIES_MAX` entries are accepted
+fn gpt_entries(disk: &Arc<Intex<Box<Disk>>>) -> Vec<(usize, u64, u64)> {
+    let mut entries = Vec::new();
+
+    let mut header = [0; 512];
+    if disk.lock().read(1, &mut header).ok() != Some(512) || &header[0..8] != b"EFI PART" {
+        return entries;
+    }
+
+    // The checksum of the header is calculated with its own field zeroed
+    let header_size = read_u32(&header[12..]) as usize;
+    let header_crc = read_u32(&header[16..]);
+    if header_size < 92 || header_size > 512 {
+        return entries;
+    }
+    for b in header[16..20].iter_mut() {
+        *b = 0;
+    }
+    if crc32(&header[.. header_size]) != header_crc {
+        return entries;
+    }
+
+    let table = read_u64(&header[72..]);
+    let count = read_u32(&header[80..]) as usize;
+    let size = read_u32(&header[84..]) as usize;
+    let table_crc = read_u32(&header[88..]);
+    if count > GPT_ENTRIES_MAX || size < 128 || size > 512 || 512 % size != 0 {
+        return entries;
+    }
+
+    let mut data = vec![0; (count * size + 511) / 512 * 512];
+    if disk.lock().read(table, &mut data).ok() != Some(data.len()) || crc32(&data[.. count * size]) != table_crc {
+        return entries;
+    }
+
+    for (i, entry) in data[.. count * size].chunks(size).enumerate() {
+        if entry[..16].iter().any(|&b| b != 0) {
+            let first = read_u64(&entry[32..]);
+            let last = read_u64(&entry[40..]);
+            if first > 0 && last >= first {
+                entries.push((i + 1, first, last - first + 1));
+            }
+        }
+    }
+
+    entries
+}
@@ -1,14 +1,47 @@
 /// Public keyboard layouts
 /// The layout can be:
-/// *   English
-/// *   French
+/// *   English (US)
+/// *   English (UK)
+/// *   Dvorak
+/// *   French (AZERTY)
 /// *   German
+///
+/// Each key of a layout holds the characters without a modifier, with shift, and with AltGr.
+/// Characters are Unicode, so those outside of ASCII are read from the console as multi-byte UTF-8
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Layout {
     English,
+    EnglishUk,
+    Dvorak,
     French,
     German,
 }
 
+impl Layout {
+    /// Get a layout by the name used to select it, such as `us` or `azerty`
+    pub fn from_name(name: &str) -> Option<Layout> {
+        match name {
+            "us" => Some(Layout::English),
+            "uk" => Some(Layout::EnglishUk),
+            "dvorak" => Some(Layout::Dvorak),
+            "azerty" | "fr" => Some(Layout::French),
+            "de" => Some(Layout::German),
+            _ => None,
+        }
+    }
+
+    /// The name used to select the layout
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Layout::English => "us",
+            Layout::EnglishUk => "uk",
+            Layout::Dvorak => "dvorak",
+            Layout::French => "azerty",
+            Layout::German => "de",
+        }
+    }
+}
+
 /// Function to get the scancode from the current layout
 ///
 /// # Example
@@ -21,6 +54,8 @@ pub enum Layout {
 pub fn get_scancode_from_layout(layout: &Layout, scancode: u8) -> [char; 3] {
     match *layout {
         Layout::English => SCANCODES_EN[scancode as usize],
+        Layout::EnglishUk => SCANCODES_UK[scancode as usize],
+        Layout::Dvorak => SCANCODES_DVORAK[scancode as usize],
         Layout::French => SCANCODES_FR[scancode as usize],
         Layout::German => SCANCODES_DE[scancode as usize],
     }
@@ -29,6 +64,8 @@ pub fn get_scancode_from_layout(layout: &Layout, scancode: u8) -> [char; 3] {
 fn get_special_keys_from_layout(layout: &Layout, scancode: u8) -> [char; 3] {
     let keys: &[(u8, [char; 3])] = match *layout {
         Layout::English => SCANCODES_EXTRA_EN,
+        Layout::EnglishUk => SCANCODES_EXTRA_UK,
+        Layout::Dvorak => SCANCODES_EXTRA_DVORAK,
         Layout::French => SCANCODES_EXTRA_FR,
         Layout::German => SCANCODES_EXTRA_DE,
     };
@@ -125,6 +162,132 @@ static SCANCODES_EN: [[char; 3]; 58] = [['\0', '\0', '\0'],
 /// Special keys, not present on every keyboard
 static SCANCODES_EXTRA_EN: &'static [(u8, [char; 3])] = &[];
 
+/// Scancodes for English (UK) keyboards
+static SCANCODES_UK: [[char; 3]; 58] = [['\0', '\0', '\0'],
+                                        ['\x1B', '\x1B', '\x1B'],
+                                        ['1', '!', '1'],
+                                        ['2', '"', '2'],
+                                        ['3', '£', '3'],
+                                        ['4', '$', '4'],
+                                        ['5', '%', '5'],
+                                        ['6', '^', '6'],
+                                        ['7', '&', '7'],
+                                        ['8', '*', '8'],
+                                        ['9', '(', '9'],
+                                        ['0', ')', '0'],
+                                        ['-', '_', '-'],
+                                        ['=', '+', '='],
+                                        ['\0', '\0', '\0'],
+                                        ['\t', '\t', '\t'],
+                                        ['q', 'Q', 'q'],
+                                        ['w', 'W', 'w'],
+                                        ['e', 'E', 'e'],
+                                        ['r', 'R', 'r'],
+                                        ['t', 'T', 't'],
+                                        ['y', 'Y', 'y'],
+                                        ['u', 'U', 'u'],
+                                        ['i', 'I', 'i'],
+                                        ['o', 'O', 'o'],
+                                        ['p', 'P', 'p'],
+                                        ['[', '{', '['],
+                                        [']', '}', ']'],
+                                        ['\n', '\n', '\n'],
+                                        ['\0', '\0', '\0'],
+                                        ['a', 'A', 'a'],
+                                        ['s', 'S', 's'],
+                                        ['d', 'D', 'd'],
+                                        ['f', 'F', 'f'],
+                                        ['g', 'G', 'g'],
+                                        ['h', 'H', 'h'],
+                                        ['j', 'J', 'j'],
+                                        ['k', 'K', 'k'],
+                                        ['l', 'L', 'l'],
+                                        [';', ':', ';'],
+                                        ['\'', '@', '\''],
+                                        ['`', '¬', '¦'],
+                                        ['\0', '\0', '\0'],
+                                        ['#', '~', '#'],
+                                        ['z', 'Z', 'z'],
+                                        ['x', 'X', 'x'],
+                                        ['c', 'C', 'c'],
+                                        ['v', 'V', 'v'],
+                                        ['b', 'B', 'b'],
+                                        ['n', 'N', 'n'],
+                                        ['m', 'M', 'm'],
+                                        [',', '<', ','],
+                                        ['.', '>', '.'],
+                                        ['/', '?', '/'],
+                                        ['\0', '\0', '\0'],
+                                        ['\0', '\0', '\0'],
+                                        ['\0', '\0', '\0'],
+                                        [' ', ' ', ' ']];
+
+/// Special keys, not present on every keyboard
+static SCANCODES_EXTRA_UK: &'static [(u8, [char; 3])] = &[(0x56, ['\\', '|', '\\'])];
+
+/// Scancodes for Dvorak keyboards
+static SCANCODES_DVORAK: [[char; 3]; 58] = [['\0', '\0', '\0'],
+                                            ['\x1B', '\x1B', '\x1B'],
+                                            ['1', '!', '1'],
+                                            ['2', '@', '2'],
+                                            ['3', '#', '3'],
+                                            ['4', '$', '4'],
+                                            ['5', '%', '5'],
+                                            ['6', '^', '6'],
+                                            ['7', '&', '7'],
+                                            ['8', '*', '8'],
+                                            ['9', '(', '9'],
+                                            ['0', ')', '0'],
+                                            ['[', '{', '['],
+                                            [']', '}', ']'],
+                                            ['\0', '\0', '\0'],
+                                            ['\t', '\t', '\t'],
+                                            ['\'', '"', '\''],
+                                            [',', '<', ','],
+                                            ['.', '>', '.'],
+                                            ['p', 'P', 'p'],
+                                            ['y', 'Y', 'y'],
+                                            ['f', 'F', 'f'],
+                                            ['g', 'G', 'g'],
+                                            ['c', 'C', 'c'],
+                                            ['r', 'R', 'r'],
+                                            ['l', 'L', 'l'],
+                                            ['/', '?', '/'],
+                                            ['=', '+', '='],
+                                            ['\n', '\n', '\n'],
+                                            ['\0', '\0', '\0'],
+                                            ['a', 'A', 'a'],
+                                            ['o', 'O', 'o'],
+                                            ['e', 'E', 'e'],
+                                            ['u', 'U', 'u'],
+                                            ['i', 'I', 'i'],
+                                            ['d', 'D', 'd'],
+                                            ['h', 'H', 'h'],
+                                            ['t', 'T', 't'],
+                                            ['n', 'N', 'n'],
+                                            ['s', 'S', 's'],
+                                            ['-', '_', '-'],
+                                            ['`', '~', '`'],
+                                            ['\0', '\0', '\0'],
+                                            ['\\', '|', '\\'],
+                                            [';', ':', ';'],
+                                            ['q', 'Q', 'q'],
+                                            ['j', 'J', 'j'],
+                                            ['k', 'K', 'k'],
+                                            ['x', 'X', 'x'],
+                                            ['b', 'B', 'b'],
+                                            ['m', 'M', 'm'],
+                                            ['w', 'W', 'w'],
+                                            ['v', 'V', 'v'],
+                                            ['z', 'Z', 'z'],
+                                            ['\0', '\0', '\0'],
+                                            ['\0', '\0', '\0'],
+                                            ['\0', '\0', '\0'],
+                                            [' ', ' ', ' ']];
+
+/// Special keys, not present on every keyboard
+static SCANCODES_EXTRA_DVORAK: &'static [(u8, [char; 3])] = &[];
+
 /// Scancodes for French keyboards
 static SCANCODES_FR: [[char; 3]; 58] = [['\0', '\0', '\0'],
                                         ['\x1B', '\x1B', '\0'],
@@ -24,28 +24,64 @@ use usb::xhci::Xhci;
 
 /// PCI device
 pub unsafe fn pci_device(env: &mut Environment,
-                         pci: PciConfig,
+                         mut pci: PciConfig,
                          class_id: u8,
                          subclass_id: u8,
                          interface_id: u8,
                          vendor_code: u16,
                          device_code: u16) {
-    match (class_id, subclass_id, interface_id) {
-        (MASS_STORAGE, IDE, _) => env.disks.lock().append(&mut Ide::disks(pci)),
-        (MASS_STORAGE, SATA, AHCI) => env.disks.lock().append(&mut Ahci::disks(pci)),
-        (SERIAL_BUS, USB, UHCI) => env.schemes.lock().push(Uhci::new(pci)),
-        (SERIAL_BUS, USB, OHCI) => env.schemes.lock().push(Ohci::new(pci)),
-        (SERIAL_BUS, USB, EHCI) => env.schemes.lock().push(Ehci::new(pci)),
-        (SERIAL_BUS, USB, XHCI) => env.schemes.lock().push(Xhci::new(pci)),
+    let irq = pci.read(0x3C) as u8 & 0xF;
+
+    let name = match (class_id, subclass_id, interface_id) {
+        (MASS_STORAGE, IDE, _) => {
+            env.disks.lock().append(&mut Ide::disks(pci));
+            "IDE"
+        },
+        (MASS_STORAGE, SATA, AHCI) => {
+            env.disks.lock().append(&mut Ahci::disks(pci));
+            "AHCI"
+        },
+        (SERIAL_BUS, USB, UHCI) => {
+            env.register(Uhci::new(pci));
+            "UHCI"
+        },
+        (SERIAL_BUS, USB, OHCI) => {
+            env.register(Ohci::new(pci));
+            "OHCI"
+        },
+        (SERIAL_BUS, USB, EHCI) => {
+            env.register(Ehci::new(pci));
+            "EHCI"
+        },
+        (SERIAL_BUS, USB, XHCI) => {
+            env.register(Xhci::new(pci));
+            "XHCI"
+        },
         _ => match (vendor_code, device_code) {
-            (REALTEK, RTL8139) => env.schemes.lock().push(Rtl8139::new(pci)),
-            (INTEL, GBE_82540EM) => env.schemes.lock().push(Intel8254x::new(pci)),
-            (INTEL, AC97_82801AA) => env.schemes.lock().push(Ac97::new(pci)),
-            (INTEL, AC97_ICH4) => env.schemes.lock().push(Ac97::new(pci)),
-            (INTEL, INTELHDA_ICH6) => env.schemes.lock().push(IntelHda::new(pci)),
-            _ => debugln!(" ? CLASS {:02X}.{:02X}.{:02X} ID {:04X}:{:04X}", class_id, subclass_id, interface_id, vendor_code, device_code),
+            (REALTEK, RTL8139) => {
+                env.register(Rtl8139::new(pci));
+                "RTL8139"
+            },
+            (INTEL, GBE_82540EM) => {
+                env.register(Intel8254x::new(pci));
+                "Intel 8254x"
+            },
+            (INTEL, AC97_82801AA) | (INTEL, AC97_ICH4) => {
+                env.register(Ac97::new(pci));
+                "AC97"
+            },
+            (INTEL, INTELHDA_ICH6) => {
+                env.register(IntelHda::new(pci));
+                "Intel HDA"
+            },
+            _ => {
+                debugln!(" ? CLASS {:02X}.{:02X}.{:02X} ID {:04X}:{:04X}", class_id, subclass_id, interface_id, vendor_code, device_code);
+                return;
+            },
         }
-    }
+    };
+
+    env.name_irq(irq, name);
 }
 
 /// Initialize PCI session
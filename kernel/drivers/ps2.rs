@@ -1,6 +1,7 @@
+use alloc::arc::Arc;
 use alloc::boxed::Box;
 
-use core::cmp;
+use core::{cmp, str};
 
 use common::event::{KeyEvent, MouseEvent};
 
@@ -8,10 +9,66 @@ use drivers::io::{Io, Pio, ReadOnly, WriteOnly};
 
 use graphics::display::VBEMODEINFO;
 
-use fs::KScheme;
+use fs::{KScheme, Resource, Url};
 
 use drivers::kb_layouts::layouts;
 
+use sync::Intex;
+
+use system::error::{Error, Result, EINVAL, ENOENT};
+
+/// The keyboard layout, at `ps2:layout`
+///
+/// Reading returns the name of the layout, such as `us`. Writing a name selects that layout, and
+/// fails with `EINVAL` if there is no such layout.
+pub struct LayoutResource {
+    layout: Arc<Intex<layouts::Layout>>,
+    seek: usize,
+}
+
+impl Resource for LayoutResource {
+    fn dup(&self) -> Result<Box<Resource>> {
+        Ok(box LayoutResource {
+            layout: self.layout.clone(),
+            seek: self.seek,
+        })
+    }
+
+    fn path(&self, buf: &mut [u8]) -> Result<usize> {
+        let path = b"ps2:layout";
+
+        for (b, p) in buf.iter_mut().zip(path.iter()) {
+            *b = *p;
+        }
+
+        Ok(cmp::min(buf.len(), path.len()))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let name = format!("{}\n", self.layout.lock().name());
+
+        let mut i = 0;
+        while i < buf.len() && self.seek < name.len() {
+            buf[i] = name.as_bytes()[self.seek];
+            self.seek += 1;
+            i += 1;
+        }
+
+        Ok(i)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let name = try!(str::from_utf8(buf).or(Err(Error::new(EINVAL))));
+        match layouts::Layout::from_name(name.trim()) {
+            Some(layout) => {
+                *self.layout.lock() = layout;
+                Ok(buf.len())
+            },
+            None => Err(Error::new(EINVAL)),
+        }
+    }
+}
+
 pub struct Ps2Keyboard<'a> {
     bus: &'a mut Ps2
 }
@@ -26,6 +83,74 @@ impl<'a> Ps2Keyboard<'a> {
     }
 }
 
+/// The device ID of a mouse with 3 byte packets
+pub const MOUSE_ID_STANDARD: u8 = 0;
+/// The device ID of a mouse with a scroll wheel, which sends 4 byte packets
+pub const MOUSE_ID_WHEEL: u8 = 3;
+/// The device ID of a mouse with a scroll wheel and 5 buttons, which sends 4 byte packets
+pub const MOUSE_ID_FIVE_BUTTONS: u8 = 4;
+
+/// The movement and buttons of one mouse packet
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MousePacket {
+    pub dx: i32,
+    pub dy: i32,
+    pub dz: i32,
+    /// The buttons, left, right, middle, fourth and fifth from the lowest bit
+    pub buttons: u8,
+}
+
+/// The length of the packets sent by a mouse with this device ID
+pub fn mouse_packet_len(id: u8) -> usize {
+    if id == MOUSE_ID_WHEEL || id == MOUSE_ID_FIVE_BUTTONS {
+        4
+    } else {
+        3
+    }
+}
+
+/// Parse a packet of `mouse_packet_len(id)` bytes, returning `None` if it is not a valid packet,
+/// which means the stream is out of sync
+pub fn mouse_packet(packet: &[u8], id: u8) -> Option<MousePacket> {
+    if packet.len() < mouse_packet_len(id) || packet[0] & 0x8 != 0x8 {
+        return None;
+    }
+
+    // Deltas that overflowed are meaningless, so they are dropped
+    let dx = if packet[0] & 0x40 != 0x40 && packet[1] != 0 {
+        packet[1] as i32 - (((packet[0] as i32) << 4) & 0x100)
+    } else {
+        0
+    };
+
+    // The mouse counts y upwards, the screen downwards
+    let dy = if packet[0] & 0x80 != 0x80 && packet[2] != 0 {
+        (((packet[0] as i32) << 3) & 0x100) - packet[2] as i32
+    } else {
+        0
+    };
+
+    let mut dz = 0;
+    let mut buttons = packet[0] & 0x7;
+    if id == MOUSE_ID_WHEEL {
+        dz = packet[3] as i8 as i32;
+    } else if id == MOUSE_ID_FIVE_BUTTONS {
+        // The top two bits are always clear, so anything else is a byte from another packet
+        if packet[3] & 0xC0 != 0 {
+            return None;
+        }
+        dz = (((packet[3] << 4) as i8) >> 4) as i32;
+        buttons |= (packet[3] >> 1) & 0x18;
+    }
+
+    Some(MousePacket {
+        dx: dx,
+        dy: dy,
+        dz: dz,
+        buttons: buttons,
+    })
+}
+
 pub struct Ps2Mouse<'a> {
     bus: &'a mut Ps2
 }
@@ -37,6 +162,19 @@ impl<'a> Ps2Mouse<'a> {
         self.bus.wait_read();
         self.bus.data.read()
     }
+
+    /// Set the sample rate
+    fn sample_rate(&mut self, rate: u8) {
+        self.cmd(0xF3);
+        self.cmd(rate);
+    }
+
+    /// Get the device ID
+    fn id(&mut self) -> u8 {
+        self.cmd(0xF2);
+        self.bus.wait_read();
+        self.bus.data.read()
+    }
 }
 
 /// PS2
@@ -57,17 +195,21 @@ pub struct Ps2 {
     caps_lock_toggle: bool,
     /// AltGr?
     altgr: bool,
+    /// Control?
+    ctrl: bool,
     /// The mouse packet
     mouse_packet: [u8; 4],
+    /// The device ID of the mouse, which decides the packet length
+    mouse_id: u8,
     /// Mouse packet index
     mouse_i: usize,
     /// Mouse point x
     mouse_x: i32,
     /// Mouse point y
     mouse_y: i32,
-    /// Layout for keyboard
+    /// Layout for keyboard, shared with the layout resource
     /// Default: English
-    layout: layouts::Layout,
+    layout: Arc<Intex<layouts::Layout>>,
 }
 
 impl Ps2 {
@@ -82,11 +224,13 @@ impl Ps2 {
             caps_lock: false,
             caps_lock_toggle: false,
             altgr: false,
+            ctrl: false,
             mouse_packet: [0; 4],
+            mouse_id: MOUSE_ID_STANDARD,
             mouse_i: 0,
             mouse_x: 0,
             mouse_y: 0,
-            layout: layouts::Layout::English,
+            layout: Arc::new(Intex::new(layouts::Layout::English)),
         };
 
         module.init();
@@ -188,7 +332,7 @@ impl Ps2 {
 
         {
             // Reset
-            debug!("     - Reset {:X}", self.keyboard().cmd(0xFF));
+            debug!("     - Reset {:X}", self.mouse().cmd(0xFF));
             self.wait_read();
             debugln!(", {:X}", self.data.read());
 
@@ -203,6 +347,33 @@ impl Ps2 {
                 debugln!("Extra {}: {:X}", line!(), self.data.read());
             }
 
+            // The magic sample rates 200, 100, 80 enable the scroll wheel, then 200, 200, 80 enable
+            // the extra buttons. A mouse without them ignores this and keeps its ID
+            {
+                let mut mouse = self.mouse();
+                mouse.sample_rate(200);
+                mouse.sample_rate(100);
+                mouse.sample_rate(80);
+                let mut id = mouse.id();
+                if id == MOUSE_ID_WHEEL {
+                    mouse.sample_rate(200);
+                    mouse.sample_rate(200);
+                    mouse.sample_rate(80);
+                    id = mouse.id();
+                }
+                // Restore the default sample rate
+                mouse.sample_rate(100);
+                mouse.bus.mouse_id = match id {
+                    MOUSE_ID_WHEEL | MOUSE_ID_FIVE_BUTTONS => id,
+                    _ => MOUSE_ID_STANDARD,
+                };
+            }
+            debugln!("     - ID {:X}", self.mouse_id);
+
+            while self.sts.readf(1) {
+                debugln!("Extra {}: {:X}", line!(), self.data.read());
+            }
+
             // Enable Streaming
             debugln!("     - Enable streaming {:X}", self.mouse().cmd(0xF4));
 
@@ -242,12 +413,20 @@ impl Ps2 {
             if self.caps_lock && !self.caps_lock_toggle {
                 self.caps_lock = false;
             }
+        } else if scancode == 0x1D {
+            self.ctrl = true;
+        } else if scancode == 0x9D {
+            self.ctrl = false;
         } else if scancode == 0xE0 {
             let scancode_byte_2 = self.data.read();
             if scancode_byte_2 == 0x38 {
                 self.altgr = true;
             } else if scancode_byte_2 == 0xB8 {
                 self.altgr = false;
+            } else if scancode_byte_2 == 0x1D {
+                self.ctrl = true;
+            } else if scancode_byte_2 == 0x9D {
+                self.ctrl = false;
             } else {
                 scancode = scancode_byte_2;
             }
@@ -255,8 +434,18 @@ impl Ps2 {
 
         let shift = self.caps_lock != (self.lshift || self.rshift);
 
+        let layout = *self.layout.lock();
+        let mut character = layouts::char_for_scancode(scancode & 0x7F, shift, self.altgr, &layout);
+        if self.ctrl {
+            // Control characters, such as Ctrl+C as ETX
+            match character {
+                'a' ... 'z' | 'A' ... 'Z' => character = ((character as u8) & 0x1F) as char,
+                _ => (),
+            }
+        }
+
         return Some(KeyEvent {
-            character: layouts::char_for_scancode(scancode & 0x7F, shift, self.altgr, &self.layout),
+            character: character,
             scancode: scancode & 0x7F,
             pressed: scancode < 0x80,
         });
@@ -264,69 +453,71 @@ impl Ps2 {
 
     /// Mouse interrupt
     pub fn mouse_interrupt(&mut self, byte: u8) -> Option<MouseEvent> {
-        if self.mouse_i == 0 {
-            if byte & 0x8 == 0x8 {
-                self.mouse_packet[0] = byte;
-                self.mouse_i += 1;
-            }
-        } else if self.mouse_i == 1 {
-            self.mouse_packet[1] = byte;
-
-            self.mouse_i += 1;
-        } else {
-            self.mouse_packet[2] = byte;
+        // Bytes that cannot start a packet are dropped until the stream is in sync again
+        if self.mouse_i == 0 && byte & 0x8 != 0x8 {
+            return None;
+        }
 
-            let left_button = (self.mouse_packet[0] & 1) == 1;
-            let right_button = (self.mouse_packet[0] & 2) == 2;
-            let middle_button = (self.mouse_packet[0] & 4) == 4;
+        self.mouse_packet[self.mouse_i] = byte;
+        self.mouse_i += 1;
 
-            let x;
-            if (self.mouse_packet[0] & 0x40) != 0x40 && self.mouse_packet[1] != 0 {
-                x = (self.mouse_packet[1] as isize -
-                     (((self.mouse_packet[0] as isize) << 4) & 0x100)) as i32;
-            } else {
-                x = 0;
-            }
+        let len = mouse_packet_len(self.mouse_id);
+        if self.mouse_i < len {
+            return None;
+        }
+        self.mouse_i = 0;
 
-            let y;
-            if (self.mouse_packet[0] & 0x80) != 0x80 && self.mouse_packet[2] != 0 {
-                y = ((((self.mouse_packet[0] as isize) << 3) & 0x100) -
-                     self.mouse_packet[2] as isize) as i32;
-            } else {
-                y = 0;
-            }
+        match mouse_packet(&self.mouse_packet[.. len], self.mouse_id) {
+            Some(packet) => {
+                if let Some(mode_info) = unsafe { VBEMODEINFO } {
+                    self.mouse_x = cmp::max(0, cmp::min(mode_info.xresolution as i32, self.mouse_x + packet.dx));
+                    self.mouse_y = cmp::max(0, cmp::min(mode_info.yresolution as i32, self.mouse_y + packet.dy));
+                }
 
-            if let Some(mode_info) = unsafe { VBEMODEINFO } {
-                self.mouse_x = cmp::max(0, cmp::min(mode_info.xresolution as i32, self.mouse_x + x));
-                self.mouse_y = cmp::max(0, cmp::min(mode_info.yresolution as i32, self.mouse_y + y));
+                Some(MouseEvent {
+                    x: self.mouse_x,
+                    y: self.mouse_y,
+                    left_button: packet.buttons & 1 == 1,
+                    right_button: packet.buttons & 2 == 2,
+                    middle_button: packet.buttons & 4 == 4,
+                    fourth_button: packet.buttons & 8 == 8,
+                    fifth_button: packet.buttons & 0x10 == 0x10,
+                    wheel: packet.dz,
+                })
+            },
+            None => {
+                // Keep the bytes after the first one that could start a packet
+                for i in 1..len {
+                    if self.mouse_packet[i] & 0x8 == 0x8 {
+                        for j in i..len {
+                            self.mouse_packet[j - i] = self.mouse_packet[j];
+                        }
+                        self.mouse_i = len - i;
+                        break;
+                    }
+                }
+                None
             }
-
-            self.mouse_i = 0;
-
-            return Some(MouseEvent {
-                x: self.mouse_x,
-                y: self.mouse_y,
-                left_button: left_button,
-                right_button: right_button,
-                middle_button: middle_button,
-            });
         }
+    }
+}
 
-        return None;
+impl KScheme for Ps2 {
+    fn scheme(&self) -> &str {
+        "ps2"
     }
 
-    /// Function to change the layout of the keyboard
-    pub fn change_layout(&mut self, layout: usize) {
-        self.layout = match layout {
-            0 => layouts::Layout::English,
-            1 => layouts::Layout::French,
-            2 => layouts::Layout::German,
-            _ => layouts::Layout::English,
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        if url.reference().trim_matches('/') == "layout" {
+            Ok(box LayoutResource {
+                layout: self.layout.clone(),
+                seek: 0,
+            })
+        } else {
+            Err(Error::new(ENOENT))
         }
     }
-}
 
-impl KScheme for Ps2 {
     fn on_irq(&mut self, irq: u8) {
         if irq == 0xC || irq == 0x1 {
             loop {
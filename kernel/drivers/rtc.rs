@@ -1,7 +1,10 @@
-use common::time::Duration;
+use common::time::{Duration, NANOS_PER_SEC};
 
 use drivers::io::{Io, Pio};
 
+/// How often the real time, advanced by the PIT, is corrected from the RTC, in seconds
+pub const RTC_SYNC_SECS: i64 = 60;
+
 fn cvt_bcd(value: usize) -> usize {
     (value & 0xF) + ((value / 16) * 10)
 }
@@ -33,8 +36,23 @@ impl Rtc {
         while self.read(0xA) & 0x80 == 0x80 {}
     }
 
-    /// Get time
+    /// Get time, waiting for the next update of the clock
     pub fn time(&mut self) -> Duration {
+        unsafe { self.wait() };
+        self.read_time()
+    }
+
+    /// Get time without waiting, or `None` if the clock is being updated
+    pub fn try_time(&mut self) -> Option<Duration> {
+        if unsafe { self.read(0xA) } & 0x80 == 0x80 {
+            None
+        } else {
+            Some(self.read_time())
+        }
+    }
+
+    /// Read the time registers
+    fn read_time(&mut self) -> Duration {
         let mut second;
         let mut minute;
         let mut hour;
@@ -43,7 +61,6 @@ impl Rtc {
         let mut year;
         let register_b;
         unsafe {
-            self.wait();
             second = self.read(0) as usize;
             minute = self.read(2) as usize;
             hour = self.read(4) as usize;
@@ -103,3 +120,16 @@ impl Rtc {
         Duration::new(secs, 0)
     }
 }
+
+/// Correct the real time from the time read from the RTC. The RTC counts whole seconds, so the
+/// real time is only moved when it is outside the second read, to the nearest end of it.
+pub fn rtc_correct(realtime: Duration, rtc: Duration) -> Duration {
+    let end = rtc + Duration::new(0, NANOS_PER_SEC - 1);
+    if realtime < rtc {
+        rtc
+    } else if realtime > end {
+        end
+    } else {
+        realtime
+    }
+}
@@ -1,107 +1,252 @@
+use alloc::arc::Arc;
 use alloc::boxed::Box;
 
-use collections::string::String;
+use collections::String;
 
 use common::event;
 
+use core::cmp;
+
 use drivers::io::{Io, Pio};
 
-use fs::KScheme;
+use fs::{KScheme, Resource, Url};
+
+use sync::WaitQueue;
+
+use system::error::Result;
+use system::syscall::{POLLIN, POLLOUT};
+
+/// The I/O port of the first serial port
+pub const COM1: u16 = 0x3F8;
+/// The I/O port of the second serial port
+pub const COM2: u16 = 0x2F8;
 
-#[repr(packed)]
-struct SerialInfo {
-    pub ports: [u16; 4],
+/// The baud rate the serial ports are set up with
+pub const SERIAL_BAUD: u32 = 38400;
+
+/// The baud rate with a divisor of one
+const SERIAL_CLOCK: u32 = 115200;
+
+/// How many times the line status is checked for room to transmit before a byte is dropped, so
+/// that output does not hang if nothing drains the port
+const SERIAL_TIMEOUT: usize = 100000;
+
+/// The parity bit of each character
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
 }
 
-const SERIALINFO: *const SerialInfo = 0x400 as *const SerialInfo;
+/// Check if a serial port is present, by writing to its scratch register
+pub fn present(port: u16) -> bool {
+    let mut scratch = Pio::<u8>::new(port + 7);
+    scratch.write(0x5A);
+    scratch.read() == 0x5A
+}
+
+/// Write bytes to a serial port, dropping any that cannot be sent in time
+pub fn write(port: u16, bytes: &[u8]) {
+    let status = Pio::<u8>::new(port + 5);
+    let mut data = Pio::<u8>::new(port);
+    for &byte in bytes.iter() {
+        let mut timeout = SERIAL_TIMEOUT;
+        while ! status.readf(0x20) && timeout > 0 {
+            timeout -= 1;
+        }
+        if timeout > 0 {
+            data.write(byte);
+        }
+    }
+}
+
+/// A resource reading and writing a serial port
+///
+/// Input only arrives while the port is not used by the console.
+pub struct SerialResource {
+    path: String,
+    port: u16,
+    input: Arc<WaitQueue<u8>>,
+}
+
+impl Resource for SerialResource {
+    fn dup(&self) -> Result<Box<Resource>> {
+        Ok(box SerialResource {
+            path: self.path.clone(),
+            port: self.port,
+            input: self.input.clone(),
+        })
+    }
+
+    fn path(&self, buf: &mut [u8]) -> Result<usize> {
+        let path = self.path.as_bytes();
+
+        for (b, p) in buf.iter_mut().zip(path.iter()) {
+            *b = *p;
+        }
+
+        Ok(cmp::min(buf.len(), path.len()))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Wait for the first byte, then take whatever else has arrived
+        buf[0] = self.input.receive();
+        let mut i = 1;
+        let mut input = self.input.inner.lock();
+        while i < buf.len() {
+            match input.pop_front() {
+                Some(b) => {
+                    buf[i] = b;
+                    i += 1;
+                },
+                None => break,
+            }
+        }
+
+        Ok(i)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        write(self.port, buf);
+        Ok(buf.len())
+    }
+
+    fn poll(&mut self) -> usize {
+        if self.input.inner.lock().is_empty() {
+            POLLOUT
+        } else {
+            POLLIN | POLLOUT
+        }
+    }
+}
 
 /// Serial
 pub struct Serial {
+    pub name: &'static str,
+    pub port: u16,
     pub data: Pio<u8>,
     pub status: Pio<u8>,
     pub irq: u8,
+    /// Deliver input to the console, like the keyboard, instead of to resources of the scheme
+    /// while the console uses this port
+    pub console: bool,
+    pub input: Arc<WaitQueue<u8>>,
     pub escape: bool,
     pub cursor_control: bool,
 }
 
 impl Serial {
-    /// Create new
-    pub fn new(port: u16, irq: u8) -> Box<Self> {
+    /// Create new, with 8 data bits and 1 stop bit
+    pub fn new(name: &'static str, port: u16, irq: u8, baud: u32, parity: Parity, console: bool) -> Box<Self> {
+        let divisor = cmp::max(SERIAL_CLOCK / cmp::max(baud, 1), 1);
+        let line = 0x03 | match parity {
+            Parity::None => 0x00,
+            Parity::Odd => 0x08,
+            Parity::Even => 0x18,
+        };
+
         Pio::<u8>::new(port + 1).write(0x00);
+        // The divisor latch is accessed while bit 7 of the line control register is set
         Pio::<u8>::new(port + 3).write(0x80);
-        Pio::<u8>::new(port + 0).write(0x03);
-        Pio::<u8>::new(port + 1).write(0x00);
-        Pio::<u8>::new(port + 3).write(0x03);
+        Pio::<u8>::new(port + 0).write(divisor as u8);
+        Pio::<u8>::new(port + 1).write((divisor >> 8) as u8);
+        Pio::<u8>::new(port + 3).write(line);
         Pio::<u8>::new(port + 2).write(0xC7);
         Pio::<u8>::new(port + 4).write(0x0B);
         Pio::<u8>::new(port + 1).write(0x01);
 
         box Serial {
+            name: name,
+            port: port,
             data: Pio::<u8>::new(port),
             status: Pio::<u8>::new(port + 5),
             irq: irq,
+            console: console,
+            input: Arc::new(WaitQueue::new()),
             escape: false,
             cursor_control: false,
         }
     }
-}
 
-impl KScheme for Serial {
-    fn on_irq(&mut self, irq: u8) {
-        if irq == self.irq {
-            while self.status.read() & 1 == 0 {}
+    /// Translate a character received by the console into a key event
+    fn console_input(&mut self, mut c: char) {
+        let mut sc = 0;
+
+        if self.escape {
+            self.escape = false;
 
-            let mut c = self.data.read() as char;
-            let mut sc = 0;
+            if c == '[' {
+                self.cursor_control = true;
+            }
 
-            let mut console = ::env().console.lock();
+            c = '\0';
+        } else if self.cursor_control {
+            self.cursor_control = false;
+
+            if c == 'A' {
+                sc = event::K_UP;
+            } else if c == 'B' {
+                sc = event::K_DOWN;
+            } else if c == 'C' {
+                sc = event::K_RIGHT;
+            } else if c == 'D' {
+                sc = event::K_LEFT;
+            }
 
-            if self.escape {
-                self.escape = false;
+            c = '\0';
+        } else if c == '\x1B' {
+            self.escape = true;
+            c = '\0';
+        } else if c == '\r' {
+            c = '\n';
+        } else if c == '\x7F' {
+            c = '\0';
+            sc = event::K_BKSP;
+        }
 
-                if c == '[' {
-                    self.cursor_control = true;
-                }
+        if c != '\0' || sc != 0 {
+            let key_event = event::KeyEvent {
+                character: c,
+                scancode: sc,
+                pressed: true,
+            };
 
-                c = '\0';
-            } else if self.cursor_control {
-                self.cursor_control = false;
-
-                if c == 'A' {
-                    sc = event::K_UP;
-                } else if c == 'B' {
-                    sc = event::K_DOWN;
-                } else if c == 'C' {
-                    sc = event::K_RIGHT;
-                } else if c == 'D' {
-                    sc = event::K_LEFT;
-                }
+            ::env().console.lock().event(key_event.to_event());
+        }
+    }
+}
 
-                c = '\0';
-            } else if c == '\x03' {
-                console.write(b"^C\n");
-                console.commands.send(String::new());
-
-                c = '\0';
-                sc = 0;
-            } else if c == '\x1B' {
-                self.escape = true;
-                c = '\0';
-            } else if c == '\r' {
-                c = '\n';
-            } else if c == '\x7F' {
-                c = '\0';
-                sc = event::K_BKSP;
-            }
+impl KScheme for Serial {
+    fn scheme(&self) -> &str {
+        self.name
+    }
 
-            if c != '\0' || sc != 0 {
-                let key_event = event::KeyEvent {
-                    character: c,
-                    scancode: sc,
-                    pressed: true,
-                };
+    fn open(&mut self, _: Url, _: usize) -> Result<Box<Resource>> {
+        Ok(box SerialResource {
+            path: format!("{}:", self.name),
+            port: self.port,
+            input: self.input.clone(),
+        })
+    }
 
-                console.event(key_event.to_event());
+    fn on_irq(&mut self, irq: u8) {
+        if irq == self.irq {
+            let console = self.console && ::env().console.lock().serial;
+
+            // Empty the receive FIFO
+            while self.status.readf(1) {
+                let b = self.data.read();
+                if console {
+                    self.console_input(b as char);
+                } else {
+                    self.input.send(b);
+                    ::env().input.notify();
+                }
             }
         }
     }
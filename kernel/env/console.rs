@@ -7,13 +7,22 @@ use common::event::{self, Event, EventOption};
 
 use core::{cmp, mem};
 
-use drivers::io::{Io, Pio};
+use drivers::serial::{self, COM1};
 
 use graphics::color::Color;
 use graphics::display::Display;
 
 use sync::WaitQueue;
 
+use super::scrollback::{Cell, Scrollback};
+
+use syscall::signal_send;
+
+use system::syscall::SIGINT;
+
+/// The most lines kept after they scroll off the screen
+const CONSOLE_SCROLLBACK_LINES: usize = 500;
+
 fn ansi_color(value: u8) -> Color {
     match value {
         0 => Color::new(0x00, 0x00, 0x00),
@@ -61,135 +70,302 @@ pub struct Console {
     pub background: Color,
     pub draw: bool,
     pub redraw: bool,
+    /// Use COM1 as a console too, writing output to it and reading input from it
+    pub serial: bool,
     pub command: String,
     pub commands: WaitQueue<String>,
     pub escape: bool,
     pub escape_sequence: bool,
     pub sequence: Vec<String>,
+    /// Does the control sequence have private parameters?
+    pub sequence_private: bool,
+    /// Are the basic foreground colors shown bright?
+    pub bold: bool,
+    /// The cursor position and colors saved by ESC 7 or CSI s
+    pub saved: Option<(usize, usize, Color, Color)>,
+    /// Deliver input immediately, without echo or line editing
     pub raw_mode: bool,
+    /// Echo input in canonical mode
+    pub echo: bool,
+    /// The pid of the context in the foreground, which is sent SIGINT by Ctrl+C
+    pub foreground_pid: usize,
+    /// The text on the screen and the lines that scrolled off it
+    pub scrollback: Scrollback,
+    /// How many lines back from the live view the screen shows
+    pub scroll_offset: usize,
+    /// Is a shift key held?
+    pub shift: bool,
 }
 
 impl Console {
     pub fn new() -> Console {
+        Console::with_display(Display::root())
+    }
+
+    /// Create a console drawing to a display, or only keeping the text if there is none
+    pub fn with_display(display: Option<Box<Display>>) -> Console {
+        let scrollback = match display {
+            Some(ref display) => Scrollback::new(display.width / 8, display.height / 16, CONSOLE_SCROLLBACK_LINES,
+                                                 ansi_color(7), ansi_color(0)),
+            None => Scrollback::new(80, 30, 0, ansi_color(7), ansi_color(0)),
+        };
+
         Console {
-            display: Display::root(),
+            display: display,
             point_x: 0,
             point_y: 0,
             foreground: ansi_color(7),
             background: ansi_color(0),
             draw: false,
             redraw: true,
+            serial: false,
             command: String::new(),
             commands: WaitQueue::new(),
             escape: false,
             escape_sequence: false,
             sequence: Vec::new(),
+            sequence_private: false,
+            bold: false,
+            saved: None,
             raw_mode: false,
+            echo: true,
+            foreground_pid: 0,
+            scrollback: scrollback,
+            scroll_offset: 0,
+            shift: false,
         }
     }
 
-    pub fn code(&mut self, c: char) {
-        if self.escape_sequence {
-            match c {
-                '0' ... '9' => {
-                    // Add a number to the sequence list
-                    if let Some(mut value) = self.sequence.last_mut() {
-                        value.push(c);
+    /// The width and height of the console in pixels
+    fn size(&self) -> (usize, usize) {
+        if let Some(ref display) = self.display {
+            (display.width, display.height)
+        } else {
+            (self.scrollback.columns * 8, self.scrollback.rows * 16)
+        }
+    }
+
+    /// Get a parameter of the control sequence, using `default` if it is missing or zero
+    fn param(&self, i: usize, default: usize) -> usize {
+        match self.sequence.get(i).and_then(|p| p.parse::<usize>().ok()) {
+            Some(0) | None => default,
+            Some(value) => value,
+        }
+    }
+
+    /// Draw the cell under the cursor again, from the text on the screen
+    fn cursor_hide(&mut self) {
+        let i = self.scrollback.len() + self.point_y / 16;
+        let cell = self.scrollback.line(i).get(self.point_x / 8).map(|cell| *cell);
+        if let Some(ref mut display) = self.display {
+            match cell {
+                Some(cell) => {
+                    display.rect(self.point_x, self.point_y, 8, 16, cell.background);
+                    if cell.c != ' ' {
+                        display.char(self.point_x, self.point_y, cell.c, cell.foreground);
                     }
                 },
-                ';' => {
-                    // Split sequence into list
-                    self.sequence.push(String::new());
+                None => display.rect(self.point_x, self.point_y, 8, 16, self.background),
+            }
+        }
+    }
+
+    /// Draw the cursor
+    fn cursor_show(&mut self) {
+        if let Some(ref mut display) = self.display {
+            display.rect(self.point_x, self.point_y, 8, 16, self.foreground);
+        }
+    }
+
+    /// Move the cursor to a cell, keeping it on the screen
+    fn cursor_move(&mut self, column: usize, row: usize) {
+        let (width, height) = self.size();
+        let columns = cmp::max(width / 8, 1);
+        let rows = cmp::max(height / 16, 1);
+
+        self.cursor_hide();
+        self.point_x = cmp::min(column, columns - 1) * 8;
+        self.point_y = cmp::min(row, rows - 1) * 16;
+        self.cursor_show();
+    }
+
+    /// Erase `count` cells of a row, starting at `column`
+    fn erase_cells(&mut self, column: usize, row: usize, count: usize) {
+        for i in 0..count {
+            self.scrollback.set(column + i, row, Cell::blank(self.foreground, self.background));
+        }
+        if let Some(ref mut display) = self.display {
+            display.rect(column * 8, row * 16, count * 8, 16, self.background);
+        }
+    }
+
+    /// Save the cursor position and colors
+    fn cursor_save(&mut self) {
+        self.saved = Some((self.point_x, self.point_y, self.foreground, self.background));
+    }
+
+    /// Restore the cursor position and colors, if they were saved
+    fn cursor_restore(&mut self) {
+        if let Some((x, y, foreground, background)) = self.saved {
+            self.foreground = foreground;
+            self.background = background;
+            self.cursor_move(x / 8, y / 16);
+        }
+    }
+
+    /// Set the display attributes, from the parameters of an SGR sequence
+    fn sgr(&mut self) {
+        let mut value_iter = self.sequence.iter();
+        while let Some(value_str) = value_iter.next() {
+            let value = value_str.parse::<u8>().unwrap_or(0);
+            match value {
+                0 => {
+                    self.foreground = ansi_color(7);
+                    self.background = ansi_color(0);
+                    self.bold = false;
                 },
-                'm' => {
-                    // Display attributes
-                    let mut value_iter = self.sequence.iter();
-                    while let Some(value_str) = value_iter.next() {
-                        let value = value_str.parse::<u8>().unwrap_or(0);
-                        match value {
-                            0 => {
-                                self.foreground = ansi_color(7);
-                                self.background = ansi_color(0);
-                            },
-                            30 ... 37 => self.foreground = ansi_color(value - 30),
-                            38 => match value_iter.next().map_or("", |s| &s).parse::<usize>().unwrap_or(0) {
-                                2 => {
-                                    //True color
-                                    let r = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
-                                    let g = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
-                                    let b = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
-                                    self.foreground = Color::new(r, g, b);
-                                },
-                                5 => {
-                                    //256 color
-                                    let color_value = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
-                                    self.foreground = ansi_color(color_value);
-                                },
-                                _ => {}
-                            },
-                            40 ... 47 => self.background = ansi_color(value - 40),
-                            48 => match value_iter.next().map_or("", |s| &s).parse::<usize>().unwrap_or(0) {
-                                2 => {
-                                    //True color
-                                    let r = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
-                                    let g = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
-                                    let b = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
-                                    self.background = Color::new(r, g, b);
-                                },
-                                5 => {
-                                    //256 color
-                                    let color_value = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
-                                    self.background = ansi_color(color_value);
-                                },
-                                _ => {}
-                            },
-                            _ => {},
+                1 => {
+                    // Bold shows the basic colors brighter
+                    self.bold = true;
+                    for i in 0..8 {
+                        if self.foreground.data == ansi_color(i).data {
+                            self.foreground = ansi_color(i + 8);
+                            break;
                         }
                     }
-
-                    self.escape_sequence = false;
                 },
-                'J' => {
-                    match self.sequence.get(0).map_or("", |p| &p).parse::<usize>().unwrap_or(0) {
-                        0 => {
-                            //TODO: Erase down
-                        },
-                        1 => {
-                            //TODO: Erase up
-                        },
-                        2 => {
-                            // Erase all
-                            self.point_x = 0;
-                            self.point_y = 0;
-                            if let Some(ref mut display) = self.display {
-                                display.set(self.background);
-                            }
-                            if ! self.raw_mode {
-                                self.redraw = true;
-                            }
-                        },
-                        _ => {}
+                22 => {
+                    self.bold = false;
+                    for i in 8..16 {
+                        if self.foreground.data == ansi_color(i).data {
+                            self.foreground = ansi_color(i - 8);
+                            break;
+                        }
                     }
-
-                    self.escape_sequence = false;
                 },
-                'H' | 'f' => {
-                    if let Some(ref mut display) = self.display {
-                        display.rect(self.point_x, self.point_y, 8, 16, self.background);
-                    }
+                30 ... 37 => self.foreground = ansi_color(value - 30 + if self.bold { 8 } else { 0 }),
+                38 => match value_iter.next().map_or("", |s| &s).parse::<usize>().unwrap_or(0) {
+                    2 => {
+                        //True color
+                        let r = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
+                        let g = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
+                        let b = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
+                        self.foreground = Color::new(r, g, b);
+                    },
+                    5 => {
+                        //256 color
+                        let color_value = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
+                        self.foreground = ansi_color(color_value);
+                    },
+                    _ => {}
+                },
+                39 => self.foreground = ansi_color(if self.bold { 15 } else { 7 }),
+                40 ... 47 => self.background = ansi_color(value - 40),
+                48 => match value_iter.next().map_or("", |s| &s).parse::<usize>().unwrap_or(0) {
+                    2 => {
+                        //True color
+                        let r = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
+                        let g = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
+                        let b = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
+                        self.background = Color::new(r, g, b);
+                    },
+                    5 => {
+                        //256 color
+                        let color_value = value_iter.next().map_or("", |s| &s).parse::<u8>().unwrap_or(0);
+                        self.background = ansi_color(color_value);
+                    },
+                    _ => {}
+                },
+                49 => self.background = ansi_color(0),
+                90 ... 97 => self.foreground = ansi_color(value - 90 + 8),
+                100 ... 107 => self.background = ansi_color(value - 100 + 8),
+                _ => {},
+            }
+        }
+    }
 
-                    let row = self.sequence.get(0).map_or("", |p| &p).parse::<isize>().unwrap_or(1);
-                    self.point_y = cmp::max(0, row - 1) as usize * 16;
+    /// Run a control sequence, given its final character
+    fn csi(&mut self, c: char) {
+        let (width, height) = self.size();
+        let columns = width / 8;
+        let rows = height / 16;
+        let column = self.point_x / 8;
+        let row = self.point_y / 16;
 
-                    let col = self.sequence.get(1).map_or("", |p| &p).parse::<isize>().unwrap_or(1);
-                    self.point_x = cmp::max(0, col - 1) as usize * 8;
+        match c {
+            'A' => {
+                let count = self.param(0, 1);
+                self.cursor_move(column, row.saturating_sub(count));
+            },
+            'B' => {
+                let count = self.param(0, 1);
+                self.cursor_move(column, row.saturating_add(count));
+            },
+            'C' => {
+                let count = self.param(0, 1);
+                self.cursor_move(column.saturating_add(count), row);
+            },
+            'D' => {
+                let count = self.param(0, 1);
+                self.cursor_move(column.saturating_sub(count), row);
+            },
+            'H' | 'f' => {
+                let new_row = self.param(0, 1) - 1;
+                let new_column = self.param(1, 1) - 1;
+                self.cursor_move(new_column, new_row);
+            },
+            'J' => {
+                match self.param(0, 0) {
+                    0 => {
+                        // Erase down
+                        self.erase_cells(column, row, columns.saturating_sub(column));
+                        for i in row + 1..rows {
+                            self.erase_cells(0, i, columns);
+                        }
+                    },
+                    1 => {
+                        // Erase up
+                        for i in 0..row {
+                            self.erase_cells(0, i, columns);
+                        }
+                        self.erase_cells(0, row, column + 1);
+                    },
+                    2 => {
+                        // Erase all
+                        self.point_x = 0;
+                        self.point_y = 0;
+                        if let Some(ref mut display) = self.display {
+                            display.set(self.background);
+                        }
+                        self.scrollback.clear(self.foreground, self.background);
+                    },
+                    _ => {}
+                }
 
-                    if let Some(ref mut display) = self.display {
-                        display.rect(self.point_x, self.point_y, 8, 16, self.foreground);
-                    }
+                self.cursor_show();
+                if ! self.raw_mode {
+                    self.redraw = true;
+                }
+            },
+            'K' => {
+                match self.param(0, 0) {
+                    // Erase to the end of the line
+                    0 => self.erase_cells(column, row, columns.saturating_sub(column)),
+                    // Erase to the start of the line
+                    1 => self.erase_cells(0, row, column + 1),
+                    // Erase the line
+                    2 => self.erase_cells(0, row, columns),
+                    _ => {}
+                }
 
-                    self.escape_sequence = false;
-                },
+                self.cursor_show();
+                if ! self.raw_mode {
+                    self.redraw = true;
+                }
+            },
+            'm' => self.sgr(),
+            's' => self.cursor_save(),
+            'u' => self.cursor_restore(),
 /*
 @MANSTART{terminal-raw-mode}
 INTRODUCTION
@@ -198,6 +374,16 @@ INTRODUCTION
 ENTERING AND EXITING RAW MODE
     Entering raw mode is done using CSI-r (^[r). Unsetting raw mode is done by CSI-R (^[R).
 
+    The mode can also be set by writing "raw" or "canon" to debug:mode, and echo in canonical
+    mode is turned on and off by writing "echo" or "noecho". Writing "serial" or "noserial"
+    chooses whether COM1 is used as a console, which it is by default when there is no display.
+    Reading debug:mode returns the current settings.
+
+CANONICAL MODE
+    Input is buffered until enter is pressed, and echoed unless echo is off. Backspace and delete
+    erase the last character, and Ctrl+U erases the whole line. Ctrl+C discards the line, and
+    interrupts a read of the console, which returns EINTR.
+
 RAW MODE
     Raw mode means that the stdin must be handled solely by the program itself. It will not automatically be printed nor will it be modified in any way (modulo escape codes).
 
@@ -207,12 +393,34 @@ RAW MODE
         - stdin is not buffered, meaning that the stream of bytes goes directly to the program, without the user having to press enter.
 @MANEND
 */
-                'r' => {
-                    self.raw_mode = true;
-                    self.escape_sequence = false;
+            'r' => self.raw_mode = true,
+            'R' => self.raw_mode = false,
+            _ => {},
+        }
+    }
+
+    pub fn code(&mut self, c: char) {
+        if self.escape_sequence {
+            match c {
+                '0' ... '9' => {
+                    // Add a number to the sequence list
+                    if let Some(mut value) = self.sequence.last_mut() {
+                        value.push(c);
+                    }
                 },
-                'R' => {
-                    self.raw_mode = false;
+                ';' => {
+                    // Split sequence into list
+                    self.sequence.push(String::new());
+                },
+                // Private parameters, which are not supported
+                '<' ... '?' => self.sequence_private = true,
+                // Intermediate characters and sub-parameters are ignored
+                ' ' ... '/' | ':' => {},
+                '@' ... '~' => {
+                    // Final character, unknown sequences are ignored
+                    if ! self.sequence_private {
+                        self.csi(c);
+                    }
                     self.escape_sequence = false;
                 },
                 _ => self.escape_sequence = false,
@@ -220,6 +428,7 @@ RAW MODE
 
             if !self.escape_sequence {
                 self.sequence.clear();
+                self.sequence_private = false;
                 self.escape = false;
             }
         } else {
@@ -230,6 +439,14 @@ RAW MODE
                     self.escape_sequence = true;
                     self.sequence.push(String::new());
                 },
+                '7' => {
+                    self.cursor_save();
+                    self.escape = false;
+                },
+                '8' => {
+                    self.cursor_restore();
+                    self.escape = false;
+                },
                 'c' => {
                     // Reset
                     self.point_x = 0;
@@ -237,9 +454,12 @@ RAW MODE
                     self.raw_mode = false;
                     self.foreground = ansi_color(7);
                     self.background = ansi_color(0);
+                    self.bold = false;
+                    self.saved = None;
                     if let Some(ref mut display) = self.display {
                         display.set(self.background);
                     }
+                    self.scrollback.clear(self.foreground, self.background);
                     self.redraw = true;
 
                     self.escape = false;
@@ -250,15 +470,9 @@ RAW MODE
     }
 
     pub fn character(&mut self, c: char) {
-        let (width, height) = if let Some(ref mut display) = self.display {
-            (display.width, display.height)
-        } else {
-            (80, 30)
-        };
+        let (width, height) = self.size();
 
-        if let Some(ref mut display) = self.display {
-            display.rect(self.point_x, self.point_y, 8, 16, self.background);
-        }
+        self.cursor_hide();
 
         match c {
             '\0' => {},
@@ -280,11 +494,17 @@ RAW MODE
                 if let Some(ref mut display) = self.display {
                     display.rect(self.point_x, self.point_y, 8, 16, self.background);
                 }
+                self.scrollback.set(self.point_x / 8, self.point_y / 16, Cell::blank(self.foreground, self.background));
             },
             _ => {
                 if let Some(ref mut display) = self.display {
                     display.char(self.point_x, self.point_y, c, self.foreground);
                 }
+                self.scrollback.set(self.point_x / 8, self.point_y / 16, Cell {
+                    c: c,
+                    foreground: self.foreground,
+                    background: self.background,
+                });
 
                 self.point_x += 8;
             }
@@ -299,17 +519,75 @@ RAW MODE
             if let Some(ref mut display) = self.display {
                 display.scroll(16, self.background);
             }
+            self.scrollback.scroll(self.foreground, self.background);
+            // Keep showing the same lines while scrolled back
+            if self.scroll_offset > 0 {
+                self.scroll_offset = cmp::min(self.scroll_offset + 1, self.scrollback.len());
+            }
             self.point_y -= 16;
         }
 
+        self.cursor_show();
+    }
+
+    /// Show the screen `offset` lines back from the live view. The lines are drawn into the back
+    /// buffer of the display, so that the live view is kept in the front buffer to return to.
+    pub fn scroll_to(&mut self, offset: usize) {
+        let offset = cmp::min(offset, self.scrollback.len());
+        if offset == self.scroll_offset {
+            return;
+        }
+        self.scroll_offset = offset;
+
         if let Some(ref mut display) = self.display {
-            display.rect(self.point_x, self.point_y, 8, 16, self.foreground);
+            if offset == 0 {
+                display.flip();
+            } else {
+                let buffer = display.backbuffer;
+                display.rect_in(buffer, 0, 0, display.width, display.height, ansi_color(0));
+
+                let first = self.scrollback.len() - offset;
+                for row in 0..self.scrollback.rows {
+                    for (column, cell) in self.scrollback.line(first + row).iter().enumerate() {
+                        display.rect_in(buffer, column * 8, row * 16, 8, 16, cell.background);
+                        if cell.c != ' ' {
+                            display.char_in(buffer, column * 8, row * 16, cell.c, cell.foreground);
+                        }
+                    }
+                }
+
+                let size = display.size;
+                display.damage_range(0, size);
+                display.sync();
+            }
         }
     }
 
     pub fn event(&mut self, event: Event) {
         match event.to_option() {
             EventOption::Key(key_event) => {
+                let shift_key = key_event.scancode == event::K_LEFT_SHIFT || key_event.scancode == event::K_RIGHT_SHIFT;
+                if shift_key {
+                    self.shift = key_event.pressed;
+                }
+
+                // Shift+PageUp and Shift+PageDown scroll by half a screen, any other key returns
+                // to the live view
+                if key_event.pressed && ! shift_key {
+                    let page = cmp::max(self.scrollback.rows / 2, 1);
+                    if self.shift && key_event.scancode == event::K_PGUP {
+                        let offset = self.scroll_offset + page;
+                        self.scroll_to(offset);
+                        return;
+                    } else if self.shift && key_event.scancode == event::K_PGDN {
+                        let offset = self.scroll_offset.saturating_sub(page);
+                        self.scroll_to(offset);
+                        return;
+                    } else {
+                        self.scroll_to(0);
+                    }
+                }
+
                 if key_event.pressed {
                     if self.raw_mode {
                         match key_event.scancode {
@@ -333,18 +611,31 @@ RAW MODE
                         }
                     } else {
                         match key_event.scancode {
-                            event::K_BKSP => if ! self.command.is_empty() {
-                                self.redraw = true;
-
-                                self.write(&[8]);
-                                self.command.pop();
-                            },
+                            event::K_BKSP | event::K_DEL => self.erase(1),
                             _ => match key_event.character {
                                 '\0' => (),
+                                '\x03' => {
+                                    // Discard the line, interrupt the foreground context, and
+                                    // wake the readers with an empty command
+                                    self.command.clear();
+                                    if self.echo {
+                                        self.redraw = true;
+                                        self.write(b"^C\n");
+                                    }
+                                    if self.foreground_pid > 0 {
+                                        let _ = signal_send(self.foreground_pid, SIGINT);
+                                    }
+                                    self.commands.send(String::new());
+                                },
+                                '\x15' => {
+                                    let count = self.command.chars().count();
+                                    self.erase(count);
+                                },
                                 c => {
-                                    self.redraw = true;
-
-                                    self.write(&[c as u8]);
+                                    if self.echo {
+                                        self.redraw = true;
+                                        self.write(&[c as u8]);
+                                    }
                                     self.command.push(c);
 
                                     if c == '\n' {
@@ -362,7 +653,19 @@ RAW MODE
         }
     }
 
-    pub fn write(&mut self, bytes: &[u8]) {
+    /// Erase characters from the end of the line being edited
+    fn erase(&mut self, count: usize) {
+        for _ in 0..count {
+            if self.command.pop().is_some() && self.echo {
+                self.redraw = true;
+                self.write(&[8]);
+            }
+        }
+    }
+
+    /// Interpret output, updating the text and cursor without writing to the serial port or
+    /// showing the display
+    pub fn put(&mut self, bytes: &[u8]) {
         for byte in bytes.iter() {
             let c = *byte as char;
 
@@ -371,25 +674,24 @@ RAW MODE
             } else {
                 self.character(c);
             }
+        }
+    }
 
-            if self.display.is_none() || ! self.draw {
-                let serial_status = Pio::<u8>::new(0x3F8 + 5);
-                let mut serial_data = Pio::<u8>::new(0x3F8);
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes.iter() {
+            self.put(&[*byte]);
 
-                while !serial_status.readf(0x20) {}
-                serial_data.write(*byte);
+            if self.serial || self.display.is_none() || ! self.draw {
+                serial::write(COM1, &[*byte]);
 
                 if *byte == 8 {
-                    while !serial_status.readf(0x20) {}
-                    serial_data.write(0x20);
-
-                    while !serial_status.readf(0x20) {}
-                    serial_data.write(8);
+                    serial::write(COM1, &[0x20, 8]);
                 }
             }
         }
 
-        if self.draw && self.redraw {
+        // While scrolled back the output is drawn, but only shown on returning to the live view
+        if self.draw && self.redraw && self.scroll_offset == 0 {
             self.redraw = false;
             if let Some(ref mut display) = self.display {
                 display.flip();
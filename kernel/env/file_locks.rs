@@ -0,0 +1,127 @@
+use collections::{BTreeMap, Vec};
+use collections::string::{String, ToString};
+
+use sync::{Intex, WaitCondition};
+
+use system::error::{Error, Result, EWOULDBLOCK};
+
+/// The holder of a lock, a file descriptor of a context
+#[derive(Copy, Clone, PartialEq)]
+pub struct LockOwner {
+    pub pid: usize,
+    pub fd: usize,
+}
+
+/// The lock of a file, either exclusive with a single owner, or shared
+struct FileLock {
+    exclusive: bool,
+    owners: Vec<LockOwner>,
+}
+
+/// The advisory locks taken with flock, by path
+///
+/// Locks belong to a file descriptor of a context. Taking a lock that conflicts with one held by
+/// another owner waits until it is released, which happens when it is unlocked, when the file
+/// descriptor is closed, or when the context exits.
+pub struct FileLocks {
+    inner: Intex<BTreeMap<String, FileLock>>,
+    condition: WaitCondition,
+}
+
+impl FileLocks {
+    pub fn new() -> FileLocks {
+        FileLocks {
+            inner: Intex::new(BTreeMap::new()),
+            condition: WaitCondition::new(),
+        }
+    }
+
+    /// Take a shared or exclusive lock on path, replacing the lock held by owner if there is one.
+    /// Returns `EWOULDBLOCK` if nonblock is set and the lock conflicts with another owner
+    pub fn lock(&self, path: &str, owner: LockOwner, exclusive: bool, nonblock: bool) -> Result<()> {
+        loop {
+            {
+                let mut inner = self.inner.lock();
+
+                let available = match inner.get(path) {
+                    Some(lock) => {
+                        let others = lock.owners.iter().filter(|&&other| other != owner).count();
+                        others == 0 || (! exclusive && ! lock.exclusive)
+                    },
+                    None => true,
+                };
+
+                if available {
+                    let downgraded = {
+                        let lock = inner.entry(path.to_string()).or_insert(FileLock {
+                            exclusive: exclusive,
+                            owners: Vec::new(),
+                        });
+                        let downgraded = lock.exclusive && ! exclusive;
+                        lock.exclusive = exclusive;
+                        if ! lock.owners.contains(&owner) {
+                            lock.owners.push(owner);
+                        }
+                        downgraded
+                    };
+
+                    // Contexts waiting for a shared lock can take it now
+                    if downgraded {
+                        unsafe { self.condition.notify(); }
+                    }
+                    return Ok(());
+                }
+
+                if nonblock {
+                    return Err(Error::new(EWOULDBLOCK));
+                }
+            }
+
+            unsafe { self.condition.wait(); }
+        }
+    }
+
+    /// Release the lock held by owner on path, if there is one
+    pub fn unlock(&self, path: &str, owner: LockOwner) {
+        self.release(|lock_path, lock_owner| lock_path == path && lock_owner == owner);
+    }
+
+    /// Release the locks held through a file descriptor
+    pub fn close(&self, pid: usize, fd: usize) {
+        self.release(|_, owner| owner.pid == pid && owner.fd == fd);
+    }
+
+    /// Release the locks held by a context
+    pub fn exit(&self, pid: usize) {
+        self.release(|_, owner| owner.pid == pid);
+    }
+
+    /// Release the locks matching a filter, waking the contexts waiting for locks if any were
+    fn release<F: Fn(&str, LockOwner) -> bool>(&self, filter: F) {
+        let mut released = false;
+
+        {
+            let mut inner = self.inner.lock();
+
+            let mut empty = Vec::new();
+            for (path, lock) in inner.iter_mut() {
+                let count = lock.owners.len();
+                lock.owners.retain(|&owner| ! filter(path, owner));
+                if lock.owners.len() < count {
+                    released = true;
+                    if lock.owners.is_empty() {
+                        empty.push(path.clone());
+                    }
+                }
+            }
+
+            for path in empty.iter() {
+                inner.remove(path);
+            }
+        }
+
+        if released {
+            unsafe { self.condition.notify(); }
+        }
+    }
+}
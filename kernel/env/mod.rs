@@ -1,24 +1,47 @@
+use alloc::arc::Arc;
 use alloc::boxed::Box;
 
+use collections::BTreeMap;
 use collections::string::{String, ToString};
 use collections::vec::Vec;
+use collections::vec_deque::VecDeque;
+
+use core::{mem, str};
+use core::ops::DerefMut;
 
 use arch::context::ContextManager;
-use arch::intex::Intex;
+use arch::hpet::Hpet;
+use arch::intex::{intex_irq_pending, Intex};
 use common::event::Event;
-use common::time::Duration;
+use common::slice::GetSlice;
+use common::time::{Duration, TickPeriod, PIT_DIVISOR};
 use disk::Disk;
-use fs::{KScheme, Resource, Scheme, VecResource, Url};
-use logging::LogLevel;
-use sync::WaitQueue;
+use fs::{DirResource, KScheme, Resource, Scheme, Url};
+use logging::{klog_entries, KernelLog, LogLevel};
+use network::arp::ArpCache;
+use network::dns::DnsCache;
+use network::interface::NetworkInterface;
+use network::schemes::tcp::TcpTimer;
+use sync::{WaitEvent, WaitQueue};
 
-use system::error::{Error, Result, ENOENT, EEXIST};
-use system::syscall::{O_CREAT, Stat};
+use system::error::{Error, Result, EINVAL, ELOOP, ENOENT, EEXIST, EXDEV};
+use system::syscall::{O_CREAT, Stat, StatVfs, TimeSpec};
 
 use self::console::Console;
+use self::file_locks::FileLocks;
+use self::scheme_list::SchemeList;
 
 /// The Kernel Console
 pub mod console;
+/// Advisory file locks
+pub mod file_locks;
+/// The scheme registry
+pub mod scheme_list;
+/// The console scrollback
+pub mod scrollback;
+
+/// The maximum number of symbolic links followed when resolving a path
+pub const MAX_SYMLINKS: usize = 8;
 
 /// The kernel environment
 pub struct Environment {
@@ -29,20 +52,45 @@ pub struct Environment {
     pub clock_realtime: Intex<Duration>,
     /// Monotonic clock
     pub clock_monotonic: Intex<Duration>,
+    /// The period of the PIT, which advances the clocks
+    pub pit: Intex<TickPeriod>,
+    /// The HPET, which drives the monotonic clock instead of the PIT when present
+    pub hpet: Option<Hpet>,
 
+    /// Cached MAC addresses of local hosts
+    pub arp: Intex<ArpCache>,
     /// Default console
     pub console: Intex<Console>,
     /// Disks
     pub disks: Intex<Vec<Box<Disk>>>,
+    /// Resolved host names
+    pub dns: Intex<DnsCache>,
     /// Pending events
     pub events: WaitQueue<Event>,
+    /// Counts input from network devices, pipes and serial ports, for contexts waiting on more
+    /// than one resource
+    pub input: WaitEvent,
+    /// Network interfaces
+    pub interfaces: Intex<Vec<Arc<Intex<NetworkInterface>>>>,
+    /// Advisory file locks
+    pub locks: FileLocks,
     /// Kernel logs
-    pub logs: Intex<Vec<(LogLevel, String)>>,
+    pub logs: Intex<KernelLog>,
+    /// Log entries waiting for the kernel logs to be free
+    pub log_staging: Intex<VecDeque<(LogLevel, Duration, String)>>,
     /// Schemes
-    pub schemes: Intex<Vec<Box<KScheme>>>,
+    pub schemes: Intex<SchemeList>,
+    /// Physical memory shared copy-on-write by fork, with the number of mappings of it
+    pub shared_memory: Intex<BTreeMap<usize, usize>>,
+    /// The send queues of TCP connections, for the retransmission timer
+    pub tcp: TcpTimer,
 
     /// Interrupt stats
     pub interrupts: Intex<[u64; 256]>,
+    /// The drivers handling each IRQ
+    pub irq_names: Intex<BTreeMap<u8, Vec<&'static str>>>,
+    /// The IRQs waiting for the schemes to be free, one bit each
+    pub irq_pending: Intex<u16>,
 }
 
 impl Environment {
@@ -52,20 +100,74 @@ impl Environment {
 
             clock_realtime: Intex::new(Duration::new(0, 0)),
             clock_monotonic: Intex::new(Duration::new(0, 0)),
+            pit: Intex::new(TickPeriod::pit(PIT_DIVISOR)),
+            hpet: None,
 
+            arp: Intex::new(ArpCache::new()),
             console: Intex::new(Console::new()),
             disks: Intex::new(Vec::new()),
+            dns: Intex::new(DnsCache::new()),
             events: WaitQueue::new(),
-            logs: Intex::new(Vec::new()),
-            schemes: Intex::new(Vec::new()),
+            input: WaitEvent::new(),
+            interfaces: Intex::new(Vec::new()),
+            locks: FileLocks::new(),
+            logs: Intex::new(KernelLog::new(klog_entries())),
+            log_staging: Intex::new(VecDeque::new()),
+            schemes: Intex::new(SchemeList::new()),
+            shared_memory: Intex::new(BTreeMap::new()),
+            tcp: TcpTimer::new(),
 
             interrupts: Intex::new([0; 256]),
+            irq_names: Intex::new(BTreeMap::new()),
+            irq_pending: Intex::new(0),
+        }
+    }
+
+    /// Register a kernel scheme, logging if its name is already taken
+    pub fn register(&self, scheme: Box<KScheme>) {
+        let name = scheme.scheme().to_string();
+        if let Err(err) = self.schemes.lock().insert(scheme) {
+            debugln!("kernel: failed to register scheme '{}': {}", name, err);
         }
     }
 
+    /// Record that a driver handles an IRQ, so that it is shown in the interrupt scheme
+    pub fn name_irq(&self, irq: u8, name: &'static str) {
+        let mut irq_names = self.irq_names.lock();
+        let names = irq_names.entry(irq).or_insert_with(|| Vec::new());
+        if ! names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    /// Pass an IRQ to the schemes
+    ///
+    /// If the IRQ came in while the interrupted context was using the schemes, it is kept pending
+    /// until they are free, when the last guard of an Intex is dropped.
     pub fn on_irq(&self, irq: u8) {
-        for mut scheme in self.schemes.lock().iter_mut() {
-            scheme.on_irq(irq);
+        if irq < 16 {
+            *self.irq_pending.lock() |= 1 << irq;
+        }
+        self.on_irq_pending();
+    }
+
+    /// Pass the pending IRQs to the schemes, unless the running context is using them
+    pub fn on_irq_pending(&self) {
+        let mut schemes = match self.schemes.try_lock() {
+            Some(schemes) => schemes,
+            None => {
+                unsafe { intex_irq_pending = true; }
+                return;
+            }
+        };
+
+        let pending = mem::replace(self.irq_pending.lock().deref_mut(), 0);
+        for irq in 0..16 {
+            if pending & 1 << irq != 0 {
+                for mut scheme in schemes.iter_mut() {
+                    scheme.on_irq(irq as u8);
+                }
+            }
         }
     }
 
@@ -75,30 +177,25 @@ impl Environment {
         if url_scheme.is_empty() {
             let url_path = url.reference();
             if url_path.trim_matches('/').is_empty() {
-                let mut list = String::new();
+                let mut entries = Vec::new();
 
                 for scheme in self.schemes.lock().iter() {
                     let scheme_str = scheme.scheme();
-                    if !scheme_str.is_empty() {
-                        if !list.is_empty() {
-                            list = list + "\n" + scheme_str;
-                        } else {
-                            list = scheme_str.to_string();
-                        }
+                    if !scheme_str.is_empty() && !scheme.draining() {
+                        entries.push(scheme_str.to_string());
                     }
                 }
 
-                Ok(box VecResource::new(":".to_string(), list.into_bytes()))
+                Ok(box DirResource::new(":".to_string(), entries))
             } else if flags & O_CREAT == O_CREAT {
-                for scheme in self.schemes.lock().iter_mut() {
-                    if scheme.scheme() == url_path {
-                        return Err(Error::new(EEXIST));
-                    }
+                let mut schemes = self.schemes.lock();
+                if schemes.contains(url_path) {
+                    return Err(Error::new(EEXIST));
                 }
 
                 match Scheme::new(url_path) {
                     Ok((scheme, server)) => {
-                        self.schemes.lock().push(scheme);
+                        try!(schemes.insert(scheme));
                         Ok(server)
                     },
                     Err(err) => Err(err)
@@ -107,23 +204,103 @@ impl Environment {
                 Err(Error::new(ENOENT))
             }
         } else {
-            for mut scheme in self.schemes.lock().iter_mut() {
-                if scheme.scheme() == url_scheme {
-                    return scheme.open(url, flags);
-                }
+            let path = try!(self.resolve(url));
+            let url = try!(Url::from_str(&path));
+            if let Some(scheme) = self.schemes.lock().get_mut(url.scheme()) {
+                return scheme.open(url.decode().as_url(), flags);
             }
             Err(Error::new(ENOENT))
         }
     }
 
+    /// Follow symbolic links until a path that is not a link is found
+    /// Relative link targets are resolved against the directory containing the link. Returns
+    /// `ELOOP` if more than `MAX_SYMLINKS` links are followed.
+    pub fn resolve(&self, url: Url) -> Result<String> {
+        let mut path = url.to_string();
+        let mut depth = 0;
+        loop {
+            let mut buf = [0; 4096];
+            let count = match Url::from_str(&path).and_then(|url| self.readlink(url, &mut buf)) {
+                Ok(count) => count,
+                Err(_) => return Ok(path),
+            };
+
+            if depth >= MAX_SYMLINKS {
+                return Err(Error::new(ELOOP));
+            }
+            depth += 1;
+
+            let target = try!(str::from_utf8(&buf[..count]).or(Err(Error::new(EINVAL))));
+            let next = if target.find(':').is_some() {
+                target.to_string()
+            } else if target.starts_with('/') {
+                path.get_slice(..path.find(':').map_or(0, |i| i + 1)).to_string() + target
+            } else {
+                path.get_slice(..path.rfind('/').or(path.find(':')).map_or(0, |i| i + 1)).to_string() + target
+            };
+            path = next;
+        }
+    }
+
+    /// Create a hard link
+    pub fn link(&self, old: Url, new: Url) -> Result<()> {
+        let url_scheme = old.scheme();
+        if !url_scheme.is_empty() {
+            if new.scheme() != url_scheme {
+                return Err(Error::new(EXDEV));
+            }
+
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.link(old.decode().as_url(), new.decode().as_url());
+            }
+        }
+        Err(Error::new(ENOENT))
+    }
+
+    /// Stat a path, without following a symbolic link
+    pub fn lstat(&self, url: Url, stat: &mut Stat) -> Result<()> {
+        let url_scheme = url.scheme();
+        if !url_scheme.is_empty() {
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.stat(url.decode().as_url(), stat);
+            }
+        }
+        Err(Error::new(ENOENT))
+    }
+
     /// Makes a directory
     pub fn mkdir(&self, url: Url, flags: usize) -> Result<()> {
         let url_scheme = url.scheme();
         if !url_scheme.is_empty() {
-            for mut scheme in self.schemes.lock().iter_mut() {
-                if scheme.scheme() == url_scheme {
-                    return scheme.mkdir(url, flags);
-                }
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.mkdir(url.decode().as_url(), flags);
+            }
+        }
+        Err(Error::new(ENOENT))
+    }
+
+    /// Read the target of a symbolic link
+    pub fn readlink(&self, url: Url, buf: &mut [u8]) -> Result<usize> {
+        let url_scheme = url.scheme();
+        if !url_scheme.is_empty() {
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.readlink(url.decode().as_url(), buf);
+            }
+        }
+        Err(Error::new(ENOENT))
+    }
+
+    /// Rename a resource
+    pub fn rename(&self, old: Url, new: Url) -> Result<()> {
+        let url_scheme = old.scheme();
+        if !url_scheme.is_empty() {
+            if new.scheme() != url_scheme {
+                return Err(Error::new(EXDEV));
+            }
+
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.rename(old.decode().as_url(), new.decode().as_url());
             }
         }
         Err(Error::new(ENOENT))
@@ -133,23 +310,64 @@ impl Environment {
     pub fn rmdir(&self, url: Url) -> Result<()> {
         let url_scheme = url.scheme();
         if !url_scheme.is_empty() {
-            for mut scheme in self.schemes.lock().iter_mut() {
-                if scheme.scheme() == url_scheme {
-                    return scheme.rmdir(url);
-                }
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.rmdir(url.decode().as_url());
+            }
+        }
+        Err(Error::new(ENOENT))
+    }
+
+    /// Set the access and modification times of a path, following symbolic links
+    pub fn set_times(&self, url: Url, atime: TimeSpec, mtime: TimeSpec) -> Result<()> {
+        let path = try!(self.resolve(url));
+        let url = try!(Url::from_str(&path));
+        let url_scheme = url.scheme();
+        if !url_scheme.is_empty() {
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.set_times(url.decode().as_url(), atime, mtime);
             }
         }
         Err(Error::new(ENOENT))
     }
 
-    /// Stat a path
+    /// Stat a path, following symbolic links
     pub fn stat(&self, url: Url, stat: &mut Stat) -> Result<()> {
+        let path = try!(self.resolve(url));
+        self.lstat(try!(Url::from_str(&path)), stat)
+    }
+
+    /// Get the statistics of the filesystem containing a path, following symbolic links
+    pub fn statvfs(&self, url: Url, stat: &mut StatVfs) -> Result<()> {
+        let path = try!(self.resolve(url));
+        let url = try!(Url::from_str(&path));
         let url_scheme = url.scheme();
         if !url_scheme.is_empty() {
-            for mut scheme in self.schemes.lock().iter_mut() {
-                if scheme.scheme() == url_scheme {
-                    return scheme.stat(url, stat);
-                }
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.statvfs(url.decode().as_url(), stat);
+            }
+        }
+        Err(Error::new(ENOENT))
+    }
+
+    /// Create a symbolic link
+    pub fn symlink(&self, target: &str, url: Url) -> Result<()> {
+        let url_scheme = url.scheme();
+        if !url_scheme.is_empty() {
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.symlink(target, url.decode().as_url());
+            }
+        }
+        Err(Error::new(ENOENT))
+    }
+
+    /// Truncate a file, following symbolic links
+    pub fn truncate(&self, url: Url, len: usize) -> Result<()> {
+        let path = try!(self.resolve(url));
+        let url = try!(Url::from_str(&path));
+        let url_scheme = url.scheme();
+        if !url_scheme.is_empty() {
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.truncate(url.decode().as_url(), len);
             }
         }
         Err(Error::new(ENOENT))
@@ -159,10 +377,8 @@ impl Environment {
     pub fn unlink(&self, url: Url) -> Result<()> {
         let url_scheme = url.scheme();
         if !url_scheme.is_empty() {
-            for mut scheme in self.schemes.lock().iter_mut() {
-                if scheme.scheme() == url_scheme {
-                    return scheme.unlink(url);
-                }
+            if let Some(scheme) = self.schemes.lock().get_mut(url_scheme) {
+                return scheme.unlink(url.decode().as_url());
             }
         }
         Err(Error::new(ENOENT))
@@ -0,0 +1,89 @@
+use alloc::boxed::Box;
+
+use collections::{BTreeMap, String, Vec};
+use collections::borrow::ToOwned;
+
+use core::slice::{Iter, IterMut};
+
+use fs::KScheme;
+
+use system::error::{Error, Result, EEXIST};
+
+/// The registered schemes
+///
+/// Schemes are kept in registration order, so that they can be listed and visited on interrupts,
+/// with an index from name to position for lookups. Schemes with an empty name only handle
+/// interrupts, and are not indexed.
+pub struct SchemeList {
+    inner: Vec<Box<KScheme>>,
+    names: BTreeMap<String, usize>,
+}
+
+impl SchemeList {
+    pub fn new() -> SchemeList {
+        SchemeList {
+            inner: Vec::new(),
+            names: BTreeMap::new(),
+        }
+    }
+
+    /// Check if a scheme with the given name is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    /// Get a scheme by name
+    pub fn get(&self, name: &str) -> Option<&Box<KScheme>> {
+        match self.names.get(name) {
+            Some(&i) => self.inner.get(i),
+            None => None,
+        }
+    }
+
+    /// Get a mutable scheme by name
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Box<KScheme>> {
+        match self.names.get(name) {
+            Some(&i) => self.inner.get_mut(i),
+            None => None,
+        }
+    }
+
+    /// Register a scheme. Returns `EEXIST` if the name is already taken
+    pub fn insert(&mut self, scheme: Box<KScheme>) -> Result<()> {
+        let name = scheme.scheme().to_owned();
+        if ! name.is_empty() {
+            if self.names.contains_key(&name) {
+                return Err(Error::new(EEXIST));
+            }
+            self.names.insert(name, self.inner.len());
+        }
+        self.inner.push(scheme);
+        Ok(())
+    }
+
+    /// Remove a scheme by name
+    pub fn remove(&mut self, name: &str) -> Option<Box<KScheme>> {
+        match self.names.remove(name) {
+            Some(i) => {
+                let scheme = self.inner.remove(i);
+                for (_, index) in self.names.iter_mut() {
+                    if *index > i {
+                        *index -= 1;
+                    }
+                }
+                Some(scheme)
+            },
+            None => None,
+        }
+    }
+
+    /// Iterate over the schemes in registration order
+    pub fn iter(&self) -> Iter<Box<KScheme>> {
+        self.inner.iter()
+    }
+
+    /// Iterate mutably over the schemes in registration order
+    pub fn iter_mut(&mut self) -> IterMut<Box<KScheme>> {
+        self.inner.iter_mut()
+    }
+}
@@ -0,0 +1,134 @@
+use collections::Vec;
+
+use graphics::color::Color;
+
+/// A character on the console, with its colors
+#[derive(Copy, Clone)]
+pub struct Cell {
+    pub c: char,
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl Cell {
+    /// An empty cell
+    pub fn blank(foreground: Color, background: Color) -> Self {
+        Cell {
+            c: ' ',
+            foreground: foreground,
+            background: background,
+        }
+    }
+}
+
+/// The text on the screen and the lines that scrolled off the top of it
+///
+/// Everything is allocated up front, so writing to the console does not allocate.
+pub struct Scrollback {
+    pub columns: usize,
+    pub rows: usize,
+    /// The screen, one row after another
+    screen: Vec<Cell>,
+    /// A ring of lines that scrolled off the screen
+    history: Vec<Cell>,
+    /// The most lines the ring holds
+    history_lines: usize,
+    /// The oldest line in the ring
+    history_start: usize,
+    /// The number of lines in the ring
+    history_len: usize,
+}
+
+impl Scrollback {
+    /// Create a scrollback for a screen, keeping up to `history_lines` lines off the screen
+    pub fn new(columns: usize, rows: usize, history_lines: usize, foreground: Color, background: Color) -> Self {
+        let blank = Cell::blank(foreground, background);
+
+        let mut screen = Vec::with_capacity(columns * rows);
+        for _ in 0..columns * rows {
+            screen.push(blank);
+        }
+
+        let mut history = Vec::with_capacity(columns * history_lines);
+        for _ in 0..columns * history_lines {
+            history.push(blank);
+        }
+
+        Scrollback {
+            columns: columns,
+            rows: rows,
+            screen: screen,
+            history: history,
+            history_lines: history_lines,
+            history_start: 0,
+            history_len: 0,
+        }
+    }
+
+    /// Set a cell of the screen
+    pub fn set(&mut self, column: usize, row: usize, cell: Cell) {
+        if column < self.columns && row < self.rows {
+            self.screen[row * self.columns + column] = cell;
+        }
+    }
+
+    /// Move the top row of the screen into the history, dropping the oldest line if it is full,
+    /// and add an empty row at the bottom
+    pub fn scroll(&mut self, foreground: Color, background: Color) {
+        if self.rows == 0 {
+            return;
+        }
+
+        if self.history_lines > 0 {
+            let line = if self.history_len < self.history_lines {
+                self.history_len += 1;
+                (self.history_start + self.history_len - 1) % self.history_lines
+            } else {
+                let line = self.history_start;
+                self.history_start = (self.history_start + 1) % self.history_lines;
+                line
+            };
+
+            for column in 0..self.columns {
+                self.history[line * self.columns + column] = self.screen[column];
+            }
+        }
+
+        for i in self.columns..self.screen.len() {
+            self.screen[i - self.columns] = self.screen[i];
+        }
+
+        let blank = Cell::blank(foreground, background);
+        let start = (self.rows - 1) * self.columns;
+        for cell in self.screen[start ..].iter_mut() {
+            *cell = blank;
+        }
+    }
+
+    /// Clear the screen, keeping the history
+    pub fn clear(&mut self, foreground: Color, background: Color) {
+        let blank = Cell::blank(foreground, background);
+        for cell in self.screen.iter_mut() {
+            *cell = blank;
+        }
+    }
+
+    /// The number of lines in the history
+    pub fn len(&self) -> usize {
+        self.history_len
+    }
+
+    /// Get a line, counting the oldest line of the history as 0 and continuing with the rows of
+    /// the screen
+    pub fn line(&self, i: usize) -> &[Cell] {
+        if i < self.history_len {
+            let line = (self.history_start + i) % self.history_lines;
+            &self.history[line * self.columns .. (line + 1) * self.columns]
+        } else if i - self.history_len < self.rows {
+            let row = i - self.history_len;
+            &self.screen[row * self.columns .. (row + 1) * self.columns]
+        } else {
+            &[]
+        }
+    }
+}
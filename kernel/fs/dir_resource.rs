@@ -0,0 +1,107 @@
+use super::{Resource, ResourceSeek, VecResource};
+
+use alloc::boxed::Box;
+
+use collections::{String, Vec};
+
+use core::{mem, ptr};
+
+use system::error::{Error, Result, EINVAL};
+use system::syscall::{Dirent, MODE_DIR, Stat};
+
+/// A directory resource
+///
+/// Reading returns the entries separated by newlines, while `readdir` returns packed `Dirent`
+/// records, each followed by the NUL terminated name of the entry.
+pub struct DirResource {
+    list: Box<Resource>,
+    entries: Vec<String>,
+    next: usize,
+}
+
+impl DirResource {
+    pub fn new(path: String, entries: Vec<String>) -> Self {
+        let mut list = String::new();
+        for entry in entries.iter() {
+            if ! list.is_empty() {
+                list.push('\n');
+            }
+            list.push_str(entry);
+        }
+
+        DirResource {
+            list: box VecResource::new(path, list.into_bytes()),
+            entries: entries,
+            next: 0,
+        }
+    }
+}
+
+impl Resource for DirResource {
+    fn dup(&self) -> Result<Box<Resource>> {
+        Ok(box DirResource {
+            list: try!(self.list.dup()),
+            entries: self.entries.clone(),
+            next: self.next,
+        })
+    }
+
+    fn path(&self, buf: &mut [u8]) -> Result<usize> {
+        self.list.path(buf)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.list.read(buf)
+    }
+
+    fn seek(&mut self, pos: ResourceSeek) -> Result<usize> {
+        self.list.seek(pos)
+    }
+
+    fn stat(&self, stat: &mut Stat) -> Result<usize> {
+        let mut size = 0;
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                size += 1;
+            }
+            size += entry.len();
+        }
+
+        stat.st_mode = MODE_DIR | 0o555;
+        stat.st_size = size as u64;
+        Ok(0)
+    }
+
+    fn readdir(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let header = mem::size_of::<Dirent>();
+
+        let mut i = 0;
+        while let Some(entry) = self.entries.get(self.next) {
+            let name = entry.as_bytes();
+            let reclen = header + name.len() + 1;
+            if i + reclen > buf.len() {
+                if i == 0 {
+                    return Err(Error::new(EINVAL));
+                }
+                break;
+            }
+
+            let dirent = Dirent {
+                d_ino: self.next as u64 + 1,
+                d_reclen: reclen as u16,
+                d_namlen: name.len() as u16,
+            };
+            unsafe { ptr::write(buf.as_mut_ptr().offset(i as isize) as *mut Dirent, dirent) };
+
+            for (b, n) in buf[i + header ..].iter_mut().zip(name.iter()) {
+                *b = *n;
+            }
+            buf[i + header + name.len()] = 0;
+
+            i += reclen;
+            self.next += 1;
+        }
+
+        Ok(i)
+    }
+}
@@ -2,8 +2,8 @@ use super::{Resource, Url};
 
 use alloc::boxed::Box;
 
-use system::error::{Error, Result, EPERM};
-use system::syscall::Stat;
+use system::error::{Error, Result, EINVAL, EISDIR, ENOSYS, EPERM, EXDEV};
+use system::syscall::{MODE_DIR, O_RDWR, Stat, StatVfs, TimeSpec};
 
 #[allow(unused_variables)]
 pub trait KScheme {
@@ -15,22 +15,66 @@ pub trait KScheme {
         ""
     }
 
+    /// Check if the scheme is going away. A draining scheme is hidden from the scheme listing
+    fn draining(&self) -> bool {
+        false
+    }
+
     fn open(&mut self, path: Url, flags: usize) -> Result<Box<Resource>> {
         Err(Error::new(EPERM))
     }
 
+    fn link(&mut self, old: Url, new: Url) -> Result<()> {
+        Err(Error::new(EPERM))
+    }
+
     fn mkdir(&mut self, path: Url, flags: usize) -> Result<()> {
         Err(Error::new(EPERM))
     }
 
+    fn readlink(&mut self, path: Url, buf: &mut [u8]) -> Result<usize> {
+        Err(Error::new(EINVAL))
+    }
+
+    fn rename(&mut self, old: Url, new: Url) -> Result<()> {
+        Err(Error::new(EXDEV))
+    }
+
     fn rmdir(&mut self, path: Url) -> Result<()> {
         Err(Error::new(EPERM))
     }
 
+    /// Set the access and modification times of path
+    fn set_times(&mut self, path: Url, atime: TimeSpec, mtime: TimeSpec) -> Result<()> {
+        Err(Error::new(EPERM))
+    }
+
     fn stat(&mut self, path: Url, stat: &mut Stat) -> Result<()> {
         Err(Error::new(EPERM))
     }
 
+    /// Get the statistics of the filesystem containing path
+    fn statvfs(&mut self, path: Url, stat: &mut StatVfs) -> Result<()> {
+        Err(Error::new(ENOSYS))
+    }
+
+    fn symlink(&mut self, target: &str, path: Url) -> Result<()> {
+        Err(Error::new(EPERM))
+    }
+
+    /// Truncate the file at path to len bytes
+    /// Opens the file for writing and truncates the resource by default.
+    fn truncate(&mut self, path: Url, len: usize) -> Result<()> {
+        let mut resource = try!(self.open(path, O_RDWR));
+
+        let mut stat = Stat::default();
+        if resource.stat(&mut stat).is_ok() && stat.st_mode & MODE_DIR == MODE_DIR {
+            return Err(Error::new(EISDIR));
+        }
+
+        resource.truncate(len)
+    }
+
     fn unlink(&mut self, path: Url) -> Result<()> {
         Err(Error::new(EPERM))
     }
@@ -1,3 +1,4 @@
+pub use self::dir_resource::DirResource;
 pub use self::kscheme::KScheme;
 pub use self::resource::{Resource, ResourceSeek};
 pub use self::scheme::Scheme;
@@ -5,6 +6,8 @@ pub use self::url::{Url, OwnedUrl};
 pub use self::vec_resource::VecResource;
 pub use self::supervisor_resource::SupervisorResource;
 
+/// Directory resource
+pub mod dir_resource;
 /// Kernel schemes
 pub mod kscheme;
 /// Internal resource representation
@@ -1,7 +1,7 @@
 use alloc::boxed::Box;
 
-use system::error::{Error, Result, EPERM, ESPIPE};
-use system::syscall::Stat;
+use system::error::{Error, Result, ENOTDIR, EPERM, ESPIPE};
+use system::syscall::{Stat, TimeSpec, POLLIN, POLLOUT};
 
 /// Resource seek
 #[derive(Copy, Clone, Debug)]
@@ -41,12 +41,58 @@ pub trait Resource {
         Err(Error::new(EPERM))
     }
 
+    /// Read data to buffer, starting at offset, without moving the seek position
+    /// The default implementation seeks, reads, and seeks back, so it returns `ESPIPE` if the
+    /// resource cannot seek.
+    fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let position = try!(self.seek(ResourceSeek::Current(0)));
+        try!(self.seek(ResourceSeek::Start(offset)));
+        let result = self.read(buf);
+        try!(self.seek(ResourceSeek::Start(position)));
+        result
+    }
+
+    /// Write to resource, starting at offset, without moving the seek position
+    /// The default implementation seeks, writes, and seeks back, so it returns `ESPIPE` if the
+    /// resource cannot seek.
+    fn write_at(&mut self, offset: usize, buf: &[u8]) -> Result<usize> {
+        let position = try!(self.seek(ResourceSeek::Current(0)));
+        try!(self.seek(ResourceSeek::Start(offset)));
+        let result = self.write(buf);
+        try!(self.seek(ResourceSeek::Start(position)));
+        result
+    }
+
+    /// Get the events that are ready, as POLLIN, POLLOUT, POLLERR and POLLHUP flags
+    /// Returns `POLLIN | POLLOUT` by default, as most resources do not block.
+    fn poll(&mut self) -> usize {
+        POLLIN | POLLOUT
+    }
+
+    /// Read directory entries to buffer, as packed `Dirent` records
+    /// Returns `ENOTDIR` if the resource is not a directory.
+    fn readdir(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Err(Error::new(ENOTDIR))
+    }
+
     /// Seek to the given offset
     /// Returns `ESPIPE` if the operation is not supported.
     fn seek(&mut self, pos: ResourceSeek) -> Result<usize> {
         Err(Error::new(ESPIPE))
     }
 
+    /// Update the file status flags, such as `O_NONBLOCK`
+    /// Returns `Ok(())` by default, as most resources do not depend on the status flags.
+    fn set_flags(&mut self, flags: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Set the access and modification times
+    /// Returns `EPERM` if the operation is not supported.
+    fn set_times(&mut self, atime: TimeSpec, mtime: TimeSpec) -> Result<()> {
+        Err(Error::new(EPERM))
+    }
+
     /// Get informations about the resource, such as mode and size
     /// Returns `EPERM` if the operation is not supported.
     fn stat(&self, stat: &mut Stat) -> Result<usize> {
@@ -1,32 +1,45 @@
-use alloc::arc::{Arc, Weak};
+use alloc::arc::Arc;
 use alloc::boxed::Box;
 
-use collections::String;
+use collections::{String, Vec};
 use collections::borrow::ToOwned;
 
 use core::cell::Cell;
 use core::mem::size_of;
 use core::ops::DerefMut;
-use core::{ptr, slice};
+use core::ptr;
 
 use arch::context::{Context, ContextMemory};
 
-use sync::{WaitMap, WaitQueue};
+use sync::{Intex, WaitMap, WaitQueue};
 
-use system::error::{Error, Result, EBADF, EFAULT, EINVAL, ENODEV, ESPIPE};
+use system::error::{Error, Result, EBADF, EFAULT, EINVAL, EIO, ENODEV, ENOENT, ESPIPE};
 use system::scheme::Packet;
-use system::syscall::{SYS_CLOSE, SYS_FPATH, SYS_FSTAT, SYS_FSYNC, SYS_FTRUNCATE,
+use system::syscall::{SYS_CLOSE, SYS_FPATH, SYS_FSTAT, SYS_FSYNC, SYS_FTRUNCATE, SYS_FUTIMENS,
                     SYS_OPEN, SYS_LSEEK, SEEK_SET, SEEK_CUR, SEEK_END, SYS_MKDIR,
-                    SYS_READ, SYS_WRITE, SYS_RMDIR, SYS_STAT, SYS_UNLINK, Stat};
+                    SYS_READ, SYS_WRITE, SYS_RMDIR, SYS_STAT, SYS_UNLINK, SYS_UTIMENS, Stat, TimeSpec};
 
 use super::{Resource, ResourceSeek, KScheme, Url};
 
+/// The kernel side of a userspace scheme, shared by its server resources, its entry in the
+/// scheme list, and the resources opened through it
+///
+/// Closing the last server resource drains the scheme: new opens fail with `ENOENT`, calls that
+/// are waiting fail with `EIO` and those still to come with `ENODEV`, and the entry is removed
+/// once the last resource opened through it is closed. Until then the name stays taken.
 struct SchemeInner {
     name: String,
     context: *mut Context,
     next_id: Cell<usize>,
     todo: WaitQueue<Packet>,
     done: WaitMap<usize, (usize, usize, usize, usize)>,
+    /// The ids of the calls waiting for an answer
+    pending: Intex<Vec<usize>>,
+    /// The number of server resources
+    servers: Cell<usize>,
+    /// The number of resources opened through the scheme
+    resources: Cell<usize>,
+    draining: Cell<bool>,
 }
 
 impl SchemeInner {
@@ -37,59 +50,73 @@ impl SchemeInner {
             next_id: Cell::new(1),
             todo: WaitQueue::new(),
             done: WaitMap::new(),
+            pending: Intex::new(Vec::new()),
+            servers: Cell::new(1),
+            resources: Cell::new(0),
+            draining: Cell::new(false),
         }
     }
 
-    fn call(inner: &Weak<SchemeInner>, a: usize, b: usize, c: usize, d: usize) -> Result<usize> {
-        if let Some(scheme) = inner.upgrade() {
-            let id = scheme.next_id.get();
+    fn call(&self, a: usize, b: usize, c: usize, d: usize) -> Result<usize> {
+        // The scheme drains under the same lock, so a call is either failed by `drain` or refused
+        let id = {
+            let mut pending = self.pending.lock();
+            if self.draining.get() {
+                return Err(Error::new(ENODEV));
+            }
+
+            let id = self.next_id.get();
 
             //TODO: What should be done about collisions in self.todo or self.done?
             let mut next_id = id + 1;
             if next_id <= 0 {
                 next_id = 1;
             }
-            scheme.next_id.set(next_id);
-
-            scheme.todo.send(Packet {
-                id: id,
-                a: a,
-                b: b,
-                c: c,
-                d: d
-            });
-            Error::demux(scheme.done.receive(&id).0)
-        } else {
-            Err(Error::new(ENODEV))
-        }
-    }
+            self.next_id.set(next_id);
 
-    fn capture(inner: &Weak<SchemeInner>, mut physical_address: usize, size: usize, writeable: bool) -> Result<usize> {
-        if let Some(scheme) = inner.upgrade() {
-            if physical_address >= 0x80000000 {
-                physical_address -= 0x80000000;
-            }
-            unsafe {
-                let mmap = &mut *(*scheme.context).mmap.get();
-                let virtual_address = mmap.next_mem();
-                mmap.memory.push(ContextMemory {
-                    physical_address: physical_address,
-                    virtual_address: virtual_address,
-                    virtual_size: size,
-                    writeable: writeable,
-                    allocated: false,
-                });
-                return Ok(virtual_address);
-            }
-        } else {
+            pending.push(id);
+            id
+        };
+
+        self.todo.send(Packet {
+            id: id,
+            a: a,
+            b: b,
+            c: c,
+            d: d
+        });
+        let result = self.done.receive(&id).0;
+        self.pending.lock().retain(|&pending| pending != id);
+        Error::demux(result)
+    }
+
+    fn capture(&self, mut physical_address: usize, size: usize, writeable: bool) -> Result<usize> {
+        // The server context may be gone once the scheme drains
+        if self.draining.get() {
             return Err(Error::new(ENODEV));
         }
+
+        if physical_address >= 0x80000000 {
+            physical_address -= 0x80000000;
+        }
+        unsafe {
+            let mmap = &mut *(*self.context).mmap.get();
+            let virtual_address = mmap.next_mem();
+            mmap.memory.push(ContextMemory {
+                physical_address: physical_address,
+                virtual_address: virtual_address,
+                virtual_size: size,
+                writeable: writeable,
+                allocated: false,
+            });
+            Ok(virtual_address)
+        }
     }
 
-    fn release(inner: &Weak<SchemeInner>, virtual_address: usize) {
-        if let Some(scheme) = inner.upgrade() {
+    fn release(&self, virtual_address: usize) {
+        if ! self.draining.get() {
             unsafe {
-                let mmap = &mut *(*scheme.context).mmap.get();
+                let mmap = &mut *(*self.context).mmap.get();
                 if let Ok(mut mem) = mmap.get_mem_mut(virtual_address) {
                     mem.virtual_size = 0;
                 }
@@ -97,30 +124,57 @@ impl SchemeInner {
             }
         }
     }
-}
 
-impl Drop for SchemeInner {
-    fn drop(&mut self) {
-        ::env().schemes.lock().retain(|scheme| scheme.scheme() != self.name);
+    /// Drain the scheme once its last server resource is closed, failing the calls waiting for
+    /// an answer with `EIO`
+    fn drain(&self) {
+        let pending = {
+            let pending = self.pending.lock();
+            self.draining.set(true);
+            pending.clone()
+        };
+
+        for id in pending {
+            if ! self.done.inner.lock().contains_key(&id) {
+                self.done.send(id, (Error::mux(Err(Error::new(EIO))), 0, 0, 0));
+            }
+        }
+
+        self.remove_unused();
+    }
+
+    /// Remove the entry of a drained scheme once no resource uses it
+    fn remove_unused(&self) {
+        if self.draining.get() && self.resources.get() == 0 {
+            ::env().schemes.lock().remove(&self.name);
+        }
     }
 }
 
 pub struct SchemeResource {
-    inner: Weak<SchemeInner>,
+    inner: Arc<SchemeInner>,
     file_id: usize,
 }
 
 impl SchemeResource {
+    fn new(inner: Arc<SchemeInner>, file_id: usize) -> SchemeResource {
+        inner.resources.set(inner.resources.get() + 1);
+        SchemeResource {
+            inner: inner,
+            file_id: file_id,
+        }
+    }
+
     fn call(&self, a: usize, b: usize, c: usize, d: usize) -> Result<usize> {
-        SchemeInner::call(&self.inner, a, b, c, d)
+        self.inner.call(a, b, c, d)
     }
 
     fn capture(&self, physical_address: usize, size: usize, writeable: bool) -> Result<usize> {
-        SchemeInner::capture(&self.inner, physical_address, size, writeable)
+        self.inner.capture(physical_address, size, writeable)
     }
 
     fn release(&self, virtual_address: usize){
-        SchemeInner::release(&self.inner, virtual_address);
+        self.inner.release(virtual_address);
     }
 }
 
@@ -208,25 +262,19 @@ impl Resource for SchemeResource {
     }
 
     /// Stat
+    ///
+    /// The kernel copies the Stat to the caller, so it is captured from the kernel.
     fn stat(&self, stat: &mut Stat) -> Result<usize> {
-        let buf = unsafe { slice::from_raw_parts_mut(stat as *mut Stat as *mut u8, size_of::<Stat>()) };
-
-        let contexts = ::env().contexts.lock();
-        let current = try!(contexts.current());
-        if let Ok(physical_address) = current.translate(buf.as_mut_ptr() as usize, buf.len()) {
-            let offset = physical_address % 4096;
+        let physical_address = stat as *mut Stat as usize;
+        let offset = physical_address % 4096;
 
-            let virtual_address = try!(self.capture(physical_address - offset, buf.len() + offset, true));
+        let virtual_address = try!(self.capture(physical_address - offset, size_of::<Stat>() + offset, true));
 
-            let result = self.call(SYS_FSTAT, self.file_id, virtual_address + offset, buf.len());
+        let result = self.call(SYS_FSTAT, self.file_id, virtual_address + offset, size_of::<Stat>());
 
-            self.release(virtual_address);
+        self.release(virtual_address);
 
-            result
-        } else {
-            debugln!("{}:{} fault {:X} {}", file!(), line!(), buf.as_ptr() as usize, buf.len());
-            Err(Error::new(EFAULT))
-        }
+        result
     }
 
     /// Sync the resource
@@ -237,11 +285,26 @@ impl Resource for SchemeResource {
     fn truncate(&mut self, len: usize) -> Result<()> {
         self.call(SYS_FTRUNCATE, self.file_id, len, 0).and(Ok(()))
     }
+
+    fn set_times(&mut self, atime: TimeSpec, mtime: TimeSpec) -> Result<()> {
+        let times = vec![atime, mtime];
+
+        let virtual_address = try!(self.capture(times.as_ptr() as usize, times.len() * size_of::<TimeSpec>(), false));
+
+        let result = self.call(SYS_FUTIMENS, self.file_id, virtual_address, 0);
+
+        self.release(virtual_address);
+
+        result.and(Ok(()))
+    }
 }
 
 impl Drop for SchemeResource {
     fn drop(&mut self) {
         let _ = self.call(SYS_CLOSE, self.file_id, 0, 0);
+
+        self.inner.resources.set(self.inner.resources.get() - 1);
+        self.inner.remove_unused();
     }
 }
 
@@ -252,6 +315,7 @@ pub struct SchemeServerResource {
 impl Resource for SchemeServerResource {
     /// Duplicate the resource
     fn dup(&self) -> Result<Box<Resource>> {
+        self.inner.servers.set(self.inner.servers.get() + 1);
         Ok(box SchemeServerResource {
             inner: self.inner.clone()
         })
@@ -333,10 +397,19 @@ impl Resource for SchemeServerResource {
     }
 }
 
+impl Drop for SchemeServerResource {
+    fn drop(&mut self) {
+        self.inner.servers.set(self.inner.servers.get() - 1);
+        if self.inner.servers.get() == 0 {
+            self.inner.drain();
+        }
+    }
+}
+
 /// Scheme has to be wrapped
 pub struct Scheme {
     name: String,
-    inner: Weak<SchemeInner>
+    inner: Arc<SchemeInner>
 }
 
 impl Scheme {
@@ -348,21 +421,28 @@ impl Scheme {
         };
         let scheme = box Scheme {
             name: name.to_owned(),
-            inner: Arc::downgrade(&server.inner)
+            inner: server.inner.clone()
         };
         Ok((scheme, server))
     }
 
+    /// Calls through a draining scheme fail as if it were gone
     fn call(&self, a: usize, b: usize, c: usize, d: usize) -> Result<usize> {
-        SchemeInner::call(&self.inner, a, b, c, d)
+        if self.inner.draining.get() {
+            return Err(Error::new(ENOENT));
+        }
+        self.inner.call(a, b, c, d)
     }
 
     fn capture(&self, physical_address: usize, size: usize, writeable: bool) -> Result<usize> {
-        SchemeInner::capture(&self.inner, physical_address, size, writeable)
+        if self.inner.draining.get() {
+            return Err(Error::new(ENOENT));
+        }
+        self.inner.capture(physical_address, size, writeable)
     }
 
     fn release(&self, virtual_address: usize){
-        SchemeInner::release(&self.inner, virtual_address);
+        self.inner.release(virtual_address);
     }
 }
 
@@ -375,6 +455,10 @@ impl KScheme for Scheme {
         &self.name
     }
 
+    fn draining(&self) -> bool {
+        self.inner.draining.get()
+    }
+
     fn open(&mut self, url: Url, flags: usize) -> Result<Box<Resource>> {
         let c_str = url.to_string() + "\0";
 
@@ -385,10 +469,7 @@ impl KScheme for Scheme {
         self.release(virtual_address);
 
         match result {
-            Ok(file_id) => Ok(box SchemeResource {
-                inner: self.inner.clone(),
-                file_id: file_id,
-            }),
+            Ok(file_id) => Ok(box SchemeResource::new(self.inner.clone(), file_id)),
             Err(err) => Err(err)
         }
     }
@@ -417,29 +498,51 @@ impl KScheme for Scheme {
         result.and(Ok(()))
     }
 
-    fn stat(&mut self, url: Url, stat: &mut Stat) -> Result<()> {
-        let buf = unsafe { slice::from_raw_parts_mut(stat as *mut Stat as *mut u8, size_of::<Stat>()) };
+    fn set_times(&mut self, url: Url, atime: TimeSpec, mtime: TimeSpec) -> Result<()> {
+        let c_str = url.to_string() + "\0";
+        let times = vec![atime, mtime];
 
-        let contexts = ::env().contexts.lock();
-        let current = try!(contexts.current());
-        if let Ok(physical_address) = current.translate(buf.as_mut_ptr() as usize, buf.len()) {
-            let offset = physical_address % 4096;
+        let c_str_address = try!(self.capture(c_str.as_ptr() as usize, c_str.len(), false));
 
-            let virtual_address = try!(self.capture(physical_address - offset, buf.len() + offset, true));
+        let times_address = match self.capture(times.as_ptr() as usize, times.len() * size_of::<TimeSpec>(), false) {
+            Ok(times_address) => times_address,
+            Err(err) => {
+                self.release(c_str_address);
+                return Err(err);
+            }
+        };
 
-            let c_str = url.to_string() + "\0";
+        let result = self.call(SYS_UTIMENS, c_str_address, times_address, 0);
 
-            let c_str_address = try!(self.capture(c_str.as_ptr() as usize, c_str.len(), false));
+        self.release(times_address);
+        self.release(c_str_address);
 
-            let result = self.call(SYS_STAT, c_str_address, virtual_address + offset, buf.len());
+        result.and(Ok(()))
+    }
 
-            self.release(c_str_address);
+    /// The kernel copies the Stat to the caller, so it is captured from the kernel
+    fn stat(&mut self, url: Url, stat: &mut Stat) -> Result<()> {
+        let c_str = url.to_string() + "\0";
 
-            result.and(Ok(()))
-        } else {
-            debugln!("{}:{} fault {:X} {}", file!(), line!(), buf.as_ptr() as usize, buf.len());
-            Err(Error::new(EFAULT))
-        }
+        let c_str_address = try!(self.capture(c_str.as_ptr() as usize, c_str.len(), false));
+
+        let physical_address = stat as *mut Stat as usize;
+        let offset = physical_address % 4096;
+
+        let stat_address = match self.capture(physical_address - offset, size_of::<Stat>() + offset, true) {
+            Ok(stat_address) => stat_address,
+            Err(err) => {
+                self.release(c_str_address);
+                return Err(err);
+            }
+        };
+
+        let result = self.call(SYS_STAT, c_str_address, stat_address + offset, size_of::<Stat>());
+
+        self.release(stat_address);
+        self.release(c_str_address);
+
+        result.and(Ok(()))
     }
 
     fn unlink(&mut self, url: Url) -> Result<()> {
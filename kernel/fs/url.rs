@@ -4,6 +4,7 @@ use alloc::boxed::Box;
 
 use collections::String;
 use collections::borrow::ToOwned;
+use collections::vec::Vec;
 
 use core::result::Result::{Ok, Err};
 
@@ -12,11 +13,45 @@ use common::slice::GetSlice;
 use system::error::{Result, Error};
 use system::syscall::{O_CREAT, O_RDWR, O_TRUNC};
 
+/// Decode percent-encoded bytes, like `%20` for a space. A `%` that is not followed by two hex
+/// digits is kept as it is. If the decoded bytes are not valid UTF-8, the string is returned
+/// without decoding
+pub fn percent_decode(string: &str) -> String {
+    fn hex(b: u8) -> Option<u8> {
+        match b {
+            b'0' ... b'9' => Some(b - b'0'),
+            b'a' ... b'f' => Some(b - b'a' + 10),
+            b'A' ... b'F' => Some(b - b'A' + 10),
+            _ => None,
+        }
+    }
+
+    let bytes = string.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                decoded.push(high << 4 | low);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8(decoded).unwrap_or(string.to_owned())
+}
+
 /// An URL, see wiki
+///
+/// The reference is everything after the ':' up to an optional '?', which starts the query.
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub struct Url<'a> {
     scheme: &'a str,
     reference: &'a str,
+    query: Option<&'a str>,
 }
 
 impl<'a> Url<'a> {
@@ -25,6 +60,7 @@ impl<'a> Url<'a> {
         Url {
             scheme: "",
             reference: "",
+            query: None,
         }
     }
 
@@ -36,20 +72,32 @@ impl<'a> Url<'a> {
             return Err(Error::new(22));
         };
 
+        let rest = string.get_slice(split + 1..);
+        let (reference, query) = match rest.find('?') {
+            Some(i) => (rest.get_slice(..i), Some(rest.get_slice(i + 1..))),
+            None => (rest, None),
+        };
+
         Ok(Url {
             scheme: string.get_slice(..split),
-            reference: string.get_slice(split + 1..),
+            reference: reference,
+            query: query,
         })
     }
 
     /// Convert the url to string
     pub fn to_string(self) -> String {
-        self.scheme.to_owned() + ":" + self.reference
+        let mut string = self.scheme.to_owned() + ":" + self.reference;
+        if let Some(query) = self.query {
+            string.push('?');
+            string.push_str(query);
+        }
+        string
     }
 
     /// Get the length of this URL
     pub fn len(self) -> usize {
-        self.scheme.len() + self.reference.len() + 1
+        self.scheme.len() + self.reference.len() + 1 + self.query.map_or(0, |query| query.len() + 1)
     }
 
     /// Open this URL (returns a resource)
@@ -72,11 +120,30 @@ impl<'a> Url<'a> {
         self.reference
     }
 
+    /// Get the query (after the '?') of the url, if there is one
+    pub fn query(self) -> Option<&'a str> {
+        self.query
+    }
+
+    /// Decode the percent-encoded bytes of the reference. URLs without a '%' are not copied
+    pub fn decode(self) -> CowUrl<'a> {
+        if self.reference.contains('%') {
+            CowUrl::Owned(OwnedUrl {
+                scheme: self.scheme.to_owned(),
+                reference: percent_decode(self.reference),
+                query: self.query.map(|query| query.to_owned()),
+            })
+        } else {
+            CowUrl::Ref(self)
+        }
+    }
+
     /// To owned equivalent
     pub fn to_owned(&self) -> OwnedUrl {
         OwnedUrl {
             scheme: self.scheme.to_owned(),
             reference: self.reference.to_owned(),
+            query: self.query.map(|query| query.to_owned()),
         }
     }
 
@@ -90,6 +157,7 @@ impl<'a> Url<'a> {
 pub struct OwnedUrl {
     scheme: String,
     reference: String,
+    query: Option<String>,
 }
 
 impl OwnedUrl {
@@ -98,6 +166,7 @@ impl OwnedUrl {
         OwnedUrl {
             scheme: String::new(),
             reference: String::new(),
+            query: None,
         }
     }
 
@@ -122,6 +191,7 @@ impl OwnedUrl {
         Url {
             scheme: &self.scheme,
             reference: &self.reference,
+            query: self.query.as_ref().map(|query| query.as_str()),
         }
     }
 }
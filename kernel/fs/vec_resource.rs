@@ -78,16 +78,39 @@ impl Resource for VecResource {
         return Ok(i);
     }
 
+    fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let mut i = 0;
+        while i < buf.len() && offset + i < self.data.len() {
+            buf[i] = self.data[offset + i];
+            i += 1;
+        }
+        return Ok(i);
+    }
+
+    fn write_at(&mut self, offset: usize, buf: &[u8]) -> Result<usize> {
+        while self.data.len() < offset {
+            self.data.push(0);
+        }
+
+        let mut i = 0;
+        while i < buf.len() && offset + i < self.data.len() {
+            self.data[offset + i] = buf[i];
+            i += 1;
+        }
+        while i < buf.len() {
+            self.data.push(buf[i]);
+            i += 1;
+        }
+        return Ok(i);
+    }
+
     fn seek(&mut self, pos: ResourceSeek) -> Result<usize> {
         match pos {
             ResourceSeek::Start(offset) => self.seek = min(self.data.len(), offset),
             ResourceSeek::Current(offset) =>
-                self.seek = max(0, min(self.seek as isize, self.seek as isize + offset)) as usize,
+                self.seek = max(0, min(self.data.len() as isize, self.seek as isize + offset)) as usize,
             ResourceSeek::End(offset) =>
-                self.seek = max(0,
-                                min(self.seek as isize,
-                                    self.data.len() as isize +
-                                    offset)) as usize,
+                self.seek = max(0, min(self.data.len() as isize, self.data.len() as isize + offset)) as usize,
         }
         return Ok(self.seek);
     }
@@ -1,9 +1,14 @@
 use alloc::boxed::Box;
 
-use core::cmp;
+use collections::Vec;
+
+use core::{cmp, ptr};
+use core::str::StrExt;
 
 use arch::memory;
 
+use drivers::io::{Io, Pio};
+
 use system::graphics::{fast_copy, fast_set};
 
 use super::FONT;
@@ -21,13 +26,13 @@ pub struct VBEModeInfo {
     segment_a: u16,
     segment_b: u16,
     winfuncptr: u32,
-    bytesperscanline: u16,
+    pub bytesperscanline: u16,
     pub xresolution: u16,
     pub yresolution: u16,
     xcharsize: u8,
     ycharsize: u8,
     numberofplanes: u8,
-    bitsperpixel: u8,
+    pub bitsperpixel: u8,
     numberofbanks: u8,
     memorymodel: u8,
     banksize: u8,
@@ -42,29 +47,199 @@ pub struct VBEModeInfo {
     rsvdmasksize: u8,
     rsvdfieldposition: u8,
     directcolormodeinfo: u8,
-    physbaseptr: u32,
+    pub physbaseptr: u32,
     offscreenmemoryoffset: u32,
     offscreenmemsize: u16,
 }
 
+/// A mode with a linear framebuffer, as recorded by the bootloader
+#[derive(Copy, Clone, Default, Debug)]
+#[repr(packed)]
+pub struct VBEMode {
+    pub mode: u16,
+    pub xresolution: u16,
+    pub yresolution: u16,
+    pub bytesperscanline: u16,
+    pub bitsperpixel: u8,
+    reserved: [u8; 3],
+    pub physbaseptr: u32,
+}
+
+impl VBEMode {
+    /// Create a mode
+    pub fn new(mode: u16, xresolution: u16, yresolution: u16, bytesperscanline: u16, bitsperpixel: u8, physbaseptr: u32) -> Self {
+        VBEMode {
+            mode: mode,
+            xresolution: xresolution,
+            yresolution: yresolution,
+            bytesperscanline: bytesperscanline,
+            bitsperpixel: bitsperpixel,
+            reserved: [0; 3],
+            physbaseptr: physbaseptr,
+        }
+    }
+}
+
 pub static mut VBEMODEINFO: Option<VBEModeInfo> = None;
 
+/// The modes the bootloader found, which have to be read before low memory is unmapped
+pub static mut VBEMODES: Option<Vec<VBEMode>> = None;
+
+/// Where the bootloader leaves the info of the mode it set
+const VBE_MODE_INFO_ADDR: usize = 0x5200;
+/// Where the bootloader leaves the number of modes, followed by the modes
+const VBE_MODE_LIST_ADDR: usize = 0x5500;
+/// The offset of the modes in the list
+const VBE_MODE_LIST_MODES: usize = 16;
+/// The most modes the bootloader records
+const VBE_MODE_LIST_MAX: usize = 128;
+
+/// The index and data ports of the display interface of Bochs, QEMU and VirtualBox
+const DISPI_INDEX: u16 = 0x1CE;
+const DISPI_DATA: u16 = 0x1CF;
+const DISPI_ID: u16 = 0;
+const DISPI_XRES: u16 = 1;
+const DISPI_YRES: u16 = 2;
+const DISPI_BPP: u16 = 3;
+const DISPI_ENABLE: u16 = 4;
+const DISPI_VIRT_WIDTH: u16 = 6;
+const DISPI_ENABLED: u16 = 0x01;
+const DISPI_LFB_ENABLED: u16 = 0x40;
+
+/// Parse a resolution like `1024x768`
+pub fn parse_resolution(string: &str) -> Option<(u16, u16)> {
+    let mut parts = string.trim().split('x');
+    match (parts.next().and_then(|w| w.parse::<u16>().ok()), parts.next().and_then(|h| h.parse::<u16>().ok()), parts.next()) {
+        (Some(width), Some(height), None) if width > 0 && height > 0 => Some((width, height)),
+        _ => None,
+    }
+}
+
+/// Find a mode with the resolution. Only modes with 32 bits per pixel are supported.
+pub fn vbe_find_mode(modes: &[VBEMode], width: u16, height: u16) -> Option<VBEMode> {
+    for mode in modes.iter() {
+        let (xresolution, yresolution, bitsperpixel) = (mode.xresolution, mode.yresolution, mode.bitsperpixel);
+        if xresolution == width && yresolution == height && bitsperpixel == 32 {
+            return Some(*mode);
+        }
+    }
+    None
+}
+
+/// Read and write the display interface registers
+fn dispi(index: u16) -> u16 {
+    Pio::<u16>::new(DISPI_INDEX).write(index);
+    Pio::<u16>::new(DISPI_DATA).read()
+}
+
+fn dispi_write(index: u16, data: u16) {
+    Pio::<u16>::new(DISPI_INDEX).write(index);
+    Pio::<u16>::new(DISPI_DATA).write(data);
+}
+
+/// Set a mode. Setting a mode through the VBE BIOS needs real mode, which the kernel has left, so
+/// this only works with the display interface the VBE BIOS of Bochs, QEMU and VirtualBox is built
+/// on. The framebuffer of such a mode is not padded.
+unsafe fn vbe_set_mode(mode: &VBEMode) -> Option<VBEModeInfo> {
+    let id = dispi(DISPI_ID);
+    if id < 0xB0C0 || id > 0xB0CF {
+        return None;
+    }
+
+    dispi_write(DISPI_ENABLE, 0);
+    dispi_write(DISPI_XRES, mode.xresolution);
+    dispi_write(DISPI_YRES, mode.yresolution);
+    dispi_write(DISPI_BPP, 32);
+    dispi_write(DISPI_VIRT_WIDTH, mode.xresolution);
+    dispi_write(DISPI_ENABLE, DISPI_ENABLED | DISPI_LFB_ENABLED);
+
+    if dispi(DISPI_XRES) != mode.xresolution || dispi(DISPI_YRES) != mode.yresolution {
+        return None;
+    }
+
+    let mut mode_info = *(VBE_MODE_INFO_ADDR as *const VBEModeInfo);
+    mode_info.xresolution = mode.xresolution;
+    mode_info.yresolution = mode.yresolution;
+    mode_info.bytesperscanline = mode.xresolution * 4;
+    mode_info.bitsperpixel = 32;
+    mode_info.physbaseptr = mode.physbaseptr;
+    Some(mode_info)
+}
+
+/// Get the mode the bootloader set and the modes it found. Building with `REDOX_RESOLUTION` set,
+/// such as to `1024x768`, switches to that resolution if there is a mode for it, otherwise the
+/// mode of the bootloader is kept.
 pub unsafe fn vbe_init(){
-    let mode_info = *(0x5200 as *const VBEModeInfo);
+    let mode_info = *(VBE_MODE_INFO_ADDR as *const VBEModeInfo);
     if mode_info.physbaseptr > 0 {
         VBEMODEINFO = Some(mode_info);
     }else{
         VBEMODEINFO = None;
     }
+
+    let mut modes = Vec::new();
+    let count = cmp::min(ptr::read(VBE_MODE_LIST_ADDR as *const u16) as usize, VBE_MODE_LIST_MAX);
+    for i in 0..count {
+        modes.push(ptr::read(((VBE_MODE_LIST_ADDR + VBE_MODE_LIST_MODES) as *const VBEMode).offset(i as isize)));
+    }
+
+    if VBEMODEINFO.is_some() {
+        if let Some((width, height)) = option_env!("REDOX_RESOLUTION").and_then(parse_resolution) {
+            let current = VBEMODEINFO.map(|info| (info.xresolution, info.yresolution));
+            if current != Some((width, height)) {
+                match vbe_find_mode(&modes, width, height) {
+                    Some(mode) => match vbe_set_mode(&mode) {
+                        Some(new_info) => VBEMODEINFO = Some(new_info),
+                        None => debugln!("Could not switch to {}x{}, keeping the mode of the bootloader", width, height),
+                    },
+                    None => debugln!("No 32 bit mode for {}x{}, keeping the mode of the bootloader", width, height),
+                }
+            }
+        }
+    }
+
+    VBEMODES = Some(modes);
+}
+
+/// A rectangle of the display, from `(x1, y1)` up to but not including `(x2, y2)`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Damage {
+    pub x1: usize,
+    pub y1: usize,
+    pub x2: usize,
+    pub y2: usize,
+}
+
+impl Damage {
+    /// The smallest rectangle holding both rectangles
+    pub fn union(&self, other: Damage) -> Damage {
+        Damage {
+            x1: cmp::min(self.x1, other.x1),
+            y1: cmp::min(self.y1, other.y1),
+            x2: cmp::max(self.x2, other.x2),
+            y2: cmp::max(self.y2, other.y2),
+        }
+    }
 }
 
 /// A display
 pub struct Display {
+    /// The buffer the console draws into, copied to the screen by `flip`
     pub offscreen: *mut u32,
+    /// The framebuffer
     pub onscreen: *mut u32,
+    /// The buffer display resources write into, copied to the screen by `sync`
+    pub backbuffer: *mut u32,
+    /// The part of the back buffer written since the last `sync`
+    pub damage: Option<Damage>,
+    /// Set by a timer tick that found damage, so that the next tick pushes it out
+    pub stale: bool,
     pub size: usize,
     pub width: usize,
     pub height: usize,
+    /// The pixels from one scanline of the framebuffer to the next, more than the width if the
+    /// scanlines are padded
+    pub stride: usize,
 }
 
 impl Display {
@@ -74,12 +249,18 @@ impl Display {
                 offscreen: unsafe { memory::alloc(mode_info.xresolution as usize *
                                          mode_info.yresolution as usize * 4) as *mut u32 },
                 onscreen: mode_info.physbaseptr as usize as *mut u32,
+                backbuffer: unsafe { memory::alloc(mode_info.xresolution as usize *
+                                         mode_info.yresolution as usize * 4) as *mut u32 },
+                damage: None,
+                stale: false,
                 size: mode_info.xresolution as usize * mode_info.yresolution as usize,
                 width: mode_info.xresolution as usize,
                 height: mode_info.yresolution as usize,
+                stride: cmp::max(mode_info.bytesperscanline as usize / 4, mode_info.xresolution as usize),
             };
 
             ret.set(Color::new(0, 0, 0));
+            unsafe { fast_set(ret.backbuffer, 0, ret.size); }
 
             Some(ret)
         } else {
@@ -107,13 +288,86 @@ impl Display {
 
     /// Flip the display
     pub fn flip(&self) {
-        unsafe {
-            fast_copy(self.onscreen, self.offscreen, self.size);
+        if self.stride == self.width {
+            unsafe {
+                fast_copy(self.onscreen, self.offscreen, self.size);
+            }
+        } else {
+            for y in 0..self.height {
+                unsafe {
+                    fast_copy(self.onscreen.offset((y * self.stride) as isize),
+                              self.offscreen.offset((y * self.width) as isize), self.width);
+                }
+            }
+        }
+    }
+
+    /// Add the pixels from `offset` up to `offset + len` in the back buffer to the damage
+    pub fn damage_range(&mut self, offset: usize, len: usize) {
+        if len == 0 || self.width == 0 {
+            return;
+        }
+
+        let end = offset + len - 1;
+        let y1 = offset / self.width;
+        let y2 = end / self.width + 1;
+        let damage = if y2 - y1 == 1 {
+            Damage {
+                x1: offset % self.width,
+                y1: y1,
+                x2: end % self.width + 1,
+                y2: y2,
+            }
+        } else {
+            Damage {
+                x1: 0,
+                y1: y1,
+                x2: self.width,
+                y2: y2,
+            }
+        };
+
+        self.damage = Some(match self.damage {
+            Some(old) => old.union(damage),
+            None => damage,
+        });
+    }
+
+    /// Copy the damaged part of the back buffer to the screen, a scanline at a time
+    pub fn sync(&mut self) {
+        if let Some(damage) = self.damage.take() {
+            let x2 = cmp::min(damage.x2, self.width);
+            let y2 = cmp::min(damage.y2, self.height);
+            if damage.x1 < x2 {
+                for y in damage.y1..y2 {
+                    unsafe {
+                        fast_copy(self.onscreen.offset((y * self.stride + damage.x1) as isize),
+                                  self.backbuffer.offset((y * self.width + damage.x1) as isize), x2 - damage.x1);
+                    }
+                }
+            }
+        }
+        self.stale = false;
+    }
+
+    /// Called on each timer tick, to push out damage that was not synced within a tick
+    pub fn tick(&mut self) {
+        if self.damage.is_some() {
+            if self.stale {
+                self.sync();
+            } else {
+                self.stale = true;
+            }
         }
     }
 
     /// Draw a rectangle
     pub fn rect(&self, x: usize, y: usize, w: usize, h: usize, color: Color) {
+        self.rect_in(self.offscreen, x, y, w, h, color);
+    }
+
+    /// Draw a rectangle into a buffer the size of the display
+    pub fn rect_in(&self, buffer: *mut u32, x: usize, y: usize, w: usize, h: usize, color: Color) {
         let data = color.data;
 
         let start_y = cmp::min(self.height - 1, y);
@@ -124,16 +378,21 @@ impl Display {
 
         for y in start_y..end_y {
             unsafe {
-                fast_set(self.offscreen.offset((y * self.width + start_x) as isize), data, len);
+                fast_set(buffer.offset((y * self.width + start_x) as isize), data, len);
             }
         }
     }
 
     /// Draw a char
     pub fn char(&self, x: usize, y: usize, character: char, color: Color) {
+        self.char_in(self.offscreen, x, y, character, color);
+    }
+
+    /// Draw a char into a buffer the size of the display
+    pub fn char_in(&self, buffer: *mut u32, x: usize, y: usize, character: char, color: Color) {
         if x + 8 <= self.width && y + 16 <= self.height {
             let data = color.data;
-            let mut dst = unsafe { self.offscreen.offset((y * self.width + x) as isize) };
+            let mut dst = unsafe { buffer.offset((y * self.width + x) as isize) };
 
             let font_i = 16 * (character as usize);
             for row in 0..16 {
@@ -155,6 +414,9 @@ impl Drop for Display {
             if self.offscreen as usize > 0 {
                 memory::unalloc(self.offscreen as usize);
             }
+            if self.backbuffer as usize > 0 {
+                memory::unalloc(self.backbuffer as usize);
+            }
         }
     }
 }
@@ -1,6 +1,11 @@
 use collections::borrow::ToOwned;
+use collections::string::String;
+use collections::vec_deque::VecDeque;
 
-#[derive(Copy, Clone)]
+use common::time::Duration;
+
+/// The priority of a log entry, from the most to the least important
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogLevel {
     Critical,
     Error,
@@ -9,8 +14,152 @@ pub enum LogLevel {
     Debug,
 }
 
+impl LogLevel {
+    /// Parse the name of a level, in lower case or as written by `name`
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        match name {
+            "crit" | "critical" | "CRIT" => Some(LogLevel::Critical),
+            "error" | "ERROR" => Some(LogLevel::Error),
+            "warn" | "warning" | "WARN" => Some(LogLevel::Warning),
+            "info" | "INFO" => Some(LogLevel::Info),
+            "debug" | "DEBUG" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+
+    /// The name of the level, as shown in the logs
+    pub fn name(&self) -> &'static str {
+        match *self {
+            LogLevel::Critical => "CRIT",
+            LogLevel::Error => "ERROR",
+            LogLevel::Warning => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+        }
+    }
+}
+
+/// The number of entries kept by the kernel log, unless set by `REDOX_KLOG_ENTRIES` at build time
+pub const KLOG_ENTRIES: usize = 1024;
+/// The most entries staged while the kernel log is in use by the interrupted context
+pub const KLOG_STAGING: usize = 16;
+
+/// A message in the kernel log
+pub struct LogEntry {
+    /// The number of the entry, counting every entry ever logged
+    pub seq: u64,
+    /// The monotonic time of the entry
+    pub time: Duration,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+impl LogEntry {
+    /// Format the entry as a line, like "[12.345] INFO message"
+    pub fn line(&self) -> String {
+        format!("[{}.{:03}] {} {}\n", self.time.secs, self.time.nanos / 1000000, self.level.name(), self.message)
+    }
+}
+
+/// The kernel log, keeping the newest entries up to a limit
+pub struct KernelLog {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    /// The number of the next entry
+    next_seq: u64,
+    /// Entries less important than this are not kept
+    pub level: LogLevel,
+}
+
+impl KernelLog {
+    /// Create a log keeping up to `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        KernelLog {
+            entries: VecDeque::new(),
+            capacity: capacity,
+            next_seq: 0,
+            level: LogLevel::Debug,
+        }
+    }
+
+    /// Add an entry, dropping the oldest if the log is full
+    pub fn push(&mut self, level: LogLevel, time: Duration, message: &str) {
+        if level > self.level || self.capacity == 0 {
+            return;
+        }
+
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(LogEntry {
+            seq: self.next_seq,
+            time: time,
+            level: level,
+            message: message.to_owned(),
+        });
+        self.next_seq += 1;
+    }
+
+    /// Remove every entry. Entries keep being numbered from where they were.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// The number of the next entry to be logged
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Format the entries numbered `seq` or later that are at least as important as `level`.
+    /// Returns the lines and the number of the entry after the last one.
+    pub fn lines_since(&self, seq: u64, level: LogLevel) -> (String, u64) {
+        let mut string = String::new();
+        for entry in self.entries.iter() {
+            if entry.seq >= seq && entry.level <= level {
+                string.push_str(&entry.line());
+            }
+        }
+        (string, self.next_seq)
+    }
+}
+
+/// The number of entries to keep in the kernel log
+pub fn klog_entries() -> usize {
+    option_env!("REDOX_KLOG_ENTRIES").and_then(|entries| entries.parse::<usize>().ok()).unwrap_or(KLOG_ENTRIES)
+}
+
 /// Add `message` to the kernel logs, with a priority level of `level`
+///
+/// If the kernel logs are in use by the interrupted context, such as when logging from an IRQ
+/// handler, the entry is staged and added by the next call that finds them free. The staging
+/// area is only held for an instant, so it is never in use when interrupted.
 pub fn klog(level: LogLevel, message: &str) {
-    let mut logs = ::env().logs.lock();
-    logs.push((level, message.to_owned()));
+    let time = Duration::monotonic();
+    match ::env().logs.try_lock() {
+        Some(mut logs) => {
+            klog_unstage(&mut logs);
+            logs.push(level, time, message);
+        },
+        None => {
+            let mut staging = ::env().log_staging.lock();
+            while staging.len() >= KLOG_STAGING {
+                staging.pop_front();
+            }
+            staging.push_back((level, time, message.to_owned()));
+        }
+    }
+}
+
+/// Add the staged entries to the kernel logs
+pub fn klog_flush() {
+    if let Some(mut logs) = ::env().logs.try_lock() {
+        klog_unstage(&mut logs);
+    }
+}
+
+fn klog_unstage(logs: &mut KernelLog) {
+    for (level, time, message) in ::env().log_staging.lock().drain(..) {
+        logs.push(level, time, &message);
+    }
 }
@@ -34,10 +34,12 @@ extern crate collections;
 extern crate system;
 
 use acpi::Acpi;
+use acpi::hpet::ADDRESS_SPACE_MEMORY;
 
 use alloc::boxed::Box;
 
-use arch::context::{context_switch, Context};
+use arch::context::{context_switch, sched_cost, Context, CONTEXT_STACK_SIZE, PRIO_KERNEL};
+use arch::hpet::Hpet;
 use arch::memory;
 use arch::paging::Page;
 use arch::regs::Regs;
@@ -46,16 +48,14 @@ use arch::tss::Tss;
 use collections::Vec;
 use collections::string::ToString;
 
-use core::{mem, usize};
+use core::{mem, ptr, usize};
 use core::slice::SliceExt;
 
-use common::time::Duration;
-
 use drivers::pci;
 use drivers::io::{Io, Pio};
 use drivers::ps2::*;
 use drivers::rtc::*;
-use drivers::serial::*;
+use drivers::serial::{self, Parity, Serial, COM1, COM2, SERIAL_BAUD};
 
 use env::Environment;
 
@@ -63,11 +63,14 @@ use graphics::display;
 
 use logging::{LogLevel, klog};
 
-use network::schemes::{ArpScheme, EthernetScheme, IcmpScheme, IpScheme, TcpScheme, UdpScheme};
+use network::dhcp;
+use network::schemes::{ArpScheme, DnsScheme, EthernetScheme, IcmpScheme, IpScheme, NetcfgScheme, NetstatScheme,
+                       TcpScheme, UdpScheme};
 
 use schemes::context::ContextScheme;
+use schemes::cpu::CpuScheme;
 use schemes::debug::DebugScheme;
-use schemes::disk::DiskScheme;
+use schemes::disk::{DiskScheme, DISK_CACHE_BLOCKS};
 use schemes::display::DisplayScheme;
 use schemes::env::EnvScheme;
 //use schemes::file::FileScheme;
@@ -75,10 +78,12 @@ use schemes::initfs::InitFsScheme;
 use schemes::interrupt::InterruptScheme;
 use schemes::klog::KlogScheme;
 use schemes::memory::MemoryScheme;
+use schemes::pci::PciScheme;
 use schemes::test::TestScheme;
 
 use syscall::execute::execute;
-use syscall::{do_sys_chdir, do_sys_exit, do_sys_open, syscall_handle};
+use syscall::{do_sys_chdir, do_sys_exit, do_sys_open, signal_fault, signal_handle, syscall_handle, O_RDONLY,
+              O_WRONLY, SIGSEGV};
 
 pub use externs::*;
 
@@ -205,15 +210,6 @@ pub fn env() -> &'static Environment {
     }
 }
 
-/// The PIT (programmable interval timer) duration.
-///
-/// This duration defines the PIT interval, which is added to the monotonic clock and the real time
-/// clock, when interrupt 0x20 is received.
-static PIT_DURATION: Duration = Duration {
-    secs: 0,
-    nanos: 4500572,
-};
-
 /// The idle loop.
 ///
 /// This loop runs while the system is idle.
@@ -263,6 +259,98 @@ static BSS_TEST_ZERO: usize = 0;
 /// Test of non-zero values in BSS.
 static BSS_TEST_NONZERO: usize = !0;
 
+/// Give the double fault handler its own stack.
+///
+/// When the kernel stack overflows into its guard page, the page fault cannot be pushed and a
+/// double fault follows, which would otherwise fail the same way and triple fault. On x86 the
+/// double fault is a task gate to the TSS that the boot code places after `tss`, which gets its
+/// stack and page directory here. Switching back to the task that faulted loads the page
+/// directory of `tss`, so it is set too.
+#[cfg(target_arch = "x86")]
+unsafe fn double_fault_stack_init(tss: &mut Tss) {
+    let cr3: usize;
+    asm!("mov $0, cr3" : "=r"(cr3) : : : "intel", "volatile");
+    tss.cr3 = cr3;
+
+    let double_fault_tss = &mut *((tss as *mut Tss as usize + mem::size_of::<Tss>()) as *mut Tss);
+    double_fault_tss.cr3 = cr3;
+    let stack = memory::alloc_aligned(CONTEXT_STACK_SIZE, 4096);
+    if stack > 0 {
+        double_fault_tss.sp = stack + CONTEXT_STACK_SIZE - 128;
+    }
+}
+
+/// Give the double fault handler its own stack, in `ist1`, which the IDT entry of the double
+/// fault uses.
+#[cfg(target_arch = "x86_64")]
+unsafe fn double_fault_stack_init(tss: &mut Tss) {
+    let stack = memory::alloc_aligned(CONTEXT_STACK_SIZE, 4096);
+    if stack > 0 {
+        tss.ist1 = stack + CONTEXT_STACK_SIZE - 128;
+    }
+}
+
+/// Fill in the registers of the task that double faulted, which the task switch saved in `tss`,
+/// in the order of an exception with an error code. Returns false where the double fault runs on
+/// the stack of the task that faulted, with its registers already in place
+#[cfg(target_arch = "x86")]
+fn double_fault_regs(regs: &mut Regs) -> bool {
+    if let Some(ref tss) = unsafe { TSS_PTR.as_ref() } {
+        regs.ax = tss.ax;
+        regs.bx = tss.bx;
+        regs.cx = tss.cx;
+        regs.dx = tss.dx;
+        regs.di = tss.di;
+        regs.si = tss.si;
+        regs.bp = tss.bp;
+        regs.cs = tss.ip;
+        regs.flags = tss.cs;
+        regs.sp = tss.flags;
+        regs.ss = tss.sp;
+    }
+    true
+}
+
+#[cfg(target_arch = "x86_64")]
+fn double_fault_regs(_regs: &mut Regs) -> bool {
+    false
+}
+
+/// Make the task that double faulted exit its context once the handler switches back to it, as
+/// its kernel stack can not be used anymore. It continues in `double_fault_exit` at the top of
+/// the kernel stack, with interrupts disabled.
+#[cfg(target_arch = "x86")]
+fn double_fault_resume() {
+    let stack = match env().contexts.lock().current() {
+        Ok(current) if current.kernel_stack > 0 => current.kernel_stack + CONTEXT_STACK_SIZE - 128,
+        _ => 0x800000 - 128,
+    };
+
+    if let Some(ref mut tss) = unsafe { TSS_PTR.as_mut() } {
+        tss.ip = double_fault_exit as usize;
+        tss.flags = 2;
+        tss.sp = stack;
+        tss.bp = 0;
+        tss.cs = 0x08;
+        tss.ss = 0x10;
+        tss.ds = 0x10;
+        tss.es = 0x10;
+        tss.fs = 0x10;
+        tss.gs = 0x10;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn double_fault_resume() {}
+
+/// Exit the context that double faulted
+#[cfg(target_arch = "x86")]
+extern "cdecl" fn double_fault_exit() -> ! {
+    loop {
+        do_sys_exit(usize::MAX);
+    }
+}
+
 /// Initialize the kernel.
 ///
 /// This will initialize the kernel: the environment, the memory allocator, the memory pager, PCI and so
@@ -348,13 +436,22 @@ unsafe fn init(tss_data: usize) {
     }
 
     TSS_PTR = Some(&mut *(tss_data as *mut Tss));
+    if let Some(ref mut tss) = TSS_PTR {
+        double_fault_stack_init(tss);
+    }
     ENV_PTR = Some(&mut *Box::into_raw(Environment::new()));
 
     match ENV_PTR {
         Some(ref mut env) => {
             env.contexts.lock().push(Context::root());
 
-            env.console.lock().draw = true;
+            {
+                let mut console = env.console.lock();
+                console.draw = true;
+                // Without a display, COM1 is the console. Writing `serial` or `noserial` to
+                // debug:mode changes this later
+                console.serial = console.display.is_none();
+            }
 
             debugln!("\x1B[1mRedox {} bits\x1B[0m", mem::size_of::<usize>() * 8);
             debugln!("  * text={:X}:{:X} rodata={:X}:{:X}",
@@ -365,48 +462,73 @@ unsafe fn init(tss_data: usize) {
                     & __bss_start as *const u8 as usize, & __bss_end as *const u8 as usize);
 
             if let Some(acpi) = Acpi::new() {
-                env.schemes.lock().push(acpi);
+                if let Some(hpet) = acpi.hpet() {
+                    let address = hpet.data.base_address;
+                    if address.address_space == ADDRESS_SPACE_MEMORY {
+                        env.hpet = Hpet::new(address.address as usize);
+                        if env.hpet.is_some() {
+                            debugln!("  * HPET at {:X}", address.address);
+                        }
+                    }
+                }
+                env.register(acpi);
             }
 
             *(env.clock_realtime.lock()) = Rtc::new().time();
 
-            env.schemes.lock().push(Ps2::new());
-            env.schemes.lock().push(Serial::new(0x3F8, 0x4));
+            env.register(Ps2::new());
+            env.register(Serial::new("serial", COM1, 0x4, SERIAL_BAUD, Parity::None, true));
+            if serial::present(COM2) {
+                env.register(Serial::new("serial2", COM2, 0x3, SERIAL_BAUD, Parity::None, false));
+            }
 
             pci::pci_init(env);
 
-            env.schemes.lock().push(DebugScheme::new());
-            env.schemes.lock().push(InitFsScheme::new());
-            env.schemes.lock().push(box ContextScheme);
-            env.schemes.lock().push(box DisplayScheme);
-            env.schemes.lock().push(box EnvScheme);
-            env.schemes.lock().push(box InterruptScheme);
-            env.schemes.lock().push(box KlogScheme);
-            env.schemes.lock().push(box MemoryScheme);
-            env.schemes.lock().push(box TestScheme);
+            env.register(DebugScheme::new());
+            env.register(InitFsScheme::new());
+            env.register(box ContextScheme);
+            env.register(box CpuScheme);
+            env.register(box DisplayScheme);
+            env.register(box EnvScheme);
+            env.register(InterruptScheme::new());
+            env.register(box KlogScheme);
+            env.register(box MemoryScheme);
+            env.register(box PciScheme);
+            env.register(box TestScheme);
 
             //TODO: Do not do this! Find a better way
             let mut disks = Vec::new();
             disks.append(&mut env.disks.lock());
-            env.schemes.lock().push(DiskScheme::new(disks));
+            env.register(DiskScheme::new(disks, DISK_CACHE_BLOCKS));
+
+            env.register(box EthernetScheme);
+            env.register(box NetcfgScheme);
+            env.register(box NetstatScheme);
+            env.register(box ArpScheme);
+            env.register(box IcmpScheme);
+            env.register(box IpScheme);
+            env.register(TcpScheme::new());
+            env.register(box UdpScheme);
+            env.register(box DnsScheme);
+
+            Context::spawn_priority("karp".to_string(), PRIO_KERNEL,
+            box move || {
+                ArpScheme::reply_loop();
+            });
 
-            env.schemes.lock().push(box EthernetScheme);
-            //env.schemes.lock().push(box ArpScheme);
-            //env.schemes.lock().push(box IcmpScheme);
-            env.schemes.lock().push(box IpScheme {
-                arp: Vec::new()
+            Context::spawn_priority("kicmp".to_string(), PRIO_KERNEL,
+            box move || {
+                IcmpScheme::reply_loop();
             });
-            env.schemes.lock().push(box TcpScheme);
-            env.schemes.lock().push(box UdpScheme);
 
-            Context::spawn("karp".to_string(),
+            Context::spawn_priority("ktcp".to_string(), PRIO_KERNEL,
             box move || {
-                ArpScheme::reply_loop();
+                TcpScheme::timer_loop();
             });
 
-            Context::spawn("kicmp".to_string(),
+            Context::spawn("kdhcp".to_string(),
             box move || {
-                IcmpScheme::reply_loop();
+                dhcp::dhcp_loop();
             });
 
             env.contexts.lock().enabled = true;
@@ -418,9 +540,9 @@ unsafe fn init(tss_data: usize) {
                     do_sys_chdir(wd_c.as_ptr()).unwrap();
 
                     let stdio_c = "debug:\0";
-                    do_sys_open(stdio_c.as_ptr(), 0).unwrap();
-                    do_sys_open(stdio_c.as_ptr(), 0).unwrap();
-                    do_sys_open(stdio_c.as_ptr(), 0).unwrap();
+                    do_sys_open(stdio_c.as_ptr(), O_RDONLY).unwrap();
+                    do_sys_open(stdio_c.as_ptr(), O_WRONLY).unwrap();
+                    do_sys_open(stdio_c.as_ptr(), O_WRONLY).unwrap();
 
                     if let Some(ref display) = ::env().console.lock().display {
                         let mut contexts = ::env().contexts.lock();
@@ -446,7 +568,7 @@ unsafe fn init(tss_data: usize) {
 /// Interrupt and exception handling.
 pub extern "cdecl" fn kernel(interrupt: usize, mut regs: &mut Regs) {
     macro_rules! exception_inner {
-        ($name:expr) => ({
+        ($name:expr, $stack:expr) => ({
             {
                 let contexts = ::env().contexts.lock();
                 if let Ok(context) = contexts.current() {
@@ -458,7 +580,7 @@ pub extern "cdecl" fn kernel(interrupt: usize, mut regs: &mut Regs) {
             debugln!("    CS:  {:08X}    IP:  {:08X}    FLG: {:08X}", regs.cs, regs.ip, regs.flags);
             debugln!("    SS:  {:08X}    SP:  {:08X}    BP:  {:08X}", regs.ss, regs.sp, regs.bp);
             debugln!("    AX:  {:08X}    BX:  {:08X}    CX:  {:08X}    DX:  {:08X}", regs.ax, regs.bx, regs.cx, regs.dx);
-            debugln!("    DI:  {:08X}    SI:  {:08X}", regs.di, regs.di);
+            debugln!("    DI:  {:08X}    SI:  {:08X}", regs.di, regs.si);
 
             let cr0: usize;
             let cr2: usize;
@@ -480,18 +602,20 @@ pub extern "cdecl" fn kernel(interrupt: usize, mut regs: &mut Regs) {
             }
             debugln!("    FSW: {:08X}    FCW: {:08X}", fsw, fcw);
 
-            /* TODO: Stack dump
+            // Dump the words around the stack pointer, mostly above it where the callers' frames
+            // are. Only memory mapped in the context, or its kernel stack, is read
             {
                 let contexts = ::env().contexts.lock();
                 if let Ok(context) = contexts.current() {
-                    let sp = regs.sp as *const usize;
-                    for y in -15..16 {
-                        debug!("    {:>3}:", y * 8 * 4);
+                    let sp = $stack as *const usize;
+                    let word = mem::size_of::<usize>() as isize;
+                    for y in -4..12 {
+                        debug!("    {:>4}:", y * 8 * word);
                         for x in 0..8 {
-                            let p = unsafe { sp.offset(-(x + y * 8)) };
-                            if let Ok(_) = context.translate(p as usize, 1) {
+                            let p = unsafe { sp.offset(x + y * 8) };
+                            if let Ok(_) = context.translate(p as usize, mem::size_of::<usize>()) {
                                 debug!(" {:08X}", unsafe { ptr::read(p) });
-                            } else if context.kernel_stack > 0 && (p as usize) >= context.kernel_stack && (p as usize) < context.kernel_stack + CONTEXT_STACK_SIZE {
+                            } else if context.kernel_stack > 0 && (p as usize) >= context.kernel_stack && (p as usize) + mem::size_of::<usize>() <= context.kernel_stack + CONTEXT_STACK_SIZE {
                                 debug!(" {:08X}", unsafe { ptr::read(p) });
                             } else {
                                 debug!(" ????????");
@@ -501,21 +625,42 @@ pub extern "cdecl" fn kernel(interrupt: usize, mut regs: &mut Regs) {
                     }
                 }
             }
-            */
         })
     };
 
-    macro_rules! exception {
+    macro_rules! exception_end {
         ($name:expr) => ({
-            exception_inner!($name);
-
-            loop {
-                do_sys_exit(usize::MAX);
+            // Locks may be held when the kernel faults, so only a fault in userspace is recovered
+            // from, by killing the context
+            if regs.cs & 3 == 3 {
+                loop {
+                    do_sys_exit(usize::MAX);
+                }
+            } else {
+                panic::backtrace(regs.ip, regs.bp);
+                panic!("{} in kernel mode", $name);
             }
         })
     };
 
-    macro_rules! exception_error {
+    // The stack pointer is not pushed on x86 for an interrupt without a privilege change, so the
+    // interrupted stack starts where it would have been
+    let regs_end = (&*regs as *const Regs as usize) + mem::size_of::<Regs>();
+
+    macro_rules! exception {
+        ($name:expr) => ({
+            let stack = if regs.cs & 3 == 3 || cfg!(target_arch = "x86_64") {
+                regs.sp
+            } else {
+                regs_end - 2 * mem::size_of::<usize>()
+            };
+
+            exception_inner!($name, stack);
+            exception_end!($name);
+        })
+    };
+
+    macro_rules! exception_error_inner {
         ($name:expr) => ({
             let error = regs.ip;
             regs.ip = regs.cs;
@@ -525,12 +670,21 @@ pub extern "cdecl" fn kernel(interrupt: usize, mut regs: &mut Regs) {
             regs.ss = 0;
             //regs.ss = regs.error;
 
-            exception_inner!($name);
+            let stack = if regs.cs & 3 == 3 || cfg!(target_arch = "x86_64") {
+                regs.sp
+            } else {
+                regs_end - mem::size_of::<usize>()
+            };
+
+            exception_inner!($name, stack);
             debugln!("    ERR: {:08X}", error);
+        })
+    };
 
-            loop {
-                do_sys_exit(usize::MAX);
-            }
+    macro_rules! exception_error {
+        ($name:expr) => ({
+            exception_error_inner!($name);
+            exception_end!($name);
         })
     };
 
@@ -541,17 +695,49 @@ pub extern "cdecl" fn kernel(interrupt: usize, mut regs: &mut Regs) {
 
     match interrupt {
         0x20 => {
-            {
+            let tick = env().pit.lock().tick();
+            let (now, tick) = {
                 let mut clock_monotonic = env().clock_monotonic.lock();
-                *clock_monotonic = *clock_monotonic + PIT_DURATION;
-            }
+                let last = *clock_monotonic;
+                *clock_monotonic = match env().hpet {
+                    Some(ref hpet) => hpet.monotonic(),
+                    None => last + tick,
+                };
+                (*clock_monotonic, *clock_monotonic - last)
+            };
             {
                 let mut clock_realtime = env().clock_realtime.lock();
-                *clock_realtime = *clock_realtime + PIT_DURATION;
+                *clock_realtime = *clock_realtime + tick;
+
+                // The tick period is not exact, so the drift is corrected from the RTC every
+                // minute before it adds up. The RTC is skipped if it is being updated.
+                if now.secs / RTC_SYNC_SECS != (now - tick).secs / RTC_SYNC_SECS {
+                    if let Some(rtc) = Rtc::new().try_time() {
+                        *clock_realtime = rtc_correct(*clock_realtime, rtc);
+                    }
+                }
             }
 
-            if let Ok(mut current) = env().contexts.lock().current_mut() {
-                current.time += 1;
+            // Push out what display clients wrote without syncing
+            {
+                let mut console = env().console.lock();
+                if ! console.draw {
+                    if let Some(ref mut display) = console.display {
+                        display.tick();
+                    }
+                }
+            }
+
+            {
+                let mut contexts = env().contexts.lock();
+                contexts.wake(now);
+                if let Ok(mut current) = contexts.current_mut() {
+                    current.time += 1;
+                    current.sched_time += sched_cost(current.priority);
+                    if regs.cs & 3 == 3 {
+                        current.user_time += 1;
+                    }
+                }
             }
 
             unsafe { context_switch(); }
@@ -574,13 +760,96 @@ pub extern "cdecl" fn kernel(interrupt: usize, mut regs: &mut Regs) {
         0x5 => exception!("Bound range exceeded exception"),
         0x6 => exception!("Invalid opcode exception"),
         0x7 => exception!("Device not available exception"),
-        0x8 => exception_error!("Double fault"),
+        0x8 => {
+            // The page fault that could not be pushed is still in CR2, and is in the guard page
+            // when the kernel stack overflowed
+            let address: usize;
+            unsafe { asm!("mov $0, cr2" : "=r"(address) : : "memory" : "intel", "volatile"); }
+            if let Ok(current) = env().contexts.lock().current() {
+                if current.stack_guard(address) {
+                    debugln!("kernel stack overflow in PID {} ({})", current.pid, current.name);
+                }
+            }
+
+            if double_fault_regs(regs) {
+                // The double fault runs as its own task on x86, and returns to the task that
+                // faulted after pointing it at the exit of its context
+                exception_error_inner!("Double fault");
+                panic::backtrace(regs.ip, regs.bp);
+                double_fault_resume();
+            } else {
+                exception_error!("Double fault");
+            }
+        },
         0x9 => exception!("Coprocessor Segment Overrun"), // legacy
         0xA => exception_error!("Invalid TSS exception"),
         0xB => exception_error!("Segment not present exception"),
         0xC => exception_error!("Stack-segment fault"),
         0xD => exception_error!("General protection fault"),
-        0xE => exception_error!("Page fault"),
+        0xE => {
+            let address: usize;
+            unsafe { asm!("mov $0, cr2" : "=r"(address) : : "memory" : "intel", "volatile"); }
+
+            // A write to a present page may be to memory shared by fork, which is copied before
+            // the write is retried. The interrupt handler skips the error code when returning
+            let error = regs.ip;
+            let unshared = error & 3 == 3 && match env().contexts.lock().current_mut() {
+                Ok(mut current) => unsafe { current.unshare(address) },
+                Err(_) => false,
+            };
+            if ! unshared {
+                let access = if error & 0x10 == 0x10 {
+                    "instruction fetch"
+                } else if error & 2 == 2 {
+                    "write"
+                } else {
+                    "read"
+                };
+                let cause = if error & 1 == 1 {
+                    "protection violation"
+                } else {
+                    "page not present"
+                };
+
+                if let Ok(current) = env().contexts.lock().current() {
+                    if current.stack_guard(address) {
+                        debugln!("stack overflow in PID {} ({})", current.pid, current.name);
+                    }
+                }
+
+                // Until the error code is removed, the code segment is in the flags
+                if regs.flags & 3 == 3 {
+                    if let Ok(current) = env().contexts.lock().current() {
+                        debugln!("PID {}: {}: segmentation fault, {} of {:08X}: {}",
+                                 current.pid, current.name, access, address, cause);
+                    }
+
+                    // The registers from the instruction pointer on are shifted by the error
+                    // code, and the stack segment is just past them
+                    let ss_ptr = regs_end as *mut usize;
+                    let mut user_regs = *regs;
+                    user_regs.ip = regs.cs;
+                    user_regs.cs = regs.flags;
+                    user_regs.flags = regs.sp;
+                    user_regs.sp = regs.ss;
+                    user_regs.ss = unsafe { ptr::read(ss_ptr) };
+
+                    signal_fault(&mut user_regs, SIGSEGV);
+
+                    *regs = user_regs;
+                    regs.ip = error;
+                    regs.cs = user_regs.ip;
+                    regs.flags = user_regs.cs;
+                    regs.sp = user_regs.flags;
+                    regs.ss = user_regs.sp;
+                    unsafe { ptr::write(ss_ptr, user_regs.ss) };
+                } else {
+                    exception_error_inner!("Page fault");
+                    debugln!("    {} of {:08X}: {}", access, address, cause);
+                    exception_end!("Page fault");
+                }
+            }
+        },
         0x10 => exception!("x87 floating-point exception"),
         0x11 => exception_error!("Alignment check exception"),
         0x12 => exception!("Machine check exception"),
@@ -597,4 +866,9 @@ pub extern "cdecl" fn kernel(interrupt: usize, mut regs: &mut Regs) {
 
         Pio::<u8>::new(0x20).write(0x20);
     }
+
+    // Signals sent to a running context are acted on when it is interrupted by the timer
+    if interrupt == 0x20 {
+        signal_handle(regs);
+    }
 }
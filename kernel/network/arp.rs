@@ -0,0 +1,102 @@
+use collections::string::String;
+use collections::vec::Vec;
+
+use common::time::Duration;
+
+use network::common::{Ipv4Addr, MacAddr};
+
+/// How long an entry is used before the host is asked again, in seconds
+pub const ARP_LIFETIME: i64 = 60;
+/// The most hosts kept in the cache
+pub const ARP_CACHE_MAX: usize = 64;
+
+/// A cached MAC address
+///
+/// `inserted` and `used` are read from the monotonic clock.
+struct ArpEntry {
+    ip: Ipv4Addr,
+    mac: MacAddr,
+    inserted: Duration,
+    used: Duration,
+}
+
+/// The cache of the MAC addresses of hosts on the local network
+///
+/// Entries expire `lifetime` after they were last confirmed by the host. Once `ARP_CACHE_MAX`
+/// hosts are cached, the least recently used entry is evicted to make room.
+pub struct ArpCache {
+    entries: Vec<ArpEntry>,
+    pub lifetime: Duration,
+}
+
+impl ArpCache {
+    pub fn new() -> Self {
+        ArpCache {
+            entries: Vec::new(),
+            lifetime: Duration::new(ARP_LIFETIME, 0),
+        }
+    }
+
+    /// Get the MAC address of a host, dropping the entry if it has expired
+    pub fn lookup(&mut self, ip: Ipv4Addr, now: Duration) -> Option<MacAddr> {
+        let position = self.entries.iter().position(|entry| entry.ip.equals(ip));
+        match position {
+            Some(i) => if now - self.entries[i].inserted >= self.lifetime {
+                self.entries.remove(i);
+                None
+            } else {
+                self.entries[i].used = now;
+                Some(self.entries[i].mac)
+            },
+            None => None,
+        }
+    }
+
+    /// Add or refresh the MAC address of a host
+    pub fn insert(&mut self, ip: Ipv4Addr, mac: MacAddr, now: Duration) {
+        if self.update(ip, mac, now) {
+            return;
+        }
+
+        if self.entries.len() >= ARP_CACHE_MAX {
+            let mut lru = 0;
+            for (i, entry) in self.entries.iter().enumerate() {
+                if entry.used < self.entries[lru].used {
+                    lru = i;
+                }
+            }
+            self.entries.remove(lru);
+        }
+
+        self.entries.push(ArpEntry {
+            ip: ip,
+            mac: mac,
+            inserted: now,
+            used: now,
+        });
+    }
+
+    /// Refresh the MAC address of a host, if it is cached. Returns true if it was
+    pub fn update(&mut self, ip: Ipv4Addr, mac: MacAddr, now: Duration) -> bool {
+        for entry in self.entries.iter_mut() {
+            if entry.ip.equals(ip) {
+                entry.mac = mac;
+                entry.inserted = now;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// List the entries, one per line, with their age in seconds
+    pub fn list(&self, now: Duration) -> String {
+        let mut string = format!("{:<16}{:<18}{}\n", "IP", "MAC", "AGE");
+        for entry in self.entries.iter() {
+            string.push_str(&format!("{:<16}{:<18}{}\n",
+                                     entry.ip.to_string(),
+                                     entry.mac.to_string(),
+                                     (now - entry.inserted).secs));
+        }
+        string
+    }
+}
@@ -114,6 +114,25 @@ pub struct Ipv4Addr {
 }
 
 impl Ipv4Addr {
+    /// Parse a dotted quad, such as `10.85.85.2`
+    pub fn parse(string: &str) -> Option<Self> {
+        let mut addr = Ipv4Addr { bytes: [0, 0, 0, 0] };
+
+        let mut parts = string.split('.');
+        for byte in addr.bytes.iter_mut() {
+            match parts.next().and_then(|part| part.parse::<u8>().ok()) {
+                Some(octet) => *byte = octet,
+                None => return None,
+            }
+        }
+
+        if parts.next().is_some() {
+            None
+        } else {
+            Some(addr)
+        }
+    }
+
     pub fn equals(&self, other: Self) -> bool {
         for i in 0..4 {
             if self.bytes[i] != other.bytes[i] {
@@ -176,9 +195,44 @@ impl Ipv6Addr {
     }
 }
 
-pub static BROADCAST_IP_ADDR: Ipv4Addr = Ipv4Addr { bytes: [10, 85, 85, 255] };
+/// The broadcast address of any network, used before the address of this host is known
+pub static LIMITED_BROADCAST_IP_ADDR: Ipv4Addr = Ipv4Addr { bytes: [255, 255, 255, 255] };
+/// The address of no host, used as the source before the address of this host is known
+pub static UNSPECIFIED_IP_ADDR: Ipv4Addr = Ipv4Addr { bytes: [0, 0, 0, 0] };
+
+/// The static configuration, used until DHCP leases an address, or if no DHCP server answers
+pub static DEFAULT_IP_ADDR: Ipv4Addr = Ipv4Addr { bytes: [10, 85, 85, 2] };
+pub static DEFAULT_IP_SUBNET_MASK: Ipv4Addr = Ipv4Addr { bytes: [255, 255, 255, 0] };
+pub static DEFAULT_IP_ROUTER_ADDR: Ipv4Addr = Ipv4Addr { bytes: [10, 85, 85, 1] };
+pub static DEFAULT_DNS_ADDR: Ipv4Addr = Ipv4Addr { bytes: [10, 85, 85, 1] };
+
+/// The broadcast address of the local network
+pub static mut BROADCAST_IP_ADDR: Ipv4Addr = Ipv4Addr { bytes: [10, 85, 85, 255] };
+/// The address of this host
+pub static mut IP_ADDR: Ipv4Addr = Ipv4Addr { bytes: [10, 85, 85, 2] };
+/// The mask of the local network
+pub static mut IP_SUBNET_MASK: Ipv4Addr = Ipv4Addr { bytes: [255, 255, 255, 0] };
+/// The router to hosts outside of the local network
+pub static mut IP_ROUTER_ADDR: Ipv4Addr = Ipv4Addr { bytes: [10, 85, 85, 1] };
+/// The nameserver used when the `DNS` environment variable is not set
+pub static mut DNS_ADDR: Ipv4Addr = Ipv4Addr { bytes: [10, 85, 85, 1] };
+
+/// Check if an address is a broadcast address this host receives
+pub fn is_broadcast(addr: Ipv4Addr) -> bool {
+    addr.equals(LIMITED_BROADCAST_IP_ADDR) || addr.equals(unsafe { BROADCAST_IP_ADDR })
+}
 
-pub static IP_ADDR: Ipv4Addr = Ipv4Addr { bytes: [10, 85, 85, 2] };
+/// The host a packet to `addr` is sent through: the host itself if it is on the local network,
+/// and the router otherwise
+pub fn next_hop(addr: Ipv4Addr) -> Ipv4Addr {
+    let (ip_addr, mask, router) = unsafe { (IP_ADDR, IP_SUBNET_MASK, IP_ROUTER_ADDR) };
+    let local = (0..4).all(|i| addr.bytes[i] & mask.bytes[i] == ip_addr.bytes[i] & mask.bytes[i]);
+    if local || router.equals(UNSPECIFIED_IP_ADDR) {
+        addr
+    } else {
+        router
+    }
+}
 
 #[derive(Copy, Clone)]
 pub struct Checksum {
@@ -0,0 +1,334 @@
+use alloc::boxed::Box;
+
+use collections::string::ToString;
+use collections::vec::Vec;
+
+use arch::context::context_switch;
+
+use common::random::rand;
+use common::time::Duration;
+
+use core::cmp;
+
+use fs::{Resource, Url};
+
+use network::common::{n16, n32, Ipv4Addr, MacAddr, DEFAULT_DNS_ADDR, DEFAULT_IP_ADDR, DEFAULT_IP_ROUTER_ADDR,
+                      DEFAULT_IP_SUBNET_MASK, LIMITED_BROADCAST_IP_ADDR, UNSPECIFIED_IP_ADDR, BROADCAST_IP_ADDR,
+                      DNS_ADDR, IP_ADDR, IP_ROUTER_ADDR, IP_SUBNET_MASK, MAC_ADDR};
+
+use system::syscall::POLLIN;
+
+/// How long to wait for the answer of a DHCP server, in seconds
+const DHCP_TIMEOUT: i64 = 2;
+/// How many times a message is sent before giving up on an answer
+const DHCP_RETRIES: usize = 3;
+/// How long to wait before asking for a lease again while using the static configuration, in
+/// seconds
+const DHCP_RETRY_SECS: i64 = 60;
+/// The shortest wait before asking to renew a lease again after a failed renewal, in seconds
+const DHCP_RENEW_MIN_SECS: i64 = 60;
+/// The lease time assumed when the server does not give one, in seconds
+const DHCP_LEASE_DEFAULT: u32 = 3600;
+
+pub const DHCP_DISCOVER: u8 = 1;
+pub const DHCP_OFFER: u8 = 2;
+pub const DHCP_REQUEST: u8 = 3;
+pub const DHCP_ACK: u8 = 5;
+pub const DHCP_NAK: u8 = 6;
+
+const DHCP_BOOTREQUEST: u8 = 1;
+const DHCP_BOOTREPLY: u8 = 2;
+/// Ask the server to broadcast its answer, as this host has no address to receive it at
+const DHCP_FLAG_BROADCAST: u16 = 0x8000;
+const DHCP_MAGIC: [u8; 4] = [99, 130, 83, 99];
+/// The size of a message before its options
+const DHCP_HEADER_LEN: usize = 240;
+
+const DHCP_OPT_PAD: u8 = 0;
+const DHCP_OPT_SUBNET_MASK: u8 = 1;
+const DHCP_OPT_ROUTER: u8 = 3;
+const DHCP_OPT_DNS: u8 = 6;
+const DHCP_OPT_REQUESTED_ADDR: u8 = 50;
+const DHCP_OPT_LEASE_TIME: u8 = 51;
+const DHCP_OPT_MESSAGE_TYPE: u8 = 53;
+const DHCP_OPT_SERVER_ID: u8 = 54;
+const DHCP_OPT_PARAMETERS: u8 = 55;
+const DHCP_OPT_RENEWAL_TIME: u8 = 58;
+const DHCP_OPT_END: u8 = 255;
+
+/// The configuration leased by a DHCP server
+///
+/// `router` is unspecified if the server did not name one, and `dns` is the default nameserver
+/// if it did not name any.
+#[derive(Copy, Clone)]
+pub struct DhcpLease {
+    pub addr: Ipv4Addr,
+    pub mask: Ipv4Addr,
+    pub router: Ipv4Addr,
+    pub dns: Ipv4Addr,
+    pub server: Ipv4Addr,
+    /// How long the lease lasts, in seconds
+    pub lease: u32,
+    /// How long until the lease is renewed, in seconds
+    pub renew: u32,
+}
+
+/// An answer of a DHCP server, with its message type
+pub struct DhcpReply {
+    pub kind: u8,
+    pub lease: DhcpLease,
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    n32 { bytes: [bytes[0], bytes[1], bytes[2], bytes[3]] }.get()
+}
+
+fn read_addr(bytes: &[u8]) -> Ipv4Addr {
+    Ipv4Addr { bytes: [bytes[0], bytes[1], bytes[2], bytes[3]] }
+}
+
+/// Build a message of type `kind` from this host, at `ciaddr` if it has an address. A request for
+/// an offered address names it and the server that offered it in `offer`
+pub fn message(kind: u8, xid: u32, ciaddr: Ipv4Addr, offer: Option<(Ipv4Addr, Ipv4Addr)>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(300);
+    bytes.push(DHCP_BOOTREQUEST);
+    bytes.push(1); // Ethernet
+    bytes.push(6);
+    bytes.push(0);
+    bytes.extend_from_slice(&n32::new(xid).bytes);
+    bytes.extend_from_slice(&n16::new(0).bytes);
+    let flags = if ciaddr.equals(UNSPECIFIED_IP_ADDR) { DHCP_FLAG_BROADCAST } else { 0 };
+    bytes.extend_from_slice(&n16::new(flags).bytes);
+    bytes.extend_from_slice(&ciaddr.bytes);
+    bytes.extend_from_slice(&[0; 12]); // yiaddr, siaddr, giaddr
+    bytes.extend_from_slice(&unsafe { MAC_ADDR }.bytes);
+    bytes.extend_from_slice(&[0; 10 + 64 + 128]); // chaddr padding, sname, file
+    bytes.extend_from_slice(&DHCP_MAGIC);
+
+    bytes.extend_from_slice(&[DHCP_OPT_MESSAGE_TYPE, 1, kind]);
+    if let Some((addr, server)) = offer {
+        bytes.extend_from_slice(&[DHCP_OPT_REQUESTED_ADDR, 4]);
+        bytes.extend_from_slice(&addr.bytes);
+        bytes.extend_from_slice(&[DHCP_OPT_SERVER_ID, 4]);
+        bytes.extend_from_slice(&server.bytes);
+    }
+    bytes.extend_from_slice(&[DHCP_OPT_PARAMETERS, 4, DHCP_OPT_SUBNET_MASK, DHCP_OPT_ROUTER, DHCP_OPT_DNS,
+                              DHCP_OPT_LEASE_TIME]);
+    bytes.push(DHCP_OPT_END);
+
+    bytes
+}
+
+/// Parse the answer of a server to the messages numbered `xid`
+pub fn parse(bytes: &[u8], xid: u32) -> Option<DhcpReply> {
+    if bytes.len() < DHCP_HEADER_LEN || bytes[0] != DHCP_BOOTREPLY || read_u32(&bytes[4..]) != xid ||
+       bytes[236 .. 240] != DHCP_MAGIC[..] {
+        return None;
+    }
+
+    let mut kind = None;
+    let mut lease = DhcpLease {
+        addr: read_addr(&bytes[16..]),
+        mask: DEFAULT_IP_SUBNET_MASK,
+        router: UNSPECIFIED_IP_ADDR,
+        dns: DEFAULT_DNS_ADDR,
+        server: read_addr(&bytes[20..]),
+        lease: DHCP_LEASE_DEFAULT,
+        renew: 0,
+    };
+
+    let mut i = DHCP_HEADER_LEN;
+    while i < bytes.len() {
+        let code = bytes[i];
+        if code == DHCP_OPT_END {
+            break;
+        } else if code == DHCP_OPT_PAD {
+            i += 1;
+            continue;
+        }
+
+        if i + 2 > bytes.len() || i + 2 + bytes[i + 1] as usize > bytes.len() {
+            return None;
+        }
+        let len = bytes[i + 1] as usize;
+        let value = &bytes[i + 2 .. i + 2 + len];
+
+        match code {
+            DHCP_OPT_MESSAGE_TYPE if len == 1 => kind = Some(value[0]),
+            DHCP_OPT_SUBNET_MASK if len == 4 => lease.mask = read_addr(value),
+            DHCP_OPT_ROUTER if len >= 4 => lease.router = read_addr(value),
+            DHCP_OPT_DNS if len >= 4 => lease.dns = read_addr(value),
+            DHCP_OPT_SERVER_ID if len == 4 => lease.server = read_addr(value),
+            DHCP_OPT_LEASE_TIME if len == 4 => lease.lease = read_u32(value),
+            DHCP_OPT_RENEWAL_TIME if len == 4 => lease.renew = read_u32(value),
+            _ => (),
+        }
+
+        i += 2 + len;
+    }
+
+    if lease.renew == 0 || lease.renew > lease.lease {
+        lease.renew = lease.lease / 2;
+    }
+
+    kind.map(|kind| DhcpReply {
+        kind: kind,
+        lease: lease,
+    })
+}
+
+/// Configure this host with a lease
+pub fn install(lease: &DhcpLease) {
+    let mut broadcast = lease.addr;
+    for i in 0..4 {
+        broadcast.bytes[i] |= ! lease.mask.bytes[i];
+    }
+
+    unsafe {
+        IP_ADDR = lease.addr;
+        IP_SUBNET_MASK = lease.mask;
+        IP_ROUTER_ADDR = lease.router;
+        BROADCAST_IP_ADDR = broadcast;
+        DNS_ADDR = lease.dns;
+    }
+}
+
+/// Configure this host with the static defaults
+pub fn fallback() {
+    install(&DhcpLease {
+        addr: DEFAULT_IP_ADDR,
+        mask: DEFAULT_IP_SUBNET_MASK,
+        router: DEFAULT_IP_ROUTER_ADDR,
+        dns: DEFAULT_DNS_ADDR,
+        server: UNSPECIFIED_IP_ADDR,
+        lease: 0,
+        renew: 0,
+    });
+}
+
+fn sleep_until(wake: Duration) {
+    if let Ok(mut context) = ::env().contexts.lock().current_mut() {
+        context.blocked = true;
+        context.wake = Some(wake);
+    }
+    unsafe { context_switch(); }
+}
+
+fn open(path: &str) -> Option<Box<Resource>> {
+    Url::from_str(path).and_then(|url| url.open()).ok()
+}
+
+/// Send a message to the server at `dst`, or to every server if it is the broadcast address, and
+/// wait for an answer of type `kind`, sending it again if none comes. Returns `None` if none came,
+/// or if the server refused
+fn exchange(dst: Ipv4Addr, message: &[u8], xid: u32, kind: u8) -> Option<DhcpLease> {
+    let (mut listener, mut udp) = match (open("udp:/68"), open(&format!("udp:{}:67/68", dst.to_string()))) {
+        (Some(listener), Some(udp)) => (listener, udp),
+        _ => return None,
+    };
+
+    for _ in 0..DHCP_RETRIES {
+        if udp.write(message).is_err() {
+            return None;
+        }
+
+        let deadline = Duration::monotonic() + Duration::new(DHCP_TIMEOUT, 0);
+        loop {
+            let seen = ::env().input.count();
+            while listener.poll() & POLLIN == POLLIN {
+                let mut bytes = [0; 1500];
+                let count = match listener.read(&mut bytes) {
+                    Ok(count) => count,
+                    Err(_) => return None,
+                };
+                if let Some(reply) = parse(&bytes[.. count], xid) {
+                    if reply.kind == kind {
+                        return Some(reply.lease);
+                    } else if reply.kind == DHCP_NAK {
+                        return None;
+                    }
+                }
+            }
+
+            let now = Duration::monotonic();
+            if now >= deadline {
+                break;
+            }
+            ::env().input.wait_until(seen, Some(deadline));
+        }
+    }
+
+    None
+}
+
+/// Ask any server for a lease. This host has no address meanwhile
+fn discover() -> Option<DhcpLease> {
+    unsafe { IP_ADDR = UNSPECIFIED_IP_ADDR; }
+
+    let xid = rand() as u32;
+    let offer = match exchange(LIMITED_BROADCAST_IP_ADDR, &message(DHCP_DISCOVER, xid, UNSPECIFIED_IP_ADDR, None),
+                               xid, DHCP_OFFER) {
+        Some(offer) => offer,
+        None => return None,
+    };
+
+    exchange(LIMITED_BROADCAST_IP_ADDR,
+             &message(DHCP_REQUEST, xid, UNSPECIFIED_IP_ADDR, Some((offer.addr, offer.server))),
+             xid, DHCP_ACK)
+}
+
+/// Ask the server of a lease to renew it
+fn renew(lease: &DhcpLease) -> Option<DhcpLease> {
+    let xid = rand() as u32;
+    exchange(lease.server, &message(DHCP_REQUEST, xid, lease.addr, None), xid, DHCP_ACK)
+}
+
+/// Configure this host by DHCP, for as long as the kernel runs
+///
+/// The lease is renewed when half of it, or the renewal time of the server, has passed, and
+/// asked for again if it runs out. Until a server answers, the static defaults are used.
+pub fn dhcp_loop() {
+    if unsafe { MAC_ADDR }.equals(MacAddr { bytes: [0; 6] }) {
+        return;
+    }
+
+    loop {
+        let mut lease = match discover() {
+            Some(lease) => lease,
+            None => {
+                debugln!("DHCP: no answer, using {}", DEFAULT_IP_ADDR.to_string());
+                fallback();
+                sleep_until(Duration::monotonic() + Duration::new(DHCP_RETRY_SECS, 0));
+                continue;
+            }
+        };
+
+        install(&lease);
+        debugln!("DHCP: leased {} for {} seconds", lease.addr.to_string(), lease.lease);
+
+        let mut granted = Duration::monotonic();
+        let mut renew_at = granted + Duration::new(lease.renew as i64, 0);
+        loop {
+            sleep_until(renew_at);
+
+            if let Some(renewed) = renew(&lease) {
+                lease = renewed;
+                install(&lease);
+                granted = Duration::monotonic();
+                renew_at = granted + Duration::new(lease.renew as i64, 0);
+                continue;
+            }
+
+            // Try again halfway to the end of the lease, and start over once it has run out
+            let now = Duration::monotonic();
+            let expires = granted + Duration::new(lease.lease as i64, 0);
+            if now >= expires {
+                break;
+            }
+            renew_at = now + Duration::new(cmp::max(DHCP_RENEW_MIN_SECS, (expires - now).secs / 2), 0);
+            if renew_at > expires {
+                renew_at = expires;
+            }
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use collections::{BTreeMap, String, Vec};
+use collections::string::ToString;
+
+use common::random::rand;
+use common::time::Duration;
+
+use fs::Url;
+
+use network::common::{Ipv4Addr, DNS_ADDR};
+
+use system::error::{Error, Result, EINVAL, EIO, ENOENT, ETIMEDOUT};
+use system::syscall::POLLIN;
+
+/// The most names kept in the cache
+pub const DNS_CACHE_MAX: usize = 64;
+/// How long to wait for an answer, in seconds
+const DNS_TIMEOUT: i64 = 5;
+
+const DNS_FLAG_RESPONSE: u16 = 1 << 15;
+const DNS_FLAG_TRUNCATED: u16 = 1 << 9;
+const DNS_FLAG_RECURSE: u16 = 1 << 8;
+const DNS_RCODE_NXDOMAIN: u16 = 3;
+
+const DNS_TYPE_A: u16 = 1;
+const DNS_CLASS_IN: u16 = 1;
+
+/// A resolved address, valid until `expires` on the realtime clock
+struct DnsEntry {
+    addr: Ipv4Addr,
+    expires: Duration,
+}
+
+/// The cache of resolved names
+///
+/// Entries expire with the TTL of their answer. Once `DNS_CACHE_MAX` names are cached, expired
+/// entries are dropped to make room, and failing that the entry closest to expiring.
+pub struct DnsCache {
+    entries: BTreeMap<String, DnsEntry>,
+}
+
+impl DnsCache {
+    pub fn new() -> Self {
+        DnsCache {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Look up the address of a name, dropping it if it has expired
+    pub fn get(&mut self, name: &str, now: Duration) -> Option<Ipv4Addr> {
+        let expired = match self.entries.get(name) {
+            Some(entry) => if entry.expires > now {
+                return Some(entry.addr);
+            } else {
+                true
+            },
+            None => false,
+        };
+
+        if expired {
+            self.entries.remove(name);
+        }
+
+        None
+    }
+
+    /// Add or replace the address of a name, valid until `expires`
+    pub fn insert(&mut self, name: String, addr: Ipv4Addr, expires: Duration, now: Duration) {
+        if ! self.entries.contains_key(&name) && self.entries.len() >= DNS_CACHE_MAX {
+            let expired: Vec<String> = self.entries.iter()
+                                           .filter(|&(_, entry)| entry.expires <= now)
+                                           .map(|(name, _)| name.clone())
+                                           .collect();
+            for name in expired.iter() {
+                self.entries.remove(name);
+            }
+
+            if self.entries.len() >= DNS_CACHE_MAX {
+                let mut soonest: Option<(String, Duration)> = None;
+                for (name, entry) in self.entries.iter() {
+                    if soonest.as_ref().map_or(true, |&(_, expires)| entry.expires < expires) {
+                        soonest = Some((name.clone(), entry.expires));
+                    }
+                }
+
+                if let Some((name, _)) = soonest {
+                    self.entries.remove(&name);
+                }
+            }
+        }
+
+        self.entries.insert(name, DnsEntry {
+            addr: addr,
+            expires: expires,
+        });
+    }
+
+    /// List the entries that have not expired, one per line, with the seconds left of their TTL
+    pub fn list(&self, now: Duration) -> String {
+        let mut string = format!("{:<32}{:<16}{}\n", "NAME", "ADDRESS", "TTL");
+        for (name, entry) in self.entries.iter() {
+            if entry.expires > now {
+                string.push_str(&format!("{:<32}{:<16}{}\n",
+                                         name,
+                                         entry.addr.to_string(),
+                                         (entry.expires - now).secs));
+            }
+        }
+        string
+    }
+}
+
+fn read_u16(bytes: &[u8], i: usize) -> Option<u16> {
+    if i + 2 <= bytes.len() {
+        Some((bytes[i] as u16) << 8 | bytes[i + 1] as u16)
+    } else {
+        None
+    }
+}
+
+fn read_u32(bytes: &[u8], i: usize) -> Option<u32> {
+    match (read_u16(bytes, i), read_u16(bytes, i + 2)) {
+        (Some(high), Some(low)) => Some((high as u32) << 16 | low as u32),
+        _ => None,
+    }
+}
+
+/// Skip an encoded name, returning the offset after it
+fn skip_name(bytes: &[u8], mut i: usize) -> Option<usize> {
+    loop {
+        let len = match bytes.get(i) {
+            Some(&len) => len as usize,
+            None => return None,
+        };
+
+        if len & 0xC0 == 0xC0 {
+            // A pointer ends the name
+            return if i + 2 <= bytes.len() {
+                Some(i + 2)
+            } else {
+                None
+            };
+        } else if len == 0 {
+            return Some(i + 1);
+        } else {
+            i += 1 + len;
+        }
+    }
+}
+
+/// Encode a query for the A records of name
+fn query(id: u16, name: &str) -> Result<Vec<u8>> {
+    let flags = DNS_FLAG_RECURSE;
+    let mut bytes = vec![(id >> 8) as u8, id as u8, (flags >> 8) as u8, flags as u8,
+                         0, 1, 0, 0, 0, 0, 0, 0];
+
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(Error::new(EINVAL));
+        }
+        bytes.push(label.len() as u8);
+        bytes.extend_from_slice(label.as_bytes());
+    }
+    bytes.push(0);
+
+    bytes.extend_from_slice(&[(DNS_TYPE_A >> 8) as u8, DNS_TYPE_A as u8,
+                              (DNS_CLASS_IN >> 8) as u8, DNS_CLASS_IN as u8]);
+
+    Ok(bytes)
+}
+
+/// Decode the first A record and its TTL from a response to the query `id`
+///
+/// Returns `Ok(None)` if the message is not a response to the query. A response that ends
+/// before an A record, such as a truncated one, is an `EIO` error.
+fn answer(id: u16, bytes: &[u8]) -> Result<Option<(Ipv4Addr, u32)>> {
+    let flags = match (read_u16(bytes, 0), read_u16(bytes, 2)) {
+        (Some(response_id), Some(flags)) if response_id == id && flags & DNS_FLAG_RESPONSE == DNS_FLAG_RESPONSE => flags,
+        _ => return Ok(None),
+    };
+
+    match flags & 0xF {
+        0 => (),
+        DNS_RCODE_NXDOMAIN => return Err(Error::new(ENOENT)),
+        _ => return Err(Error::new(EIO)),
+    }
+
+    let (questions, answers) = match (read_u16(bytes, 4), read_u16(bytes, 6)) {
+        (Some(questions), Some(answers)) => (questions, answers),
+        _ => return Err(Error::new(EIO)),
+    };
+
+    let mut i = 12;
+    for _ in 0..questions {
+        i = match skip_name(bytes, i) {
+            Some(end) => end + 4,
+            None => return Err(Error::new(EIO)),
+        };
+    }
+
+    for _ in 0..answers {
+        i = match skip_name(bytes, i) {
+            Some(end) => end,
+            None => return Err(Error::new(EIO)),
+        };
+
+        let (kind, class, ttl, len) = match (read_u16(bytes, i), read_u16(bytes, i + 2),
+                                             read_u32(bytes, i + 4), read_u16(bytes, i + 8)) {
+            (Some(kind), Some(class), Some(ttl), Some(len)) => (kind, class, ttl, len as usize),
+            _ => return Err(Error::new(EIO)),
+        };
+        i += 10;
+
+        if i + len > bytes.len() {
+            return Err(Error::new(EIO));
+        }
+
+        if kind == DNS_TYPE_A && class == DNS_CLASS_IN && len == 4 {
+            let addr = Ipv4Addr {
+                bytes: [bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]],
+            };
+            return Ok(Some((addr, ttl)));
+        }
+
+        i += len;
+    }
+
+    if flags & DNS_FLAG_TRUNCATED == DNS_FLAG_TRUNCATED {
+        Err(Error::new(EIO))
+    } else {
+        Err(Error::new(ENOENT))
+    }
+}
+
+/// Resolve a host to an address
+///
+/// Dotted quads are parsed directly. Names are looked up in the cache, and otherwise queried over
+/// UDP from the nameserver in the `DNS` environment variable of the current context, or
+/// `DNS_ADDR` if it is not set. Returns `ENOENT` if the name does not exist or has no address,
+/// `ETIMEDOUT` if the nameserver does not answer, and `EIO` for a malformed answer.
+pub fn resolve(host: &str) -> Result<Ipv4Addr> {
+    if let Some(addr) = Ipv4Addr::parse(host) {
+        return Ok(addr);
+    }
+
+    let name = host.trim_right_matches('.').to_string();
+    if name.is_empty() {
+        return Err(Error::new(ENOENT));
+    }
+
+    if let Some(addr) = ::env().dns.lock().get(&name, Duration::realtime()) {
+        return Ok(addr);
+    }
+
+    let nameserver = {
+        let contexts = ::env().contexts.lock();
+        contexts.current().ok()
+                .and_then(|current| current.get_env_var("DNS").ok())
+                .and_then(|value| Ipv4Addr::parse(value.trim()))
+                .unwrap_or(unsafe { DNS_ADDR })
+    };
+
+    let id = (rand() % 65536) as u16;
+    let mut udp = try!(try!(Url::from_str(&format!("udp:{}:53", nameserver.to_string()))).open());
+    try!(udp.write(&try!(query(id, &name))));
+
+    let deadline = Duration::monotonic() + Duration::new(DNS_TIMEOUT, 0);
+    loop {
+        let seen = ::env().input.count();
+        while udp.poll() & POLLIN == POLLIN {
+            let mut bytes = [0; 512];
+            let count = try!(udp.read(&mut bytes));
+            if let Some((addr, ttl)) = try!(answer(id, &bytes[.. count])) {
+                let now = Duration::realtime();
+                ::env().dns.lock().insert(name, addr, now + Duration::new(ttl as i64, 0), now);
+                return Ok(addr);
+            }
+        }
+
+        let now = Duration::monotonic();
+        if now >= deadline {
+            return Err(Error::new(ETIMEDOUT));
+        }
+
+        ::env().input.wait_until(seen, Some(deadline));
+    }
+}
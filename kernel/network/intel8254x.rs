@@ -1,3 +1,4 @@
+use alloc::arc::Arc;
 use alloc::boxed::Box;
 
 use arch::memory;
@@ -13,6 +14,7 @@ use common::debug;
 use drivers::pci::config::PciConfig;
 
 use network::common::*;
+use network::interface::NetworkInterface;
 use network::scheme::*;
 
 use fs::{KScheme, Resource, Url};
@@ -113,6 +115,7 @@ pub struct Intel8254x {
     pub resources: Intex<Vec<*mut NetworkResource>>,
     pub inbound: VecDeque<Vec<u8>>,
     pub outbound: VecDeque<Vec<u8>>,
+    pub interface: Arc<Intex<NetworkInterface>>,
 }
 
 impl KScheme for Intel8254x {
@@ -120,8 +123,8 @@ impl KScheme for Intel8254x {
         "network"
     }
 
-    fn open(&mut self, _: Url, _: usize) -> Result<Box<Resource>> {
-        Ok(NetworkResource::new(self))
+    fn open(&mut self, _: Url, flags: usize) -> Result<Box<Resource>> {
+        Ok(NetworkResource::new(self, flags))
     }
 
     fn on_irq(&mut self, irq: u8) {
@@ -162,12 +165,18 @@ impl NetworkScheme for Intel8254x {
 
     fn sync(&mut self) {
         unsafe {
+            // Frames are still taken from the resources and the card while the interface is
+            // down, but they are dropped instead of being passed on
+            let up = self.interface.lock().up;
+
             {
                 let resources = self.resources.lock();
 
                 for resource in resources.iter() {
-                    while let Some(bytes) = (**resource).outbound.lock().pop_front() {
-                        self.outbound.push_back(bytes);
+                    while let Some(bytes) = (**resource).pop_outbound() {
+                        if up {
+                            self.outbound.push_back(bytes);
+                        }
                     }
                 }
             }
@@ -180,9 +189,14 @@ impl NetworkScheme for Intel8254x {
                 let resources = self.resources.lock();
 
                 while let Some(bytes) = self.inbound.pop_front() {
+                    if ! up {
+                        continue;
+                    }
+
                     for resource in resources.iter() {
                         (**resource).inbound.send(bytes.clone());
                     }
+                    ::env().input.notify();
                 }
             }
         }
@@ -201,6 +215,7 @@ impl Intel8254x {
             resources: Intex::new(Vec::new()),
             inbound: VecDeque::new(),
             outbound: VecDeque::new(),
+            interface: NetworkInterface::register(),
         };
 
         module.init();
@@ -215,8 +230,15 @@ impl Intel8254x {
         for tail in 0..length / 16 {
             let rd = &mut *receive_ring.offset(tail as isize);
             if rd.status & RD_DD == RD_DD {
-                self.inbound.push_back(Vec::from(slice::from_raw_parts(rd.buffer as *const u8, rd.length as usize)));
-                
+                let mut interface = self.interface.lock();
+                if rd.error == 0 {
+                    self.inbound.push_back(Vec::from(slice::from_raw_parts(rd.buffer as *const u8, rd.length as usize)));
+                    interface.rx_packets += 1;
+                    interface.rx_bytes += rd.length as u64;
+                } else {
+                    interface.rx_errors += 1;
+                }
+
                 rd.status = 0;
             }
         }
@@ -250,6 +272,10 @@ impl Intel8254x {
                         td.special = 0;
 
                         self.write(TDT, tail);
+
+                        let mut interface = self.interface.lock();
+                        interface.tx_packets += 1;
+                        interface.tx_bytes += bytes.len() as u64;
                     } else {
                         // TODO: More than one TD
                         debug::dl();
@@ -326,6 +352,7 @@ impl Intel8254x {
                     (mac_high >> 8) as u8],
         };
         debug::d(&MAC_ADDR.to_string());
+        self.interface.lock().mac = MAC_ADDR;
 
         //
         // MTA => 0;
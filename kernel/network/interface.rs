@@ -0,0 +1,83 @@
+use alloc::arc::Arc;
+
+use collections::string::String;
+
+use network::common::MacAddr;
+
+use sync::Intex;
+
+/// The columns of the `netstat:` table
+pub const NETSTAT_HEADER: &'static str =
+    "name      rx_packets  tx_packets      rx_bytes      tx_bytes  rx_errors  rx_overflows  tx_timeouts\n";
+
+/// The state and statistics of a network interface
+///
+/// Shared between the driver, which updates the counters as it sends and receives, and the
+/// `netcfg:` and `netstat:` schemes, which report them. While the interface is down, the driver
+/// drops frames instead of passing them between the card and its resources.
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac: MacAddr,
+    pub up: bool,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_errors: u64,
+    /// Times the receive buffer of the card overflowed, dropping frames
+    pub rx_overflows: u64,
+    /// Frames the card did not finish sending in time
+    pub tx_timeouts: u64,
+}
+
+impl NetworkInterface {
+    /// Create an interface named after the number of registered interfaces, and register it. The
+    /// driver sets the MAC address once it has read it from the card
+    pub fn register() -> Arc<Intex<NetworkInterface>> {
+        let mut interfaces = ::env().interfaces.lock();
+
+        let interface = Arc::new(Intex::new(NetworkInterface {
+            name: format!("eth{}", interfaces.len()),
+            mac: MacAddr { bytes: [0; 6] },
+            up: true,
+            rx_packets: 0,
+            tx_packets: 0,
+            rx_bytes: 0,
+            tx_bytes: 0,
+            rx_errors: 0,
+            rx_overflows: 0,
+            tx_timeouts: 0,
+        }));
+        interfaces.push(interface.clone());
+
+        interface
+    }
+
+    /// Format the state and statistics, one `key: value` pair per line
+    pub fn stats(&self) -> String {
+        format!("name: {}\nmac: {}\nstate: {}\nrx_packets: {}\ntx_packets: {}\nrx_bytes: {}\ntx_bytes: {}\nrx_errors: {}\nrx_overflows: {}\ntx_timeouts: {}\n",
+                self.name,
+                self.mac.to_string(),
+                if self.up { "up" } else { "down" },
+                self.rx_packets,
+                self.tx_packets,
+                self.rx_bytes,
+                self.tx_bytes,
+                self.rx_errors,
+                self.rx_overflows,
+                self.tx_timeouts)
+    }
+
+    /// Format the counters as a line of the `netstat:` table, in the order of `NETSTAT_HEADER`
+    pub fn netstat(&self) -> String {
+        format!("{:<8}{:>12}{:>12}{:>14}{:>14}{:>11}{:>14}{:>13}\n",
+                self.name,
+                self.rx_packets,
+                self.tx_packets,
+                self.rx_bytes,
+                self.tx_bytes,
+                self.rx_errors,
+                self.rx_overflows,
+                self.tx_timeouts)
+    }
+}
@@ -3,10 +3,29 @@ use common::slice::GetSlice;
 use collections::slice;
 use collections::vec::Vec;
 
-use core::mem;
+use core::{cmp, mem};
+
+use common::time::Duration;
 
 use network::common::*;
 
+/// The Don't Fragment flag of `flags_fragment`
+pub const IP_DF: u16 = 0x4000;
+/// The More Fragments flag of `flags_fragment`
+pub const IP_MF: u16 = 0x2000;
+/// The fragment offset of `flags_fragment`, in units of 8 bytes
+pub const IP_OFFSET: u16 = 0x1FFF;
+
+/// The largest packet sent in one ethernet frame, header included
+pub const IP_MTU: usize = 1500;
+
+/// How long the fragments of a datagram wait for the rest, in seconds
+pub const IP_REASSEMBLY_TIMEOUT: i64 = 30;
+/// The largest datagram reassembled, header included
+pub const IP_REASSEMBLY_MAX: usize = 65535;
+/// The most bytes held by incomplete datagrams
+pub const IP_REASSEMBLY_BYTES: usize = 256 * 1024;
+
 #[derive(Copy, Clone)]
 #[repr(packed)]
 pub struct Ipv4Header {
@@ -28,17 +47,71 @@ pub struct Ipv4 {
     pub data: Vec<u8>,
 }
 
+impl Ipv4 {
+    /// Fill in the checksum of the header
+    pub fn checksum(&mut self) {
+        unsafe {
+            self.header.checksum.data = 0;
+
+            let header_ptr: *const Ipv4Header = &self.header;
+            self.header.checksum.data =
+                Checksum::compile(Checksum::sum(header_ptr as usize, mem::size_of::<Ipv4Header>()) +
+                                  Checksum::sum(self.options.as_ptr() as usize, self.options.len()));
+        }
+    }
+
+    /// Split the packet into fragments of at most `mtu` bytes, with their checksums filled in
+    ///
+    /// Every fragment but the last has the More Fragments flag set, and the offsets continue
+    /// from that of the packet, which may be a fragment itself. A packet that fits is returned
+    /// whole. The Don't Fragment flag is not checked, that is up to the caller.
+    pub fn fragments(&self, mtu: usize) -> Vec<Ipv4> {
+        let header_len = mem::size_of::<Ipv4Header>() + self.options.len();
+        let max = cmp::max(8, mtu.saturating_sub(header_len) & !7);
+        let flags_fragment = self.header.flags_fragment.get();
+        let start = (flags_fragment & IP_OFFSET) as usize * 8;
+
+        let mut fragments = Vec::new();
+        let mut offset = 0;
+        loop {
+            let end = cmp::min(offset + max, self.data.len());
+            let last = end == self.data.len();
+
+            let mut fragment = Ipv4 {
+                header: self.header,
+                options: self.options.clone(),
+                data: self.data[offset .. end].to_vec(),
+            };
+            fragment.header.len.set((header_len + end - offset) as u16);
+            let more = if last { flags_fragment & IP_MF } else { IP_MF };
+            fragment.header.flags_fragment.set((flags_fragment & IP_DF) | more |
+                                               ((start + offset) / 8) as u16);
+            fragment.checksum();
+            fragments.push(fragment);
+
+            if last {
+                break;
+            }
+            offset = end;
+        }
+
+        fragments
+    }
+}
+
 impl FromBytes for Ipv4 {
     fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
         if bytes.len() >= mem::size_of::<Ipv4Header>() {
             unsafe {
                 let header = *(bytes.as_ptr() as *const Ipv4Header);
                 let header_len = ((header.ver_hlen & 0xF) << 2) as usize;
+                // Frames shorter than the minimum ethernet payload are padded after the packet
+                let len = cmp::max(header_len, cmp::min(header.len.get() as usize, bytes.len()));
 
                 return Some(Ipv4 {
                     header: header,
                     options: bytes.get_slice(mem::size_of::<Ipv4Header>() .. header_len).to_vec(),
-                    data: bytes.get_slice(header_len ..).to_vec(),
+                    data: bytes.get_slice(header_len .. len).to_vec(),
                 });
             }
         }
@@ -58,3 +131,167 @@ impl ToBytes for Ipv4 {
         }
     }
 }
+
+/// A datagram whose fragments are being collected
+///
+/// `data` is indexed by fragment offset, and `received` holds the sorted, disjoint ranges of it
+/// that have arrived. `len` is known once the last fragment has arrived.
+struct Fragments {
+    header: Ipv4Header,
+    options: Vec<u8>,
+    data: Vec<u8>,
+    received: Vec<(usize, usize)>,
+    len: Option<usize>,
+    started: Duration,
+}
+
+impl Fragments {
+    fn matches(&self, header: &Ipv4Header) -> bool {
+        self.header.src.equals(header.src) && self.header.dst.equals(header.dst) &&
+        self.header.id.get() == header.id.get() && self.header.proto == header.proto
+    }
+
+    /// Mark the bytes from `start` to `end` as received, merging the ranges they touch
+    fn receive(&mut self, start: usize, end: usize) {
+        let mut start = start;
+        let mut end = end;
+        let mut i = 0;
+        while i < self.received.len() {
+            let (other_start, other_end) = self.received[i];
+            if other_end < start {
+                i += 1;
+            } else if other_start > end {
+                break;
+            } else {
+                start = cmp::min(start, other_start);
+                end = cmp::max(end, other_end);
+                self.received.remove(i);
+            }
+        }
+        self.received.insert(i, (start, end));
+    }
+
+    fn complete(&self) -> bool {
+        match self.len {
+            Some(len) => self.received.len() == 1 && self.received[0] == (0, len),
+            None => false,
+        }
+    }
+}
+
+/// The reassembly of fragmented IP datagrams
+///
+/// Fragments are collected by source, destination, identification and protocol. A datagram is
+/// complete once its last fragment has arrived and no holes are left. Incomplete datagrams are
+/// dropped after `IP_REASSEMBLY_TIMEOUT`, and the oldest ones are dropped while they hold more
+/// than `IP_REASSEMBLY_BYTES`.
+pub struct IpReassembly {
+    datagrams: Vec<Fragments>,
+}
+
+impl IpReassembly {
+    pub fn new() -> Self {
+        IpReassembly { datagrams: Vec::new() }
+    }
+
+    /// The number of incomplete datagrams
+    pub fn len(&self) -> usize {
+        self.datagrams.len()
+    }
+
+    /// Add a packet. Returns the packet if it is not a fragment, the whole datagram if the packet
+    /// completes one, or `None` while fragments are missing
+    ///
+    /// Fragments that have the Don't Fragment flag set, are not a multiple of 8 bytes long
+    /// before the last one, or reach beyond `IP_REASSEMBLY_MAX` are dropped.
+    pub fn push(&mut self, packet: Ipv4, now: Duration) -> Option<Ipv4> {
+        let flags_fragment = packet.header.flags_fragment.get();
+        let offset = (flags_fragment & IP_OFFSET) as usize * 8;
+        let more = flags_fragment & IP_MF == IP_MF;
+
+        if offset == 0 && ! more {
+            return Some(packet);
+        }
+
+        let timeout = Duration::new(IP_REASSEMBLY_TIMEOUT, 0);
+        self.datagrams.retain(|datagram| now - datagram.started < timeout);
+
+        let header_len = mem::size_of::<Ipv4Header>() + packet.options.len();
+        let end = offset + packet.data.len();
+        if flags_fragment & IP_DF == IP_DF || (more && packet.data.len() % 8 != 0) ||
+           header_len + end > IP_REASSEMBLY_MAX {
+            return None;
+        }
+
+        let i = match self.datagrams.iter().position(|datagram| datagram.matches(&packet.header)) {
+            Some(i) => i,
+            None => {
+                self.datagrams.push(Fragments {
+                    header: packet.header,
+                    options: Vec::new(),
+                    data: Vec::new(),
+                    received: Vec::new(),
+                    len: None,
+                    started: now,
+                });
+                self.datagrams.len() - 1
+            }
+        };
+
+        {
+            let datagram = &mut self.datagrams[i];
+
+            // A second last fragment, or data past the last one, does not belong to this datagram
+            let conflicts = match datagram.len {
+                Some(len) => end > len || (! more && end != len),
+                None => ! more && datagram.received.last().map_or(false, |range| range.1 > end),
+            };
+            if conflicts {
+                return None;
+            }
+
+            if datagram.data.len() < end {
+                datagram.data.resize(end, 0);
+            }
+            for (d, b) in datagram.data[offset .. end].iter_mut().zip(packet.data.iter()) {
+                *d = *b;
+            }
+            datagram.receive(offset, end);
+
+            // The header and options of the datagram are those of the first fragment
+            if offset == 0 {
+                datagram.header = packet.header;
+                datagram.options = packet.options;
+            }
+            if ! more {
+                datagram.len = Some(end);
+            }
+        }
+
+        if self.datagrams[i].complete() {
+            let mut datagram = self.datagrams.remove(i);
+            let header_len = mem::size_of::<Ipv4Header>() + datagram.options.len();
+            datagram.header.ver_hlen = 0x40 | (header_len / 4 & 0xF) as u8;
+            datagram.header.len.set((header_len + datagram.data.len()) as u16);
+            datagram.header.flags_fragment.set(0);
+            return Some(Ipv4 {
+                header: datagram.header,
+                options: datagram.options,
+                data: datagram.data,
+            });
+        }
+
+        let mut bytes = self.datagrams.iter().fold(0, |bytes, datagram| bytes + datagram.data.len());
+        while bytes > IP_REASSEMBLY_BYTES {
+            let mut oldest = 0;
+            for (j, datagram) in self.datagrams.iter().enumerate() {
+                if datagram.started < self.datagrams[oldest].started {
+                    oldest = j;
+                }
+            }
+            bytes -= self.datagrams.remove(oldest).data.len();
+        }
+
+        None
+    }
+}
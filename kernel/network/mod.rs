@@ -1,6 +1,10 @@
+pub mod arp;
 pub mod common;
+pub mod dhcp;
+pub mod dns;
 pub mod ethernet;
 pub mod intel8254x;
+pub mod interface;
 pub mod ipv4;
 pub mod ipv6;
 pub mod rtl8139;
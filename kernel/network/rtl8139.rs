@@ -1,3 +1,4 @@
+use alloc::arc::Arc;
 use alloc::boxed::Box;
 
 use arch::memory;
@@ -10,11 +11,13 @@ use collections::vec_deque::VecDeque;
 use core::ptr;
 
 use common::debug;
+use common::time::Duration;
 
 use drivers::pci::config::PciConfig;
 use drivers::io::{Io, Pio};
 
 use network::common::*;
+use network::interface::NetworkInterface;
 use network::scheme::*;
 
 use fs::{KScheme, Resource, Url};
@@ -23,8 +26,29 @@ use system::error::Result;
 
 use sync::Intex;
 
+/// The size of the receive ring, not counting the overflow area
+pub const RTL8139_RX_SIZE: usize = 8192;
+/// Because of the WRAP bit, a frame that crosses the end of the ring continues past it, so the
+/// ring is followed by room for a full frame plus the header padding
+pub const RTL8139_RX_ALLOC: usize = RTL8139_RX_SIZE + 16 + 2048;
+
+/// The largest frame accepted, including the CRC
+pub const RTL8139_RX_MAX: usize = 1518;
+
+const RTL8139_RSR_ROK: u16 = 1 << 0;
+
 const RTL8139_TSR_OWN: u32 = 1 << 13;
 
+/// The number of transmit descriptors, used in order as a ring
+const RTL8139_TXDS: usize = 4;
+
+/// The most frames waiting in the driver for a free descriptor. Beyond this, frames are left
+/// queued in their resources, which refuse more writes once they are full
+const RTL8139_TX_BACKLOG: usize = 16;
+
+/// How long the card has to send a frame before its descriptor is taken back, in seconds
+const RTL8139_TX_TIMEOUT_SECS: i64 = 1;
+
 const RTL8139_CR_RST: u8 = 1 << 4;
 const RTL8139_CR_RE: u8 = 1 << 3;
 const RTL8139_CR_TE: u8 = 1 << 2;
@@ -49,11 +73,40 @@ const RTL8139_RCR_AB: u32 = 1 << 3;
 const RTL8139_RCR_AM: u32 = 1 << 2;
 const RTL8139_RCR_APM: u32 = 1 << 1;
 
+/// Find the frame at `capr` in the receive ring. Returns the frame without its CRC and the offset
+/// of the next frame, or `None` if the header is bad and the frame cannot be trusted.
+///
+/// Each frame starts with a status and a length, which counts the CRC, and the next frame starts
+/// on a 4 byte boundary. A frame crossing the end of the ring is written on past it, so it is
+/// always contiguous.
+pub fn rx_frame(ring: &[u8], capr: usize) -> Option<(&[u8], usize)> {
+    if capr + 4 > ring.len() {
+        return None;
+    }
+
+    let status = ring[capr] as u16 | (ring[capr + 1] as u16) << 8;
+    let len = ring[capr + 2] as usize | (ring[capr + 3] as usize) << 8;
+    if status & RTL8139_RSR_ROK != RTL8139_RSR_ROK || len < 4 || len > RTL8139_RX_MAX || capr + 4 + len > ring.len() {
+        return None;
+    }
+
+    let mut next = (capr + 4 + len + 3) & !3;
+    if next >= RTL8139_RX_SIZE {
+        next -= RTL8139_RX_SIZE;
+    }
+
+    Some((&ring[capr + 4 .. capr + len], next))
+}
+
 #[repr(packed)]
 struct Txd {
     pub address_port: Pio<u32>,
     pub status_port: Pio<u32>,
     pub buffer: usize,
+    /// Set when a frame is queued, cleared once the card has copied it out of the buffer
+    pub in_use: bool,
+    /// When the frame was queued
+    pub sent: Duration,
 }
 
 pub struct Rtl8139Port {
@@ -101,7 +154,12 @@ pub struct Rtl8139 {
     outbound: VecDeque<Vec<u8>>,
     txds: Vec<Txd>,
     txd_i: usize,
+    /// The receive ring, at its virtual address
+    rx_buffer: usize,
     port: Rtl8139Port,
+    /// The hardware address, read from the ID registers
+    mac: MacAddr,
+    interface: Arc<Intex<NetworkInterface>>,
 }
 
 impl Rtl8139 {
@@ -125,7 +183,10 @@ impl Rtl8139 {
             outbound: VecDeque::new(),
             txds: Vec::new(),
             txd_i: 0,
+            rx_buffer: 0,
             port: Rtl8139Port::new((base & 0xFFFFFFF0) as u16),
+            mac: MacAddr { bytes: [0; 6] },
+            interface: NetworkInterface::register(),
         };
 
         unsafe { module.init() };
@@ -133,9 +194,17 @@ impl Rtl8139 {
         module
     }
 
-    unsafe fn init(&mut self) {
-        debugln!(" + RTL8139 on: {:X}, IRQ: {:X}", self.base, self.irq);
+    /// Read the hardware address from the ID registers, which the card loads from its EEPROM
+    fn mac(&self) -> [u8; 6] {
+        [self.port.idr[0].read(),
+         self.port.idr[1].read(),
+         self.port.idr[2].read(),
+         self.port.idr[3].read(),
+         self.port.idr[4].read(),
+         self.port.idr[5].read()]
+    }
 
+    unsafe fn init(&mut self) {
         self.pci.flag(4, 4, true); // Bus mastering
 
         let base = self.base as u16;
@@ -144,29 +213,36 @@ impl Rtl8139 {
         self.port.cr.write(RTL8139_CR_RST);
         while self.port.cr.read() & RTL8139_CR_RST != 0 {}
 
-        debug::d("   - MAC: ");
-        MAC_ADDR = MacAddr {
-            bytes: [self.port.idr[0].read(),
-                    self.port.idr[1].read(),
-                    self.port.idr[2].read(),
-                    self.port.idr[3].read(),
-                    self.port.idr[4].read(),
-                    self.port.idr[5].read()],
-        };
-        debug::d(&MAC_ADDR.to_string());
+        self.mac = MacAddr { bytes: self.mac() };
+        debugln!(" + RTL8139 on: {:X}, IRQ: {:X}, MAC: {}", self.base, self.irq, self.mac.to_string());
 
-        let receive_buffer = memory::alloc(10240);
-        self.port.rbstart.write(receive_buffer as u32);
+        // The Ethernet scheme sends from and accepts frames for this address. Only the first card
+        // registers the network scheme, so another card must not take the address over
+        if MAC_ADDR.equals(MacAddr { bytes: [0; 6] }) {
+            MAC_ADDR = self.mac;
+        }
+        self.interface.lock().mac = self.mac;
+
+        debug::d("   -");
+
+        // The card is a 32-bit bus master that is given physical addresses, so its buffers are
+        // allocated in the first 4 GiB and translated, rather than assuming that the kernel's
+        // virtual addresses are the physical ones
+        self.rx_buffer = memory::alloc_dma32(RTL8139_RX_ALLOC, 4);
+        self.port.rbstart.write(memory::virt_to_phys(self.rx_buffer) as u32);
 
-        for i in 0..4 {
+        for i in 0..RTL8139_TXDS {
             self.txds.push(Txd {
                 address_port: Pio::<u32>::new(base + 0x20 + (i as u16) * 4),
                 status_port: Pio::<u32>::new(base + 0x10 + (i as u16) * 4),
-                buffer: memory::alloc(4096),
+                buffer: memory::alloc_dma32(4096, 4),
+                in_use: false,
+                sent: Duration::new(0, 0),
             });
         }
 
-        self.port.imr.write(RTL8139_ISR_TOK | RTL8139_ISR_ROK);
+        self.port.imr.write(RTL8139_ISR_FOVW | RTL8139_ISR_RXOVW | RTL8139_ISR_TER | RTL8139_ISR_TOK |
+                            RTL8139_ISR_RER | RTL8139_ISR_ROK);
         debug::d(" IMR: ");
         debug::dh(self.port.imr.read() as usize);
 
@@ -187,39 +263,131 @@ impl Rtl8139 {
     }
 
     unsafe fn receive_inbound(&mut self) {
-        let receive_buffer = self.port.rbstart.read() as usize;
-        let mut capr = (self.port.capr.read() + 16) as usize;
+        let receive_buffer = self.rx_buffer;
+        let ring = slice::from_raw_parts(receive_buffer as *const u8, RTL8139_RX_ALLOC);
+        let mut capr = (self.port.capr.read().wrapping_add(16)) as usize % RTL8139_RX_SIZE;
         let cbr = self.port.cbr.read() as usize;
 
         while capr != cbr {
-            let frame_addr = receive_buffer + capr + 4;
-            //let frame_status = ptr::read((receive_buffer + capr) as *const u16) as usize;
-            let frame_len = ptr::read((receive_buffer + capr + 2) as *const u16) as usize;
+            match rx_frame(ring, capr) {
+                Some((frame, next)) => {
+                    // Copy the frame out of the ring, so that the card can reuse the space as
+                    // soon as CAPR is moved
+                    self.inbound.push_back(Vec::from(frame));
+                    {
+                        let mut interface = self.interface.lock();
+                        interface.rx_packets += 1;
+                        interface.rx_bytes += frame.len() as u64;
+                    }
 
-            self.inbound.push_back(Vec::from(slice::from_raw_parts(frame_addr as *const u8, frame_len - 4)));
+                    capr = next;
+                    self.port.capr.write((capr as u16).wrapping_sub(16));
+                },
+                None => {
+                    // The header cannot be trusted to find the next frame, so drop everything
+                    // received
+                    debugln!("RTL8139: Bad frame status {:X} length {}",
+                             ptr::read((receive_buffer + capr) as *const u16),
+                             ptr::read((receive_buffer + capr + 2) as *const u16));
+                    self.interface.lock().rx_errors += 1;
+                    self.port.capr.write((cbr as u16).wrapping_sub(16));
+                    break;
+                }
+            }
+        }
+    }
 
-            capr = capr + frame_len + 4;
-            capr = (capr + 3) & (0xFFFFFFFF - 3);
-            if capr >= 8192 {
-                capr -= 8192
+    /// Restart the receiver after the ring or the FIFO overflowed, dropping everything in the ring.
+    /// Without this, the card can stop receiving with the ring still marked full.
+    unsafe fn reset_receive(&mut self) {
+        self.port.cr.write(RTL8139_CR_TE);
+        self.port.cr.write(RTL8139_CR_RE | RTL8139_CR_TE);
+        self.port.rcr.write(RTL8139_RCR_WRAP | RTL8139_RCR_AR | RTL8139_RCR_AB | RTL8139_RCR_AM |
+                            RTL8139_RCR_APM);
+        // Restarting the receiver moves the card back to the start of the ring
+        self.port.capr.write(0u16.wrapping_sub(16));
+    }
+
+    /// Pass received frames on to the resources, or drop them if the interface is down
+    unsafe fn deliver_inbound(&mut self) {
+        let up = self.interface.lock().up;
+        let resources = self.resources.lock();
+
+        while let Some(bytes) = self.inbound.pop_front() {
+            if ! up {
+                continue;
             }
 
-            self.port.capr.write((capr as u16) - 16);
+            for resource in resources.iter() {
+                (**resource).inbound.send(bytes.clone());
+            }
+            ::env().input.notify();
+        }
+    }
+
+    /// Free the transmit descriptors whose frames have been copied out by the card
+    unsafe fn reclaim_txds(&mut self) {
+        let now = Duration::monotonic();
+        for txd in self.txds.iter_mut() {
+            if txd.in_use {
+                if txd.status_port.readf(RTL8139_TSR_OWN) {
+                    txd.in_use = false;
+                } else if now - txd.sent > Duration::new(RTL8139_TX_TIMEOUT_SECS, 0) {
+                    // Give up on the frame, so that a stuck descriptor does not stop sending
+                    debugln!("RTL8139: Transmit timeout");
+                    self.interface.lock().tx_timeouts += 1;
+                    txd.in_use = false;
+                }
+            }
+        }
+    }
+
+    /// Take the frames of the resources, up to the backlog
+    unsafe fn take_outbound(&mut self) {
+        // Frames are still taken from the resources and the card while the interface is down, but
+        // they are dropped instead of being passed on
+        let up = self.interface.lock().up;
+
+        let resources = self.resources.lock();
+
+        for resource in resources.iter() {
+            // While the interface is down the frames are dropped, so none are held back
+            while ! up || self.outbound.len() < RTL8139_TX_BACKLOG {
+                match (**resource).pop_outbound() {
+                    Some(bytes) => if up {
+                        self.outbound.push_back(bytes);
+                    },
+                    None => break,
+                }
+            }
         }
     }
 
     unsafe fn send_outbound(&mut self) {
+        self.reclaim_txds();
+
         while let Some(bytes) = self.outbound.pop_front() {
             if let Some(ref mut txd) = self.txds.get_mut(self.txd_i) {
                 if bytes.len() < 4096 {
-                    while !txd.status_port.readf(RTL8139_TSR_OWN) {}
+                    // Descriptors are used in order, so if the next one is busy, all of them are.
+                    // The frame is sent from sync on a later transmit interrupt
+                    if txd.in_use {
+                        self.outbound.push_front(bytes);
+                        break;
+                    }
 
                     ::memcpy(txd.buffer as *mut u8, bytes.as_ptr(), bytes.len());
 
-                    txd.address_port.write(txd.buffer as u32);
+                    txd.address_port.write(memory::virt_to_phys(txd.buffer) as u32);
                     txd.status_port.write(bytes.len() as u32 & 0xFFF);
+                    txd.in_use = true;
+                    txd.sent = Duration::monotonic();
+
+                    let mut interface = self.interface.lock();
+                    interface.tx_packets += 1;
+                    interface.tx_bytes += bytes.len() as u64;
 
-                    self.txd_i = (self.txd_i + 1) % 4;
+                    self.txd_i = (self.txd_i + 1) % RTL8139_TXDS;
                 } else {
                     debug::dl();
                     debug::d("RTL8139: Frame too long for transmit: ");
@@ -239,19 +407,50 @@ impl KScheme for Rtl8139 {
         "network"
     }
 
-    fn open(&mut self, _: Url, _: usize) -> Result<Box<Resource>> {
-        Ok(NetworkResource::new(self))
+    fn open(&mut self, _: Url, flags: usize) -> Result<Box<Resource>> {
+        Ok(NetworkResource::new(self, flags))
     }
 
     fn on_irq(&mut self, irq: u8) {
         if irq == self.irq {
             let isr = self.port.isr.read();
+            // The line may be shared with another device
+            if isr == 0 {
+                return;
+            }
+
+            // Acknowledge exactly the bits that were seen, so that an event that happens while
+            // handling these raises the interrupt again
             self.port.isr.write(isr);
 
-            // dh(isr as usize);
-            // dl();
+            unsafe {
+                if isr & (RTL8139_ISR_RXOVW | RTL8139_ISR_FOVW) != 0 {
+                    debugln!("RTL8139: Receive overflow, ISR {:X}", isr);
+                    self.interface.lock().rx_overflows += 1;
+                    self.reset_receive();
+                } else if isr & (RTL8139_ISR_ROK | RTL8139_ISR_RER) != 0 {
+                    if isr & RTL8139_ISR_RER != 0 {
+                        self.interface.lock().rx_errors += 1;
+                    }
+                    self.receive_inbound();
+                }
+
+                if isr & (RTL8139_ISR_TOK | RTL8139_ISR_TER) != 0 {
+                    if isr & RTL8139_ISR_TER != 0 {
+                        debugln!("RTL8139: Transmit error, ISR {:X}", isr);
+                    }
+                    // Frames waiting for a descriptor can be sent now that one is free, making room
+                    // for the writers waiting on the resources
+                    self.take_outbound();
+                    self.send_outbound();
+                }
 
-            self.sync();
+                if isr & RTL8139_ISR_SERR != 0 {
+                    debugln!("RTL8139: System error, ISR {:X}", isr);
+                }
+
+                self.deliver_inbound();
+            }
         }
     }
 }
@@ -285,29 +484,13 @@ impl NetworkScheme for Rtl8139 {
 
     fn sync(&mut self) {
         unsafe {
-            {
-                let resources = self.resources.lock();
-
-                for resource in resources.iter() {
-                    while let Some(bytes) = (**resource).outbound.lock().pop_front() {
-                        self.outbound.push_back(bytes);
-                    }
-                }
-            }
+            self.take_outbound();
 
             self.send_outbound();
 
             self.receive_inbound();
 
-            {
-                let resources = self.resources.lock();
-
-                while let Some(bytes) = self.inbound.pop_front() {
-                    for resource in resources.iter() {
-                        (**resource).inbound.send(bytes.clone());
-                    }
-                }
-            }
+            self.deliver_inbound();
         }
     }
 }
@@ -7,9 +7,14 @@ use core::ops::DerefMut;
 
 use fs::Resource;
 
-use system::error::Result;
+use system::error::{Error, Result, EAGAIN};
+use system::syscall::{O_NONBLOCK, POLLIN, POLLOUT};
 
-use sync::{Intex, WaitQueue};
+use sync::{Intex, WaitCondition, WaitQueue};
+
+/// The most frames a resource queues for sending. Writes block while the queue is full, or fail
+/// with `EAGAIN` if the resource is nonblocking
+pub const NETWORK_OUTBOUND_MAX: usize = 64;
 
 pub trait NetworkScheme {
     fn add(&mut self, resource: *mut NetworkResource);
@@ -22,15 +27,20 @@ pub struct NetworkResource {
     pub ptr: *mut NetworkResource,
     pub inbound: WaitQueue<Vec<u8>>,
     pub outbound: Intex<VecDeque<Vec<u8>>>,
+    /// Notified when frames are taken from `outbound`
+    room: WaitCondition,
+    nonblock: bool,
 }
 
 impl NetworkResource {
-    pub fn new(nic: *mut NetworkScheme) -> Box<Self> {
+    pub fn new(nic: *mut NetworkScheme, flags: usize) -> Box<Self> {
         let mut ret = box NetworkResource {
             nic: nic,
             ptr: 0 as *mut NetworkResource,
             inbound: WaitQueue::new(),
             outbound: Intex::new(VecDeque::new()),
+            room: WaitCondition::new(),
+            nonblock: flags & O_NONBLOCK == O_NONBLOCK,
         };
 
         unsafe {
@@ -41,6 +51,15 @@ impl NetworkResource {
 
         ret
     }
+
+    /// Take the next frame to send, waking the writers waiting for room in the queue
+    pub fn pop_outbound(&self) -> Option<Vec<u8>> {
+        let bytes = self.outbound.lock().pop_front();
+        if bytes.is_some() {
+            unsafe { self.room.notify(); }
+        }
+        bytes
+    }
 }
 
 impl Resource for NetworkResource {
@@ -50,6 +69,8 @@ impl Resource for NetworkResource {
             ptr: 0 as *mut NetworkResource,
             inbound: self.inbound.clone(),
             outbound: Intex::new(self.outbound.lock().clone()),
+            room: WaitCondition::new(),
+            nonblock: self.nonblock,
         };
 
         unsafe {
@@ -88,9 +109,29 @@ impl Resource for NetworkResource {
         return Ok(bytes.len());
     }
 
+    /// Queue a frame and pass it to the card. While the card is too far behind to take more
+    /// frames, this blocks, or returns `EAGAIN` if the resource is nonblocking
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
         unsafe {
-            (*self.ptr).outbound.lock().push_back(Vec::from(buf));
+            if (*self.ptr).outbound.lock().len() >= NETWORK_OUTBOUND_MAX {
+                (*self.nic).sync();
+            }
+
+            // The card takes frames from the queue as its transmit interrupts free descriptors
+            loop {
+                let context = {
+                    let mut outbound = (*self.ptr).outbound.lock();
+                    if outbound.len() < NETWORK_OUTBOUND_MAX {
+                        outbound.push_back(Vec::from(buf));
+                        break;
+                    }
+                    if self.nonblock {
+                        return Err(Error::new(EAGAIN));
+                    }
+                    (*self.ptr).room.block(None)
+                };
+                (*self.ptr).room.sleep(context);
+            }
 
             (*self.nic).sync();
         }
@@ -98,6 +139,28 @@ impl Resource for NetworkResource {
         Ok(buf.len())
     }
 
+    fn poll(&mut self) -> usize {
+        let (empty, full) = unsafe {
+            (*self.nic).sync();
+            ((*self.ptr).inbound.inner.lock().is_empty(),
+             (*self.ptr).outbound.lock().len() >= NETWORK_OUTBOUND_MAX)
+        };
+
+        let mut events = 0;
+        if ! empty {
+            events |= POLLIN;
+        }
+        if ! full {
+            events |= POLLOUT;
+        }
+        events
+    }
+
+    fn set_flags(&mut self, flags: usize) -> Result<()> {
+        self.nonblock = flags & O_NONBLOCK == O_NONBLOCK;
+        Ok(())
+    }
+
     fn sync(&mut self) -> Result<()> {
         unsafe {
             (*self.nic).sync();
@@ -1,6 +1,10 @@
+use alloc::boxed::Box;
+
 use common::debug;
 use common::slice::GetSlice;
+use common::time::Duration;
 
+use collections::string::ToString;
 use collections::vec::Vec;
 
 use core::{mem, slice};
@@ -9,7 +13,9 @@ use arch::context::context_switch;
 
 use network::common::*;
 
-use fs::{KScheme, Url};
+use fs::{KScheme, Resource, Url, VecResource};
+
+use system::error::{Error, Result, ENOENT};
 
 #[derive(Copy, Clone)]
 #[repr(packed)]
@@ -30,6 +36,26 @@ pub struct Arp {
     pub data: Vec<u8>,
 }
 
+impl Arp {
+    /// Create a request for the MAC address of `dst_ip`
+    pub fn request(dst_ip: Ipv4Addr) -> Self {
+        Arp {
+            header: ArpHeader {
+                htype: n16::new(1),
+                ptype: n16::new(0x800),
+                hlen: 6,
+                plen: 4,
+                oper: n16::new(1),
+                src_mac: unsafe { MAC_ADDR },
+                src_ip: unsafe { IP_ADDR },
+                dst_mac: BROADCAST_MAC_ADDR,
+                dst_ip: dst_ip,
+            },
+            data: Vec::new(),
+        }
+    }
+}
+
 impl FromBytes for Arp {
     fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
         if bytes.len() >= mem::size_of::<ArpHeader>() {
@@ -56,22 +82,38 @@ impl ToBytes for Arp {
     }
 }
 
+/// A scheme listing the ARP cache
+///
+/// `arp:` lists the cached hosts, one per line, with their MAC address and the age of the entry
+/// in seconds.
 pub struct ArpScheme;
 
 impl KScheme for ArpScheme {
     fn scheme(&self) -> &str {
         "arp"
     }
+
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        if url.reference().trim_matches('/').is_empty() {
+            let list = ::env().arp.lock().list(Duration::monotonic());
+            Ok(box VecResource::new("arp:".to_string(), list.into_bytes()))
+        } else {
+            Err(Error::new(ENOENT))
+        }
+    }
 }
 
 impl ArpScheme {
     pub fn reply_loop() {
-        while let Ok(mut link) = Url::from_str("ethernet:/806").unwrap().open() {
+        while let Ok(mut link) = Url::from_str(&format!("ethernet:{}/806", BROADCAST_MAC_ADDR.to_string())).unwrap().open() {
             loop {
                 let mut bytes = [0; 8192];
                 if let Ok(count) = link.read(&mut bytes) {
                     if let Some(packet) = Arp::from_bytes(bytes[.. count].to_vec()) {
-                        if packet.header.oper.get() == 1 && packet.header.dst_ip.equals(IP_ADDR) {
+                        // Refresh hosts that are already cached from their requests and replies
+                        ::env().arp.lock().update(packet.header.src_ip, packet.header.src_mac, Duration::monotonic());
+
+                        if packet.header.oper.get() == 1 && packet.header.dst_ip.equals(unsafe { IP_ADDR }) {
                             let mut response = Arp {
                                 header: packet.header,
                                 data: packet.data.clone(),
@@ -80,9 +122,11 @@ impl ArpScheme {
                             response.header.dst_mac = packet.header.src_mac;
                             response.header.dst_ip = packet.header.src_ip;
                             response.header.src_mac = unsafe { MAC_ADDR };
-                            response.header.src_ip = IP_ADDR;
+                            response.header.src_ip = unsafe { IP_ADDR };
 
-                            let _ = link.write(&response.to_bytes());
+                            if let Ok(mut reply_link) = Url::from_str(&format!("ethernet:{}/806", packet.header.src_mac.to_string())).unwrap().open() {
+                                let _ = reply_link.write(&response.to_bytes());
+                            }
                         }
                     }
                 } else {
@@ -0,0 +1,34 @@
+use alloc::boxed::Box;
+
+use collections::string::ToString;
+
+use common::time::Duration;
+
+use fs::{KScheme, Resource, Url, VecResource};
+
+use network::dns;
+
+use system::error::Result;
+
+/// A scheme resolving names to IPv4 addresses
+///
+/// `dns:name` resolves name, and returns its address as text. `dns:` lists the cached names with
+/// the seconds left of their TTL.
+pub struct DnsScheme;
+
+impl KScheme for DnsScheme {
+    fn scheme(&self) -> &str {
+        "dns"
+    }
+
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        let name = url.reference().trim_matches('/');
+        if name.is_empty() {
+            let list = ::env().dns.lock().list(Duration::realtime());
+            Ok(box VecResource::new("dns:".to_string(), list.into_bytes()))
+        } else {
+            let addr = try!(dns::resolve(name));
+            Ok(box VecResource::new(format!("dns:{}", name), addr.to_string().into_bytes()))
+        }
+    }
+}
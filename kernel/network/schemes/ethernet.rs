@@ -14,6 +14,7 @@ use network::ethernet::*;
 use fs::{KScheme, Resource, Url};
 
 use system::error::{Error, Result, ENOENT};
+use system::syscall::{POLLERR, POLLIN, POLLOUT};
 
 /// A ethernet resource
 pub struct EthernetResource {
@@ -27,6 +28,15 @@ pub struct EthernetResource {
     ethertype: u16,
 }
 
+impl EthernetResource {
+    /// Check if a frame is addressed to this resource
+    fn accepts(&self, frame: &EthernetII) -> bool {
+        frame.header.ethertype.get() == self.ethertype && (unsafe { frame.header.dst.equals(MAC_ADDR) }
+            || frame.header.dst.equals(BROADCAST_MAC_ADDR)) && (frame.header.src.equals(self.peer_addr)
+            || self.peer_addr.equals(BROADCAST_MAC_ADDR))
+    }
+}
+
 impl Resource for EthernetResource {
     fn dup(&self) -> Result<Box<Resource>> {
         match self.network.dup() {
@@ -68,10 +78,7 @@ impl Resource for EthernetResource {
             match self.network.read(&mut bytes) {
                 Ok(count) => {
                     if let Some(frame) = EthernetII::from_bytes(bytes[.. count].to_vec()) {
-                        if frame.header.ethertype.get() == self.ethertype && (unsafe { frame.header.dst.equals(MAC_ADDR) }
-                            || frame.header.dst.equals(BROADCAST_MAC_ADDR)) && (frame.header.src.equals(self.peer_addr)
-                            || self.peer_addr.equals(BROADCAST_MAC_ADDR))
-                        {
+                        if self.accepts(&frame) {
                             for (b, d) in buf.iter_mut().zip(frame.data.iter()) {
                                 *b = *d;
                             }
@@ -102,6 +109,27 @@ impl Resource for EthernetResource {
         }
     }
 
+    fn poll(&mut self) -> usize {
+        // Take frames until one is for this resource, keeping it for the next read
+        while self.data.is_empty() && self.network.poll() & POLLIN == POLLIN {
+            let mut bytes = [0; 8192];
+            match self.network.read(&mut bytes) {
+                Ok(count) => if let Some(frame) = EthernetII::from_bytes(bytes[.. count].to_vec()) {
+                    if self.accepts(&frame) {
+                        self.data = frame.data;
+                    }
+                },
+                Err(_) => return POLLERR,
+            }
+        }
+
+        if self.data.is_empty() {
+            POLLOUT
+        } else {
+            POLLIN | POLLOUT
+        }
+    }
+
     fn sync(&mut self) -> Result<()> {
         self.network.sync()
     }
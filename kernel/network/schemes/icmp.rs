@@ -1,14 +1,22 @@
+use alloc::boxed::Box;
+
+use common::random::rand;
 use common::slice::GetSlice;
+use common::time::Duration;
 
+use collections::string::ToString;
 use collections::vec::Vec;
 
-use core::{mem, slice};
+use core::{cmp, mem, slice};
 
 use arch::context::context_switch;
 
 use network::common::*;
 
-use fs::{KScheme, Url};
+use fs::{KScheme, Resource, Url};
+
+use system::error::{Error, Result, EINVAL, ETIMEDOUT};
+use system::syscall::POLLIN;
 
 #[derive(Copy, Clone)]
 #[repr(packed)]
@@ -24,6 +32,27 @@ pub struct Icmp {
     pub data: Vec<u8>,
 }
 
+pub const ICMP_ECHO_REPLY: u8 = 0x00;
+pub const ICMP_ECHO_REQUEST: u8 = 0x08;
+
+/// How long to wait for an echo reply, in seconds
+const ICMP_TIMEOUT: i64 = 5;
+
+impl Icmp {
+    /// Fill in the checksum of the message
+    pub fn checksum(&mut self) {
+        unsafe {
+            self.header.checksum.data = 0;
+
+            let header_ptr: *const IcmpHeader = &self.header;
+            self.header.checksum.data = Checksum::compile(
+                Checksum::sum(header_ptr as usize, mem::size_of::<IcmpHeader>()) +
+                Checksum::sum(self.data.as_ptr() as usize, self.data.len())
+            );
+        }
+    }
+}
+
 impl FromBytes for Icmp {
     fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
         if bytes.len() >= mem::size_of::<IcmpHeader>() {
@@ -50,12 +79,144 @@ impl ToBytes for Icmp {
     }
 }
 
+/// An echo request that is waiting for its reply
+#[derive(Copy, Clone)]
+struct IcmpPending {
+    sequence: u16,
+    sent: Duration,
+}
+
+/// A ping resource, opened as `icmp:host`
+///
+/// Writing sends an echo request carrying the written bytes. Reading blocks until the matching
+/// echo reply arrives, returning its payload, or fails with `ETIMEDOUT`. After a reply, `path`
+/// reports the round trip time in microseconds as `icmp:host/rtt`.
+pub struct IcmpResource {
+    ip: Box<Resource>,
+    peer_addr: Ipv4Addr,
+    identifier: u16,
+    sequence: u16,
+    pending: Option<IcmpPending>,
+    rtt: Option<Duration>,
+}
+
+impl Resource for IcmpResource {
+    fn dup(&self) -> Result<Box<Resource>> {
+        Ok(box IcmpResource {
+            ip: try!(self.ip.dup()),
+            peer_addr: self.peer_addr,
+            identifier: self.identifier,
+            sequence: self.sequence,
+            pending: self.pending,
+            rtt: self.rtt,
+        })
+    }
+
+    fn path(&self, buf: &mut [u8]) -> Result<usize> {
+        let path_string = match self.rtt {
+            Some(rtt) => format!("icmp:{}/{}", self.peer_addr.to_string(),
+                                 rtt.secs * 1000000 + rtt.nanos as i64 / 1000),
+            None => format!("icmp:{}", self.peer_addr.to_string()),
+        };
+        let path = path_string.as_bytes();
+
+        for (b, p) in buf.iter_mut().zip(path.iter()) {
+            *b = *p;
+        }
+
+        Ok(cmp::min(buf.len(), path.len()))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let pending = match self.pending {
+            Some(pending) => pending,
+            None => return Err(Error::new(EINVAL)),
+        };
+
+        let deadline = pending.sent + Duration::new(ICMP_TIMEOUT, 0);
+        loop {
+            let seen = ::env().input.count();
+            while self.ip.poll() & POLLIN == POLLIN {
+                let mut bytes = [0; 8192];
+                let count = try!(self.ip.read(&mut bytes));
+                if let Some(message) = Icmp::from_bytes(bytes[.. count].to_vec()) {
+                    let identifier = (message.header.data[0] as u16) << 8 | message.header.data[1] as u16;
+                    let sequence = (message.header.data[2] as u16) << 8 | message.header.data[3] as u16;
+                    if message.header._type == ICMP_ECHO_REPLY && identifier == self.identifier &&
+                       sequence == pending.sequence {
+                        self.pending = None;
+                        self.rtt = Some(Duration::monotonic() - pending.sent);
+
+                        for (b, d) in buf.iter_mut().zip(message.data.iter()) {
+                            *b = *d;
+                        }
+
+                        return Ok(cmp::min(buf.len(), message.data.len()));
+                    }
+                }
+            }
+
+            let now = Duration::monotonic();
+            if now >= deadline {
+                self.pending = None;
+                return Err(Error::new(ETIMEDOUT));
+            }
+
+            ::env().input.wait_until(seen, Some(deadline));
+        }
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let mut request = Icmp {
+            header: IcmpHeader {
+                _type: ICMP_ECHO_REQUEST,
+                code: 0,
+                checksum: Checksum { data: 0 },
+                data: [(self.identifier >> 8) as u8, self.identifier as u8,
+                       (self.sequence >> 8) as u8, self.sequence as u8],
+            },
+            data: Vec::from(buf),
+        };
+        request.checksum();
+
+        self.pending = Some(IcmpPending {
+            sequence: self.sequence,
+            sent: Duration::monotonic(),
+        });
+
+        self.ip.write(&request.to_bytes()).and(Ok(buf.len()))
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.ip.sync()
+    }
+}
+
 pub struct IcmpScheme;
 
 impl KScheme for IcmpScheme {
     fn scheme(&self) -> &str {
         "icmp"
     }
+
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        let host = url.reference().trim_matches('/');
+        if host.is_empty() {
+            return Err(Error::new(EINVAL));
+        }
+
+        let ip = try!(try!(Url::from_str(&format!("ip:{}/1", host))).open());
+        Ok(box IcmpResource {
+            ip: ip,
+            peer_addr: Ipv4Addr::from_string(&host.to_string()),
+            identifier: (rand() % 65536) as u16,
+            sequence: 0,
+            pending: None,
+            rtt: None,
+        })
+    }
 }
 
 impl IcmpScheme {
@@ -65,23 +226,14 @@ impl IcmpScheme {
                 let mut bytes = [0; 8192];
                 if let Ok(count) = ip.read(&mut bytes) {
                     if let Some(message) = Icmp::from_bytes(bytes[.. count].to_vec()) {
-                        if message.header._type == 0x08 {
+                        if message.header._type == ICMP_ECHO_REQUEST {
                             let mut response = Icmp {
                                 header: message.header,
                                 data: message.data,
                             };
 
-                            response.header._type = 0x00;
-
-                            unsafe {
-                                response.header.checksum.data = 0;
-
-                                let header_ptr: *const IcmpHeader = &response.header;
-                                response.header.checksum.data = Checksum::compile(
-                                    Checksum::sum(header_ptr as usize, mem::size_of::<IcmpHeader>()) +
-                                    Checksum::sum(response.data.as_ptr() as usize, response.data.len())
-                                );
-                            }
+                            response.header._type = ICMP_ECHO_REPLY;
+                            response.checksum();
 
                             let _ = ip.write(&response.to_bytes());
                         }
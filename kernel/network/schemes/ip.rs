@@ -9,38 +9,148 @@ use network::common::*;
 use network::ipv4::*;
 
 use common::{debug, random};
+use common::time::Duration;
 use common::to_num::ToNum;
 
-use super::arp::{Arp, ArpHeader};
+use super::arp::Arp;
 use fs::{KScheme, Resource, Url};
 
-use system::error::{Error, Result, ENOENT};
+use system::error::{Error, Result, EHOSTUNREACH, EMSGSIZE, ENOENT};
+use system::syscall::{POLLERR, POLLIN, POLLOUT};
+
+/// How long to wait for an ARP reply before asking again, in seconds
+const ARP_RETRY: i64 = 1;
+/// How many times a host is asked for its MAC address before it is unreachable
+const ARP_MAX_RETRIES: usize = 3;
+/// The most datagrams waiting for the MAC address of a host
+const ARP_QUEUE_MAX: usize = 16;
+
+/// The ARP request for the MAC address of the peer of an IP resource, or of the router to it
+///
+/// Datagrams written before the reply arrives wait in `queue` as their fragments, and are sent once
+/// it does. When the queue is full, the oldest datagram is dropped as a whole.
+struct IpArp {
+    link: Box<Resource>,
+    hop_addr: Ipv4Addr,
+    sent: Duration,
+    retries: usize,
+    queue: Vec<Vec<Vec<u8>>>,
+}
 
 /// A IP (internet protocole) resource
+///
+/// While the MAC address of the peer is unknown, the link accepts frames from any host and
+/// outbound packets are queued. Fragmented datagrams are reassembled before they are read, and
+/// datagrams larger than `IP_MTU` are fragmented as they are written, unless the resource was
+/// opened with `df` to set the Don't Fragment flag, as in `ip:host/proto/df`.
 pub struct IpResource {
     link: Box<Resource>,
+    arp: Option<IpArp>,
+    reassembly: IpReassembly,
     data: Vec<u8>,
     peer_addr: Ipv4Addr,
     proto: u8,
     id: u16,
+    dont_fragment: bool,
+}
+
+impl IpResource {
+    /// Check if a packet is addressed to this resource
+    fn accepts(&self, packet: &Ipv4) -> bool {
+        packet.header.proto == self.proto && packet.header.dst.equals(unsafe { IP_ADDR }) &&
+        packet.header.src.equals(self.peer_addr)
+    }
+
+    /// Check for the ARP reply of the peer or its router, asking again if it is late
+    ///
+    /// Once the reply has arrived, the link is reopened to the MAC address of the peer and the
+    /// queued packets are sent. Returns `Ok(true)` if the peer is resolved, and `EHOSTUNREACH` if
+    /// it never answered.
+    fn resolve(&mut self) -> Result<bool> {
+        let peer_mac = match self.arp {
+            Some(ref mut arp) => {
+                let mut peer_mac = None;
+                while peer_mac.is_none() && arp.link.poll() & POLLIN == POLLIN {
+                    let mut bytes = [0; 8192];
+                    let count = try!(arp.link.read(&mut bytes));
+                    if let Some(packet) = Arp::from_bytes(bytes[.. count].to_vec()) {
+                        if packet.header.oper.get() == 2 && packet.header.src_ip.equals(arp.hop_addr) {
+                            peer_mac = Some((arp.hop_addr, packet.header.src_mac));
+                        }
+                    }
+                }
+
+                match peer_mac {
+                    Some(peer_mac) => peer_mac,
+                    None => {
+                        let now = Duration::monotonic();
+                        if now - arp.sent >= Duration::new(ARP_RETRY, 0) {
+                            if arp.retries >= ARP_MAX_RETRIES {
+                                arp.queue.clear();
+                                return Err(Error::new(EHOSTUNREACH));
+                            }
+
+                            try!(arp.link.write(&Arp::request(arp.hop_addr).to_bytes()));
+                            arp.sent = now;
+                            arp.retries += 1;
+                        }
+                        return Ok(false);
+                    }
+                }
+            }
+            None => return Ok(true),
+        };
+
+        let (hop_addr, peer_mac) = peer_mac;
+        ::env().arp.lock().insert(hop_addr, peer_mac, Duration::monotonic());
+
+        self.link = try!(try!(Url::from_str(&format!("ethernet:{}/800", peer_mac.to_string()))).open());
+        if let Some(arp) = self.arp.take() {
+            for datagram in arp.queue.iter() {
+                for fragment in datagram.iter() {
+                    try!(self.link.write(fragment));
+                }
+            }
+        }
+
+        Ok(true)
+    }
 }
 
 impl Resource for IpResource {
     fn dup(&self) -> Result<Box<Resource>> {
+        let arp = match self.arp {
+            // The queued packets are sent by the original
+            Some(ref arp) => Some(IpArp {
+                link: try!(arp.link.dup()),
+                hop_addr: arp.hop_addr,
+                sent: arp.sent,
+                retries: arp.retries,
+                queue: Vec::new(),
+            }),
+            None => None,
+        };
+
         match self.link.dup() {
             Ok(link) => Ok(box IpResource {
                 link: link,
+                arp: arp,
+                reassembly: IpReassembly::new(),
                 data: self.data.clone(),
                 peer_addr: self.peer_addr,
                 proto: self.proto,
                 id: self.id,
+                dont_fragment: self.dont_fragment,
             }),
             Err(err) => Err(err),
         }
     }
 
     fn path(&self, buf: &mut [u8]) -> Result<usize> {
-        let path_string = format!("ip:{}/{:X}", self.peer_addr.to_string(), self.proto);
+        let mut path_string = format!("ip:{}/{:X}", self.peer_addr.to_string(), self.proto);
+        if self.dont_fragment {
+            path_string.push_str("/df");
+        }
         let path = path_string.as_bytes();
 
         for (b, p) in buf.iter_mut().zip(path.iter()) {
@@ -51,6 +161,18 @@ impl Resource for IpResource {
     }
 
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // Wait for the peer to be resolved, keeping a packet that arrives meanwhile
+        loop {
+            let seen = ::env().input.count();
+            if ! self.data.is_empty() || try!(self.resolve()) {
+                break;
+            }
+            if self.poll() & POLLIN != POLLIN {
+                let retry = self.arp.as_ref().map(|arp| arp.sent + Duration::new(ARP_RETRY, 0));
+                ::env().input.wait_until(seen, retry);
+            }
+        }
+
         if !self.data.is_empty() {
             let mut data: Vec<u8> = Vec::new();
             mem::swap(&mut self.data, &mut data);
@@ -67,13 +189,14 @@ impl Resource for IpResource {
             match self.link.read(&mut bytes) {
                 Ok(count) => {
                     if let Some(packet) = Ipv4::from_bytes(bytes[.. count].to_vec()) {
-                        if packet.header.proto == self.proto && packet.header.dst.equals(IP_ADDR) &&
-                           packet.header.src.equals(self.peer_addr) {
-                            for (b, d) in buf.iter_mut().zip(packet.data.iter()) {
-                                *b = *d;
-                            }
+                        if self.accepts(&packet) {
+                            if let Some(packet) = self.reassembly.push(packet, Duration::monotonic()) {
+                                for (b, d) in buf.iter_mut().zip(packet.data.iter()) {
+                                    *b = *d;
+                                }
 
-                            return Ok(cmp::min(buf.len(), packet.data.len()));
+                                return Ok(cmp::min(buf.len(), packet.data.len()));
+                            }
                         }
                     }
                 }
@@ -83,36 +206,71 @@ impl Resource for IpResource {
     }
 
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        let ip_data = Vec::from(buf);
+        let len = mem::size_of::<Ipv4Header>() + buf.len(); // No Options
+        if len > IP_REASSEMBLY_MAX || (self.dont_fragment && len > IP_MTU) {
+            return Err(Error::new(EMSGSIZE));
+        }
 
-        self.id += 1;
-        let mut ip = Ipv4 {
+        self.id = self.id.wrapping_add(1);
+        let ip = Ipv4 {
             header: Ipv4Header {
                 ver_hlen: 0x40 | (mem::size_of::<Ipv4Header>() / 4 & 0xF) as u8, // No Options
                 services: 0,
-                len: n16::new((mem::size_of::<Ipv4Header>() + ip_data.len()) as u16), // No Options
+                len: n16::new(len as u16),
                 id: n16::new(self.id),
-                flags_fragment: n16::new(0),
+                flags_fragment: n16::new(if self.dont_fragment { IP_DF } else { 0 }),
                 ttl: 128,
                 proto: self.proto,
                 checksum: Checksum { data: 0 },
-                src: IP_ADDR,
+                src: unsafe { IP_ADDR },
                 dst: self.peer_addr,
             },
             options: Vec::new(),
-            data: ip_data,
+            data: Vec::from(buf),
         };
 
-        unsafe {
-            let header_ptr: *const Ipv4Header = &ip.header;
-            ip.header.checksum.data =
-                Checksum::compile(Checksum::sum(header_ptr as usize, mem::size_of::<Ipv4Header>()) +
-                                  Checksum::sum(ip.options.as_ptr() as usize, ip.options.len()));
+        let fragments = ip.fragments(IP_MTU);
+
+        if try!(self.resolve()) {
+            for fragment in fragments.iter() {
+                try!(self.link.write(&fragment.to_bytes()));
+            }
+            Ok(buf.len())
+        } else {
+            if let Some(ref mut arp) = self.arp {
+                if arp.queue.len() >= ARP_QUEUE_MAX {
+                    arp.queue.remove(0);
+                }
+                arp.queue.push(fragments.iter().map(|fragment| fragment.to_bytes()).collect());
+            }
+            Ok(buf.len())
+        }
+    }
+
+    fn poll(&mut self) -> usize {
+        if self.resolve().is_err() {
+            return POLLERR;
         }
 
-        match self.link.write(&ip.to_bytes()) {
-            Ok(_) => Ok(buf.len()),
-            Err(err) => Err(err),
+        // Take packets until one is for this resource, keeping it for the next read
+        while self.data.is_empty() && self.link.poll() & POLLIN == POLLIN {
+            let mut bytes = [0; 8192];
+            match self.link.read(&mut bytes) {
+                Ok(count) => if let Some(packet) = Ipv4::from_bytes(bytes[.. count].to_vec()) {
+                    if self.accepts(&packet) {
+                        if let Some(packet) = self.reassembly.push(packet, Duration::monotonic()) {
+                            self.data = packet.data;
+                        }
+                    }
+                },
+                Err(_) => return POLLERR,
+            }
+        }
+
+        if self.data.is_empty() {
+            POLLOUT
+        } else {
+            POLLIN | POLLOUT
         }
     }
 
@@ -121,16 +279,12 @@ impl Resource for IpResource {
     }
 }
 
-/// A ARP entry (MAC + IP)
-pub struct ArpEntry {
-    ip: Ipv4Addr,
-    mac: MacAddr,
-}
-
 /// A IP scheme
-pub struct IpScheme {
-    pub arp: Vec<ArpEntry>,
-}
+///
+/// The MAC addresses of peers are taken from the ARP cache, and those of peers outside of the local
+/// network are the MAC address of the router. On a miss, the resource is returned right away and
+/// resolves the peer as it is used.
+pub struct IpScheme;
 
 impl KScheme for IpScheme {
     fn scheme(&self) -> &str {
@@ -142,81 +296,76 @@ impl KScheme for IpScheme {
         if let Some(host_string) = parts.get(0) {
             if let Some(proto_string) = parts.get(1) {
                 let proto = proto_string.to_num_radix(16) as u8;
+                let dont_fragment = parts.get(2) == Some(&"df");
 
                 if !host_string.is_empty() {
                     let peer_addr = Ipv4Addr::from_string(&host_string.to_string());
-                    let mut peer_mac = BROADCAST_MAC_ADDR;
-
-                    for entry in self.arp.iter() {
-                        if entry.ip.equals(peer_addr) {
-                            peer_mac = entry.mac;
-                            break;
-                        }
-                    }
-
-                    if peer_mac.equals(BROADCAST_MAC_ADDR) {
-                        if let Ok(mut link) = Url::from_str(&format!("ethernet:{}/806", &peer_mac.to_string())).unwrap().open() {
-                            let arp = Arp {
-                                header: ArpHeader {
-                                    htype: n16::new(1),
-                                    ptype: n16::new(0x800),
-                                    hlen: 6,
-                                    plen: 4,
-                                    oper: n16::new(1),
-                                    src_mac: unsafe { MAC_ADDR },
-                                    src_ip: IP_ADDR,
-                                    dst_mac: peer_mac,
-                                    dst_ip: peer_addr,
-                                },
-                                data: Vec::new(),
-                            };
-
-                            match link.write(&arp.to_bytes()) {
-                                Ok(_) => loop {
-                                    let mut bytes = [0; 8192];
-                                    match link.read(&mut bytes) {
-                                        Ok(count) => if let Some(packet) = Arp::from_bytes(bytes[.. count].to_vec()) {
-                                            if packet.header.oper.get() == 2 &&
-                                               packet.header.src_ip.equals(peer_addr) {
-                                                peer_mac = packet.header.src_mac;
-                                                self.arp.push(ArpEntry {
-                                                    ip: peer_addr,
-                                                    mac: peer_mac,
-                                                });
-                                                break;
-                                            }
-                                        },
-                                        Err(_) => (),
-                                    }
-                                },
-                                Err(err) => debugln!("IP: ARP Write Failed: {}", err),
-                            }
-                        }
-                    }
+                    let hop_addr = next_hop(peer_addr);
+                    let peer_mac = if is_broadcast(peer_addr) {
+                        Some(BROADCAST_MAC_ADDR)
+                    } else {
+                        ::env().arp.lock().lookup(hop_addr, Duration::monotonic())
+                    };
 
-                    if let Ok(link) = Url::from_str(&format!("ethernet:{}/800", &peer_mac.to_string())).unwrap().open(){
+                    if let Some(peer_mac) = peer_mac {
+                        let link = try!(try!(Url::from_str(&format!("ethernet:{}/800", peer_mac.to_string()))).open());
                         return Ok(box IpResource {
                             link: link,
+                            arp: None,
+                            reassembly: IpReassembly::new(),
                             data: Vec::new(),
                             peer_addr: peer_addr,
                             proto: proto,
                             id: (random::rand() % 65536) as u16,
+                            dont_fragment: dont_fragment,
                         });
                     }
+
+                    // Listen to any host until the peer has answered
+                    let link = try!(try!(Url::from_str(&format!("ethernet:{}/800", BROADCAST_MAC_ADDR.to_string()))).open());
+                    let mut arp_link = try!(try!(Url::from_str(&format!("ethernet:{}/806", BROADCAST_MAC_ADDR.to_string()))).open());
+                    if let Err(err) = arp_link.write(&Arp::request(hop_addr).to_bytes()) {
+                        debugln!("IP: ARP Write Failed: {}", err);
+                    }
+
+                    return Ok(box IpResource {
+                        link: link,
+                        arp: Some(IpArp {
+                            link: arp_link,
+                            hop_addr: hop_addr,
+                            sent: Duration::monotonic(),
+                            retries: 0,
+                            queue: Vec::new(),
+                        }),
+                        reassembly: IpReassembly::new(),
+                        data: Vec::new(),
+                        peer_addr: peer_addr,
+                        proto: proto,
+                        id: (random::rand() % 65536) as u16,
+                        dont_fragment: dont_fragment,
+                    });
                 } else {
+                    let mut reassembly = IpReassembly::new();
                     while let Ok(mut link) = Url::from_str("ethernet:/800").unwrap().open() {
                         let mut bytes = [0; 8192];
                         match link.read(&mut bytes) {
                             Ok(count) => {
                                 if let Some(packet) = Ipv4::from_bytes(bytes[.. count].to_vec()) {
-                                    if packet.header.proto == proto &&
-                                       packet.header.dst.equals(IP_ADDR) {
+                                    if packet.header.proto != proto ||
+                                       ! packet.header.dst.equals(unsafe { IP_ADDR }) {
+                                        continue;
+                                    }
+
+                                    if let Some(packet) = reassembly.push(packet, Duration::monotonic()) {
                                         return Ok(box IpResource {
                                             link: link,
+                                            arp: None,
+                                            reassembly: reassembly,
                                             data: packet.data,
                                             peer_addr: packet.header.src,
                                             proto: proto,
                                             id: (random::rand() % 65536) as u16,
+                                            dont_fragment: dont_fragment,
                                         });
                                     }
                                 }
@@ -1,13 +1,19 @@
 pub use self::arp::ArpScheme;
+pub use self::dns::DnsScheme;
 pub use self::ethernet::EthernetScheme;
 pub use self::icmp::IcmpScheme;
 pub use self::ip::IpScheme;
+pub use self::netcfg::NetcfgScheme;
+pub use self::netstat::NetstatScheme;
 pub use self::tcp::TcpScheme;
 pub use self::udp::UdpScheme;
 
 pub mod arp;
+pub mod dns;
 pub mod ethernet;
 pub mod icmp;
 pub mod ip;
+pub mod netcfg;
+pub mod netstat;
 pub mod tcp;
 pub mod udp;
@@ -0,0 +1,126 @@
+use alloc::arc::Arc;
+use alloc::boxed::Box;
+
+use collections::string::ToString;
+use collections::vec::Vec;
+
+use core::cmp::{max, min};
+use core::str;
+
+use fs::{DirResource, KScheme, Resource, ResourceSeek, Url};
+
+use network::interface::NetworkInterface;
+
+use sync::Intex;
+
+use system::error::{Error, Result, EINVAL, ENOENT};
+use system::syscall::{MODE_FILE, Stat};
+
+/// A network interface
+///
+/// Reading returns the statistics as they were when the resource was opened. Writing `up` or
+/// `down` changes whether the driver passes frames through the interface.
+pub struct NetcfgResource {
+    interface: Arc<Intex<NetworkInterface>>,
+    data: Vec<u8>,
+    seek: usize,
+}
+
+impl Resource for NetcfgResource {
+    fn dup(&self) -> Result<Box<Resource>> {
+        Ok(box NetcfgResource {
+            interface: self.interface.clone(),
+            data: self.data.clone(),
+            seek: self.seek,
+        })
+    }
+
+    fn path(&self, buf: &mut [u8]) -> Result<usize> {
+        let path = format!("netcfg:{}", self.interface.lock().name);
+
+        let mut i = 0;
+        for (b, p) in buf.iter_mut().zip(path.bytes()) {
+            *b = p;
+            i += 1;
+        }
+
+        Ok(i)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut i = 0;
+        while i < buf.len() && self.seek < self.data.len() {
+            buf[i] = self.data[self.seek];
+            self.seek += 1;
+            i += 1;
+        }
+        Ok(i)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let up = match str::from_utf8(buf).map(|command| command.trim()) {
+            Ok("up") => true,
+            Ok("down") => false,
+            _ => return Err(Error::new(EINVAL)),
+        };
+
+        self.interface.lock().up = up;
+        Ok(buf.len())
+    }
+
+    fn seek(&mut self, pos: ResourceSeek) -> Result<usize> {
+        match pos {
+            ResourceSeek::Start(offset) => self.seek = min(self.data.len(), offset),
+            ResourceSeek::Current(offset) =>
+                self.seek = max(0, min(self.data.len() as isize, self.seek as isize + offset)) as usize,
+            ResourceSeek::End(offset) =>
+                self.seek = max(0, min(self.data.len() as isize, self.data.len() as isize + offset)) as usize,
+        }
+        Ok(self.seek)
+    }
+
+    fn stat(&self, stat: &mut Stat) -> Result<usize> {
+        stat.st_mode = MODE_FILE | 0o644;
+        stat.st_size = self.data.len() as u64;
+        Ok(0)
+    }
+}
+
+/// A scheme exposing the network interfaces
+///
+/// `netcfg:` lists the interfaces, and `netcfg:name` opens an interface.
+pub struct NetcfgScheme;
+
+impl KScheme for NetcfgScheme {
+    fn scheme(&self) -> &str {
+        "netcfg"
+    }
+
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        let reference = url.reference().trim_matches('/');
+
+        let interfaces = ::env().interfaces.lock();
+        if reference.is_empty() {
+            let entries = interfaces.iter().map(|interface| interface.lock().name.clone()).collect();
+            return Ok(box DirResource::new("netcfg:".to_string(), entries));
+        }
+
+        for interface in interfaces.iter() {
+            let data = {
+                let interface = interface.lock();
+                if interface.name != reference {
+                    continue;
+                }
+                interface.stats().into_bytes()
+            };
+
+            return Ok(box NetcfgResource {
+                interface: interface.clone(),
+                data: data,
+                seek: 0,
+            });
+        }
+
+        Err(Error::new(ENOENT))
+    }
+}
@@ -0,0 +1,29 @@
+use alloc::boxed::Box;
+
+use collections::string::ToString;
+
+use fs::{KScheme, Resource, Url, VecResource};
+
+use network::interface::NETSTAT_HEADER;
+
+use system::error::Result;
+
+/// A read-only scheme with the counters of every network interface
+///
+/// `netstat:` returns a table with a line for each interface.
+pub struct NetstatScheme;
+
+impl KScheme for NetstatScheme {
+    fn scheme(&self) -> &str {
+        "netstat"
+    }
+
+    fn open(&mut self, _: Url, _: usize) -> Result<Box<Resource>> {
+        let mut string = NETSTAT_HEADER.to_string();
+        for interface in ::env().interfaces.lock().iter() {
+            string.push_str(&interface.lock().netstat());
+        }
+
+        Ok(box VecResource::new("netstat:".to_string(), string.into_bytes()))
+    }
+}
@@ -1,10 +1,11 @@
-use alloc::arc::Arc;
+use alloc::arc::{Arc, Weak};
 use alloc::boxed::Box;
 
-use collections::Vec;
+use collections::{BTreeMap, Vec, VecDeque};
 use collections::string::ToString;
 
 use common::random::rand;
+use common::time::{Duration, NANOS_PER_MILLI};
 
 use core::{cmp, mem, slice, str};
 use core::cell::UnsafeCell;
@@ -12,8 +13,12 @@ use core::cell::UnsafeCell;
 use fs::{KScheme, Resource, Url};
 
 use network::common::{n16, n32, Checksum, Ipv4Addr, IP_ADDR, FromBytes, ToBytes};
+use network::dns;
+
+use sync::{Intex, WaitEvent};
 
 use system::error::{Error, Result, ENOENT, EPIPE};
+use system::syscall::{POLLERR, POLLHUP, POLLIN, POLLOUT};
 
 #[derive(Copy, Clone)]
 #[repr(packed)]
@@ -40,6 +45,21 @@ pub const TCP_RST: u16 = 1 << 2;
 pub const TCP_PSH: u16 = 1 << 3;
 pub const TCP_ACK: u16 = 1 << 4;
 
+/// The largest amount of data sent in one segment
+const TCP_MSS: usize = 1460;
+/// The initial retransmission timeout, in milliseconds
+const TCP_RTO_INITIAL: i64 = 1000;
+/// The largest retransmission timeout, in milliseconds
+const TCP_RTO_MAX: i64 = 60000;
+/// How many times a segment is resent before the connection is reset
+const TCP_MAX_RETRIES: usize = 8;
+/// The most data sent and not acknowledged yet, however large the window of the peer
+const TCP_SEND_MAX: usize = 8 * TCP_MSS;
+/// The most connections accepted on a port that wait to be opened
+const TCP_BACKLOG_MAX: usize = 16;
+/// How long an accepted connection waits to be opened before it is closed, in seconds
+const TCP_ACCEPT_TIMEOUT: i64 = 30;
+
 impl FromBytes for Tcp {
     fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
         if bytes.len() >= mem::size_of::<TcpHeader>() {
@@ -71,6 +91,160 @@ impl ToBytes for Tcp {
     }
 }
 
+impl Tcp {
+    /// Check if the segment pushes data to the receiver
+    fn has_data(&self) -> bool {
+        (self.header.flags.get() & (TCP_PSH | TCP_SYN | TCP_ACK)) == (TCP_PSH | TCP_ACK)
+    }
+}
+
+/// A sent segment that has not been acknowledged yet
+struct TcpUnacked {
+    /// The segment, ready to be resent
+    bytes: Vec<u8>,
+    /// The sequence number following the data of the segment
+    end: u32,
+    /// When to resend the segment
+    deadline: Duration,
+    /// How many times the segment was resent
+    retries: usize,
+}
+
+/// The segments a connection sent that were not acknowledged yet
+///
+/// The queue is shared with the retransmission timer, which resends the segments whose timeout
+/// expired whether or not the connection is used meanwhile.
+pub struct TcpSendQueue {
+    /// The peer to resend the segments to
+    peer_addr: Ipv4Addr,
+    /// The segments, by sequence number
+    unacked: BTreeMap<u32, TcpUnacked>,
+    /// The retransmission timeout, in milliseconds, doubled on every timeout
+    rto: i64,
+    /// Set when the peer reset the connection, or stopped acknowledging segments
+    reset: bool,
+}
+
+impl TcpSendQueue {
+    pub fn new(peer_addr: Ipv4Addr) -> TcpSendQueue {
+        TcpSendQueue {
+            peer_addr: peer_addr,
+            unacked: BTreeMap::new(),
+            rto: TCP_RTO_INITIAL,
+            reset: false,
+        }
+    }
+
+    /// Queue a segment that was sent, ending before the sequence number `end`
+    pub fn push(&mut self, sequence: u32, end: u32, bytes: Vec<u8>) {
+        let deadline = Duration::monotonic() + Duration::new(self.rto / 1000, (self.rto % 1000) as i32 * NANOS_PER_MILLI);
+        self.unacked.insert(sequence, TcpUnacked {
+            bytes: bytes,
+            end: end,
+            deadline: deadline,
+            retries: 0,
+        });
+    }
+
+    /// Forget the segments acknowledged by `ack_num`
+    pub fn ack(&mut self, ack_num: u32) {
+        let acked: Vec<u32> = self.unacked.iter()
+                                  .filter(|&(_, unacked)| ack_num.wrapping_sub(unacked.end) as i32 >= 0)
+                                  .map(|(&sequence, _)| sequence)
+                                  .collect();
+        if ! acked.is_empty() {
+            self.rto = TCP_RTO_INITIAL;
+        }
+        for sequence in acked.iter() {
+            self.unacked.remove(sequence);
+        }
+    }
+
+    /// Forget all the segments, as the connection is gone
+    pub fn reset(&mut self) {
+        self.reset = true;
+        self.unacked.clear();
+    }
+
+    pub fn is_reset(&self) -> bool {
+        self.reset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unacked.is_empty()
+    }
+
+    /// The number of bytes sent and not acknowledged
+    pub fn in_flight(&self) -> usize {
+        self.unacked.iter().fold(0, |sum, (&sequence, unacked)| sum + unacked.end.wrapping_sub(sequence) as usize)
+    }
+
+    /// When the next segment has to be resent
+    pub fn deadline(&self) -> Option<Duration> {
+        let mut deadline = None;
+        for (_, unacked) in self.unacked.iter() {
+            if deadline.map_or(true, |deadline| unacked.deadline < deadline) {
+                deadline = Some(unacked.deadline);
+            }
+        }
+        deadline
+    }
+
+    /// Take the segments whose timeout expired at `now` to resend them, doubling the timeout. If
+    /// one of them was resent too many times, the connection is reset instead
+    pub fn expired(&mut self, now: Duration) -> Vec<Vec<u8>> {
+        let mut segments = Vec::new();
+        if ! self.unacked.iter().any(|(_, unacked)| unacked.deadline <= now) {
+            return segments;
+        }
+
+        self.rto = cmp::min(self.rto * 2, TCP_RTO_MAX);
+        let deadline = now + Duration::new(self.rto / 1000, (self.rto % 1000) as i32 * NANOS_PER_MILLI);
+        for (_, unacked) in self.unacked.iter_mut() {
+            if unacked.deadline <= now {
+                if unacked.retries >= TCP_MAX_RETRIES {
+                    self.reset = true;
+                    break;
+                }
+
+                segments.push(unacked.bytes.clone());
+                unacked.retries += 1;
+                unacked.deadline = deadline;
+            }
+        }
+
+        if self.reset {
+            self.unacked.clear();
+            segments.clear();
+        }
+        segments
+    }
+}
+
+/// The retransmission timer of the TCP connections
+///
+/// Connections register their send queues here, and `TcpScheme::timer_loop` resends the segments
+/// that were not acknowledged in time.
+pub struct TcpTimer {
+    queues: Intex<Vec<Weak<Intex<TcpSendQueue>>>>,
+    /// Counts the segments queued, waking the timer to wait for their deadlines
+    queued: WaitEvent,
+}
+
+impl TcpTimer {
+    pub fn new() -> TcpTimer {
+        TcpTimer {
+            queues: Intex::new(Vec::new()),
+            queued: WaitEvent::new(),
+        }
+    }
+
+    /// Register the send queue of a connection. It is forgotten when the connection is gone
+    fn register(&self, queue: &Arc<Intex<TcpSendQueue>>) {
+        self.queues.lock().push(Arc::downgrade(queue));
+    }
+}
+
 pub struct TcpStream {
     ip: Box<Resource>,
     peer_addr: Ipv4Addr,
@@ -78,9 +252,120 @@ pub struct TcpStream {
     host_port: u16,
     sequence: u32,
     acknowledge: u32,
+    /// The window advertised by the peer
+    window: u16,
+    /// The segments sent and not acknowledged yet
+    send: Arc<Intex<TcpSendQueue>>,
+    /// Segments carrying data that arrived while polling or waiting for acknowledgements
+    received: VecDeque<Tcp>,
 }
 
 impl TcpStream {
+    fn new(ip: Box<Resource>, peer_addr: Ipv4Addr, peer_port: u16, host_port: u16, acknowledge: u32) -> TcpStream {
+        let send = Arc::new(Intex::new(TcpSendQueue::new(peer_addr)));
+        ::env().tcp.register(&send);
+
+        TcpStream {
+            ip: ip,
+            peer_addr: peer_addr,
+            peer_port: peer_port,
+            host_port: host_port,
+            sequence: rand() as u32,
+            acknowledge: acknowledge,
+            window: 0,
+            send: send,
+            received: VecDeque::new(),
+        }
+    }
+
+    fn is_reset(&self) -> bool {
+        self.send.lock().is_reset()
+    }
+
+    /// Take the next segment of this connection, if one has arrived
+    fn poll_segment(&mut self) -> Result<Option<Tcp>> {
+        while self.ip.poll() & POLLIN == POLLIN {
+            let mut bytes = [0; 8192];
+            let count = try!(self.ip.read(&mut bytes));
+            if let Some(segment) = Tcp::from_bytes(bytes[.. count].to_vec()) {
+                if segment.header.dst.get() == self.host_port &&
+                   segment.header.src.get() == self.peer_port {
+                    return Ok(Some(segment));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Update the window and the retransmission queue from the ACK of a segment
+    fn handle_ack(&mut self, segment: &Tcp) {
+        let flags = segment.header.flags.get();
+        if flags & TCP_RST == TCP_RST {
+            self.send.lock().reset();
+        } else if flags & TCP_ACK == TCP_ACK {
+            self.send.lock().ack(segment.header.ack_num.get());
+            self.window = segment.header.window_size.get();
+        }
+    }
+
+    /// Handle the segments that arrived, keeping the ones carrying data for the next read
+    fn receive(&mut self) {
+        while let Ok(Some(segment)) = self.poll_segment() {
+            self.handle_ack(&segment);
+            if segment.has_data() {
+                self.received.push_back(segment);
+            }
+        }
+    }
+
+    /// The number of bytes that can be sent before the send window is full. A closed window is
+    /// probed with a single byte at a time
+    fn space(&self) -> usize {
+        let in_flight = self.send.lock().in_flight();
+        let window = cmp::min(self.window as usize, TCP_SEND_MAX);
+        if window == 0 {
+            if in_flight == 0 { 1 } else { 0 }
+        } else {
+            window.saturating_sub(in_flight)
+        }
+    }
+
+    /// Wait until all sent segments are acknowledged. The timer resends them meanwhile, and
+    /// resets the connection if the peer stops answering
+    fn flush(&mut self) -> Result<()> {
+        loop {
+            let seen = ::env().input.count();
+            self.receive();
+            if self.is_reset() {
+                return Err(Error::new(EPIPE));
+            }
+            if self.send.lock().is_empty() {
+                return Ok(());
+            }
+            ::env().input.wait_until(seen, None);
+        }
+    }
+
+    /// Get the events that are ready. Segments that arrived are handled, and the ones carrying
+    /// data are kept for the next read
+    fn poll(&mut self) -> usize {
+        self.receive();
+
+        if self.is_reset() {
+            POLLERR | POLLHUP
+        } else {
+            let mut events = 0;
+            if ! self.received.is_empty() {
+                events |= POLLIN;
+            }
+            if self.space() > 0 {
+                events |= POLLOUT;
+            }
+            events
+        }
+    }
+
     fn path(&self, buf: &mut [u8]) -> Result<usize> {
         let path_string = format!("tcp:{}:{}/{}", self.peer_addr.to_string(), self.peer_port, self.host_port);
         let path = path_string.as_bytes();
@@ -94,69 +379,108 @@ impl TcpStream {
 
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
         loop {
-            let mut bytes = [0; 8192];
-            match self.ip.read(&mut bytes) {
-                Ok(count) => {
-                    if let Some(segment) = Tcp::from_bytes(bytes[.. count].to_vec()) {
-                        if (segment.header.flags.get() & (TCP_PSH | TCP_SYN | TCP_ACK)) ==
-                           (TCP_PSH | TCP_ACK) &&
-                           segment.header.dst.get() == self.host_port &&
-                           segment.header.src.get() == self.peer_port {
-                            // Send ACK
-                            self.sequence = segment.header.ack_num.get();
-                            self.acknowledge = segment.header.sequence.get() +
-                                               segment.data.len() as u32;
-                            let mut tcp = Tcp {
-                                        header: TcpHeader {
-                                            src: n16::new(self.host_port),
-                                            dst: n16::new(self.peer_port),
-                                            sequence: n32::new(self.sequence),
-                                            ack_num: n32::new(self.acknowledge),
-                                            flags: n16::new(((mem::size_of::<TcpHeader>() << 10) & 0xF000) as u16 | TCP_ACK),
-                                            window_size: n16::new(65535),
-                                            checksum: Checksum {
-                                                data: 0
-                                            },
-                                            urgent_pointer: n16::new(0)
-                                        },
-                                        options: Vec::new(),
-                                        data: Vec::new()
-                                    };
+            if self.is_reset() {
+                return Err(Error::new(EPIPE));
+            }
 
-                            unsafe {
-                                let proto = n16::new(0x06);
-                                let segment_len = n16::new((mem::size_of::<TcpHeader>() + tcp.options.len() + tcp.data.len()) as u16);
-                                tcp.header.checksum.data = Checksum::compile(
-                                            Checksum::sum((&IP_ADDR as *const Ipv4Addr) as usize, mem::size_of::<Ipv4Addr>()) +
-                                            Checksum::sum((&self.peer_addr as *const Ipv4Addr) as usize, mem::size_of::<Ipv4Addr>()) +
-                                            Checksum::sum((&proto as *const n16) as usize, mem::size_of::<n16>()) +
-                                            Checksum::sum((&segment_len as *const n16) as usize, mem::size_of::<n16>()) +
-                                            Checksum::sum((&tcp.header as *const TcpHeader) as usize, mem::size_of::<TcpHeader>()) +
-                                            Checksum::sum(tcp.options.as_ptr() as usize, tcp.options.len()) +
-                                            Checksum::sum(tcp.data.as_ptr() as usize, tcp.data.len())
-                                            );
-                            }
+            let segment = match self.received.pop_front() {
+                Some(segment) => segment,
+                None => {
+                    let mut bytes = [0; 8192];
+                    let count = try!(self.ip.read(&mut bytes));
+                    match Tcp::from_bytes(bytes[.. count].to_vec()) {
+                        Some(segment) => segment,
+                        None => continue,
+                    }
+                },
+            };
 
-                            let _ = self.ip.write(&tcp.to_bytes());
+            if segment.header.dst.get() == self.host_port &&
+               segment.header.src.get() == self.peer_port {
+                self.handle_ack(&segment);
+            }
 
-                            // TODO: Support broken packets (one packet in two buffers)
-                            let mut i = 0;
-                            while i < buf.len() && i < segment.data.len() {
-                                buf[i] = segment.data[i];
-                                i += 1;
-                            }
-                            return Ok(i);
-                        }
-                    }
+            if segment.has_data() &&
+               segment.header.dst.get() == self.host_port &&
+               segment.header.src.get() == self.peer_port {
+                // Send ACK
+                self.acknowledge = segment.header.sequence.get() +
+                                   segment.data.len() as u32;
+                let mut tcp = Tcp {
+                            header: TcpHeader {
+                                src: n16::new(self.host_port),
+                                dst: n16::new(self.peer_port),
+                                sequence: n32::new(self.sequence),
+                                ack_num: n32::new(self.acknowledge),
+                                flags: n16::new(((mem::size_of::<TcpHeader>() << 10) & 0xF000) as u16 | TCP_ACK),
+                                window_size: n16::new(65535),
+                                checksum: Checksum {
+                                    data: 0
+                                },
+                                urgent_pointer: n16::new(0)
+                            },
+                            options: Vec::new(),
+                            data: Vec::new()
+                        };
+
+                unsafe {
+                    let proto = n16::new(0x06);
+                    let segment_len = n16::new((mem::size_of::<TcpHeader>() + tcp.options.len() + tcp.data.len()) as u16);
+                    tcp.header.checksum.data = Checksum::compile(
+                                Checksum::sum((&IP_ADDR as *const Ipv4Addr) as usize, mem::size_of::<Ipv4Addr>()) +
+                                Checksum::sum((&self.peer_addr as *const Ipv4Addr) as usize, mem::size_of::<Ipv4Addr>()) +
+                                Checksum::sum((&proto as *const n16) as usize, mem::size_of::<n16>()) +
+                                Checksum::sum((&segment_len as *const n16) as usize, mem::size_of::<n16>()) +
+                                Checksum::sum((&tcp.header as *const TcpHeader) as usize, mem::size_of::<TcpHeader>()) +
+                                Checksum::sum(tcp.options.as_ptr() as usize, tcp.options.len()) +
+                                Checksum::sum(tcp.data.as_ptr() as usize, tcp.data.len())
+                                );
                 }
-                Err(err) => return Err(err),
+
+                let _ = self.ip.write(&tcp.to_bytes());
+
+                // TODO: Support broken packets (one packet in two buffers)
+                let mut i = 0;
+                while i < buf.len() && i < segment.data.len() {
+                    buf[i] = segment.data[i];
+                    i += 1;
+                }
+                return Ok(i);
             }
         }
     }
 
+    /// Send as much of buf as the send window allows, in segments of at most `TCP_MSS` bytes,
+    /// without waiting for them to be acknowledged. While the window is full, wait for it to open
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        let tcp_data = Vec::from(buf);
+        if buf.is_empty() {
+            return Ok(0);
+        }
 
+        loop {
+            let seen = ::env().input.count();
+            self.receive();
+            if self.is_reset() {
+                return Err(Error::new(EPIPE));
+            }
+            if self.space() > 0 {
+                break;
+            }
+            ::env().input.wait_until(seen, None);
+        }
+
+        let count = cmp::min(buf.len(), self.space());
+        let mut sent = 0;
+        while sent < count {
+            let len = cmp::min(count - sent, TCP_MSS);
+            try!(self.send_segment(&buf[sent .. sent + len]));
+            sent += len;
+        }
+        Ok(count)
+    }
+
+    /// Send a segment of data, and queue it to be resent until it is acknowledged
+    fn send_segment(&mut self, data: &[u8]) -> Result<()> {
         let mut tcp = Tcp {
             header: TcpHeader {
                 src: n16::new(self.host_port),
@@ -170,7 +494,7 @@ impl TcpStream {
                 urgent_pointer: n16::new(0),
             },
             options: Vec::new(),
-            data: tcp_data,
+            data: data.to_vec(),
         };
 
         unsafe {
@@ -191,35 +515,67 @@ impl TcpStream {
                                   Checksum::sum(tcp.data.as_ptr() as usize, tcp.data.len()));
         }
 
-        match self.ip.write(&tcp.to_bytes()) {
-            Ok(size) => {
-                loop {
-                    // Wait for ACK
-                    let mut bytes = [0; 8192];
-                    match self.ip.read(&mut bytes) {
-                        Ok(count) => {
-                            if let Some(segment) = Tcp::from_bytes(bytes[.. count].to_vec()) {
-                                if segment.header.dst.get() == self.host_port &&
-                                   segment.header.src.get() == self.peer_port {
-                                    return if (segment.header.flags.get() & (TCP_PSH | TCP_SYN | TCP_ACK)) == TCP_ACK {
-                                        self.sequence = segment.header.ack_num.get();
-                                        self.acknowledge = segment.header.sequence.get();
-                                        Ok(size)
-                                    } else {
-                                        Err(Error::new(EPIPE))
-                                    };
-                                }
-                            }
-                        }
-                        Err(err) => return Err(err),
-                    }
-                }
-            }
-            Err(err) => Err(err),
+        let bytes = tcp.to_bytes();
+        try!(self.ip.write(&bytes));
+
+        let end = self.sequence.wrapping_add(data.len() as u32);
+        self.send.lock().push(self.sequence, end, bytes);
+        self.sequence = end;
+        ::env().tcp.queued.notify();
+
+        Ok(())
+    }
+
+    /// Send a segment without data, that is not resent
+    fn send_flags(&mut self, flags: u16) {
+        let mut tcp = Tcp {
+            header: TcpHeader {
+                src: n16::new(self.host_port),
+                dst: n16::new(self.peer_port),
+                sequence: n32::new(self.sequence),
+                ack_num: n32::new(self.acknowledge),
+                flags: n16::new((((mem::size_of::<TcpHeader>()) << 10) & 0xF000) as u16 | flags),
+                window_size: n16::new(65535),
+                checksum: Checksum { data: 0 },
+                urgent_pointer: n16::new(0),
+            },
+            options: Vec::new(),
+            data: Vec::new(),
+        };
+
+        unsafe {
+            let proto = n16::new(0x06);
+            let segment_len = n16::new((mem::size_of::<TcpHeader>() + tcp.options.len() +
+                                        tcp.data
+                                           .len()) as u16);
+            tcp.header.checksum.data =
+                Checksum::compile(Checksum::sum((&IP_ADDR as *const Ipv4Addr) as usize,
+                                                mem::size_of::<Ipv4Addr>()) +
+                                  Checksum::sum((&self.peer_addr as *const Ipv4Addr) as usize,
+                                                mem::size_of::<Ipv4Addr>()) +
+                                  Checksum::sum((&proto as *const n16) as usize,
+                                                mem::size_of::<n16>()) +
+                                  Checksum::sum((&segment_len as *const n16) as usize,
+                                                mem::size_of::<n16>()) +
+                                  Checksum::sum((&tcp.header as *const TcpHeader) as usize,
+                                                mem::size_of::<TcpHeader>()) +
+                                  Checksum::sum(tcp.options.as_ptr() as usize, tcp.options.len()) +
+                                  Checksum::sum(tcp.data.as_ptr() as usize, tcp.data.len()));
         }
+
+        let _ = self.ip.write(&tcp.to_bytes());
+    }
+
+    /// Close the connection without waiting for the data written to be acknowledged, resetting
+    /// it and forgetting the segments to resend
+    fn abort(mut self) {
+        self.send.lock().reset();
+        self.send_flags(TCP_RST | TCP_ACK);
     }
 
+    /// Wait for the data written to be acknowledged
     fn sync(&mut self) -> Result<()> {
+        try!(self.flush());
         self.ip.sync()
     }
 
@@ -274,6 +630,7 @@ impl TcpStream {
                                     return if (segment.header.flags.get() & (TCP_PSH | TCP_SYN | TCP_ACK)) == (TCP_SYN | TCP_ACK) {
                                         self.sequence = segment.header.ack_num.get();
                                         self.acknowledge = segment.header.sequence.get();
+                                        self.window = segment.header.window_size.get();
 
                                         self.acknowledge += 1;
                                         tcp = Tcp {
@@ -377,6 +734,7 @@ impl TcpStream {
                                     return if (segment.header.flags.get() & (TCP_PSH | TCP_SYN | TCP_ACK)) == TCP_ACK {
                                         self.sequence = segment.header.ack_num.get();
                                         self.acknowledge = segment.header.sequence.get();
+                                        self.window = segment.header.window_size.get();
                                         true
                                     } else {
                                         false
@@ -395,43 +753,15 @@ impl TcpStream {
 
 impl Drop for TcpStream {
     fn drop(&mut self) {
-        // Send FIN-ACK
-        let mut tcp = Tcp {
-            header: TcpHeader {
-                src: n16::new(self.host_port),
-                dst: n16::new(self.peer_port),
-                sequence: n32::new(self.sequence),
-                ack_num: n32::new(self.acknowledge),
-                flags: n16::new((((mem::size_of::<TcpHeader>()) << 10) & 0xF000) as u16 | TCP_FIN | TCP_ACK),
-                window_size: n16::new(65535),
-                checksum: Checksum { data: 0 },
-                urgent_pointer: n16::new(0),
-            },
-            options: Vec::new(),
-            data: Vec::new(),
-        };
-
-        unsafe {
-            let proto = n16::new(0x06);
-            let segment_len = n16::new((mem::size_of::<TcpHeader>() + tcp.options.len() +
-                                        tcp.data
-                                           .len()) as u16);
-            tcp.header.checksum.data =
-                Checksum::compile(Checksum::sum((&IP_ADDR as *const Ipv4Addr) as usize,
-                                                mem::size_of::<Ipv4Addr>()) +
-                                  Checksum::sum((&self.peer_addr as *const Ipv4Addr) as usize,
-                                                mem::size_of::<Ipv4Addr>()) +
-                                  Checksum::sum((&proto as *const n16) as usize,
-                                                mem::size_of::<n16>()) +
-                                  Checksum::sum((&segment_len as *const n16) as usize,
-                                                mem::size_of::<n16>()) +
-                                  Checksum::sum((&tcp.header as *const TcpHeader) as usize,
-                                                mem::size_of::<TcpHeader>()) +
-                                  Checksum::sum(tcp.options.as_ptr() as usize, tcp.options.len()) +
-                                  Checksum::sum(tcp.data.as_ptr() as usize, tcp.data.len()));
+        // A reset connection is already closed
+        if self.is_reset() {
+            return;
         }
 
-        let _ = self.ip.write(&tcp.to_bytes());
+        // Let the data written arrive before closing
+        let _ = self.flush();
+
+        self.send_flags(TCP_FIN | TCP_ACK);
     }
 }
 
@@ -462,10 +792,188 @@ impl Resource for TcpResource {
     fn sync(&mut self) -> Result<()> {
         unsafe { (*self.stream.get()).sync() }
     }
+
+    fn poll(&mut self) -> usize {
+        unsafe { (*self.stream.get()).poll() }
+    }
+}
+
+/// A TCP listener
+///
+/// Each read waits for a client to connect to the port, establishes the connection, and returns
+/// its path. Opening that path takes the established connection, so that a server can accept
+/// more clients while it handles the ones it has. Connections that are not opened in time, or that
+/// are past the backlog of the port, are reset without waiting for their data to be acknowledged.
+pub struct TcpListener {
+    host_port: u16,
+    accepted: Arc<Intex<Vec<TcpAccepted>>>,
+}
+
+/// A connection accepted by a listener, waiting to be opened
+struct TcpAccepted {
+    stream: TcpStream,
+    /// When the connection was accepted
+    time: Duration,
+}
+
+/// Take the accepted connections that waited too long to be opened out of `accepted`, so they are
+/// aborted once the lock is released
+fn expire_accepted(accepted: &mut Vec<TcpAccepted>) -> Vec<TcpAccepted> {
+    let now = Duration::monotonic();
+    let mut expired = Vec::new();
+    let mut i = 0;
+    while i < accepted.len() {
+        if now - accepted[i].time >= Duration::new(TCP_ACCEPT_TIMEOUT, 0) {
+            expired.push(accepted.remove(i));
+        } else {
+            i += 1;
+        }
+    }
+    expired
+}
+
+impl TcpListener {
+    /// Wait for a SYN to the port, and establish a connection with the sender
+    fn accept(&mut self) -> Result<TcpStream> {
+        loop {
+            let mut ip = try!(Url::from_str("ip:/6").unwrap().open());
+
+            let mut bytes = [0; 8192];
+            let count = try!(ip.read(&mut bytes));
+            if let Some(segment) = Tcp::from_bytes(bytes[.. count].to_vec()) {
+                if segment.header.dst.get() == self.host_port && (segment.header.flags.get() & (TCP_PSH | TCP_SYN | TCP_ACK)) == TCP_SYN {
+                    let mut path = [0; 256];
+                    if let Ok(path_count) = ip.path(&mut path) {
+                        let ip_reference = unsafe { str::from_utf8_unchecked(&path[.. path_count]) }.split(':').nth(1).unwrap_or("");
+                        let ip_remote = ip_reference.split('/').next().unwrap_or("");
+                        let peer_addr = ip_remote.split(':').next().unwrap_or("");
+
+                        let mut stream = TcpStream::new(ip,
+                                                        Ipv4Addr::from_string(&peer_addr.to_string()),
+                                                        segment.header.src.get(),
+                                                        self.host_port,
+                                                        segment.header.sequence.get());
+
+                        if stream.server_establish(segment) {
+                            return Ok(stream);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Resource for TcpListener {
+    fn dup(&self) -> Result<Box<Resource>> {
+        Ok(box TcpListener {
+            host_port: self.host_port,
+            accepted: self.accepted.clone(),
+        })
+    }
+
+    fn path(&self, buf: &mut [u8]) -> Result<usize> {
+        let path_string = format!("tcp:/{}", self.host_port);
+        let path = path_string.as_bytes();
+
+        for (b, p) in buf.iter_mut().zip(path.iter()) {
+            *b = *p;
+        }
+
+        Ok(cmp::min(buf.len(), path.len()))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let stream = try!(self.accept());
+        let count = try!(stream.path(buf));
+
+        // Close connections nobody opened, and the oldest ones of this port past the backlog
+        let closed = {
+            let mut accepted = self.accepted.lock();
+            let mut closed = expire_accepted(&mut accepted);
+            while accepted.iter().filter(|accepted| accepted.stream.host_port == self.host_port).count() >= TCP_BACKLOG_MAX {
+                match accepted.iter().position(|accepted| accepted.stream.host_port == self.host_port) {
+                    Some(i) => closed.push(accepted.remove(i)),
+                    None => break,
+                }
+            }
+            accepted.push(TcpAccepted {
+                stream: stream,
+                time: Duration::monotonic(),
+            });
+            closed
+        };
+        for accepted in closed {
+            accepted.stream.abort();
+        }
+
+        Ok(count)
+    }
 }
 
 /// A TCP scheme
-pub struct TcpScheme;
+///
+/// `tcp:host:port` connects to a server, and `tcp:/port` listens on a port. The connections
+/// accepted by listeners are kept here until they are opened with `tcp:host:port/port`, for up to
+/// `TCP_ACCEPT_TIMEOUT` seconds and `TCP_BACKLOG_MAX` connections a port. Sent segments are resent
+/// by `timer_loop`, which runs in its own context.
+pub struct TcpScheme {
+    accepted: Arc<Intex<Vec<TcpAccepted>>>,
+}
+
+impl TcpScheme {
+    pub fn new() -> Box<TcpScheme> {
+        box TcpScheme {
+            accepted: Arc::new(Intex::new(Vec::new())),
+        }
+    }
+
+    /// Resend the segments of every connection whose timeout expired, sleeping until the next
+    /// deadline or until a segment is queued
+    pub fn timer_loop() {
+        let timer = &::env().tcp;
+        loop {
+            let seen = timer.queued.count();
+            let queues: Vec<Arc<Intex<TcpSendQueue>>> = {
+                let mut queues = timer.queues.lock();
+                queues.retain(|queue| queue.upgrade().is_some());
+                queues.iter().filter_map(|queue| queue.upgrade()).collect()
+            };
+
+            let now = Duration::monotonic();
+            let mut wake: Option<Duration> = None;
+            for queue in queues.iter() {
+                let (peer_addr, segments, reset) = {
+                    let mut queue = queue.lock();
+                    let segments = queue.expired(now);
+                    (queue.peer_addr, segments, queue.is_reset())
+                };
+
+                if ! segments.is_empty() {
+                    if let Ok(mut ip) = Url::from_str(&format!("ip:{}/6", peer_addr.to_string())).and_then(|url| url.open()) {
+                        for bytes in segments.iter() {
+                            let _ = ip.write(bytes);
+                        }
+                    }
+                }
+
+                // Wake the users of a connection that was given up on, so they see it is reset
+                if reset {
+                    ::env().input.notify();
+                }
+
+                if let Some(deadline) = queue.lock().deadline() {
+                    if wake.map_or(true, |wake| deadline < wake) {
+                        wake = Some(deadline);
+                    }
+                }
+            }
+            drop(queues);
+
+            timer.queued.wait_until(seen, wake);
+        }
+    }
+}
 
 impl KScheme for TcpScheme {
     fn scheme(&self) -> &str {
@@ -482,20 +990,39 @@ impl KScheme for TcpScheme {
         let port = remote_parts.next().unwrap_or("");
 
         if ! host.is_empty() && ! port.is_empty() {
-            let peer_addr = Ipv4Addr::from_string(&host.to_string());
+            let peer_addr = try!(dns::resolve(host));
             let peer_port = port.parse::<u16>().unwrap_or(0);
+
+            if ! path.is_empty() {
+                // Take a connection accepted by a listener
+                let host_port = path.parse::<u16>().unwrap_or(0);
+
+                let (taken, closed) = {
+                    let mut accepted = self.accepted.lock();
+                    let closed = expire_accepted(&mut accepted);
+                    let position = accepted.iter().position(|accepted| {
+                        let stream = &accepted.stream;
+                        stream.peer_addr.equals(peer_addr) && stream.peer_port == peer_port && stream.host_port == host_port
+                    });
+                    (position.map(|i| accepted.remove(i).stream), closed)
+                };
+                for accepted in closed {
+                    accepted.stream.abort();
+                }
+
+                return match taken {
+                    Some(stream) => Ok(box TcpResource {
+                        stream: Arc::new(UnsafeCell::new(stream))
+                    }),
+                    None => Err(Error::new(ENOENT)),
+                };
+            }
+
             let host_port = (rand() % 32768 + 32768) as u16;
 
             match Url::from_str(&format!("ip:{}/6", peer_addr.to_string())).unwrap().open() {
                 Ok(ip) => {
-                    let mut stream = TcpStream {
-                        ip: ip,
-                        peer_addr: peer_addr,
-                        peer_port: peer_port,
-                        host_port: host_port,
-                        sequence: rand() as u32,
-                        acknowledge: 0,
-                    };
+                    let mut stream = TcpStream::new(ip, peer_addr, peer_port, host_port, 0);
 
                     if stream.client_establish() {
                         return Ok(box TcpResource {
@@ -506,40 +1033,11 @@ impl KScheme for TcpScheme {
                 Err(err) => return Err(err),
             }
         } else if ! path.is_empty() {
-            let host_port = path.parse::<u16>().unwrap_or(0);
-
-            while let Ok(mut ip) = Url::from_str("ip:/6").unwrap().open() {
-                let mut bytes = [0; 8192];
-                match ip.read(&mut bytes) {
-                    Ok(count) => {
-                        if let Some(segment) = Tcp::from_bytes(bytes[.. count].to_vec()) {
-                            if segment.header.dst.get() == host_port && (segment.header.flags.get() & (TCP_PSH | TCP_SYN | TCP_ACK)) == TCP_SYN {
-                                let mut path = [0; 256];
-                                if let Ok(path_count) = ip.path(&mut path) {
-                                    let ip_reference = unsafe { str::from_utf8_unchecked(&path[.. path_count]) }.split(':').nth(1).unwrap_or("");
-                                    let ip_remote = ip_reference.split('/').next().unwrap_or("");
-                                    let peer_addr = ip_remote.split(':').next().unwrap_or("");
-
-                                    let mut stream = TcpStream {
-                                        ip: ip,
-                                        peer_addr: Ipv4Addr::from_string(&peer_addr.to_string()),
-                                        peer_port: segment.header.src.get(),
-                                        host_port: host_port,
-                                        sequence: rand() as u32,
-                                        acknowledge: segment.header.sequence.get(),
-                                    };
-
-                                    if stream.server_establish(segment) {
-                                        return Ok(box TcpResource {
-                                            stream: Arc::new(UnsafeCell::new(stream))
-                                        });
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Err(err) => return Err(err),
-                }
+            if let Ok(host_port) = path.parse::<u16>() {
+                return Ok(box TcpListener {
+                    host_port: host_port,
+                    accepted: self.accepted.clone(),
+                });
             }
         }
 
@@ -1,17 +1,22 @@
 use alloc::boxed::Box;
 
 use collections::Vec;
-use collections::string::ToString;
+use collections::vec_deque::VecDeque;
 
 use common::random::rand;
+use common::time::Duration;
 
-use core::{cmp, mem, ptr, slice, str};
+use core::{cmp, mem, ptr, slice};
 
 use fs::{KScheme, Resource, Url};
 
-use network::common::{n16, Checksum, Ipv4Addr, IP_ADDR, FromBytes, ToBytes};
+use network::common::{n16, is_broadcast, Checksum, Ipv4Addr, BROADCAST_MAC_ADDR, IP_ADDR, FromBytes,
+                      ToBytes};
+use network::dns;
+use network::ipv4::{Ipv4, IpReassembly};
 
-use system::error::{Error, Result, ENOENT};
+use system::error::{Error, Result, EDESTADDRREQ, ENOENT};
+use system::syscall::{POLLERR, POLLIN, POLLOUT};
 
 #[derive(Copy, Clone)]
 #[repr(packed)]
@@ -27,6 +32,40 @@ pub struct Udp {
     pub data: Vec<u8>,
 }
 
+impl Udp {
+    /// Create a datagram from `host_port` to `peer_addr:peer_port`, with the checksum filled in
+    pub fn new(host_port: u16, peer_addr: Ipv4Addr, peer_port: u16, data: Vec<u8>) -> Self {
+        let mut udp = Udp {
+            header: UdpHeader {
+                src: n16::new(host_port),
+                dst: n16::new(peer_port),
+                len: n16::new((mem::size_of::<UdpHeader>() + data.len()) as u16),
+                checksum: Checksum { data: 0 },
+            },
+            data: data,
+        };
+
+        unsafe {
+            let proto = n16::new(0x11);
+            let datagram_len = n16::new((mem::size_of::<UdpHeader>() + udp.data.len()) as u16);
+            udp.header.checksum.data =
+                Checksum::compile(Checksum::sum((&IP_ADDR as *const Ipv4Addr) as usize,
+                                                mem::size_of::<Ipv4Addr>()) +
+                                  Checksum::sum((&peer_addr as *const Ipv4Addr) as usize,
+                                                mem::size_of::<Ipv4Addr>()) +
+                                  Checksum::sum((&proto as *const n16) as usize,
+                                                mem::size_of::<n16>()) +
+                                  Checksum::sum((&datagram_len as *const n16) as usize,
+                                                mem::size_of::<n16>()) +
+                                  Checksum::sum((&udp.header as *const UdpHeader) as usize,
+                                                mem::size_of::<UdpHeader>()) +
+                                  Checksum::sum(udp.data.as_ptr() as usize, udp.data.len()));
+        }
+
+        udp
+    }
+}
+
 impl FromBytes for Udp {
     fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
         if bytes.len() >= mem::size_of::<UdpHeader>() {
@@ -95,12 +134,11 @@ impl Resource for UdpResource {
             let mut bytes: Vec<u8> = Vec::new();
             mem::swap(&mut self.data, &mut bytes);
 
-            // TODO: Allow splitting
-            let i = 0;
-            while i < buf.len() && i < bytes.len() {
-                buf[i] = bytes[i];
+            for (b, d) in buf.iter_mut().zip(bytes.iter()) {
+                *b = *d;
             }
-            return Ok(i);
+
+            return Ok(cmp::min(buf.len(), bytes.len()));
         }
 
         loop {
@@ -110,12 +148,11 @@ impl Resource for UdpResource {
                     if let Some(datagram) = Udp::from_bytes(bytes[.. count].to_vec()) {
                         if datagram.header.dst.get() == self.host_port &&
                            datagram.header.src.get() == self.peer_port {
-                            // TODO: Allow splitting
-                            let i = 0;
-                            while i < buf.len() && i < datagram.data.len() {
-                                buf[i] = datagram.data[i];
+                            for (b, d) in buf.iter_mut().zip(datagram.data.iter()) {
+                                *b = *d;
                             }
-                            return Ok(i);
+
+                            return Ok(cmp::min(buf.len(), datagram.data.len()));
                         }
                     }
                 }
@@ -125,34 +162,7 @@ impl Resource for UdpResource {
     }
 
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        let udp_data = Vec::from(buf);
-
-        let mut udp = Udp {
-            header: UdpHeader {
-                src: n16::new(self.host_port),
-                dst: n16::new(self.peer_port),
-                len: n16::new((mem::size_of::<UdpHeader>() + udp_data.len()) as u16),
-                checksum: Checksum { data: 0 },
-            },
-            data: udp_data,
-        };
-
-        unsafe {
-            let proto = n16::new(0x11);
-            let datagram_len = n16::new((mem::size_of::<UdpHeader>() + udp.data.len()) as u16);
-            udp.header.checksum.data =
-                Checksum::compile(Checksum::sum((&IP_ADDR as *const Ipv4Addr) as usize,
-                                                mem::size_of::<Ipv4Addr>()) +
-                                  Checksum::sum((&self.peer_addr as *const Ipv4Addr) as usize,
-                                                mem::size_of::<Ipv4Addr>()) +
-                                  Checksum::sum((&proto as *const n16) as usize,
-                                                mem::size_of::<n16>()) +
-                                  Checksum::sum((&datagram_len as *const n16) as usize,
-                                                mem::size_of::<n16>()) +
-                                  Checksum::sum((&udp.header as *const UdpHeader) as usize,
-                                                mem::size_of::<UdpHeader>()) +
-                                  Checksum::sum(udp.data.as_ptr() as usize, udp.data.len()));
-        }
+        let udp = Udp::new(self.host_port, self.peer_addr, self.peer_port, Vec::from(buf));
 
         match self.ip.write(&udp.to_bytes()) {
             Ok(_) => Ok(buf.len()),
@@ -160,11 +170,199 @@ impl Resource for UdpResource {
         }
     }
 
+    fn poll(&mut self) -> usize {
+        // Take datagrams until one is for this resource, keeping it for the next read
+        while self.data.is_empty() && self.ip.poll() & POLLIN == POLLIN {
+            let mut bytes = [0; 8192];
+            match self.ip.read(&mut bytes) {
+                Ok(count) => if let Some(datagram) = Udp::from_bytes(bytes[.. count].to_vec()) {
+                    if datagram.header.dst.get() == self.host_port &&
+                       datagram.header.src.get() == self.peer_port {
+                        self.data = datagram.data;
+                    }
+                },
+                Err(_) => return POLLERR,
+            }
+        }
+
+        if self.data.is_empty() {
+            POLLOUT
+        } else {
+            POLLIN | POLLOUT
+        }
+    }
+
     fn sync(&mut self) -> Result<()> {
         self.ip.sync()
     }
 }
 
+/// A datagram received by a bound socket, along with its sender
+#[derive(Clone)]
+struct UdpPending {
+    peer_addr: Ipv4Addr,
+    peer_port: u16,
+    data: Vec<u8>,
+}
+
+/// A bound UDP socket, opened as `udp:/port`
+///
+/// Datagrams from any peer to the port are queued together with their sender. Each read returns
+/// one datagram and makes its sender the current peer, which is reported by `path` as
+/// `udp:peer_addr:peer_port/port`. Writes are sent to the current peer, so a reply always goes
+/// to the sender of the last datagram read, regardless of what else has been queued since.
+/// Fragmented datagrams are reassembled before they are queued.
+pub struct UdpListener {
+    link: Box<Resource>,
+    ip: Option<Box<Resource>>,
+    ip_flags: &'static str,
+    reassembly: IpReassembly,
+    pending: VecDeque<UdpPending>,
+    peer_addr: Ipv4Addr,
+    peer_port: u16,
+    host_port: u16,
+}
+
+impl UdpListener {
+    /// Bind to `host_port`, taking frames from `link`
+    pub fn new(link: Box<Resource>, ip_flags: &'static str, host_port: u16) -> UdpListener {
+        UdpListener {
+            link: link,
+            ip: None,
+            ip_flags: ip_flags,
+            reassembly: IpReassembly::new(),
+            pending: VecDeque::new(),
+            peer_addr: Ipv4Addr { bytes: [0, 0, 0, 0] },
+            peer_port: 0,
+            host_port: host_port,
+        }
+    }
+
+    /// Decode a frame from the link, returning a datagram for this socket once all of its
+    /// fragments have arrived
+    fn accept(&mut self, bytes: &[u8]) -> Option<UdpPending> {
+        if let Some(packet) = Ipv4::from_bytes(bytes.to_vec()) {
+            if packet.header.proto == 0x11 &&
+               (packet.header.dst.equals(unsafe { IP_ADDR }) || is_broadcast(packet.header.dst)) {
+                if let Some(packet) = self.reassembly.push(packet, Duration::monotonic()) {
+                    if let Some(datagram) = Udp::from_bytes(packet.data) {
+                        if datagram.header.dst.get() == self.host_port {
+                            return Some(UdpPending {
+                                peer_addr: packet.header.src,
+                                peer_port: datagram.header.src.get(),
+                                data: datagram.data,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Resource for UdpListener {
+    fn dup(&self) -> Result<Box<Resource>> {
+        let ip = match self.ip {
+            Some(ref ip) => Some(try!(ip.dup())),
+            None => None,
+        };
+
+        Ok(box UdpListener {
+            link: try!(self.link.dup()),
+            ip: ip,
+            ip_flags: self.ip_flags,
+            reassembly: IpReassembly::new(),
+            pending: self.pending.clone(),
+            peer_addr: self.peer_addr,
+            peer_port: self.peer_port,
+            host_port: self.host_port,
+        })
+    }
+
+    fn path(&self, buf: &mut [u8]) -> Result<usize> {
+        let path_string = if self.peer_port > 0 {
+            format!("udp:{}:{}/{}", self.peer_addr.to_string(), self.peer_port, self.host_port)
+        } else {
+            format!("udp:/{}", self.host_port)
+        };
+        let path = path_string.as_bytes();
+
+        for (b, p) in buf.iter_mut().zip(path.iter()) {
+            *b = *p;
+        }
+
+        Ok(cmp::min(buf.len(), path.len()))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while self.pending.is_empty() {
+            let mut bytes = [0; 8192];
+            let count = try!(self.link.read(&mut bytes));
+            if let Some(datagram) = self.accept(&bytes[.. count]) {
+                self.pending.push_back(datagram);
+            }
+        }
+
+        let datagram = match self.pending.pop_front() {
+            Some(datagram) => datagram,
+            None => return Ok(0),
+        };
+
+        // Replies go to this sender from now on
+        if ! datagram.peer_addr.equals(self.peer_addr) {
+            self.ip = None;
+        }
+        self.peer_addr = datagram.peer_addr;
+        self.peer_port = datagram.peer_port;
+
+        for (b, d) in buf.iter_mut().zip(datagram.data.iter()) {
+            *b = *d;
+        }
+
+        Ok(cmp::min(buf.len(), datagram.data.len()))
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.peer_port == 0 {
+            return Err(Error::new(EDESTADDRREQ));
+        }
+
+        if self.ip.is_none() {
+            self.ip = Some(try!(try!(Url::from_str(&format!("ip:{}/11{}", self.peer_addr.to_string(), self.ip_flags))).open()));
+        }
+
+        let udp = Udp::new(self.host_port, self.peer_addr, self.peer_port, Vec::from(buf));
+        match self.ip {
+            Some(ref mut ip) => ip.write(&udp.to_bytes()).and(Ok(buf.len())),
+            None => Err(Error::new(EDESTADDRREQ)),
+        }
+    }
+
+    fn poll(&mut self) -> usize {
+        while self.link.poll() & POLLIN == POLLIN {
+            let mut bytes = [0; 8192];
+            match self.link.read(&mut bytes) {
+                Ok(count) => if let Some(datagram) = self.accept(&bytes[.. count]) {
+                    self.pending.push_back(datagram);
+                },
+                Err(_) => return POLLERR,
+            }
+        }
+
+        if self.pending.is_empty() {
+            POLLOUT
+        } else {
+            POLLIN | POLLOUT
+        }
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.link.sync()
+    }
+}
+
 /// UDP UdpScheme
 pub struct UdpScheme;
 
@@ -177,48 +375,36 @@ impl KScheme for UdpScheme {
         let mut parts = url.reference().split('/');
         let remote = parts.next().unwrap_or("");
         let path = parts.next().unwrap_or("");
+        // Datagrams too large for a frame fail with EMSGSIZE instead of being fragmented
+        let ip_flags = if parts.next() == Some("df") { "/df" } else { "" };
 
-        // Check host and port vs path
-        if ! path.is_empty() {
-            let mut remote_parts = remote.split(':');
-            let host_port = remote_parts.nth(1).unwrap_or("").parse::<usize>().unwrap_or(0);
-            if host_port > 0 && host_port < 65536 {
-                if let Ok(mut ip) = Url::from_str("ip:/11").unwrap().open() {
-                    let mut bytes = [0; 8192];
-                    if let Ok(count) = ip.read(&mut bytes) {
-                        if let Some(datagram) = Udp::from_bytes(bytes[.. count].to_vec()) {
-                            if datagram.header.dst.get() as usize == host_port {
-                                let mut path = [0; 256];
-                                if let Ok(path_count) = ip.path(&mut path) {
-                                    let ip_reference = unsafe { str::from_utf8_unchecked(&path[.. path_count]) }.split(':').nth(1).unwrap_or("");
-                                    let ip_remote = ip_reference.split('/').next().unwrap_or("");
-                                    let peer_addr = ip_remote.split(':').next().unwrap_or("");
-
-                                    return Ok(Box::new(UdpResource {
-                                        ip: ip,
-                                        data: datagram.data,
-                                        peer_addr: Ipv4Addr::from_string(&peer_addr.to_string()),
-                                        peer_port: datagram.header.src.get(),
-                                        host_port: host_port as u16,
-                                    }));
-                                }
-                            }
-                        }
-                    }
-                }
+        let host_port = if path.is_empty() {
+            (rand() % 32768 + 32768) as u16
+        } else {
+            match path.parse::<u16>() {
+                Ok(port) if port > 0 => port,
+                _ => return Err(Error::new(ENOENT)),
+            }
+        };
+
+        if remote.is_empty() {
+            // Bind to the port, taking datagrams from any peer
+            if ! path.is_empty() {
+                let link = try!(try!(Url::from_str(&format!("ethernet:{}/800", BROADCAST_MAC_ADDR.to_string())))
+                                    .open());
+                return Ok(box UdpListener::new(link, ip_flags, host_port));
             }
         } else {
             let mut remote_parts = remote.split(':');
-            let peer_addr = remote_parts.next().unwrap_or("");
+            let host = remote_parts.next().unwrap_or("");
             let peer_port = remote_parts.next().unwrap_or("").parse::<usize>().unwrap_or(0);
             if peer_port > 0 && peer_port < 65536 {
-                let host_port = (rand() % 32768 + 32768) as u16;
-
-                if let Ok(ip) = Url::from_str(&format!("ip:{}/11", peer_addr)).unwrap().open() {
+                let peer_addr = try!(dns::resolve(host));
+                if let Ok(ip) = Url::from_str(&format!("ip:{}/11{}", peer_addr.to_string(), ip_flags)).unwrap().open() {
                     return Ok(Box::new(UdpResource {
                         ip: ip,
                         data: Vec::new(),
-                        peer_addr: Ipv4Addr::from_string(&peer_addr.to_string()),
+                        peer_addr: peer_addr,
                         peer_port: peer_port as u16,
                         host_port: host_port,
                     }));
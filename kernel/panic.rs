@@ -1,7 +1,15 @@
-use core::{fmt, result};
+use arch::context::CONTEXT_STACK_SIZE;
+
+use core::{fmt, mem, ptr, result, slice, str};
+use core::cmp::Ordering;
+use core::slice::SliceExt;
+use core::str::StrExt;
 
 use common::debug;
 
+/// The most frames a backtrace shows
+const BACKTRACE_FRAMES: usize = 64;
+
 struct DebugStream;
 
 impl fmt::Write for DebugStream {
@@ -12,6 +20,188 @@ impl fmt::Write for DebugStream {
     }
 }
 
+/// An entry of the symbol table, which the build links into the kernel sorted by address
+#[repr(packed)]
+struct Symbol {
+    address: usize,
+    name: *const u8,
+    len: usize,
+}
+
+extern {
+    /// The start of the symbol table
+    static __symbols_start: u8;
+    /// The end of the symbol table
+    static __symbols_end: u8;
+    /// The end of the text (code) segment
+    static __text_end: u8;
+}
+
+/// The symbol table, empty if the kernel was linked without one
+fn symbols() -> &'static [Symbol] {
+    unsafe {
+        let start = & __symbols_start as *const u8 as usize;
+        let end = & __symbols_end as *const u8 as usize;
+        slice::from_raw_parts(start as *const Symbol, (end - start) / mem::size_of::<Symbol>())
+    }
+}
+
+/// Find the symbol an address of code is in, and the offset of the address into it
+pub fn symbol(address: usize) -> Option<(&'static str, usize)> {
+    if address >= unsafe { & __text_end as *const u8 as usize } {
+        return None;
+    }
+
+    let symbols = symbols();
+    let i = match symbols.binary_search_by(|symbol| {
+        let symbol_address = symbol.address;
+        if symbol_address <= address {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        }
+    }) {
+        Ok(i) => i,
+        Err(0) => return None,
+        Err(i) => i - 1,
+    };
+
+    let symbol = &symbols[i];
+    let name = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(symbol.name, symbol.len)) };
+    Some((name, address - symbol.address))
+}
+
+/// Split a part off a mangled name, which is its length in decimal followed by the part
+fn demangle_part(rest: &str) -> Option<(&str, &str)> {
+    let digits = rest.bytes().take_while(|b| *b >= b'0' && *b <= b'9').count();
+    match rest[.. digits].parse::<usize>() {
+        Ok(len) if digits + len <= rest.len() => Some((&rest[digits .. digits + len], &rest[digits + len ..])),
+        _ => None,
+    }
+}
+
+/// Write a mangled symbol name as a path, such as `core::fmt::write`, dropping the hash. Names
+/// that are not mangled, or not in a way that is understood, are written as they are.
+pub fn demangle<W: fmt::Write>(w: &mut W, name: &str) -> fmt::Result {
+    if ! name.starts_with("_ZN") || ! name.ends_with("E") {
+        return w.write_str(name);
+    }
+
+    let parts = &name[3 .. name.len() - 1];
+
+    // Check the whole name first, so nothing is written for a name that cannot be split
+    let mut rest = parts;
+    while ! rest.is_empty() {
+        match demangle_part(rest) {
+            Some((_, next)) => rest = next,
+            None => return w.write_str(name),
+        }
+    }
+
+    let mut rest = parts;
+    let mut first = true;
+    while let Some((part, next)) = demangle_part(rest) {
+        rest = next;
+
+        // The last part is a hash, like h0123456789abcdef
+        let hash = rest.is_empty() && part.len() == 17 && part.starts_with("h") &&
+                   part[1..].bytes().all(|b| (b >= b'0' && b <= b'9') || (b >= b'a' && b <= b'f'));
+        if ! hash {
+            if ! first {
+                try!(w.write_str("::"));
+            }
+            try!(w.write_str(part));
+            first = false;
+        }
+
+        if rest.is_empty() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that a frame pointer is a canonical address
+#[cfg(target_arch = "x86")]
+fn canonical(_address: usize) -> bool {
+    true
+}
+
+/// Check that a frame pointer is a canonical address
+#[cfg(target_arch = "x86_64")]
+fn canonical(address: usize) -> bool {
+    let high = address >> 47;
+    high == 0 || high == 0x1FFFF
+}
+
+/// The frame pointer of the caller
+#[cfg(target_arch = "x86")]
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let bp: usize;
+    unsafe { asm!("mov $0, ebp" : "=r"(bp) : : : "intel", "volatile"); }
+    bp
+}
+
+/// The frame pointer of the caller
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let bp: usize;
+    unsafe { asm!("mov $0, rbp" : "=r"(bp) : : : "intel", "volatile"); }
+    bp
+}
+
+/// Print one frame of a backtrace
+fn frame(i: usize, address: usize, lookup: usize) {
+    let _ = fmt::write(&mut DebugStream, format_args!("    #{:<2} {:08X}  ", i, address));
+    match symbol(lookup) {
+        Some((name, offset)) => {
+            let _ = demangle(&mut DebugStream, name);
+            let _ = fmt::write(&mut DebugStream, format_args!("+{:#X}", offset + address - lookup));
+        },
+        None => debug::d("???"),
+    }
+    debug::dl();
+}
+
+/// Print the call stack, starting at `ip` if it is not zero, by following the frame pointers
+/// saved from `bp` on. The walk stops at a null, misaligned or non-canonical frame pointer, or
+/// one that does not lead further up the same stack.
+pub fn backtrace(ip: usize, mut bp: usize) {
+    debug::d("  Backtrace:");
+    debug::dl();
+
+    let mut i = 0;
+    if ip > 0 {
+        frame(i, ip, ip);
+        i += 1;
+    }
+
+    let word = mem::size_of::<usize>();
+    let limit = bp.saturating_add(CONTEXT_STACK_SIZE);
+    while i < BACKTRACE_FRAMES {
+        if bp == 0 || bp % word != 0 || ! canonical(bp) || bp >= limit - word {
+            break;
+        }
+
+        let (next, ret) = unsafe { (ptr::read(bp as *const usize), ptr::read((bp + word) as *const usize)) };
+        if ret == 0 {
+            break;
+        }
+
+        // The return address is after the call, which may be the last instruction of the caller
+        frame(i, ret, ret - 1);
+        i += 1;
+
+        if next <= bp {
+            break;
+        }
+        bp = next;
+    }
+}
+
 #[lang="panic_fmt"]
 pub extern "C" fn panic_fmt(args: fmt::Arguments, file: &'static str, line: u32) -> ! {
     debug::d(file);
@@ -21,6 +211,8 @@ pub extern "C" fn panic_fmt(args: fmt::Arguments, file: &'static str, line: u32)
     let _ = fmt::write(&mut DebugStream, args);
     debug::dl();
 
+    backtrace(0, frame_pointer());
+
     unsafe {
         loop {
             asm!("sti");
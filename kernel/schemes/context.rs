@@ -1,13 +1,93 @@
 use alloc::boxed::Box;
 
 use collections::string::{String, ToString};
+use collections::vec::Vec;
 
-use arch::context;
+use arch::context::{self, Context};
 
-use fs::{KScheme, Resource, Url, VecResource};
+use core::str;
 
-use system::error::Result;
+use fs::{DirResource, KScheme, Resource, Url, VecResource};
 
+use system::error::{Error, Result, ENOENT};
+
+/// Get the memory used by a context
+pub fn memory(context: &Context) -> usize {
+    let mut memory = 0;
+    if context.kernel_stack > 0 {
+        memory += context::CONTEXT_STACK_GUARD + context::CONTEXT_STACK_SIZE;
+    }
+    if let Some(ref stack) = context.stack {
+        memory += stack.virtual_size;
+    }
+    memory += unsafe { (*context.image.get()).size() };
+    memory += unsafe { (*context.heap.get()).size() };
+    memory += unsafe { (*context.mmap.get()).size() };
+    memory
+}
+
+/// Format a memory size using the largest fitting unit
+fn memory_string(memory: usize) -> String {
+    if memory >= 1024 * 1024 * 1024 {
+        format!("{} GB", memory / 1024 / 1024 / 1024)
+    } else if memory >= 1024 * 1024 {
+        format!("{} MB", memory / 1024 / 1024)
+    } else if memory >= 1024 {
+        format!("{} KB", memory / 1024)
+    } else {
+        format!("{} B", memory)
+    }
+}
+
+/// The status of a context, one field per line
+fn status(context: &Context) -> String {
+    format!("PID: {}\nPPID: {}\nNAME: {}\nMEM: {}\nCWD: {}\nBLOCKED: {}\nSWITCH: {}\nTIME: {}\nSCHED: {}\nPRIO: {}\n",
+            context.pid,
+            context.ppid,
+            context.name,
+            memory_string(memory(context)),
+            unsafe { &*context.cwd.get() },
+            context.blocked,
+            context.switch,
+            context.time,
+            context.sched_time,
+            context.priority)
+}
+
+/// Format a number of time slices as seconds, using the period of the PIT
+pub fn time_string(ticks: usize) -> String {
+    let duration = ::env().pit.lock().duration(ticks as u64);
+    format!("{}.{:09}", duration.secs, duration.nanos)
+}
+
+/// The CPU time used by a context and its waited for children, one field per line
+fn times(context: &Context) -> String {
+    format!("UTIME: {}\nSTIME: {}\nCUTIME: {}\nCSTIME: {}\n",
+            time_string(context.user_time),
+            time_string(context.time - context.user_time),
+            time_string(context.child_user_time),
+            time_string(context.child_time - context.child_user_time))
+}
+
+/// The open files of a context, one file descriptor and path per line
+fn files(context: &Context) -> String {
+    let mut string = format!("{:<6}{}\n", "FD", "PATH");
+    for file in unsafe { (*context.files.get()).iter() } {
+        let mut buf = [0; 4096];
+        let path = match file.resource.path(&mut buf) {
+            Ok(count) => str::from_utf8(&buf[..count]).unwrap_or("?"),
+            Err(_) => "?",
+        };
+        string.push_str(&format!("{:<6}{}\n", file.fd, path));
+    }
+    string
+}
+
+/// A scheme exposing the contexts
+///
+/// `context:` lists all contexts, while `context:pid/status`, `context:pid/files` and
+/// `context:pid/times` describe a single context. The root context only runs when no other context
+/// can, so its times are the idle time.
 pub struct ContextScheme;
 
 impl KScheme for ContextScheme {
@@ -15,12 +95,41 @@ impl KScheme for ContextScheme {
         "context"
     }
 
-    fn open(&mut self, _: Url, _: usize) -> Result<Box<Resource>> {
-        let mut string = format!("{:<6}{:<6}{:<8}{:<8}{:<8}{:<6}{:<6}{:<6}{}\n",
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        let reference = url.reference().trim_matches('/');
+        if ! reference.is_empty() {
+            let mut parts = reference.splitn(2, '/');
+            let pid = try!(parts.next().and_then(|part| part.parse::<usize>().ok()).ok_or(Error::new(ENOENT)));
+            let node = parts.next().unwrap_or("").trim_matches('/');
+
+            let contexts = ::env().contexts.lock();
+            let context = try!(contexts.find(pid).or(Err(Error::new(ENOENT))));
+            if context.exited {
+                return Err(Error::new(ENOENT));
+            }
+
+            return match node {
+                "" => {
+                    let mut entries = Vec::new();
+                    entries.push("files".to_string());
+                    entries.push("status".to_string());
+                    entries.push("times".to_string());
+                    Ok(box DirResource::new(format!("context:{}/", pid), entries))
+                },
+                "files" => Ok(box VecResource::new(format!("context:{}/files", pid), files(context).into_bytes())),
+                "status" => Ok(box VecResource::new(format!("context:{}/status", pid), status(context).into_bytes())),
+                "times" => Ok(box VecResource::new(format!("context:{}/times", pid), times(context).into_bytes())),
+                _ => Err(Error::new(ENOENT))
+            };
+        }
+
+        let mut string = format!("{:<6}{:<6}{:<8}{:<8}{:<8}{:<6}{:<8}{:<6}{:<6}{:<6}{}\n",
                                  "PID",
                                  "PPID",
                                  "SWITCH",
                                  "TIME",
+                                 "SCHED",
+                                 "PRIO",
                                  "MEM",
                                  "FDS",
                                  "FLG",
@@ -29,26 +138,7 @@ impl KScheme for ContextScheme {
         {
             let contexts = ::env().contexts.lock();
             for context in contexts.iter() {
-                let mut memory = 0;
-                if context.kernel_stack > 0 {
-                    memory += context::CONTEXT_STACK_SIZE;
-                }
-                if let Some(ref stack) = context.stack {
-                    memory += stack.virtual_size;
-                }
-                memory += unsafe { (*context.image.get()).size() };
-                memory += unsafe { (*context.heap.get()).size() };
-                memory += unsafe { (*context.mmap.get()).size() };
-
-                let memory_string = if memory >= 1024 * 1024 * 1024 {
-                    format!("{} GB", memory / 1024 / 1024 / 1024)
-                } else if memory >= 1024 * 1024 {
-                    format!("{} MB", memory / 1024 / 1024)
-                } else if memory >= 1024 {
-                    format!("{} KB", memory / 1024)
-                } else {
-                    format!("{} B", memory)
-                };
+                let memory_string = memory_string(memory(context));
 
                 let mut flags_string = String::new();
                 if context.stack.is_some() {
@@ -72,11 +162,13 @@ impl KScheme for ContextScheme {
                     flags_string.push('T');
                 }
 
-                string.push_str(&format!("{:<6}{:<6}{:<8}{:<8}{:<8}{:<6}{:<6}{:<6}{}\n",
+                string.push_str(&format!("{:<6}{:<6}{:<8}{:<8}{:<8}{:<6}{:<8}{:<6}{:<6}{:<6}{}\n",
                                    context.pid,
                                    context.ppid,
                                    context.switch,
                                    context.time,
+                                   context.sched_time,
+                                   context.priority,
                                    memory_string,
                                    unsafe { (*context.files.get()).len() },
                                    flags_string,
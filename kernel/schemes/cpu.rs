@@ -0,0 +1,56 @@
+use alloc::boxed::Box;
+
+use arch::context::CONTEXT_ROOT_PID;
+
+use collections::string::ToString;
+
+use common::time::Duration;
+
+use fs::{KScheme, Resource, Url, VecResource};
+
+use system::error::Result;
+
+use super::context::time_string;
+
+/// A scheme reporting how the CPU time was spent
+///
+/// The root context only runs when no other context can, so its time is reported as idle. The
+/// time of other contexts includes that of their children which were waited for.
+pub struct CpuScheme;
+
+impl KScheme for CpuScheme {
+    fn scheme(&self) -> &str {
+        "cpu"
+    }
+
+    fn open(&mut self, _: Url, _: usize) -> Result<Box<Resource>> {
+        let mut idle = 0;
+        let mut busy = 0;
+        let mut user = 0;
+        let mut switches = 0;
+        {
+            let contexts = ::env().contexts.lock();
+            for context in contexts.iter() {
+                if context.pid == CONTEXT_ROOT_PID {
+                    idle += context.time;
+                } else {
+                    busy += context.time + context.child_time;
+                    user += context.user_time + context.child_user_time;
+                }
+                switches += context.switch;
+            }
+        }
+
+        let uptime = Duration::monotonic();
+        let string = format!("Uptime: {}.{:09}\nBusy: {}\nIdle: {}\nUser: {}\nSystem: {}\nSwitches: {}\n",
+                             uptime.secs,
+                             uptime.nanos,
+                             time_string(busy),
+                             time_string(idle),
+                             time_string(user),
+                             time_string(busy - user),
+                             switches);
+
+        Ok(box VecResource::new("cpu:".to_string(), string.into_bytes()))
+    }
+}
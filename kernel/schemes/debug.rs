@@ -1,13 +1,14 @@
 use alloc::boxed::Box;
 
 use collections::borrow::ToOwned;
-use collections::string::String;
+use collections::string::{String, ToString};
 
-use core::cmp;
+use core::{cmp, str};
 
-use fs::{KScheme, Resource, Url};
+use fs::{KScheme, Resource, Url, VecResource};
 
-use system::error::Result;
+use system::error::{Error, Result, EINTR, EINVAL, ENOENT};
+use system::syscall::{POLLIN, POLLOUT};
 
 /// A debug resource
 pub struct DebugResource {
@@ -35,7 +36,19 @@ impl Resource for DebugResource {
 
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
         if self.command.is_empty() {
-            self.command = ::env().console.lock().commands.receive();
+            let mut console = ::env().console.lock();
+
+            // The context reading the console is in the foreground
+            if let Ok(current) = ::env().contexts.lock().current() {
+                console.foreground_pid = current.pid;
+            }
+
+            self.command = console.commands.receive();
+
+            // The console sends an empty command when the line is interrupted with Ctrl+C
+            if self.command.is_empty() {
+                return Err(Error::new(EINTR));
+            }
         }
 
         let mut i = 0;
@@ -52,6 +65,14 @@ impl Resource for DebugResource {
         Ok(buf.len())
     }
 
+    fn poll(&mut self) -> usize {
+        if self.command.is_empty() && ::env().console.lock().commands.inner.lock().is_empty() {
+            POLLOUT
+        } else {
+            POLLIN | POLLOUT
+        }
+    }
+
     fn sync(&mut self) -> Result<()> {
         let mut console = ::env().console.lock();
         console.redraw = true;
@@ -60,6 +81,121 @@ impl Resource for DebugResource {
     }
 }
 
+/// The line discipline settings of the console
+///
+/// Reading returns the current settings, such as `canon echo noserial`. Writing a list of `raw`,
+/// `canon`, `echo`, `noecho`, `serial` and `noserial` changes them.
+pub struct DebugModeResource {
+    seek: usize,
+}
+
+impl Resource for DebugModeResource {
+    fn dup(&self) -> Result<Box<Resource>> {
+        Ok(box DebugModeResource {
+            seek: self.seek,
+        })
+    }
+
+    fn path(&self, buf: &mut [u8]) -> Result<usize> {
+        let path = b"debug:mode";
+
+        for (b, p) in buf.iter_mut().zip(path.iter()) {
+            *b = *p;
+        }
+
+        Ok(cmp::min(buf.len(), path.len()))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mode = {
+            let console = ::env().console.lock();
+            format!("{} {} {}\n",
+                    if console.raw_mode { "raw" } else { "canon" },
+                    if console.echo { "echo" } else { "noecho" },
+                    if console.serial { "serial" } else { "noserial" })
+        };
+
+        let mut i = 0;
+        while i < buf.len() && self.seek < mode.len() {
+            buf[i] = mode.as_bytes()[self.seek];
+            self.seek += 1;
+            i += 1;
+        }
+
+        Ok(i)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let words = try!(str::from_utf8(buf).or(Err(Error::new(EINVAL))));
+
+        let mut console = ::env().console.lock();
+        let mut raw_mode = console.raw_mode;
+        let mut echo = console.echo;
+        let mut serial = console.serial;
+        for word in words.split_whitespace() {
+            match word {
+                "raw" => raw_mode = true,
+                "canon" => raw_mode = false,
+                "echo" => echo = true,
+                "noecho" => echo = false,
+                "serial" => serial = true,
+                "noserial" => serial = false,
+                _ => return Err(Error::new(EINVAL)),
+            }
+        }
+        console.raw_mode = raw_mode;
+        console.echo = echo;
+        console.serial = serial;
+
+        Ok(buf.len())
+    }
+}
+
+/// List the locks of the environment, with the context holding each and how often it was locked
+/// while held. The counts are only kept by debug builds
+#[cfg(debug_assertions)]
+fn lock_stats() -> Option<String> {
+    let env = ::env();
+    let mut string = format!("{:<16}{:>8}{:>12}\n", "LOCK", "HOLDER", "CONTENDED");
+
+    macro_rules! lock_stat {
+        ($name:expr, $intex:expr) => {
+            string.push_str(&format!("{:<16}{:>8}{:>12}\n", $name,
+                                     $intex.holder().map_or("-".to_string(), |pid| format!("{}", pid)),
+                                     $intex.contended()));
+        }
+    }
+
+    lock_stat!("arp", env.arp);
+    lock_stat!("clock_monotonic", env.clock_monotonic);
+    lock_stat!("clock_realtime", env.clock_realtime);
+    lock_stat!("console", env.console);
+    lock_stat!("contexts", env.contexts);
+    lock_stat!("disks", env.disks);
+    lock_stat!("dns", env.dns);
+    lock_stat!("events", env.events.inner);
+    lock_stat!("interfaces", env.interfaces);
+    lock_stat!("interrupts", env.interrupts);
+    lock_stat!("irq_names", env.irq_names);
+    lock_stat!("irq_pending", env.irq_pending);
+    lock_stat!("log_staging", env.log_staging);
+    lock_stat!("logs", env.logs);
+    lock_stat!("pit", env.pit);
+    lock_stat!("schemes", env.schemes);
+    lock_stat!("shared_memory", env.shared_memory);
+
+    Some(string)
+}
+
+#[cfg(not(debug_assertions))]
+fn lock_stats() -> Option<String> {
+    None
+}
+
+/// The debug scheme
+///
+/// `debug:` reads lines from the console and writes to it, and `debug:mode` holds the settings of
+/// its line discipline. In debug builds, `debug:locks` lists the locks of the environment.
 pub struct DebugScheme;
 
 impl DebugScheme {
@@ -73,7 +209,20 @@ impl KScheme for DebugScheme {
         "debug"
     }
 
-    fn open(&mut self, _: Url, _: usize) -> Result<Box<Resource>> {
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        if url.reference().trim_matches('/') == "mode" {
+            return Ok(box DebugModeResource {
+                seek: 0,
+            });
+        }
+
+        if url.reference().trim_matches('/') == "locks" {
+            return match lock_stats() {
+                Some(string) => Ok(box VecResource::new("debug:locks".to_string(), string.into_bytes())),
+                None => Err(Error::new(ENOENT)),
+            };
+        }
+
         let console = ::env().console.lock();
         if let Some(ref display) = console.display {
             Ok(box DebugResource {
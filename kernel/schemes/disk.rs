@@ -6,12 +6,17 @@ use collections::{String, Vec};
 
 use core::cmp;
 use disk::Disk;
+use disk::cache::BlockCache;
+use disk::partition::Partition;
 use fs::{KScheme, Resource, ResourceSeek, Url, VecResource};
 use sync::Intex;
 
-use syscall::{MODE_DIR, MODE_FILE, Stat};
+use syscall::{MODE_DIR, MODE_FILE, Stat, StatVfs};
 
-use system::error::{Error, Result, ENOENT};
+use system::error::{Error, Result, EINVAL, ENOENT};
+
+/// The default number of blocks cached for each disk
+pub const DISK_CACHE_BLOCKS: usize = 4096;
 
 /// A disk resource
 pub struct DiskResource {
@@ -50,6 +55,22 @@ impl Resource for DiskResource {
         Ok(count)
     }
 
+    /// Disks are accessed in whole blocks, so the offset has to be at the start of one
+    fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if offset % 512 != 0 {
+            return Err(Error::new(EINVAL));
+        }
+        self.disk.lock().read(offset as u64/512, buf)
+    }
+
+    /// Like reading, writing starts at a block
+    fn write_at(&mut self, offset: usize, buf: &[u8]) -> Result<usize> {
+        if offset % 512 != 0 {
+            return Err(Error::new(EINVAL));
+        }
+        self.disk.lock().write(offset as u64/512, buf)
+    }
+
     fn seek(&mut self, pos: ResourceSeek) -> Result<usize> {
         let size = self.disk.lock().size();
         match pos {
@@ -63,6 +84,19 @@ impl Resource for DiskResource {
     fn sync(&mut self) -> Result<()> {
         Ok(())
     }
+
+    /// A disk can not change its size, so truncating it only drops the cached blocks from `len`
+    /// on. A filesystem truncating or removing a file uses this to keep the blocks it freed from
+    /// being served from the cache
+    fn truncate(&mut self, len: usize) -> Result<()> {
+        let mut disk = self.disk.lock();
+        let blocks = (disk.size() + 511) / 512;
+        let block = len as u64 / 512;
+        if block < blocks {
+            disk.invalidate(block, blocks - block);
+        }
+        Ok(())
+    }
 }
 
 impl Drop for DiskResource {
@@ -74,26 +108,75 @@ impl Drop for DiskResource {
 /// A disk scheme
 pub struct DiskScheme {
     disks: Vec<Arc<Intex<Box<Disk>>>>,
+    /// Partitions of the disks, named by disk and partition number, like "0p1"
+    partitions: Vec<(String, Arc<Intex<Box<Disk>>>)>,
 }
 
 impl DiskScheme {
-    /// Create a new disk scheme from an array of Disks
-    pub fn new(mut disks: Vec<Box<Disk>>) -> Box<Self> {
+    /// Create a new disk scheme from an array of Disks, caching up to `cache_blocks` blocks of
+    /// each disk
+    pub fn new(mut disks: Vec<Box<Disk>>, cache_blocks: usize) -> Box<Self> {
         let mut scheme = box DiskScheme {
-            disks: Vec::new()
+            disks: Vec::new(),
+            partitions: Vec::new(),
         };
 
         for disk in disks.drain(..) {
-            scheme.disks.push(Arc::new(Intex::new(disk)));
+            let disk: Box<Disk> = box BlockCache::new(disk, cache_blocks);
+            let disk = Arc::new(Intex::new(disk));
+            for partition in Partition::find(&disk) {
+                let name = format!("{}p{}", scheme.disks.len(), partition.number);
+                let partition: Box<Disk> = box partition;
+                scheme.partitions.push((name, Arc::new(Intex::new(partition))));
+            }
+            scheme.disks.push(disk);
         }
 
         scheme
     }
+
+    /// Find a disk or partition by name
+    fn find(&self, path: &str) -> Option<&Arc<Intex<Box<Disk>>>> {
+        if let Ok(number) = path.parse::<usize>() {
+            self.disks.get(number)
+        } else {
+            self.partitions.iter().find(|partition| partition.0 == path).map(|partition| &partition.1)
+        }
+    }
+
+    /// The cache hits and misses of each disk, one disk per line
+    fn cache_stats(&self) -> String {
+        let mut stats = String::new();
+        for (i, disk) in self.disks.iter().enumerate() {
+            if let Some((hits, misses)) = disk.lock().cache_stats() {
+                stats.push_str(&format!("{}: {} hits, {} misses\n", i, hits, misses));
+            }
+        }
+        stats
+    }
+
+    /// List the disks and partitions
+    fn list(&self) -> String {
+        let mut list = String::new();
+        for i in 0..self.disks.len() {
+            if ! list.is_empty() {
+                list.push('\n');
+            }
+            list.push_str(&format!("{}", i));
+        }
+        for partition in self.partitions.iter() {
+            list.push('\n');
+            list.push_str(&partition.0);
+        }
+        list
+    }
 }
 
 impl KScheme for DiskScheme {
-    fn on_irq(&mut self, _irq: u8) {
-        //TODO
+    fn on_irq(&mut self, irq: u8) {
+        for disk in self.disks.iter() {
+            disk.lock().on_irq(irq);
+        }
     }
 
     fn scheme(&self) -> &str {
@@ -104,25 +187,15 @@ impl KScheme for DiskScheme {
         let path = url.reference().trim_matches('/');
 
         if path.is_empty() {
-            let mut list = String::new();
-            for i in 0..self.disks.len() {
-                if ! list.is_empty() {
-                    list.push('\n');
-                }
-                list.push_str(&format!("{}", i));
-            }
-
-            return Ok(box VecResource::new("disk:/".to_owned(), list.into_bytes()));
-        } else {
-            if let Ok(number) = path.parse::<usize>() {
-                if let Some(disk) = self.disks.get(number) {
-                    return Ok(box DiskResource {
-                        path: format!("disk:/{}", number),
-                        disk: disk.clone(),
-                        seek: 0
-                    });
-                }
-            }
+            return Ok(box VecResource::new("disk:/".to_owned(), self.list().into_bytes()));
+        } else if path == "cache" {
+            return Ok(box VecResource::new("disk:/cache".to_owned(), self.cache_stats().into_bytes()));
+        } else if let Some(disk) = self.find(path) {
+            return Ok(box DiskResource {
+                path: format!("disk:/{}", path),
+                disk: disk.clone(),
+                seek: 0
+            });
         }
 
         Err(Error::new(ENOENT))
@@ -132,25 +205,51 @@ impl KScheme for DiskScheme {
         let path = url.reference().trim_matches('/');
 
         if path.is_empty() {
-            let mut list = String::new();
-            for i in 0..self.disks.len() {
-                if ! list.is_empty() {
-                    list.push('\n');
-                }
-                list.push_str(&format!("{}", i));
+            stat.st_mode = MODE_DIR | 0o555;
+            stat.st_size = self.list().len() as u64;
+            return Ok(());
+        } else if let Some(disk) = self.find(path) {
+            stat.st_mode = MODE_FILE | 0o644;
+            stat.st_size = disk.lock().size();
+            return Ok(());
+        }
+
+        Err(Error::new(ENOENT))
+    }
+
+    /// Disks are not filesystems, so all of their blocks are reported as used. The root reports
+    /// the total of all disks, not counting their partitions twice
+    fn statvfs(&mut self, url: Url, stat: &mut StatVfs) -> Result<()> {
+        let path = url.reference().trim_matches('/');
+
+        let mut size = 0;
+        if path.is_empty() {
+            for disk in self.disks.iter() {
+                size += disk.lock().size();
             }
+        } else if let Some(disk) = self.find(path) {
+            size = disk.lock().size();
+        } else {
+            return Err(Error::new(ENOENT));
+        }
+
+        stat.f_bsize = 512;
+        stat.f_blocks = (size + 511) / 512;
+        stat.f_bfree = 0;
+        stat.f_files = self.disks.len() as u64;
+        stat.f_ffree = 0;
+        Ok(())
+    }
+
+    /// Disks can not be removed, so unlinking one only drops all of its cached blocks
+    fn unlink(&mut self, url: Url) -> Result<()> {
+        let path = url.reference().trim_matches('/');
 
-            stat.st_mode = MODE_DIR;
-            stat.st_size = list.len() as u64;
+        if let Some(disk) = self.find(path) {
+            let mut disk = disk.lock();
+            let blocks = (disk.size() + 511) / 512;
+            disk.invalidate(0, blocks);
             return Ok(());
-        } else {
-            if let Ok(number) = path.parse::<usize>() {
-                if let Some(disk) = self.disks.get(number) {
-                    stat.st_mode = MODE_FILE;
-                    stat.st_size = disk.lock().size();
-                    return Ok(());
-                }
-            }
         }
 
         Err(Error::new(ENOENT))
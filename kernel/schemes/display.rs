@@ -41,34 +41,29 @@ impl Resource for DisplayResource {
 
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
         if buf.len() >= size_of::<Event>() {
-            let event = ::env().events.receive();
-            unsafe { ptr::write(buf.as_mut_ptr().offset(0isize) as *mut Event, event) };
-            let mut i = size_of::<Event>();
-
-            while i + size_of::<Event>() <= buf.len() {
-                if let Some(event) = ::env().events.inner.lock().pop_front() {
-                    unsafe { ptr::write(buf.as_mut_ptr().offset(i as isize) as *mut Event, event) };
-                    i += size_of::<Event>();
-                } else {
-                    break;
-                }
+            let events = ::env().events.receive_max(buf.len() / size_of::<Event>());
+            let count = events.len() * size_of::<Event>();
+            for (i, event) in events.into_iter().enumerate() {
+                unsafe { ptr::write(buf.as_mut_ptr().offset((i * size_of::<Event>()) as isize) as *mut Event, event) };
             }
 
-            Ok(i)
+            Ok(count)
         } else {
             Err(Error::new(EINVAL))
         }
     }
 
+    /// Write pixels to the back buffer, they reach the screen on `sync` or on the next timer ticks
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        let console = ::env().console.lock();
-        if let Some(ref display) = console.display {
+        let mut console = ::env().console.lock();
+        if let Some(ref mut display) = console.display {
             let size = cmp::max(0, cmp::min(display.size as isize - self.seek as isize, (buf.len()/4) as isize)) as usize;
 
             if size > 0 {
                 unsafe {
-                    fast_copy(display.onscreen.offset(self.seek as isize), buf.as_ptr() as *const u32, size);
+                    fast_copy(display.backbuffer.offset(self.seek as isize), buf.as_ptr() as *const u32, size);
                 }
+                display.damage_range(self.seek, size);
             }
 
             Ok(size)
@@ -92,8 +87,15 @@ impl Resource for DisplayResource {
         }
     }
 
+    /// Copy what was written since the last sync to the screen
     fn sync(&mut self) -> Result<()> {
-        Ok(())
+        let mut console = ::env().console.lock();
+        if let Some(ref mut display) = console.display {
+            display.sync();
+            Ok(())
+        } else {
+            Err(Error::new(EBADF))
+        }
     }
 }
 
@@ -1,25 +1,49 @@
 use alloc::boxed::Box;
 
-use collections::{BTreeMap, String};
+use collections::{BTreeMap, Vec};
+use collections::string::{String, ToString};
 
-use fs::{KScheme, Resource, Url, VecResource};
+use common::time::Duration;
 
-use system::error::{Error, Result, ENOENT};
+use fs::{DirResource, KScheme, Resource, Url, VecResource};
+
+use system::error::{Error, Result, EACCES, EEXIST, EINVAL, EISDIR, ENOENT};
+use system::syscall::{MODE_DIR, MODE_FILE, MODE_SYMLINK, O_ACCMODE, O_RDONLY, Stat, StatVfs, TimeSpec};
 
 #[path="../../build/initfs.gen"]
 pub mod gen;
 
+/// The timestamps of an entry
+#[derive(Copy, Clone, Default)]
+pub struct InitFsTimes {
+    pub atime: TimeSpec,
+    pub mtime: TimeSpec,
+    pub ctime: TimeSpec,
+}
+
 /// A memory scheme
 pub struct InitFsScheme {
-    pub files: BTreeMap<&'static str, &'static [u8]>
+    pub files: BTreeMap<String, &'static [u8]>,
+    /// Symbolic links, with the path they point to as their content
+    pub links: BTreeMap<String, String>,
+    /// Timestamps that have been set. The contents are read only, but like the links, the
+    /// timestamps are kept in memory. Entries without timestamps report the epoch
+    pub times: BTreeMap<String, InitFsTimes>,
 }
 
 impl InitFsScheme {
     pub fn new() -> Box<InitFsScheme> {
         Box::new(InitFsScheme {
-            files: gen::gen()
+            files: gen::gen().into_iter().map(|(name, data)| (name.to_string(), data)).collect(),
+            links: BTreeMap::new(),
+            times: BTreeMap::new(),
         })
     }
+
+    /// Folders are not stored, they are implied by the paths of the files
+    fn is_folder(&self, reference: &str) -> bool {
+        self.files.keys().any(|name| name.starts_with(reference) && name[reference.len()..].starts_with('/'))
+    }
 }
 
 impl KScheme for InitFsScheme {
@@ -27,25 +51,193 @@ impl KScheme for InitFsScheme {
         "initfs"
     }
 
-    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+    fn open(&mut self, url: Url, flags: usize) -> Result<Box<Resource>> {
         let reference = url.reference().trim_matches('/');
         if reference.is_empty() {
-            let mut list = String::new();
+            if flags & O_ACCMODE != O_RDONLY {
+                return Err(Error::new(EISDIR));
+            }
 
-            for file in self.files.iter() {
-                if ! list.is_empty() {
-                    list.push('\n');
+            let mut entries: Vec<String> = self.files.keys().cloned().collect();
+            entries.extend(self.links.keys().cloned());
+            entries.sort();
+
+            Ok(box DirResource::new(url.to_string(), entries))
+        } else if self.is_folder(reference) {
+            if flags & O_ACCMODE != O_RDONLY {
+                return Err(Error::new(EISDIR));
+            }
+
+            let prefix = reference.to_string() + "/";
+            let mut entries: Vec<String> = Vec::new();
+            for name in self.files.keys().chain(self.links.keys()) {
+                if name.starts_with(&prefix) {
+                    let entry = match name[prefix.len()..].find('/') {
+                        Some(end) => name[prefix.len()..prefix.len() + end + 1].to_string(),
+                        None => name[prefix.len()..].to_string(),
+                    };
+                    if ! entries.contains(&entry) {
+                        entries.push(entry);
+                    }
                 }
-                list.push_str(file.0);
             }
+            entries.sort();
 
-            Ok(box VecResource::new(url.to_string(), list.into_bytes()))
-        }else {
-            if let Some(data) = self.files.get(reference) {
-                Ok(box VecResource::new(url.to_string(), data.to_vec()))
-            } else {
-                Err(Error::new(ENOENT))
+            Ok(box DirResource::new(url.to_string(), entries))
+        } else if flags & O_ACCMODE != O_RDONLY {
+            // The init filesystem is read only
+            Err(Error::new(EACCES))
+        } else if let Some(data) = self.files.get(reference) {
+            Ok(box VecResource::new(url.to_string(), data.to_vec()))
+        } else if let Some(target) = self.links.get(reference) {
+            Ok(box VecResource::new(url.to_string(), target.clone().into_bytes()))
+        } else {
+            Err(Error::new(ENOENT))
+        }
+    }
+
+    fn readlink(&mut self, url: Url, buf: &mut [u8]) -> Result<usize> {
+        let reference = url.reference().trim_matches('/');
+        if let Some(target) = self.links.get(reference) {
+            let mut i = 0;
+            for (b, t) in buf.iter_mut().zip(target.as_bytes().iter()) {
+                *b = *t;
+                i += 1;
             }
+            Ok(i)
+        } else if reference.is_empty() || self.files.contains_key(reference) {
+            Err(Error::new(EINVAL))
+        } else {
+            Err(Error::new(ENOENT))
+        }
+    }
+
+    fn rename(&mut self, old: Url, new: Url) -> Result<()> {
+        let new_reference = new.reference().trim_matches('/');
+        if new_reference.is_empty() {
+            return Err(Error::new(EINVAL));
+        }
+
+        let old_reference = old.reference().trim_matches('/');
+        if let Some(times) = self.times.remove(old_reference) {
+            self.times.insert(new_reference.to_string(), times);
+        }
+
+        if let Some(data) = self.files.remove(old_reference) {
+            self.links.remove(new_reference);
+            self.files.insert(new_reference.to_string(), data);
+            Ok(())
+        } else if let Some(target) = self.links.remove(old_reference) {
+            self.files.remove(new_reference);
+            self.links.insert(new_reference.to_string(), target);
+            Ok(())
+        } else {
+            Err(Error::new(ENOENT))
+        }
+    }
+
+    fn set_times(&mut self, url: Url, atime: TimeSpec, mtime: TimeSpec) -> Result<()> {
+        let reference = url.reference().trim_matches('/');
+        if reference.is_empty() || self.files.contains_key(reference) || self.links.contains_key(reference) ||
+           self.is_folder(reference) {
+            let now = Duration::realtime();
+            self.times.insert(reference.to_string(), InitFsTimes {
+                atime: atime,
+                mtime: mtime,
+                ctime: TimeSpec {
+                    tv_sec: now.secs,
+                    tv_nsec: now.nanos,
+                },
+            });
+            Ok(())
+        } else {
+            Err(Error::new(ENOENT))
+        }
+    }
+
+    fn stat(&mut self, url: Url, stat: &mut Stat) -> Result<()> {
+        let reference = url.reference().trim_matches('/');
+
+        let times = self.times.get(reference).map_or(InitFsTimes::default(), |times| *times);
+        stat.st_atime = times.atime;
+        stat.st_mtime = times.mtime;
+        stat.st_ctime = times.ctime;
+
+        if reference.is_empty() {
+            stat.st_mode = MODE_DIR | 0o555;
+            stat.st_size = 0;
+            Ok(())
+        } else if let Some(data) = self.files.get(reference) {
+            // The files are read only, and only programs are executable
+            stat.st_mode = if reference.starts_with("bin/") {
+                MODE_FILE | 0o555
+            } else {
+                MODE_FILE | 0o444
+            };
+            stat.st_size = data.len() as u64;
+            Ok(())
+        } else if let Some(target) = self.links.get(reference) {
+            stat.st_mode = MODE_SYMLINK | 0o777;
+            stat.st_size = target.len() as u64;
+            Ok(())
+        } else if self.is_folder(reference) {
+            stat.st_mode = MODE_DIR | 0o555;
+            stat.st_size = 0;
+            Ok(())
+        } else {
+            Err(Error::new(ENOENT))
+        }
+    }
+
+    /// The init filesystem is read only, so it has no free space. Links are stored in memory, and
+    /// only count as files
+    fn statvfs(&mut self, _: Url, stat: &mut StatVfs) -> Result<()> {
+        let mut size = 0;
+        for data in self.files.values() {
+            size += data.len() as u64;
+        }
+
+        stat.f_bsize = 512;
+        stat.f_blocks = (size + 511) / 512;
+        stat.f_bfree = 0;
+        stat.f_files = (self.files.len() + self.links.len()) as u64;
+        stat.f_ffree = 0;
+        Ok(())
+    }
+
+    fn symlink(&mut self, target: &str, path: Url) -> Result<()> {
+        let reference = path.reference().trim_matches('/');
+        if reference.is_empty() || self.files.contains_key(reference) || self.links.contains_key(reference) {
+            Err(Error::new(EEXIST))
+        } else {
+            self.links.insert(reference.to_string(), target.to_string());
+            Ok(())
+        }
+    }
+
+    /// Links have already been followed. Files are read only, so this fails with EACCES like
+    /// opening them for writing or unlinking them does
+    fn truncate(&mut self, url: Url, _: usize) -> Result<()> {
+        let reference = url.reference().trim_matches('/');
+        if reference.is_empty() || self.is_folder(reference) {
+            Err(Error::new(EISDIR))
+        } else if self.files.contains_key(reference) || self.links.contains_key(reference) {
+            Err(Error::new(EACCES))
+        } else {
+            Err(Error::new(ENOENT))
+        }
+    }
+
+    fn unlink(&mut self, url: Url) -> Result<()> {
+        // Only links can be removed, the files are read only
+        let reference = url.reference().trim_matches('/');
+        if self.links.remove(reference).is_some() {
+            self.times.remove(reference);
+            Ok(())
+        } else if self.files.contains_key(reference) {
+            Err(Error::new(EACCES))
+        } else {
+            Err(Error::new(ENOENT))
         }
     }
 }
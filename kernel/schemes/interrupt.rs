@@ -2,11 +2,28 @@ use alloc::boxed::Box;
 
 use collections::string::ToString;
 
+use common::time::{Duration, NANOS_PER_SEC};
+
 use fs::{KScheme, Resource, Url, VecResource};
 
 use system::error::Result;
 
-pub struct InterruptScheme;
+/// A scheme reporting how often each interrupt has fired, with the rate since it was last read
+pub struct InterruptScheme {
+    /// The counts at the last read
+    last: [u64; 256],
+    /// The monotonic time of the last read
+    last_time: Duration,
+}
+
+impl InterruptScheme {
+    pub fn new() -> Box<Self> {
+        box InterruptScheme {
+            last: [0; 256],
+            last_time: Duration::new(0, 0),
+        }
+    }
+}
 
 static IRQ_NAME: [&'static str; 16] = [
     "Programmable Interval Timer",
@@ -33,16 +50,30 @@ impl KScheme for InterruptScheme {
     }
 
     fn open(&mut self, _: Url, _: usize) -> Result<Box<Resource>> {
-        let mut string = format!("{:<6}{:<16}{}\n", "INT", "COUNT", "DESCRIPTION");
+        let mut string = format!("{:<6}{:<16}{:<10}{}\n", "INT", "COUNT", "RATE", "DESCRIPTION");
+
+        let now = Duration::monotonic();
+        let elapsed = now - self.last_time;
+        let elapsed_nanos = elapsed.secs as u64 * NANOS_PER_SEC as u64 + elapsed.nanos as u64;
+        self.last_time = now;
 
         {
             let interrupts = ::env().interrupts.lock();
+            let irq_names = ::env().irq_names.lock();
             for interrupt in 0..interrupts.len() {
                 let count = interrupts[interrupt];
 
+                // Interrupts per second since the last read
+                let rate = if elapsed_nanos > 0 {
+                    (count - self.last[interrupt]) * NANOS_PER_SEC as u64 / elapsed_nanos
+                } else {
+                    0
+                };
+                self.last[interrupt] = count;
+
                 if count > 0 {
-                    let description = match interrupt {
-                        i @ 0x20 ... 0x30 => IRQ_NAME[i - 0x20],
+                    let mut description = match interrupt {
+                        i @ 0x20 ... 0x2F => IRQ_NAME[i - 0x20],
                         0x80 => "System Call",
                         0x0 => "Divide by zero exception",
                         0x1 => "Debug exception",
@@ -65,9 +96,18 @@ impl KScheme for InterruptScheme {
                         0x14 => "Virtualization exception",
                         0x1E => "Security exception",
                         _ => "Unknown Interrupt",
-                    };
+                    }.to_string();
+
+                    // PCI IRQs are named by the drivers handling them
+                    if interrupt >= 0x20 && interrupt < 0x30 {
+                        if let Some(names) = irq_names.get(&((interrupt - 0x20) as u8)) {
+                            description.push_str(" (");
+                            description.push_str(&names.join(", "));
+                            description.push(')');
+                        }
+                    }
 
-                    string.push_str(&format!("{:<6X}{:<16}{}\n", interrupt, count, description));
+                    string.push_str(&format!("{:<6X}{:<16}{:<10}{}\n", interrupt, count, rate, description));
                 }
             }
         }
@@ -1,11 +1,15 @@
 use fs::{KScheme, Resource, Url};
 use fs::resource::ResourceSeek;
-use collections::string::String;
+use collections::vec::Vec;
 use alloc::boxed::Box;
-use system::error::Result;
-use logging::LogLevel;
+use core::{cmp, str};
+use system::error::{Error, Result, EINVAL, ENOENT};
+use logging::{klog_flush, LogLevel};
 
 /// The kernel log scheme.
+///
+/// `klog:` reads every entry, `klog:error`, `klog:debug` and so on read the entries at least as
+/// important as the level, and `klog:level` holds the least important level that is logged.
 pub struct KlogScheme;
 
 impl KScheme for KlogScheme {
@@ -14,9 +18,25 @@ impl KScheme for KlogScheme {
         "klog"
     }
 
-    /// Returns a resource. The `url` and `flags` arguments are currently unused.
-    fn open(&mut self, _: Url, _: usize) -> Result<Box<Resource>> {
+    /// Returns a resource for the path of `url`. The `flags` argument is currently unused.
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        let path = url.reference().trim_matches('/');
+        if path == "level" {
+            return Ok(Box::new(KlogLevelResource {
+                seek: 0,
+            }));
+        }
+
+        let level = if path.is_empty() {
+            LogLevel::Debug
+        } else {
+            try!(LogLevel::from_name(path).ok_or(Error::new(ENOENT)))
+        };
+
         Ok(Box::new(KlogResource {
+            level: level,
+            seq: 0,
+            pending: Vec::new(),
             pos: 0,
         }))
     }
@@ -31,65 +51,94 @@ impl KScheme for KlogScheme {
 
 /// The kernel log resource.
 pub struct KlogResource {
+    /// The least important level read
+    level: LogLevel,
+    /// The number of the next entry to read
+    seq: u64,
+    /// Lines formatted but not read yet
+    pending: Vec<u8>,
+    /// The number of bytes read
     pos: usize,
 }
 
-impl KlogResource {
-    fn get_log_str(&self) -> String {
-        let ref mut logs = *::env().logs.lock();
-        let mut string = String::new();
-        for &mut (ref level, ref message) in logs {
-            let prefix: &str = match *level {
-                LogLevel::Debug    => "DEBUG ",
-                LogLevel::Info     => "INFO  ",
-                LogLevel::Warning  => "WARN  ",
-                LogLevel::Error    => "ERROR ",
-                LogLevel::Critical => "CRIT  ",
-            };
-            string.push_str(prefix);
-            string.push_str(message);
-            string.push('\n');
-        }
-        string
-    }
-}
-
 impl Resource for KlogResource {
     fn dup(&self) -> Result<Box<Resource>> {
         Ok(Box::new(KlogResource {
+            level: self.level,
+            seq: self.seq,
+            pending: self.pending.clone(),
             pos: self.pos,
         }))
     }
 
-    /// Fills `buf` with the kernel log. Each message is prefixed by its log level:
-    /// - `CRIT`
-    /// - `ERROR`
-    /// - `WARN`
-    /// - `INFO`
-    /// - `DEBUG`
+    /// Fills `buf` with the kernel log. Each line has the time of the message in seconds and its
+    /// log level, like "[12.345] INFO message". Reading again returns the messages logged since,
+    /// so the log can be followed.
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let mut i = 0;
-        let logs = self.get_log_str();
-        while i < buf.len() && self.pos < logs.bytes().count() {
-            match logs.bytes().nth(self.pos) {
-                Some(c) => buf[i] = c,
-                None => ()
-            }
-            i += 1;
-            self.pos += 1;
+        if self.pending.is_empty() {
+            klog_flush();
+            let (lines, seq) = ::env().logs.lock().lines_since(self.seq, self.level);
+            self.pending = lines.into_bytes();
+            self.seq = seq;
         }
-        Ok(i)
+
+        let count = cmp::min(buf.len(), self.pending.len());
+        for (b, c) in buf.iter_mut().zip(self.pending.drain(.. count)) {
+            *b = c;
+        }
+        self.pos += count;
+        Ok(count)
     }
 
+    /// Seeking to the start reads the log again from the oldest entry kept, and seeking to the
+    /// end skips to the entries logged after
     fn seek(&mut self, pos: ResourceSeek) -> Result<usize> {
         match pos {
-            ResourceSeek::Start(offset) => self.pos = offset as usize,
-            ResourceSeek::Current(offset) => self.pos += offset as usize,
-            ResourceSeek::End(offset) => {
-                let logs = self.get_log_str();
-                self.pos = (logs.bytes().count() as isize + offset) as usize;
+            ResourceSeek::Start(_) => {
+                self.seq = 0;
+                self.pending.clear();
+                self.pos = 0;
+            },
+            ResourceSeek::Current(_) => (),
+            ResourceSeek::End(_) => {
+                self.seq = ::env().logs.lock().next_seq();
+                self.pending.clear();
             }
         }
         Ok(self.pos)
     }
 }
+
+/// The resource for the least important level that is logged
+pub struct KlogLevelResource {
+    seek: usize,
+}
+
+impl Resource for KlogLevelResource {
+    fn dup(&self) -> Result<Box<Resource>> {
+        Ok(Box::new(KlogLevelResource {
+            seek: self.seek,
+        }))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let level = format!("{}\n", ::env().logs.lock().level.name());
+
+        let mut i = 0;
+        while i < buf.len() && self.seek < level.len() {
+            buf[i] = level.as_bytes()[self.seek];
+            self.seek += 1;
+            i += 1;
+        }
+
+        Ok(i)
+    }
+
+    /// Sets the level from its name, like "warn"
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let name = try!(str::from_utf8(buf).or(Err(Error::new(EINVAL))));
+        let level = try!(LogLevel::from_name(name.trim()).ok_or(Error::new(EINVAL)));
+        ::env().logs.lock().level = level;
+        Ok(buf.len())
+    }
+}
@@ -6,9 +6,14 @@ use collections::string::ToString;
 
 use fs::{KScheme, Resource, Url, VecResource};
 
-use system::error::Result;
+use system::error::{Error, Result, ENOENT};
+
+use super::context;
 
 /// A memory scheme
+///
+/// `memory:` reports the state of the allocator as key: value lines, with sizes in KB.
+/// `memory:contexts` lists the memory mapped by each context.
 pub struct MemoryScheme;
 
 impl KScheme for MemoryScheme {
@@ -16,10 +21,32 @@ impl KScheme for MemoryScheme {
         "memory"
     }
 
-    fn open(&mut self, _: Url, _: usize) -> Result<Box<Resource>> {
-        let string = format!("Memory Used: {} KB\nMemory Free: {} KB\n",
-                             memory::memory_used() / 1024,
-                             memory::memory_free() / 1024);
-        Ok(box VecResource::new("memory:".to_string(), string.into_bytes()))
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        match url.reference().trim_matches('/') {
+            "" => {
+                let stats = memory::memory_stats();
+                let string = format!("Total: {} KB\nUsed: {} KB\nFree: {} KB\nFree Clusters: {}\nLargest Free: {} KB\n",
+                                     stats.total / 1024,
+                                     stats.used / 1024,
+                                     stats.free / 1024,
+                                     stats.free_clusters,
+                                     stats.largest_free / 1024);
+                Ok(box VecResource::new("memory:".to_string(), string.into_bytes()))
+            },
+            "contexts" => {
+                let mut string = format!("{:<6}{:<12}{}\n", "PID", "KB", "NAME");
+                {
+                    let contexts = ::env().contexts.lock();
+                    for context in contexts.iter() {
+                        string.push_str(&format!("{:<6}{:<12}{}\n",
+                                                 context.pid,
+                                                 context::memory(context) / 1024,
+                                                 context.name));
+                    }
+                }
+                Ok(box VecResource::new("memory:contexts".to_string(), string.into_bytes()))
+            },
+            _ => Err(Error::new(ENOENT))
+        }
     }
 }
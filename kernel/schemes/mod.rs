@@ -1,5 +1,7 @@
 /// Context scheme
 pub mod context;
+/// CPU time scheme
+pub mod cpu;
 /// Debug scheme
 pub mod debug;
 /// Disk scheme
@@ -16,6 +18,8 @@ pub mod interrupt;
 pub mod klog;
 /// Memory scheme
 pub mod memory;
+/// PCI scheme
+pub mod pci;
 /// Pipes
 pub mod pipe;
 /// Tests
@@ -0,0 +1,163 @@
+use alloc::boxed::Box;
+
+use collections::string::{String, ToString};
+
+use core::cmp::{max, min};
+
+use drivers::pci::config::PciConfig;
+
+use fs::{KScheme, Resource, ResourceSeek, Url, VecResource};
+
+use system::error::{Error, Result, ENOENT};
+use system::syscall::{MODE_FILE, Stat};
+
+/// The size of the configuration space of a PCI function
+const CONFIG_SIZE: usize = 256;
+
+/// The offset of the status register, whose error bits are cleared by writing ones to them
+const STATUS_OFFSET: usize = 6;
+
+/// The configuration space of a PCI function
+///
+/// Seeking addresses the configuration register offset. Reads and writes are done a byte at a
+/// time, with writes doing a read-modify-write of the containing register. The status register is
+/// not written back along with the command register, as that would clear its error bits.
+pub struct PciResource {
+    pci: PciConfig,
+    path: String,
+    seek: usize,
+}
+
+impl PciResource {
+    unsafe fn read_byte(&mut self, offset: usize) -> u8 {
+        (self.pci.read(offset as u8) >> ((offset & 3) * 8)) as u8
+    }
+
+    unsafe fn write_byte(&mut self, offset: usize, byte: u8) {
+        let shift = (offset & 3) * 8;
+        let mut value = self.pci.read(offset as u8) & !(0xFF << shift);
+        // Writing back the status bits that were read would clear them, so the status bytes are
+        // written as zero, other than the byte being written
+        if offset & !3 == STATUS_OFFSET & !3 {
+            value &= !(0xFFFF << ((STATUS_OFFSET & 3) * 8));
+        }
+        value |= (byte as u32) << shift;
+        self.pci.write(offset as u8, value);
+    }
+}
+
+impl Resource for PciResource {
+    fn dup(&self) -> Result<Box<Resource>> {
+        Ok(box PciResource {
+            pci: self.pci,
+            path: self.path.clone(),
+            seek: self.seek,
+        })
+    }
+
+    fn path(&self, buf: &mut [u8]) -> Result<usize> {
+        let path = self.path.as_bytes();
+
+        let mut i = 0;
+        while i < buf.len() && i < path.len() {
+            buf[i] = path[i];
+            i += 1;
+        }
+
+        Ok(i)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut i = 0;
+        while i < buf.len() && self.seek < CONFIG_SIZE {
+            buf[i] = unsafe { self.read_byte(self.seek) };
+            self.seek += 1;
+            i += 1;
+        }
+        Ok(i)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut i = 0;
+        while i < buf.len() && self.seek < CONFIG_SIZE {
+            unsafe { self.write_byte(self.seek, buf[i]) };
+            self.seek += 1;
+            i += 1;
+        }
+        Ok(i)
+    }
+
+    fn seek(&mut self, pos: ResourceSeek) -> Result<usize> {
+        match pos {
+            ResourceSeek::Start(offset) => self.seek = min(CONFIG_SIZE, offset),
+            ResourceSeek::Current(offset) =>
+                self.seek = max(0, min(CONFIG_SIZE as isize, self.seek as isize + offset)) as usize,
+            ResourceSeek::End(offset) =>
+                self.seek = max(0, min(CONFIG_SIZE as isize, CONFIG_SIZE as isize + offset)) as usize,
+        }
+        Ok(self.seek)
+    }
+
+    fn stat(&self, stat: &mut Stat) -> Result<usize> {
+        stat.st_mode = MODE_FILE | 0o644;
+        stat.st_size = CONFIG_SIZE as u64;
+        Ok(0)
+    }
+}
+
+/// A scheme exposing the PCI devices
+///
+/// `pci:` lists the present functions, one per line, and `pci:bus/slot/func` opens the
+/// configuration space of a function.
+pub struct PciScheme;
+
+impl KScheme for PciScheme {
+    fn scheme(&self) -> &str {
+        "pci"
+    }
+
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        let reference = url.reference().trim_matches('/');
+        if reference.is_empty() {
+            let mut string = format!("{:<12}{:<8}{:<8}{}\n", "BUS/SLOT/FN", "VENDOR", "DEVICE", "CLASS");
+            for bus in 0..256 {
+                for slot in 0..32 {
+                    for func in 0..8 {
+                        let mut pci = PciConfig::new(bus as u8, slot as u8, func as u8);
+                        let id = unsafe { pci.read(0) };
+                        if (id & 0xFFFF) != 0xFFFF {
+                            let class_id = unsafe { pci.read(8) };
+                            string.push_str(&format!("{:<12}{:<8}{:<8}{:02X}.{:02X}.{:02X}\n",
+                                                     format!("{}/{}/{}", bus, slot, func),
+                                                     format!("{:04X}", id & 0xFFFF),
+                                                     format!("{:04X}", (id >> 16) & 0xFFFF),
+                                                     (class_id >> 24) & 0xFF,
+                                                     (class_id >> 16) & 0xFF,
+                                                     (class_id >> 8) & 0xFF));
+                        }
+                    }
+                }
+            }
+            return Ok(box VecResource::new("pci:".to_string(), string.into_bytes()));
+        }
+
+        let mut parts = reference.split('/');
+        let bus = parts.next().and_then(|part| part.parse::<u8>().ok());
+        let slot = parts.next().and_then(|part| part.parse::<u8>().ok());
+        let func = parts.next().and_then(|part| part.parse::<u8>().ok());
+        if let (Some(bus), Some(slot), Some(func), None) = (bus, slot, func, parts.next()) {
+            if slot < 32 && func < 8 {
+                let mut pci = PciConfig::new(bus, slot, func);
+                if (unsafe { pci.read(0) } & 0xFFFF) != 0xFFFF {
+                    return Ok(box PciResource {
+                        pci: pci,
+                        path: format!("pci:{}/{}/{}", bus, slot, func),
+                        seek: 0,
+                    });
+                }
+            }
+        }
+
+        Err(Error::new(ENOENT))
+    }
+}
@@ -1,23 +1,33 @@
 use alloc::arc::{Arc, Weak};
 use alloc::boxed::Box;
 
-use core::cmp;
+use core::{cmp, mem};
 
 use fs::Resource;
 
-use sync::WaitQueue;
+use sync::{WaitCondition, WaitQueue};
 
-use system::error::{Error, Result, EPIPE};
+use system::error::{Error, Result, EAGAIN, EPIPE};
+use system::syscall::{O_NONBLOCK, POLLERR, POLLHUP, POLLIN, POLLOUT};
+
+/// The most bytes a pipe holds before writers have to wait for readers
+pub const PIPE_MAX: usize = 65536;
 
 /// Read side of a pipe
 pub struct PipeRead {
-    vec: Arc<WaitQueue<u8>>
+    vec: Arc<WaitQueue<u8>>,
+    /// Writers waiting for room in the pipe
+    room: Arc<WaitCondition>,
+    /// Return `EAGAIN` instead of blocking when the pipe is empty
+    nonblock: bool,
 }
 
 impl PipeRead {
-    pub fn new() -> Self {
+    pub fn new(flags: usize) -> Self {
         PipeRead {
-            vec: Arc::new(WaitQueue::new())
+            vec: Arc::new(WaitQueue::new()),
+            room: Arc::new(WaitCondition::new()),
+            nonblock: flags & O_NONBLOCK == O_NONBLOCK,
         }
     }
 }
@@ -26,6 +36,8 @@ impl Resource for PipeRead {
     fn dup(&self) -> Result<Box<Resource>> {
         Ok(box PipeRead {
             vec: self.vec.clone(),
+            room: self.room.clone(),
+            nonblock: self.nonblock,
         })
     }
 
@@ -42,15 +54,32 @@ impl Resource for PipeRead {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
         if Arc::weak_count(&self.vec) == 0 && self.vec.inner.lock().is_empty() {
             Ok(0)
+        } else if buf.is_empty() {
+            Ok(0)
         } else {
-            if !buf.is_empty() {
-                buf[0] = self.vec.receive();
+            // Block until a byte is written, or the last writer is gone
+            loop {
+                let context = {
+                    let inner = self.vec.inner.lock();
+                    if ! inner.is_empty() {
+                        break;
+                    }
+                    if Arc::weak_count(&self.vec) == 0 {
+                        return Ok(0);
+                    }
+                    if self.nonblock {
+                        return Err(Error::new(EAGAIN));
+                    }
+                    unsafe { self.vec.condition.block(None) }
+                };
+                unsafe { self.vec.condition.sleep(context); }
             }
 
-            let mut i = 1;
+            let mut inner = self.vec.inner.lock();
+            let mut i = 0;
 
             while i < buf.len() {
-                match self.vec.inner.lock().pop_front() {
+                match inner.pop_front() {
                     Some(b) => {
                         buf[i] = b;
                         i += 1;
@@ -59,20 +88,57 @@ impl Resource for PipeRead {
                 }
             }
 
+            // Another reader takes what is left, and the writers fill the room made
+            if ! inner.is_empty() {
+                self.vec.notify_n(1);
+            }
+            unsafe { self.room.notify(); }
+
             Ok(i)
         }
     }
+
+    fn poll(&mut self) -> usize {
+        if Arc::weak_count(&self.vec) == 0 {
+            POLLIN | POLLHUP
+        } else if self.vec.inner.lock().is_empty() {
+            0
+        } else {
+            POLLIN
+        }
+    }
+
+    fn set_flags(&mut self, flags: usize) -> Result<()> {
+        self.nonblock = flags & O_NONBLOCK == O_NONBLOCK;
+        Ok(())
+    }
 }
 
-/// Read side of a pipe
+impl Drop for PipeRead {
+    fn drop(&mut self) {
+        // Let go of the pipe before waking the writers, so they see if the last reader is gone
+        drop(mem::replace(&mut self.vec, Arc::new(WaitQueue::new())));
+        unsafe { self.room.notify(); }
+    }
+}
+
+/// Write side of a pipe
+///
+/// The pipe holds up to `PIPE_MAX` bytes. A write waits for the readers to make room for all of
+/// its bytes, or with `O_NONBLOCK`, writes what fits and returns `EAGAIN` if nothing does.
 pub struct PipeWrite {
     vec: Weak<WaitQueue<u8>>,
+    room: Arc<WaitCondition>,
+    /// Return `EAGAIN` instead of blocking when the pipe is full
+    nonblock: bool,
 }
 
 impl PipeWrite {
-    pub fn new(read: &PipeRead) -> Self {
+    pub fn new(read: &PipeRead, flags: usize) -> Self {
         PipeWrite {
             vec: Arc::downgrade(&read.vec),
+            room: read.room.clone(),
+            nonblock: flags & O_NONBLOCK == O_NONBLOCK,
         }
     }
 }
@@ -81,6 +147,8 @@ impl Resource for PipeWrite {
     fn dup(&self) -> Result<Box<Resource>> {
         Ok(box PipeWrite {
             vec: self.vec.clone(),
+            room: self.room.clone(),
+            nonblock: self.nonblock,
         })
     }
 
@@ -95,20 +163,67 @@ impl Resource for PipeWrite {
     }
 
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        match self.vec.upgrade() {
-            Some(vec) => {
-                for &b in buf.iter() {
-                    vec.send(b);
+        let mut i = 0;
+        loop {
+            // The pipe is only held while writing, so the readers can see when they are the last
+            let vec = match self.vec.upgrade() {
+                Some(vec) => vec,
+                None => return if i > 0 { Ok(i) } else { Err(Error::new(EPIPE)) },
+            };
+
+            let context = {
+                let mut inner = vec.inner.lock();
+                let count = cmp::min(PIPE_MAX.saturating_sub(inner.len()), buf.len() - i);
+                if count > 0 {
+                    inner.extend(buf[i .. i + count].iter().cloned());
+                    i += count;
+                    unsafe { vec.condition.notify_n(1); }
+                    ::env().input.notify();
+                }
+
+                if i == buf.len() {
+                    return Ok(i);
+                }
+                if self.nonblock {
+                    return if i > 0 { Ok(i) } else { Err(Error::new(EAGAIN)) };
                 }
+                unsafe { self.room.block(None) }
+            };
+            drop(vec);
+            unsafe { self.room.sleep(context); }
+        }
+    }
 
-                Ok(buf.len())
+    fn poll(&mut self) -> usize {
+        match self.vec.upgrade() {
+            Some(vec) => if vec.inner.lock().len() < PIPE_MAX {
+                POLLOUT
+            } else {
+                0
             },
-            None => Err(Error::new(EPIPE))
+            None => POLLERR,
         }
     }
 
+    fn set_flags(&mut self, flags: usize) -> Result<()> {
+        self.nonblock = flags & O_NONBLOCK == O_NONBLOCK;
+        Ok(())
+    }
+
     fn sync(&mut self) -> Result<()> {
         //TODO: Wait until empty
         Ok(())
     }
 }
+
+impl Drop for PipeWrite {
+    fn drop(&mut self) {
+        // Wake the readers once the last writer is gone, so they see the end of the pipe
+        if let Some(vec) = self.vec.upgrade() {
+            if Arc::weak_count(&vec) == 1 {
+                unsafe { vec.condition.notify(); }
+                ::env().input.notify();
+            }
+        }
+    }
+}
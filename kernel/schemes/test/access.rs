@@ -0,0 +1,43 @@
+use fs::{KScheme, Url};
+
+use system::error::Result;
+use system::syscall::{MODE_FILE, Stat};
+
+/// A scheme that reports files without permission bits
+struct ModelessScheme;
+
+impl KScheme for ModelessScheme {
+    fn scheme(&self) -> &str {
+        "test_access"
+    }
+
+    fn stat(&mut self, _: Url, stat: &mut Stat) -> Result<()> {
+        stat.st_mode = MODE_FILE;
+        Ok(())
+    }
+}
+
+pub fn test() -> bool {
+    use syscall::{do_sys_access, EACCES, EINVAL, ENOENT, F_OK, R_OK, W_OK, X_OK};
+
+    let program = "initfs:/bin/init\0".as_ptr();
+    let folder = "initfs:/bin\0".as_ptr();
+
+    test!(do_sys_access(program, F_OK).is_ok());
+    test!(do_sys_access(program, R_OK | X_OK).is_ok());
+    test!(do_sys_access(folder, R_OK | X_OK).is_ok());
+
+    // Initfs is read only
+    test!(do_sys_access(program, W_OK).map_err(|err| err.errno) == Err(EACCES));
+    test!(do_sys_access(program, R_OK | W_OK).map_err(|err| err.errno) == Err(EACCES));
+
+    test!(do_sys_access("initfs:/access_test\0".as_ptr(), F_OK).map_err(|err| err.errno) == Err(ENOENT));
+    test!(do_sys_access(program, 8).map_err(|err| err.errno) == Err(EINVAL));
+
+    // Files without permission bits are not restricted
+    test!(::env().schemes.lock().insert(box ModelessScheme).is_ok());
+    let modeless = do_sys_access("test_access:/file\0".as_ptr(), R_OK | W_OK | X_OK).is_ok();
+    ::env().schemes.lock().remove("test_access");
+    test!(modeless);
+    succ!();
+}
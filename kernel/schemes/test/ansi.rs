@@ -0,0 +1,73 @@
+pub fn test() -> bool {
+    use collections::String;
+    use env::console::Console;
+
+    fn row(console: &Console, row: usize, len: usize) -> String {
+        console.scrollback.line(console.scrollback.len() + row).iter().take(len).map(|cell| cell.c).collect()
+    }
+
+    // Without a display, the text is still kept in an 80x30 grid
+    let mut console = Console::with_display(None);
+    test!(console.scrollback.columns == 80 && console.scrollback.rows == 30);
+
+    // Cursor movement, with the defaults of one cell
+    console.put(b"abc\x1B[2DX\x1B[CY\x1B[BZ\x1B[AW");
+    test!(row(&console, 0, 6) == "aXcY W");
+    test!(row(&console, 1, 6) == "    Z ");
+
+    // Positions are 1-based and are kept on the screen
+    console.put(b"\x1B[3;5Hz\x1B[Hh\x1B[99;99H");
+    test!(row(&console, 2, 5) == "    z");
+    test!(row(&console, 0, 1) == "h");
+    test!(console.point_x == 79 * 8 && console.point_y == 29 * 16);
+    console.put(b"\x1B[99A\x1B[99D");
+    test!(console.point_x == 0 && console.point_y == 0);
+
+    // Erasing in a line
+    console.put(b"\x1Bc0123456789\x1B[1;5H\x1B[K");
+    test!(row(&console, 0, 10) == "0123      ");
+    console.put(b"\x1B[1;2H\x1B[1K");
+    test!(row(&console, 0, 4) == "  23");
+    console.put(b"\x1B[2K");
+    test!(row(&console, 0, 4) == "    ");
+
+    // Erasing in the display
+    console.put(b"\x1Bcaaaa\nbbbb\ncccc\x1B[2;3H\x1B[J");
+    test!(row(&console, 0, 4) == "aaaa");
+    test!(row(&console, 1, 4) == "bb  ");
+    test!(row(&console, 2, 4) == "    ");
+    console.put(b"\x1Bcaaaa\nbbbb\ncccc\x1B[2;3H\x1B[1J");
+    test!(row(&console, 0, 4) == "    ");
+    test!(row(&console, 1, 4) == "   b");
+    test!(row(&console, 2, 4) == "cccc");
+
+    // Saving and restoring the cursor
+    console.put(b"\x1Bc\x1B[5;5H\x1B7\x1B[Hq\x1B8r\x1B[2;2H\x1B[s\x1B[10;10H\x1B[us");
+    test!(row(&console, 0, 1) == "q");
+    test!(row(&console, 4, 5) == "    r");
+    test!(row(&console, 1, 2) == " s");
+
+    // Colors, with bold brightening the basic colors
+    console.put(b"\x1Bc\x1B[31;42mr\x1B[1mR\x1B[0mn\x1B[1;34mb\x1B[22mB\x1B[95;100mm\x1B[mx");
+    let line = console.scrollback.line(console.scrollback.len());
+    test!(line[0].foreground.data == 0xFF800000 && line[0].background.data == 0xFF008000);
+    test!(line[1].foreground.data == 0xFFFF0000 && line[1].background.data == 0xFF008000);
+    test!(line[2].foreground.data == 0xFFC0C0C0 && line[2].background.data == 0xFF000000);
+    test!(line[3].foreground.data == 0xFF0000FF);
+    test!(line[4].foreground.data == 0xFF000080);
+    test!(line[5].foreground.data == 0xFFFF00FF && line[5].background.data == 0xFF808080);
+    test!(line[6].foreground.data == 0xFFC0C0C0 && line[6].background.data == 0xFF000000);
+
+    // Unknown and private sequences are consumed without printing anything
+    console.put(b"\x1Bc\x1B[?25l\x1B[?1049h\x1B[5X\x1B[1 qok");
+    test!(row(&console, 0, 3) == "ok ");
+    test!(! console.escape && console.sequence.is_empty());
+
+    // Raw mode is still switched by CSI r and CSI R
+    console.put(b"\x1B[r");
+    test!(console.raw_mode);
+    console.put(b"\x1B[R");
+    test!(! console.raw_mode);
+
+    succ!();
+}
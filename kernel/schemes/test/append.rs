@@ -0,0 +1,131 @@
+use alloc::arc::Arc;
+use alloc::boxed::Box;
+
+use collections::Vec;
+
+use core::cmp;
+
+use fs::{Resource, ResourceSeek};
+
+use sync::Intex;
+
+use system::error::Result;
+
+/// A file in memory that several resources have open, each at its own offset
+struct SharedFile {
+    data: Arc<Intex<Vec<u8>>>,
+    seek: usize,
+}
+
+impl Resource for SharedFile {
+    fn dup(&self) -> Result<Box<Resource>> {
+        Ok(box SharedFile {
+            data: self.data.clone(),
+            seek: self.seek,
+        })
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let data = self.data.lock();
+        let count = cmp::min(buf.len(), data.len().saturating_sub(self.seek));
+        buf[.. count].clone_from_slice(&data[self.seek .. self.seek + count]);
+        self.seek += count;
+        Ok(count)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut data = self.data.lock();
+        for &b in buf.iter() {
+            if self.seek < data.len() {
+                data[self.seek] = b;
+            } else {
+                data.push(b);
+            }
+            self.seek += 1;
+        }
+        Ok(buf.len())
+    }
+
+    fn seek(&mut self, pos: ResourceSeek) -> Result<usize> {
+        let len = self.data.lock().len() as isize;
+        let seek = match pos {
+            ResourceSeek::Start(offset) => offset as isize,
+            ResourceSeek::Current(offset) => self.seek as isize + offset,
+            ResourceSeek::End(offset) => len + offset,
+        };
+        self.seek = cmp::max(0, cmp::min(len, seek)) as usize;
+        Ok(self.seek)
+    }
+}
+
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use syscall::{do_sys_close, do_sys_dup, do_sys_fcntl, do_sys_lseek, do_sys_pread, do_sys_write,
+                  do_sys_writev, IoVec, F_GETFL, F_SETFL, O_APPEND, O_RDWR, O_WRONLY, SEEK_SET};
+
+    let data = Arc::new(Intex::new(b"abc".to_vec()));
+    let fd = match super::install_file(box SharedFile { data: data.clone(), seek: 0 }, O_RDWR | O_APPEND) {
+        Some(fd) => fd,
+        None => { fail!(); }
+    };
+
+    let mut buf = [0; 8];
+
+    // Writes go to the end, whatever the offset
+    test!(do_sys_lseek(fd, 0, SEEK_SET).ok() == Some(0));
+    test!(do_sys_write(fd, b"def".as_ptr(), 3).ok() == Some(3));
+    test!(do_sys_lseek(fd, 1, SEEK_SET).ok() == Some(1));
+    test!(do_sys_write(fd, b"gh".as_ptr(), 2).ok() == Some(2));
+    test!(do_sys_pread(fd, buf.as_mut_ptr(), buf.len(), 0).ok() == Some(8));
+    test!(&buf == b"abcdefgh");
+
+    // Duplicates keep the flag
+    let dup = if let Ok(dup) = do_sys_dup(fd) {
+        dup
+    } else {
+        fail!();
+    };
+    test!(do_sys_fcntl(dup, F_GETFL, 0).ok() == Some(O_RDWR | O_APPEND));
+    test!(do_sys_close(dup).is_ok());
+
+    // Gathered writes go to the end as a whole
+    let iov = [IoVec { iov_base: b"ij".as_ptr() as usize, iov_len: 2 },
+               IoVec { iov_base: b"kl".as_ptr() as usize, iov_len: 2 }];
+    test!(do_sys_lseek(fd, 2, SEEK_SET).ok() == Some(2));
+    test!(do_sys_writev(fd, iov.as_ptr(), iov.len()).ok() == Some(4));
+    let mut tail = [0; 6];
+    test!(do_sys_pread(fd, tail.as_mut_ptr(), tail.len(), 6).ok() == Some(6));
+    test!(&tail == b"ghijkl");
+
+    // Without O_APPEND, writes go to the offset again
+    test!(do_sys_fcntl(fd, F_SETFL, O_RDWR).is_ok());
+    test!(do_sys_lseek(fd, 0, SEEK_SET).ok() == Some(0));
+    test!(do_sys_write(fd, b"x".as_ptr(), 1).ok() == Some(1));
+    test!(do_sys_pread(fd, buf.as_mut_ptr(), buf.len(), 0).ok() == Some(8));
+    test!(&buf == b"xbcdefgh");
+    test!(do_sys_fcntl(fd, F_GETFL, 0).ok() == Some(O_RDWR));
+    test!(do_sys_close(fd).is_ok());
+
+    // Two descriptors of the same file, each with its own offset, append after each other
+    *data.lock() = b"abc".to_vec();
+    let first = match super::install_file(box SharedFile { data: data.clone(), seek: 0 }, O_WRONLY | O_APPEND) {
+        Some(fd) => fd,
+        None => { fail!(); }
+    };
+    let second = match super::install_file(box SharedFile { data: data.clone(), seek: 0 }, O_WRONLY | O_APPEND) {
+        Some(fd) => fd,
+        None => { fail!(); }
+    };
+    test!(do_sys_write(first, b"de".as_ptr(), 2).ok() == Some(2));
+    test!(do_sys_write(second, b"fg".as_ptr(), 2).ok() == Some(2));
+    test!(do_sys_write(first, b"h".as_ptr(), 1).ok() == Some(1));
+    test!(do_sys_writev(second, iov.as_ptr(), iov.len()).ok() == Some(4));
+    test!(&data.lock()[..] == b"abcdefghijkl");
+    test!(do_sys_close(first).is_ok());
+    test!(do_sys_close(second).is_ok());
+
+    succ!();
+}
@@ -0,0 +1,42 @@
+pub fn test() -> bool {
+    use collections::string::ToString;
+    use common::time::Duration;
+    use network::arp::{ArpCache, ARP_CACHE_MAX, ARP_LIFETIME};
+    use network::common::{Ipv4Addr, MacAddr};
+
+    let ip = |last: u8| Ipv4Addr { bytes: [10, 0, 2, last] };
+    let mac = |last: u8| MacAddr { bytes: [0x52, 0x54, 0, 0x12, 0x34, last] };
+
+    let mut cache = ArpCache::new();
+    test!(cache.lifetime == Duration::new(ARP_LIFETIME, 0));
+
+    // Entries are used until they are older than the lifetime
+    cache.insert(ip(2), mac(2), Duration::new(100, 0));
+    test!(cache.lookup(ip(2), Duration::new(100 + ARP_LIFETIME - 1, 0)).map(|mac| mac.bytes) == Some(mac(2).bytes));
+    test!(cache.lookup(ip(2), Duration::new(100 + ARP_LIFETIME, 0)).is_none());
+    test!(cache.lookup(ip(2), Duration::new(100, 0)).is_none());
+
+    // Hearing from a host only refreshes it if it is cached
+    test!(! cache.update(ip(3), mac(3), Duration::new(200, 0)));
+    cache.insert(ip(3), mac(9), Duration::new(200, 0));
+    test!(cache.update(ip(3), mac(3), Duration::new(250, 0)));
+    test!(cache.lookup(ip(3), Duration::new(250 + ARP_LIFETIME - 1, 0)).map(|mac| mac.bytes) == Some(mac(3).bytes));
+
+    // Once full, the least recently used entry makes room
+    let at = |millis: usize| Duration::new(0, millis as i32 * 1000000);
+    let mut cache = ArpCache::new();
+    for i in 0..ARP_CACHE_MAX {
+        cache.insert(ip(i as u8), mac(i as u8), at(i));
+    }
+    test!(cache.lookup(ip(0), at(ARP_CACHE_MAX)).is_some());
+    cache.insert(ip(200), mac(200), at(ARP_CACHE_MAX + 1));
+    test!(cache.lookup(ip(0), at(ARP_CACHE_MAX + 2)).is_some());
+    test!(cache.lookup(ip(1), at(ARP_CACHE_MAX + 2)).is_none());
+    test!(cache.lookup(ip(200), at(ARP_CACHE_MAX + 2)).is_some());
+
+    let list = cache.list(Duration::new(10, 0));
+    test!(list.starts_with("IP"));
+    test!(list.contains(&format!("{:<16}{:<18}9\n", ip(200).to_string(), mac(200).to_string())));
+
+    succ!();
+}
@@ -0,0 +1,34 @@
+pub fn test() -> bool {
+    use collections::String;
+    use panic::{demangle, symbol};
+
+    let mut name = String::new();
+    test!(demangle(&mut name, "_ZN4core3fmt5write17h0123456789abcdefE").is_ok());
+    test!(name == "core::fmt::write");
+
+    let mut name = String::new();
+    test!(demangle(&mut name, "_ZN6kernel4init17h0123456789abcdefE").is_ok());
+    test!(name == "kernel::init");
+
+    // Names that are not mangled, or are cut short, are left as they are
+    let mut name = String::new();
+    test!(demangle(&mut name, "kernel").is_ok());
+    test!(name == "kernel");
+
+    let mut name = String::new();
+    test!(demangle(&mut name, "_ZN4core9fmtE").is_ok());
+    test!(name == "_ZN4core9fmtE");
+
+    // Nothing is past the code
+    test!(symbol(usize::max_value()).is_none());
+
+    // A kernel linked with a symbol table knows where this test is
+    if let Some((function, offset)) = symbol(test as usize + 1) {
+        let mut name = String::new();
+        test!(demangle(&mut name, function).is_ok());
+        test!(name.contains("backtrace"));
+        test!(offset == 1);
+    }
+
+    succ!();
+}
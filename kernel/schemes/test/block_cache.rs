@@ -0,0 +1,107 @@
+use alloc::arc::Arc;
+use alloc::boxed::Box;
+
+use collections::string::String;
+
+use disk::Disk;
+
+use sync::Intex;
+
+use system::error::Result;
+
+use super::partition::MemoryDisk;
+
+/// A disk counting the read requests it gets
+struct CountingDisk {
+    disk: MemoryDisk,
+    reads: Arc<Intex<usize>>,
+}
+
+impl Disk for CountingDisk {
+    fn name(&self) -> String {
+        self.disk.name()
+    }
+
+    fn size(&self) -> u64 {
+        self.disk.size()
+    }
+
+    fn read(&mut self, block: u64, buffer: &mut [u8]) -> Result<usize> {
+        *self.reads.lock() += 1;
+        self.disk.read(block, buffer)
+    }
+
+    fn write(&mut self, block: u64, buffer: &[u8]) -> Result<usize> {
+        self.disk.write(block, buffer)
+    }
+}
+
+pub fn test() -> bool {
+    use disk::cache::BlockCache;
+    use fs::Resource;
+    use schemes::disk::DiskResource;
+    use system::error::EINVAL;
+
+    let mut data = vec![0; 4 * 512];
+    for block in 0..4 {
+        data[block * 512] = block as u8;
+    }
+    let mut cache = BlockCache::new(box MemoryDisk { data: data }, 2);
+
+    let mut buf = [0; 1024];
+    test!(cache.read(0, &mut buf[.. 512]).ok() == Some(512) && buf[0] == 0);
+    test!(cache.read(0, &mut buf[.. 512]).ok() == Some(512) && buf[0] == 0);
+    test!(cache.cache_stats() == Some((1, 1)));
+
+    // Reading two more blocks evicts the least recently used one
+    test!(cache.read(1, &mut buf).ok() == Some(1024) && buf[0] == 1 && buf[512] == 2);
+    test!(cache.read(0, &mut buf[.. 512]).ok() == Some(512));
+    test!(cache.read(2, &mut buf[.. 512]).ok() == Some(512));
+    test!(cache.cache_stats() == Some((2, 4)));
+
+    // Partial blocks transfer only the bytes requested, and nothing is read past the end
+    test!(cache.read(1, &mut buf[.. 100]).ok() == Some(100) && buf[0] == 1);
+    test!(cache.read(3, &mut buf).ok() == Some(512) && buf[0] == 3);
+    test!(cache.read(4, &mut buf).ok() == Some(0));
+
+    // Writes go through, merging a partial block with its contents
+    test!(cache.write(2, &[0xAA; 100]).ok() == Some(100));
+    test!(cache.read(2, &mut buf[.. 512]).ok() == Some(512));
+    test!(buf[0] == 0xAA && buf[99] == 0xAA && buf[100] == 0);
+    test!(cache.write(3, &buf).ok() == Some(512));
+    test!(cache.write(4, &buf).ok() == Some(0));
+
+    let (hits, misses) = cache.cache_stats().unwrap_or((0, 0));
+    cache.invalidate(0, 4);
+    test!(cache.read(3, &mut buf[.. 512]).ok() == Some(512) && buf[0] == 0xAA);
+    test!(cache.cache_stats() == Some((hits, misses + 1)));
+
+    // Blocks missing one after another are read at once, around the cached ones
+    let reads = Arc::new(Intex::new(0));
+    let mut cache = BlockCache::new(box CountingDisk { disk: MemoryDisk { data: vec![0; 8 * 512] }, reads: reads.clone() }, 8);
+    let mut buf = [0; 8 * 512];
+    test!(cache.read(3, &mut buf[.. 512]).ok() == Some(512));
+    test!(cache.read(0, &mut buf[.. 4000]).ok() == Some(4000));
+    test!(*reads.lock() == 3);
+    test!(cache.cache_stats() == Some((1, 8)));
+
+    // Truncating the disk drops its cached blocks from the new length on
+    let disk: Box<Disk> = box cache;
+    let mut resource = DiskResource {
+        path: String::from("disk:/test"),
+        disk: Arc::new(Intex::new(disk)),
+        seek: 0,
+    };
+    test!(resource.truncate(6 * 512).is_ok());
+    test!(resource.read_at(0, &mut buf).ok() == Some(8 * 512));
+    test!(*reads.lock() == 4);
+    test!(resource.disk.lock().cache_stats() == Some((7, 10)));
+
+    // Positional transfers start at a block
+    test!(resource.write_at(512, &[0x55; 512]).ok() == Some(512));
+    test!(resource.read_at(512, &mut buf[.. 512]).ok() == Some(512) && buf[0] == 0x55);
+    test!(resource.read_at(100, &mut buf[.. 512]).map_err(|err| err.errno) == Err(EINVAL));
+    test!(resource.write_at(100, &[0x55; 512]).map_err(|err| err.errno) == Err(EINVAL));
+
+    succ!();
+}
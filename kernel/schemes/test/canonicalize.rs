@@ -0,0 +1,45 @@
+pub fn test() -> bool {
+    use common::parse_path::canonicalize;
+
+    // Relative paths are joined to the working directory
+    test!(canonicalize("initfs:/", "foo/bar") == "initfs:/foo/bar");
+    test!(canonicalize("initfs:/bin/", "init") == "initfs:/bin/init");
+    test!(canonicalize("initfs:/bin", "init") == "initfs:/bin/init");
+    test!(canonicalize("initfs:/bin/", "") == "initfs:/bin/");
+    test!(canonicalize("initfs:/bin/", "etc/") == "initfs:/bin/etc/");
+
+    // Absolute paths start at the root of the scheme
+    test!(canonicalize("initfs:/bin/", "/etc/passwd") == "initfs:/etc/passwd");
+    test!(canonicalize("initfs:/bin/", "/") == "initfs:/");
+
+    // Paths with a scheme are resolved from its root, unless their reference has no slash
+    test!(canonicalize("initfs:/bin/", "file:/home") == "file:/home");
+    test!(canonicalize("file:/", "initfs:/bin/../bin/init") == "initfs:/bin/init");
+    test!(canonicalize("file:/", "initfs:/bin/./init/") == "initfs:/bin/init/");
+    test!(canonicalize("file:/", "initfs:/..") == "initfs:/");
+    test!(canonicalize("file:/", "initfs:/../../bin") == "initfs:/bin");
+    test!(canonicalize("file:/", "initfs:///bin//init") == "initfs:/bin/init");
+    test!(canonicalize("file:/", "initfs:/bin/.") == "initfs:/bin/");
+    test!(canonicalize("file:/", "initfs:/") == "initfs:/");
+    test!(canonicalize("file:/", "display:") == "display:");
+    test!(canonicalize("file:/", "tcp:10.0.0.1:80") == "tcp:10.0.0.1:80");
+    test!(canonicalize("file:/", "tcp:80?a/../b") == "tcp:80?a/../b");
+    test!(canonicalize("file:/", "file:/a/../b?c/../d") == "file:/b?c/../d");
+
+    // Dots and repeated slashes are resolved
+    test!(canonicalize("initfs:/bin/", ".") == "initfs:/bin/");
+    test!(canonicalize("initfs:/bin/", "./init") == "initfs:/bin/init");
+    test!(canonicalize("initfs:/bin/", "..") == "initfs:/");
+    test!(canonicalize("initfs:/bin/", "../etc") == "initfs:/etc");
+    test!(canonicalize("initfs:/bin/", "a//b/./c/..") == "initfs:/bin/a/b/");
+    test!(canonicalize("initfs:/bin/", "//etc") == "initfs:/etc");
+    test!(canonicalize("initfs:/bin/", "a///b//") == "initfs:/bin/a/b/");
+    test!(canonicalize("initfs:/bin/", "a/b/") == "initfs:/bin/a/b/");
+
+    // Climbing above the root of the scheme stays at the root
+    test!(canonicalize("initfs:/", "..") == "initfs:/");
+    test!(canonicalize("initfs:/", "../") == "initfs:/");
+    test!(canonicalize("initfs:/", "../x") == "initfs:/x");
+    test!(canonicalize("initfs:/bin/", "../../../etc/") == "initfs:/etc/");
+    succ!();
+}
@@ -0,0 +1,20 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use syscall::{do_sys_chdir, do_sys_getcwd, ENOENT, ENOTDIR};
+
+    let mut before = [0; 4096];
+    let before_len = do_sys_getcwd(before.as_mut_ptr(), before.len()).unwrap_or(0);
+
+    test!(do_sys_chdir("initfs:/does_not_exist\0".as_ptr()).map_err(|err| err.errno) == Err(ENOENT));
+    test!(do_sys_chdir("initfs:/bin/init\0".as_ptr()).map_err(|err| err.errno) == Err(ENOTDIR));
+
+    // A failed chdir leaves the working directory alone
+    let mut after = [0; 4096];
+    let after_len = do_sys_getcwd(after.as_mut_ptr(), after.len()).unwrap_or(0);
+    test!(before[..before_len] == after[..after_len]);
+
+    succ!();
+}
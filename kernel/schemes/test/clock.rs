@@ -0,0 +1,32 @@
+pub fn test() -> bool {
+    use common::time::Duration;
+    use core::ptr;
+    use drivers::rtc::rtc_correct;
+    use syscall::{do_sys_clock_getres, do_sys_clock_gettime, TimeSpec, CLOCK_MONOTONIC, CLOCK_REALTIME,
+                  EFAULT, EINVAL};
+
+    let mut tp = TimeSpec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+
+    // The resolution is below a second, and the PIT period is a little under 4.5 ms
+    for &clock in [CLOCK_REALTIME, CLOCK_MONOTONIC].iter() {
+        test!(do_sys_clock_getres(clock, &mut tp).is_ok());
+        test!(tp.tv_sec == 0 && tp.tv_nsec > 0);
+        test!(tp.tv_nsec <= 4500000);
+    }
+
+    test!(do_sys_clock_getres(2, &mut tp).map_err(|err| err.errno) == Err(EINVAL));
+    test!(do_sys_clock_getres(CLOCK_REALTIME, ptr::null_mut()).map_err(|err| err.errno) == Err(EFAULT));
+    test!(do_sys_clock_gettime(2, &mut tp).map_err(|err| err.errno) == Err(EINVAL));
+    test!(do_sys_clock_gettime(CLOCK_MONOTONIC, ptr::null_mut()).map_err(|err| err.errno) == Err(EFAULT));
+
+    // The real time is only corrected when it is outside the second read from the RTC
+    let rtc = Duration::new(1000, 0);
+    test!(rtc_correct(Duration::new(1000, 500000000), rtc) == Duration::new(1000, 500000000));
+    test!(rtc_correct(Duration::new(999, 900000000), rtc) == rtc);
+    test!(rtc_correct(Duration::new(1002, 0), rtc) == Duration::new(1000, 999999999));
+
+    succ!();
+}
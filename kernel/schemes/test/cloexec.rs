@@ -0,0 +1,161 @@
+use alloc::boxed::Box;
+
+use collections::string::ToString;
+use collections::vec::Vec;
+
+use fs::{KScheme, Resource, Url, VecResource};
+
+use system::error::{Error, Result, ENOENT};
+
+/// Serves a program for each descriptor, at `test_exec:<fd>`, that exits with the result of
+/// `fcntl(fd, F_GETFD)`
+struct ExecScheme;
+
+impl KScheme for ExecScheme {
+    fn scheme(&self) -> &str {
+        "test_exec"
+    }
+
+    fn open(&mut self, url: Url, _: usize) -> Result<Box<Resource>> {
+        match url.reference().parse::<u32>() {
+            Ok(fd) => Ok(box VecResource::new(url.to_string(), program(fd))),
+            Err(_) => Err(Error::new(ENOENT)),
+        }
+    }
+}
+
+/// Build an executable loaded at the start of the image zone, with a single segment
+fn program(fd: u32) -> Vec<u8> {
+    use arch::context::CONTEXT_IMAGE_ADDR;
+    use arch::elf::{ElfAddr, ElfHalf, ElfHeader, ElfOff, ElfSegment, ElfXword, ELF_CLASS};
+    use core::{mem, slice};
+    use syscall::{F_GETFD, SYS_EXIT, SYS_FCNTL};
+
+    let code_offset = mem::size_of::<ElfHeader>() + mem::size_of::<ElfSegment>();
+
+    // The same encoding runs on x86 and x86_64
+    let mut code = Vec::new();
+    let mov = |code: &mut Vec<u8>, op: u8, value: u32| {
+        code.push(op);
+        for i in 0..4 {
+            code.push((value >> (i * 8)) as u8);
+        }
+    };
+    mov(&mut code, 0xB8, SYS_FCNTL as u32); // mov eax, SYS_FCNTL
+    mov(&mut code, 0xBB, fd); // mov ebx, fd
+    mov(&mut code, 0xB9, F_GETFD as u32); // mov ecx, F_GETFD
+    code.extend_from_slice(&[0x31, 0xD2]); // xor edx, edx
+    code.extend_from_slice(&[0xCD, 0x80]); // int 0x80
+    code.extend_from_slice(&[0x89, 0xC3]); // mov ebx, eax
+    mov(&mut code, 0xB8, SYS_EXIT as u32); // mov eax, SYS_EXIT
+    code.extend_from_slice(&[0xCD, 0x80]); // int 0x80
+    code.extend_from_slice(&[0xEB, 0xFE]); // jmp $
+
+    let len = code_offset + code.len();
+
+    let mut header: ElfHeader = unsafe { mem::zeroed() };
+    header.magic = *b"\x7FELF";
+    header.class = ELF_CLASS;
+    header.endian = 1;
+    header.ver = 1;
+    header._type = 2;
+    header.ver_2 = 1;
+    header.entry = (CONTEXT_IMAGE_ADDR + code_offset) as ElfAddr;
+    header.ph_off = mem::size_of::<ElfHeader>() as ElfOff;
+    header.h_len = mem::size_of::<ElfHeader>() as ElfHalf;
+    header.ph_ent_len = mem::size_of::<ElfSegment>() as ElfHalf;
+    header.ph_len = 1;
+
+    let mut segment: ElfSegment = unsafe { mem::zeroed() };
+    segment._type = 1;
+    segment.flags = 5;
+    segment.vaddr = CONTEXT_IMAGE_ADDR as ElfAddr;
+    segment.paddr = CONTEXT_IMAGE_ADDR as ElfAddr;
+    segment.file_len = len as ElfXword;
+    segment.mem_len = len as ElfXword;
+    segment.align = 4096;
+
+    let mut data = Vec::with_capacity(len);
+    unsafe {
+        data.extend_from_slice(slice::from_raw_parts(&header as *const ElfHeader as *const u8,
+                                                     mem::size_of::<ElfHeader>()));
+        data.extend_from_slice(slice::from_raw_parts(&segment as *const ElfSegment as *const u8,
+                                                     mem::size_of::<ElfSegment>()));
+    }
+    data.extend_from_slice(&code);
+    data
+}
+
+/// Open `debug:` with `flags` in a child, and execute a program that reports whether the
+/// descriptor is still open. Returns the exit status of the child
+fn exec_status(flags: usize) -> Option<usize> {
+    use arch::context::Context;
+    use syscall::execute::execute;
+    use syscall::{do_sys_exit, do_sys_open, do_sys_waitpid, O_WRONLY};
+
+    let parent = match ::env().contexts.lock().current() {
+        Ok(current) => current.pid,
+        Err(_) => return None,
+    };
+
+    let pid = Context::spawn("ktest".to_string(), box move || {
+        if let Ok(fd) = do_sys_open("debug:\0".as_ptr(), O_WRONLY | flags) {
+            let _ = execute(vec![format!("test_exec:{}", fd)]);
+        }
+        do_sys_exit(0xFF);
+    });
+    if let Ok(child) = ::env().contexts.lock().find_mut(pid) {
+        child.ppid = parent;
+    }
+
+    let mut status = 0;
+    match do_sys_waitpid(pid as isize, &mut status, 0) {
+        Ok(_) => Some(status),
+        Err(_) => None,
+    }
+}
+
+/// The new image only has the descriptors without the close-on-exec flag
+fn exec() -> bool {
+    use syscall::{EBADF, O_CLOEXEC};
+
+    ::env().register(box ExecScheme);
+    let closed = exec_status(O_CLOEXEC);
+    let kept = exec_status(0);
+    drop(::env().schemes.lock().remove("test_exec"));
+
+    test!(closed == Some((Error::mux(Err(Error::new(EBADF))) & 0xFF) << 8));
+    test!(kept == Some(0));
+    succ!();
+}
+
+pub fn test() -> bool {
+    use syscall::{do_sys_close, do_sys_dup, do_sys_fcntl, do_sys_open, FD_CLOEXEC, F_GETFD, F_GETFL,
+                  F_SETFD, O_CLOEXEC, O_WRONLY};
+
+    let fd = if let Ok(fd) = do_sys_open("debug:\0".as_ptr(), O_WRONLY | O_CLOEXEC) {
+        fd
+    } else {
+        fail!();
+    };
+
+    // The flag is a descriptor flag, not a status flag
+    test!(do_sys_fcntl(fd, F_GETFD, 0).ok() == Some(FD_CLOEXEC));
+    test!(do_sys_fcntl(fd, F_GETFL, 0).ok() == Some(O_WRONLY));
+
+    // Duplicates do not inherit the flag
+    let dup = if let Ok(dup) = do_sys_dup(fd) {
+        dup
+    } else {
+        fail!();
+    };
+    test!(do_sys_fcntl(dup, F_GETFD, 0).ok() == Some(0));
+    test!(do_sys_close(dup).is_ok());
+
+    test!(do_sys_fcntl(fd, F_SETFD, 0).is_ok());
+    test!(do_sys_fcntl(fd, F_GETFD, 0).ok() == Some(0));
+    test!(do_sys_close(fd).is_ok());
+
+    test!(super::in_kernel_context(exec));
+    succ!();
+}
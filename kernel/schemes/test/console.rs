@@ -0,0 +1,49 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use syscall::{do_sys_close, do_sys_open, do_sys_read, do_sys_write, EINVAL, O_RDWR};
+
+    let fd = if let Ok(fd) = do_sys_open("debug:mode\0".as_ptr(), O_RDWR) {
+        fd
+    } else {
+        fail!();
+    };
+
+    let mut buf = [0; 32];
+    let count = if let Ok(count) = do_sys_read(fd, buf.as_mut_ptr(), buf.len()) {
+        count
+    } else {
+        fail!();
+    };
+    let saved = buf;
+
+    // Turn echo off, and check the settings
+    test!(do_sys_write(fd, b"canon noecho noserial".as_ptr(), 21).ok() == Some(21));
+    test!(! ::env().console.lock().echo);
+    test!(! ::env().console.lock().raw_mode);
+    test!(! ::env().console.lock().serial);
+
+    let reopened = if let Ok(fd) = do_sys_open("debug:mode\0".as_ptr(), O_RDWR) {
+        fd
+    } else {
+        fail!();
+    };
+    test!(do_sys_read(reopened, buf.as_mut_ptr(), buf.len()).ok() == Some(22));
+    test!(&buf[..22] == b"canon noecho noserial\n");
+    test!(do_sys_close(reopened).is_ok());
+
+    // COM1 becomes the console at runtime
+    test!(do_sys_write(fd, b"serial".as_ptr(), 6).ok() == Some(6));
+    test!(::env().console.lock().serial);
+
+    // Unknown settings change nothing
+    test!(do_sys_write(fd, b"echo sideways".as_ptr(), 13).map_err(|err| err.errno) == Err(EINVAL));
+    test!(! ::env().console.lock().echo);
+
+    // Restore the settings
+    test!(do_sys_write(fd, saved.as_ptr(), count).ok() == Some(count));
+    test!(do_sys_close(fd).is_ok());
+    succ!();
+}
@@ -0,0 +1,138 @@
+use alloc::arc::Arc;
+
+use arch::context::ContextZone;
+use arch::memory;
+
+use core::cell::UnsafeCell;
+
+pub fn test() -> bool {
+    use arch::context::ContextMemory;
+    use core::ptr;
+
+    let physical_address = unsafe { memory::alloc_aligned(4096, 4096) };
+    if physical_address == 0 {
+        fail!();
+    }
+    unsafe { ptr::write(physical_address as *mut u32, 0x12345678) };
+
+    let mut parent = ContextMemory {
+        physical_address: physical_address,
+        virtual_address: 0x80000000,
+        virtual_size: 4096,
+        writeable: true,
+        allocated: true,
+    };
+    test!(! parent.shared());
+
+    // Sharing counts both mappings
+    let mut child = parent.share();
+    test!(child.physical_address == physical_address);
+    test!(parent.shared() && child.shared());
+
+    // The first to write gets a copy, and the other owns the memory again
+    test!(unsafe { child.unshare() });
+    test!(child.physical_address != physical_address);
+    test!(unsafe { ptr::read(child.physical_address as *const u32) } == 0x12345678);
+    test!(! parent.shared() && ! child.shared());
+    test!(unsafe { parent.unshare() });
+    test!(parent.physical_address == physical_address);
+
+    // Dropping the last mapping of shared memory frees it
+    let other = parent.share();
+    drop(parent);
+    test!(unsafe { memory::alloc_size(physical_address) } > 0);
+    test!(! other.shared());
+    drop(other);
+    test!(unsafe { memory::alloc_size(physical_address) } == 0);
+    drop(child);
+
+    test!(fork_write());
+    succ!();
+}
+
+/// Share a page of the current context like `sys_fork` does, and write to it with a syscall. The
+/// writer gets its own copy, and the copy of the other side is unchanged
+fn fork_write() -> bool {
+    use arch::context::{ContextMemory, CONTEXT_MMAP_ADDR, CONTEXT_MMAP_SIZE};
+    use core::ptr;
+
+    // Syscalls only check the memory of userspace contexts, and copy it when it is shared
+    let mmap = {
+        let contexts = ::env().contexts.lock();
+        match contexts.current() {
+            Ok(current) if current.stack.is_some() => current.mmap.clone(),
+            _ => { fail!(); }
+        }
+    };
+
+    let address = CONTEXT_MMAP_ADDR + CONTEXT_MMAP_SIZE - 4096;
+    let physical_address = unsafe { memory::alloc_aligned(4096, 4096) };
+    if physical_address == 0 {
+        fail!();
+    }
+    unsafe {
+        ptr::write_bytes(physical_address as *mut u8, 0, 4096);
+        ptr::copy(b"abcd".as_ptr(), physical_address as *mut u8, 4);
+
+        let mut mem = ContextMemory {
+            physical_address: physical_address,
+            virtual_address: address,
+            virtual_size: 4096,
+            writeable: true,
+            allocated: true,
+        };
+        mem.map();
+        (*mmap.get()).memory.push(mem);
+    }
+
+    let passed = fork_write_checks(address, physical_address, &mmap);
+
+    // Forget the page again
+    unsafe {
+        let zone = &mut *mmap.get();
+        if let Some(i) = zone.memory.iter().position(|mem| mem.virtual_address == address) {
+            let mut mem = zone.memory.remove(i);
+            mem.unmap();
+        }
+    }
+
+    passed
+}
+
+fn fork_write_checks(address: usize, physical_address: usize, mmap: &Arc<UnsafeCell<ContextZone>>) -> bool {
+    use core::{ptr, slice};
+    use syscall::{do_sys_close, do_sys_pipe2, do_sys_read, do_sys_write};
+
+    // The descriptors are written before the fork, into memory the syscall accepts
+    let fds = (address + 16) as *mut usize;
+    test!(do_sys_pipe2(fds, 0).is_ok());
+    let (read, write) = unsafe { (ptr::read(fds), ptr::read(fds.offset(1))) };
+
+    // Fork: the other side shares the page, and it becomes read only here
+    let other = unsafe { (*mmap.get()).share() };
+    unsafe { (*mmap.get()).map(); }
+    let shared = other.memory.iter().find(|mem| mem.virtual_address == address).map(|mem| mem.physical_address);
+    test!(shared == Some(physical_address));
+
+    // Reading from the page does not copy it, writing to it does
+    test!(do_sys_write(write, address as *const u8, 4).ok() == Some(4));
+    test!(unsafe { (*mmap.get()).translate(address, 4) } == Some(physical_address));
+    test!(do_sys_read(read, (address + 8) as *mut u8, 4).ok() == Some(4));
+
+    let own = unsafe { (*mmap.get()).translate(address, 4) };
+    test!(own.is_some() && own != Some(physical_address));
+    if let Some(own) = own {
+        test!(unsafe { slice::from_raw_parts((own + 8) as *const u8, 4) } == b"abcd");
+    }
+    test!(unsafe { slice::from_raw_parts((physical_address + 8) as *const u8, 4) } == [0; 4]);
+    test!(unsafe { slice::from_raw_parts(address as *const u8, 12) } == b"abcd\0\0\0\0abcd");
+
+    // The other side owns the original page again, and frees it when it is gone
+    test!(other.memory.iter().all(|mem| ! mem.shared()));
+    drop(other);
+    test!(unsafe { memory::alloc_size(physical_address) } == 0);
+
+    test!(do_sys_close(read).is_ok());
+    test!(do_sys_close(write).is_ok());
+    succ!();
+}
@@ -0,0 +1,110 @@
+pub fn test() -> bool {
+    use collections::vec::Vec;
+    use network::common::{n32, is_broadcast, next_hop, Ipv4Addr, BROADCAST_IP_ADDR, DNS_ADDR, IP_ADDR,
+                          IP_ROUTER_ADDR, IP_SUBNET_MASK, MAC_ADDR, UNSPECIFIED_IP_ADDR};
+    use network::dhcp::{self, DhcpLease, DHCP_ACK, DHCP_DISCOVER, DHCP_NAK, DHCP_OFFER, DHCP_REQUEST};
+
+    let addr = |a: u8, b: u8, c: u8, d: u8| Ipv4Addr { bytes: [a, b, c, d] };
+
+    // A discover comes from no address, asks for a broadcast answer and names this host
+    let discover = dhcp::message(DHCP_DISCOVER, 0x12345678, UNSPECIFIED_IP_ADDR, None);
+    test!(discover.len() > 240);
+    test!(discover[0] == 1 && discover[1] == 1 && discover[2] == 6);
+    test!(discover[4..8] == n32::new(0x12345678).bytes[..]);
+    test!(discover[10] == 0x80);
+    test!(discover[28..34] == unsafe { MAC_ADDR }.bytes[..]);
+    test!(discover[236..243] == [99, 130, 83, 99, 53, 1, DHCP_DISCOVER][..]);
+    test!(discover[discover.len() - 1] == 255);
+
+    // A request for an offer names the offered address and its server, and a renewal does not
+    let request = dhcp::message(DHCP_REQUEST, 1, UNSPECIFIED_IP_ADDR, Some((addr(10, 0, 2, 15), addr(10, 0, 2, 2))));
+    test!(request[240..].windows(6).any(|option| option == [50, 4, 10, 0, 2, 15]));
+    test!(request[240..].windows(6).any(|option| option == [54, 4, 10, 0, 2, 2]));
+    let renewal = dhcp::message(DHCP_REQUEST, 1, addr(10, 0, 2, 15), None);
+    test!(renewal[10] == 0 && renewal[12..16] == [10, 0, 2, 15][..]);
+    test!(! renewal[240..].windows(2).any(|option| option == [50, 4]));
+
+    let answer = |kind: u8, xid: u32, options: &[u8]| -> Vec<u8> {
+        let mut bytes = vec![0; 240];
+        bytes[0] = 2;
+        for i in 0..4 {
+            bytes[4 + i] = n32::new(xid).bytes[i];
+        }
+        bytes[16..20].clone_from_slice(&[10, 0, 2, 15]);
+        bytes[236..240].clone_from_slice(&[99, 130, 83, 99]);
+        bytes.extend_from_slice(&[53, 1, kind, 0, 0]);
+        bytes.extend_from_slice(options);
+        bytes.push(255);
+        bytes
+    };
+
+    // An offer with every option
+    let offer = answer(DHCP_OFFER, 7, &[1, 4, 255, 255, 0, 0,
+                                       3, 4, 10, 0, 2, 2,
+                                       6, 8, 10, 0, 2, 3, 8, 8, 8, 8,
+                                       54, 4, 10, 0, 2, 4,
+                                       51, 4, 0, 0, 0x0E, 0x10,
+                                       58, 4, 0, 0, 0x03, 0x84]);
+    match dhcp::parse(&offer, 7) {
+        Some(reply) => {
+            test!(reply.kind == DHCP_OFFER);
+            test!(reply.lease.addr.equals(addr(10, 0, 2, 15)));
+            test!(reply.lease.mask.equals(addr(255, 255, 0, 0)));
+            test!(reply.lease.router.equals(addr(10, 0, 2, 2)));
+            test!(reply.lease.dns.equals(addr(10, 0, 2, 3)));
+            test!(reply.lease.server.equals(addr(10, 0, 2, 4)));
+            test!(reply.lease.lease == 3600 && reply.lease.renew == 900);
+        },
+        None => { fail!(); }
+    }
+
+    // Answers to other transactions, and truncated options, are ignored
+    test!(dhcp::parse(&offer, 8).is_none());
+    test!(dhcp::parse(&offer[.. 200], 7).is_none());
+    test!(dhcp::parse(&answer(DHCP_ACK, 7, &[51, 4, 0, 0]), 7).is_none());
+
+    // Without a renewal time, the lease is renewed halfway
+    match dhcp::parse(&answer(DHCP_ACK, 7, &[51, 4, 0, 0, 0x0E, 0x10]), 7) {
+        Some(reply) => test!(reply.kind == DHCP_ACK && reply.lease.renew == 1800),
+        None => { fail!(); }
+    }
+    match dhcp::parse(&answer(DHCP_NAK, 7, &[]), 7) {
+        Some(reply) => test!(reply.kind == DHCP_NAK),
+        None => { fail!(); }
+    }
+
+    let saved = unsafe { (IP_ADDR, IP_SUBNET_MASK, IP_ROUTER_ADDR, BROADCAST_IP_ADDR, DNS_ADDR) };
+
+    dhcp::install(&DhcpLease {
+        addr: addr(192, 168, 4, 20),
+        mask: addr(255, 255, 252, 0),
+        router: addr(192, 168, 4, 1),
+        dns: addr(192, 168, 4, 1),
+        server: addr(192, 168, 4, 1),
+        lease: 3600,
+        renew: 1800,
+    });
+    let installed = unsafe { BROADCAST_IP_ADDR.equals(addr(192, 168, 7, 255)) && IP_ADDR.equals(addr(192, 168, 4, 20)) };
+    let broadcast = is_broadcast(addr(192, 168, 7, 255)) && is_broadcast(addr(255, 255, 255, 255)) &&
+                    ! is_broadcast(addr(192, 168, 4, 255));
+    let routed = next_hop(addr(192, 168, 6, 9)).equals(addr(192, 168, 6, 9)) &&
+                 next_hop(addr(8, 8, 8, 8)).equals(addr(192, 168, 4, 1));
+
+    dhcp::fallback();
+    let fallback = unsafe { IP_ADDR.equals(addr(10, 85, 85, 2)) && BROADCAST_IP_ADDR.equals(addr(10, 85, 85, 255)) };
+
+    unsafe {
+        IP_ADDR = saved.0;
+        IP_SUBNET_MASK = saved.1;
+        IP_ROUTER_ADDR = saved.2;
+        BROADCAST_IP_ADDR = saved.3;
+        DNS_ADDR = saved.4;
+    }
+
+    test!(installed);
+    test!(broadcast);
+    test!(routed);
+    test!(fallback);
+
+    succ!();
+}
@@ -0,0 +1,32 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use syscall::{do_sys_close, do_sys_fstat, do_sys_open, Stat, EISDIR, ENOTDIR, MODE_DIR, MODE_TYPE,
+                  O_DIRECTORY, O_RDONLY, O_WRONLY};
+
+    let folder = "initfs:/bin\0".as_ptr();
+    let file = "initfs:/bin/init\0".as_ptr();
+
+    let fd = if let Ok(fd) = do_sys_open(folder, O_RDONLY | O_DIRECTORY) {
+        fd
+    } else {
+        fail!();
+    };
+    let mut stat = Stat::default();
+    test!(do_sys_fstat(fd, &mut stat).is_ok());
+    test!(stat.st_mode & MODE_TYPE == MODE_DIR);
+    test!(do_sys_close(fd).is_ok());
+
+    test!(do_sys_open(folder, O_WRONLY).map_err(|err| err.errno) == Err(EISDIR));
+    test!(do_sys_open(file, O_RDONLY | O_DIRECTORY).map_err(|err| err.errno) == Err(ENOTDIR));
+
+    let fd = if let Ok(fd) = do_sys_open(file, O_RDONLY) {
+        fd
+    } else {
+        fail!();
+    };
+    test!(do_sys_close(fd).is_ok());
+    succ!();
+}
@@ -0,0 +1,40 @@
+pub fn test() -> bool {
+    use core::ptr;
+    use graphics::display::{Damage, Display};
+
+    // Only the damage is tracked here, so no buffers are needed
+    let mut display = Display {
+        offscreen: ptr::null_mut(),
+        onscreen: ptr::null_mut(),
+        backbuffer: ptr::null_mut(),
+        damage: None,
+        stale: false,
+        size: 100,
+        width: 10,
+        height: 10,
+        stride: 10,
+    };
+
+    display.damage_range(12, 0);
+    test!(display.damage.is_none());
+
+    // Within one scanline only the written columns are damaged
+    display.damage_range(12, 3);
+    test!(display.damage == Some(Damage { x1: 2, y1: 1, x2: 5, y2: 2 }));
+
+    // Across scanlines the whole width is
+    display.damage = None;
+    display.damage_range(18, 4);
+    test!(display.damage == Some(Damage { x1: 0, y1: 1, x2: 10, y2: 3 }));
+
+    // Writes between syncs are joined
+    display.damage = None;
+    display.damage_range(12, 1);
+    display.damage_range(57, 2);
+    test!(display.damage == Some(Damage { x1: 2, y1: 1, x2: 9, y2: 6 }));
+
+    display.damage_range(99, 1);
+    test!(display.damage == Some(Damage { x1: 2, y1: 1, x2: 10, y2: 10 }));
+
+    succ!();
+}
@@ -0,0 +1,18 @@
+pub fn test() -> bool {
+    use arch::memory::{self, CLUSTER_SIZE};
+
+    let address = unsafe { memory::alloc_dma32(2 * CLUSTER_SIZE, 4) };
+    test!(address > 0);
+
+    // The whole buffer can be reached by a 32-bit device
+    let physical = memory::virt_to_phys(address) as u64;
+    test!(physical + 2 * CLUSTER_SIZE as u64 <= 0x100000000);
+
+    // Translation keeps the offset in the page, and kernel memory is identity mapped
+    test!(memory::virt_to_phys(address + CLUSTER_SIZE + 5) == memory::virt_to_phys(address + CLUSTER_SIZE) + 5);
+    test!(memory::virt_to_phys(address) == address);
+
+    unsafe { memory::unalloc(address) };
+
+    succ!();
+}
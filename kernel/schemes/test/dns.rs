@@ -0,0 +1,61 @@
+pub fn test() -> bool {
+    use collections::string::{String, ToString};
+    use common::time::Duration;
+    use fs::Url;
+    use network::common::Ipv4Addr;
+    use network::dns::{DnsCache, DNS_CACHE_MAX};
+
+    let addr = |last: u8| Ipv4Addr { bytes: [10, 0, 2, last] };
+    let now = Duration::new(1000, 0);
+
+    // Entries are used until their TTL runs out
+    let mut cache = DnsCache::new();
+    cache.insert("a.example".to_string(), addr(1), now + Duration::new(30, 0), now);
+    test!(cache.get("a.example", now + Duration::new(29, 0)).map(|addr| addr.bytes) == Some(addr(1).bytes));
+    test!(cache.get("a.example", now + Duration::new(30, 0)).is_none());
+    test!(cache.get("a.example", now).is_none());
+
+    // Once full, expired entries and then those closest to expiring make room
+    let mut cache = DnsCache::new();
+    for i in 0..DNS_CACHE_MAX {
+        cache.insert(format!("{}.example", i), addr(i as u8), now + Duration::new(100 + i as i64, 0), now);
+    }
+    cache.insert("new.example".to_string(), addr(200), now + Duration::new(10, 0), now);
+    test!(cache.get("0.example", now).is_none());
+    test!(cache.get("1.example", now).is_some());
+    test!(cache.get("new.example", now).is_some());
+
+    let later = now + Duration::new(101, 0);
+    cache.insert("newer.example".to_string(), addr(201), later + Duration::new(10, 0), later);
+    test!(cache.get("1.example", later).is_none());
+    test!(cache.get("2.example", later).is_some());
+
+    // The listing skips expired entries and shows the TTL left
+    let list = cache.list(later);
+    test!(list.starts_with("NAME"));
+    test!(list.contains(&format!("{:<32}{:<16}{}\n", "2.example", addr(2).to_string(), 1)));
+    test!(list.contains(&format!("{:<32}{:<16}{}\n", "newer.example", addr(201).to_string(), 10)));
+    test!(! list.contains("new.example "));
+
+    // The scheme answers cached names and addresses without asking a nameserver
+    let now = Duration::realtime();
+    ::env().dns.lock().insert("test.dns.invalid".to_string(), addr(9), now + Duration::new(60, 0), now);
+
+    let read = |path: &str| -> Option<String> {
+        let mut buf = [0; 4096];
+        let result = Url::from_str(path).and_then(|url| ::env().open(url, 0)).and_then(|mut resource| resource.read(&mut buf));
+        match result {
+            Ok(count) => String::from_utf8(buf[.. count].to_vec()).ok(),
+            Err(_) => None,
+        }
+    };
+
+    test!(read("dns:test.dns.invalid") == Some("10.0.2.9".to_string()));
+    test!(read("dns:10.85.85.1") == Some("10.85.85.1".to_string()));
+    match read("dns:") {
+        Some(list) => test!(list.contains("test.dns.invalid")),
+        None => { fail!(); }
+    }
+
+    succ!();
+}
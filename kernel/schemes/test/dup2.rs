@@ -0,0 +1,25 @@
+pub fn test() -> bool {
+    use syscall::{do_sys_close, do_sys_dup, do_sys_dup2, do_sys_open, O_WRONLY};
+
+    // Keep the current stderr, so that it can be restored
+    let saved = if let Ok(fd) = do_sys_dup(2) {
+        fd
+    } else {
+        fail!();
+    };
+
+    let fd = if let Ok(fd) = do_sys_open("debug:\0".as_ptr(), O_WRONLY) {
+        fd
+    } else {
+        fail!();
+    };
+
+    test!(do_sys_dup2(fd, 2).ok() == Some(2));
+    test!(do_sys_dup2(2, 2).ok() == Some(2));
+    test!(do_sys_dup2(saved, 2).ok() == Some(2));
+    test!(do_sys_close(fd).is_ok());
+    test!(do_sys_close(saved).is_ok());
+    test!(do_sys_close(fd).is_err());
+    test!(do_sys_dup2(fd, 2).is_err());
+    succ!();
+}
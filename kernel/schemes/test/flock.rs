@@ -0,0 +1,39 @@
+pub fn test() -> bool {
+    use collections::Vec;
+    use collections::string::ToString;
+    use fs::VecResource;
+    use syscall::{do_sys_close, do_sys_flock, EINVAL, EWOULDBLOCK, LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN,
+                  O_RDWR};
+
+    // Open the same path twice
+    let first = match super::install_file(box VecResource::new("test:flock".to_string(), Vec::new()), O_RDWR) {
+        Some(fd) => fd,
+        None => { fail!(); }
+    };
+    let second = match super::install_file(box VecResource::new("test:flock".to_string(), Vec::new()), O_RDWR) {
+        Some(fd) => fd,
+        None => { fail!(); }
+    };
+
+    // An exclusive lock excludes every other lock
+    test!(do_sys_flock(first, LOCK_EX).is_ok());
+    test!(do_sys_flock(second, LOCK_SH | LOCK_NB).map_err(|err| err.errno) == Err(EWOULDBLOCK));
+    test!(do_sys_flock(second, LOCK_EX | LOCK_NB).map_err(|err| err.errno) == Err(EWOULDBLOCK));
+
+    // Shared locks can be held together, once the exclusive lock is converted
+    test!(do_sys_flock(first, LOCK_SH).is_ok());
+    test!(do_sys_flock(second, LOCK_SH | LOCK_NB).is_ok());
+    test!(do_sys_flock(first, LOCK_EX | LOCK_NB).map_err(|err| err.errno) == Err(EWOULDBLOCK));
+
+    // Unlocking and closing release the lock
+    test!(do_sys_flock(second, LOCK_UN).is_ok());
+    test!(do_sys_flock(first, LOCK_EX | LOCK_NB).is_ok());
+    test!(do_sys_flock(second, LOCK_SH | LOCK_NB).map_err(|err| err.errno) == Err(EWOULDBLOCK));
+    test!(do_sys_close(first).is_ok());
+    test!(do_sys_flock(second, LOCK_EX | LOCK_NB).is_ok());
+
+    test!(do_sys_flock(second, LOCK_SH | LOCK_EX).map_err(|err| err.errno) == Err(EINVAL));
+    test!(do_sys_flock(first, LOCK_SH).is_err());
+    test!(do_sys_close(second).is_ok());
+    succ!();
+}
@@ -0,0 +1,31 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use syscall::{do_sys_chdir, do_sys_getcwd, EFAULT, ERANGE};
+
+    let mut old = [0; 4096];
+    let old_len = match do_sys_getcwd(old.as_mut_ptr(), old.len()) {
+        Ok(len) => len,
+        Err(_) => { fail!(); },
+    };
+    let mut old_path = old[..old_len].to_vec();
+    old_path.push(0);
+
+    test!(do_sys_chdir("initfs:/bin\0".as_ptr()).is_ok());
+
+    let mut buf = [0; 4096];
+    let len = do_sys_getcwd(buf.as_mut_ptr(), buf.len()).unwrap_or(0);
+    let matches = &buf[..len] == b"initfs:/bin/";
+
+    let too_small = do_sys_getcwd(buf.as_mut_ptr(), len.saturating_sub(1)).map_err(|err| err.errno);
+    let null = do_sys_getcwd(0 as *mut u8, buf.len()).map_err(|err| err.errno);
+
+    test!(do_sys_chdir(old_path.as_ptr()).is_ok());
+
+    test!(matches);
+    test!(too_small == Err(ERANGE));
+    test!(null == Err(EFAULT));
+    succ!();
+}
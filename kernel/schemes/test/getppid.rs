@@ -0,0 +1,22 @@
+pub fn test() -> bool {
+    use arch::context::{Context, CONTEXT_ROOT_PID};
+    use collections::string::ToString;
+    use syscall::{do_sys_exit, do_sys_getppid};
+
+    let contexts = ::env().contexts.lock();
+
+    let ppid = match contexts.current() {
+        Ok(current) => current.ppid,
+        Err(_) => {
+            fail!();
+        },
+    };
+    test!(do_sys_getppid().ok() == Some(ppid));
+
+    // Kernel contexts are children of the root context, which removes them when they exit
+    let pid = Context::spawn("ktest".to_string(), box || {
+        do_sys_exit(0);
+    });
+    test!(contexts.find(pid).map(|child| child.ppid).ok() == Some(CONTEXT_ROOT_PID));
+    succ!();
+}
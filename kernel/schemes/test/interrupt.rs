@@ -0,0 +1,37 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use collections::String;
+    use core::str;
+    use syscall::{do_sys_close, do_sys_open, do_sys_read, O_RDONLY};
+
+    fn timer_count() -> Option<u64> {
+        let fd = match do_sys_open("interrupt:\0".as_ptr(), O_RDONLY) {
+            Ok(fd) => fd,
+            Err(_) => return None,
+        };
+        let mut buf = [0; 8192];
+        let len = do_sys_read(fd, buf.as_mut_ptr(), buf.len()).unwrap_or(0);
+        let _ = do_sys_close(fd);
+
+        let string = String::from(str::from_utf8(&buf[..len]).unwrap_or(""));
+        for line in string.lines() {
+            let mut parts = line.split_whitespace();
+            if parts.next() == Some("20") {
+                if let Some(count) = parts.next() {
+                    return count.parse().ok();
+                }
+            }
+        }
+        None
+    }
+
+    // The timer has fired, and reading does not reset its count
+    let first = timer_count();
+    test!(first.is_some());
+    let second = timer_count();
+    test!(second >= first);
+    succ!();
+}
@@ -0,0 +1,65 @@
+pub fn test() -> bool {
+    use arch::intex::intex_pid;
+    use fs::Url;
+    use logging::{klog, klog_flush, LogLevel};
+    use sync::Intex;
+
+    let intex = Intex::new(0);
+    let pid = unsafe { intex_pid };
+
+    // The running context can not lock again what it holds
+    {
+        let _guard = intex.lock();
+        test!(intex.holder() == Some(pid));
+        test!(intex.try_lock().is_none());
+    }
+    test!(intex.holder().is_none());
+    test!(intex.try_lock().is_some());
+
+    // Dropping a nested guard leaves the Intex held by the outer one
+    {
+        let _outer = intex.lock();
+        {
+            let _inner = intex.lock();
+        }
+        test!(intex.holder() == Some(pid));
+        test!(intex.try_lock().is_none());
+    }
+    test!(intex.holder().is_none());
+
+    // IRQs that come in while the schemes are held are passed on once they are released. No
+    // device uses IRQ 2, which cascades the second PIC
+    {
+        let _schemes = ::env().schemes.lock();
+        ::env().on_irq(2);
+        test!(*::env().irq_pending.lock() & 1 << 2 != 0);
+    }
+    test!(*::env().irq_pending.lock() & 1 << 2 == 0);
+
+    // A guard taken by another context does not keep the running one out
+    let free = {
+        let _guard = intex.lock();
+        unsafe { intex_pid = pid + 1; }
+        let free = intex.try_lock().is_some();
+        unsafe { intex_pid = pid; }
+        free
+    };
+    test!(free);
+
+    // Entries logged while the logs are held are staged until they are free
+    let seq = ::env().logs.lock().next_seq();
+    {
+        let _logs = ::env().logs.lock();
+        klog(LogLevel::Critical, "intex test staged");
+        test!(! ::env().log_staging.lock().is_empty());
+    }
+    klog_flush();
+    test!(::env().log_staging.lock().is_empty());
+    let (lines, _) = ::env().logs.lock().lines_since(seq, LogLevel::Critical);
+    test!(lines.contains("CRIT intex test staged"));
+
+    // Lock statistics are only kept by debug builds
+    test!(Url::from_str("debug:locks").and_then(|url| url.open()).is_ok() == cfg!(debug_assertions));
+
+    succ!();
+}
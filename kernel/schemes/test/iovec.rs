@@ -0,0 +1,62 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use collections::string::String;
+    use collections::vec::Vec;
+    use fs::VecResource;
+    use syscall::{do_sys_close, do_sys_pipe2, do_sys_readv, do_sys_write, do_sys_writev, IoVec, EINVAL, IOV_MAX,
+                  O_RDONLY};
+
+    let mut fds = [0; 2];
+    test!(do_sys_pipe2(fds.as_mut_ptr(), 0).is_ok());
+
+    let out = [IoVec { iov_base: b"abc".as_ptr() as usize, iov_len: 3 },
+               IoVec { iov_base: b"de".as_ptr() as usize, iov_len: 2 }];
+    test!(do_sys_writev(fds[1], out.as_ptr(), out.len()).ok() == Some(5));
+
+    // The data is scattered over the buffers in order
+    let mut a = [0; 3];
+    let mut b = [0; 4];
+    let mut c = [0; 4];
+    let iov = [IoVec { iov_base: a.as_mut_ptr() as usize, iov_len: a.len() },
+               IoVec { iov_base: b.as_mut_ptr() as usize, iov_len: b.len() },
+               IoVec { iov_base: c.as_mut_ptr() as usize, iov_len: c.len() }];
+    test!(do_sys_readv(fds[0], iov.as_ptr(), iov.len()).ok() == Some(5));
+    test!(&a == b"abc");
+    test!(&b[..2] == b"de");
+
+    // Data that exactly fills the first buffer is returned without waiting for more
+    test!(do_sys_write(fds[1], b"fgh".as_ptr(), 3).ok() == Some(3));
+    test!(do_sys_readv(fds[0], iov.as_ptr(), iov.len()).ok() == Some(3));
+    test!(&a == b"fgh");
+
+    // The same buffer may be given many times, without the kernel allocating for all of them
+    let repeated: Vec<IoVec> = (0..IOV_MAX).map(|_| IoVec { iov_base: c.as_mut_ptr() as usize, iov_len: 1 << 20 })
+                                           .collect();
+    test!(do_sys_write(fds[1], b"ijkl".as_ptr(), 4).ok() == Some(4));
+    test!(do_sys_readv(fds[0], repeated.as_ptr(), repeated.len()).ok() == Some(4));
+    test!(&c == b"ijkl");
+
+    test!(do_sys_readv(fds[0], iov.as_ptr(), IOV_MAX + 1).map_err(|err| err.errno) == Err(EINVAL));
+
+    // Reads larger than a piece are scattered over the buffers across the pieces
+    let data: Vec<u8> = (0..100000).map(|i| (i % 251) as u8).collect();
+    let fd = match super::install_file(box VecResource::new(String::new(), data.clone()), O_RDONLY) {
+        Some(fd) => fd,
+        None => { fail!(); }
+    };
+    let mut first = vec![0; 70000];
+    let mut second = vec![0; 40000];
+    let iov = [IoVec { iov_base: first.as_mut_ptr() as usize, iov_len: first.len() },
+               IoVec { iov_base: second.as_mut_ptr() as usize, iov_len: second.len() }];
+    test!(do_sys_readv(fd, iov.as_ptr(), iov.len()).ok() == Some(100000));
+    test!(first[..] == data[.. 70000]);
+    test!(second[.. 30000] == data[70000 ..]);
+    test!(do_sys_close(fd).is_ok());
+
+    test!(do_sys_close(fds[0]).is_ok());
+    test!(do_sys_close(fds[1]).is_ok());
+    succ!();
+}
@@ -0,0 +1,120 @@
+use alloc::boxed::Box;
+
+use collections::vec::Vec;
+use collections::vec_deque::VecDeque;
+
+use fs::Resource;
+
+use system::error::{Error, Result, EIO};
+
+/// A link that reads one frame at a time
+struct Frames {
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl Resource for Frames {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self.frames.pop_front() {
+            Some(frame) => {
+                buf[.. frame.len()].clone_from_slice(&frame);
+                Ok(frame.len())
+            },
+            None => Err(Error::new(EIO)),
+        }
+    }
+}
+
+pub fn test() -> bool {
+    use common::time::Duration;
+    use core::mem;
+    use network::common::{n16, Checksum, Ipv4Addr, ToBytes, IP_ADDR};
+    use network::ipv4::{Ipv4, Ipv4Header, IpReassembly, IP_DF, IP_MF, IP_MTU, IP_OFFSET};
+    use network::schemes::udp::{Udp, UdpListener};
+
+    let peer_addr = Ipv4Addr { bytes: [10, 0, 2, 2] };
+    let payload: Vec<u8> = (0..4096).map(|i| i as u8).collect();
+    let udp = Udp::new(4096, peer_addr, 7, payload.clone()).to_bytes();
+
+    let ip = Ipv4 {
+        header: Ipv4Header {
+            ver_hlen: 0x45,
+            services: 0,
+            len: n16::new((20 + udp.len()) as u16),
+            id: n16::new(0x1234),
+            flags_fragment: n16::new(0),
+            ttl: 128,
+            proto: 0x11,
+            checksum: Checksum { data: 0 },
+            src: unsafe { IP_ADDR },
+            dst: peer_addr,
+        },
+        options: Vec::new(),
+        data: udp.clone(),
+    };
+
+    // A 4KB datagram takes three frames, each with a valid header
+    let fragments = ip.fragments(IP_MTU);
+    test!(fragments.len() == 3);
+    let mut offset = 0;
+    for (i, fragment) in fragments.iter().enumerate() {
+        let last = i + 1 == fragments.len();
+        let flags_fragment = fragment.header.flags_fragment.get();
+
+        test!(fragment.to_bytes().len() <= IP_MTU);
+        test!(fragment.header.len.get() as usize == 20 + fragment.data.len());
+        test!(fragment.header.id.get() == 0x1234);
+        test!((flags_fragment & IP_OFFSET) as usize * 8 == offset);
+        test!((flags_fragment & IP_MF == IP_MF) == ! last);
+        test!(flags_fragment & IP_DF == 0);
+        test!(last || fragment.data.len() % 8 == 0);
+        test!(fragment.data[..] == udp[offset .. offset + fragment.data.len()]);
+        test!(unsafe {
+            fragment.header.checksum.check(&fragment.header as *const Ipv4Header as usize,
+                                           mem::size_of::<Ipv4Header>())
+        });
+
+        offset += fragment.data.len();
+    }
+    test!(offset == udp.len());
+
+    // The fragments reassemble into the datagram, in any order
+    let mut reassembly = IpReassembly::new();
+    let now = Duration::new(0, 0);
+    let mut whole = None;
+    for fragment in fragments.into_iter().rev() {
+        whole = reassembly.push(fragment, now);
+    }
+    test!(whole.map(|packet| packet.data) == Some(udp));
+
+    // A bound socket reassembles the datagram before taking it
+    let inbound = Ipv4 {
+        header: Ipv4Header {
+            src: peer_addr,
+            dst: unsafe { IP_ADDR },
+            .. ip.header
+        },
+        options: Vec::new(),
+        data: ip.data.clone(),
+    };
+    let frames = inbound.fragments(IP_MTU).iter().map(|fragment| fragment.to_bytes()).collect();
+    let mut listener = UdpListener::new(box Frames { frames: frames }, "", 7);
+    let mut buf = [0; 8192];
+    test!(listener.read(&mut buf).ok() == Some(payload.len()));
+    test!(buf[.. payload.len()] == payload[..]);
+    let mut path = [0; 64];
+    let count = listener.path(&mut path).unwrap_or(0);
+    test!(&path[.. count] == b"udp:10.0.2.2:4096/7");
+
+    // A packet that fits is sent whole
+    let small = Ipv4 {
+        header: ip.header,
+        options: Vec::new(),
+        data: ip.data[.. 100].to_vec(),
+    };
+    let fragments = small.fragments(IP_MTU);
+    test!(fragments.len() == 1);
+    test!(fragments[0].header.flags_fragment.get() == 0);
+    test!(fragments[0].header.len.get() == 120);
+
+    succ!();
+}
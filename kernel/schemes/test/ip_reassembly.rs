@@ -0,0 +1,62 @@
+pub fn test() -> bool {
+    use collections::vec::Vec;
+    use common::time::Duration;
+    use network::common::{n16, Checksum, Ipv4Addr};
+    use network::ipv4::{Ipv4, Ipv4Header, IpReassembly, IP_DF, IP_MF, IP_OFFSET,
+                        IP_REASSEMBLY_TIMEOUT};
+
+    let fragment = |id: u16, flags: u16, offset: usize, data: &[u8]| Ipv4 {
+        header: Ipv4Header {
+            ver_hlen: 0x45,
+            services: 0,
+            len: n16::new(20 + data.len() as u16),
+            id: n16::new(id),
+            flags_fragment: n16::new(flags | (offset / 8) as u16),
+            ttl: 64,
+            proto: 0x11,
+            checksum: Checksum { data: 0 },
+            src: Ipv4Addr { bytes: [10, 0, 2, 2] },
+            dst: Ipv4Addr { bytes: [10, 0, 2, 15] },
+        },
+        options: Vec::new(),
+        data: data.to_vec(),
+    };
+    let at = |secs: i64| Duration::new(secs, 0);
+
+    let mut reassembly = IpReassembly::new();
+
+    // Packets that are not fragments pass through
+    test!(reassembly.push(fragment(1, 0, 0, b"whole"), at(0)).map(|packet| packet.data) == Some(b"whole".to_vec()));
+    test!(reassembly.push(fragment(1, IP_DF, 0, b"whole"), at(0)).is_some());
+
+    // Fragments are put back in order, whatever order they arrive in
+    test!(reassembly.push(fragment(2, 0, 16, b"tail"), at(0)).is_none());
+    test!(reassembly.push(fragment(2, IP_MF, 8, b"89abcdef"), at(0)).is_none());
+    test!(reassembly.len() == 1);
+    match reassembly.push(fragment(2, IP_MF, 0, b"01234567"), at(1)) {
+        Some(packet) => {
+            test!(packet.data == b"0123456789abcdeftail".to_vec());
+            test!(packet.header.len.get() == 40);
+            test!(packet.header.flags_fragment.get() == 0);
+        }
+        None => { fail!(); }
+    }
+    test!(reassembly.len() == 0);
+
+    // Fragments of different datagrams are kept apart
+    test!(reassembly.push(fragment(3, IP_MF, 0, b"33333333"), at(0)).is_none());
+    test!(reassembly.push(fragment(4, IP_MF, 0, b"44444444"), at(0)).is_none());
+    test!(reassembly.push(fragment(4, 0, 8, b"4"), at(0)).map(|packet| packet.data) == Some(b"444444444".to_vec()));
+
+    // Incomplete datagrams time out
+    test!(reassembly.push(fragment(3, 0, 8, b"3"), at(IP_REASSEMBLY_TIMEOUT)).is_none());
+    test!(reassembly.push(fragment(3, IP_MF, 0, b"33333333"), at(IP_REASSEMBLY_TIMEOUT)).map(|packet| packet.data) == Some(b"333333333".to_vec()));
+
+    // Fragments with Don't Fragment set, misaligned, or past the largest datagram are dropped
+    test!(reassembly.push(fragment(5, IP_MF | IP_DF, 0, b"55555555"), at(100)).is_none());
+    test!(reassembly.push(fragment(6, IP_MF, 0, b"666"), at(100)).is_none());
+    test!(reassembly.push(fragment(7, 0, IP_OFFSET as usize * 8, b"7"), at(100)).is_none());
+    test!(reassembly.len() == 0);
+
+    succ!();
+}
@@ -0,0 +1,44 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use drivers::kb_layouts::layouts::{char_for_scancode, Layout};
+    use syscall::{do_sys_close, do_sys_open, do_sys_read, do_sys_write, EINVAL, O_RDWR};
+
+    test!(char_for_scancode(0x10, false, false, &Layout::English) == 'q');
+    test!(char_for_scancode(0x10, false, false, &Layout::Dvorak) == '\'');
+    test!(char_for_scancode(0x10, true, false, &Layout::French) == 'A');
+    test!(char_for_scancode(0x03, true, false, &Layout::EnglishUk) == '"');
+    test!(char_for_scancode(0x56, false, false, &Layout::EnglishUk) == '\\');
+    test!(Layout::from_name("azerty") == Some(Layout::French));
+    test!(Layout::from_name("klingon").is_none());
+
+    let fd = if let Ok(fd) = do_sys_open("ps2:layout\0".as_ptr(), O_RDWR) {
+        fd
+    } else {
+        fail!();
+    };
+
+    let mut old = [0; 32];
+    let old_len = do_sys_read(fd, old.as_mut_ptr(), old.len()).unwrap_or(0);
+
+    test!(do_sys_write(fd, b"dvorak\n".as_ptr(), 7).ok() == Some(7));
+    let unknown = do_sys_write(fd, b"klingon".as_ptr(), 7).map_err(|err| err.errno);
+
+    test!(do_sys_close(fd).is_ok());
+
+    let reopened = if let Ok(fd) = do_sys_open("ps2:layout\0".as_ptr(), O_RDWR) {
+        fd
+    } else {
+        fail!();
+    };
+    let mut buf = [0; 32];
+    let len = do_sys_read(reopened, buf.as_mut_ptr(), buf.len()).unwrap_or(0);
+    test!(do_sys_write(reopened, old.as_ptr(), old_len).ok() == Some(old_len));
+    test!(do_sys_close(reopened).is_ok());
+
+    test!(unknown == Err(EINVAL));
+    test!(&buf[..len] == b"dvorak\n");
+    succ!();
+}
@@ -0,0 +1,43 @@
+pub fn test() -> bool {
+    use common::time::Duration;
+    use logging::{KernelLog, LogLevel};
+
+    let mut log = KernelLog::new(3);
+    for i in 0..5 {
+        log.push(LogLevel::Info, Duration::new(i, 5000000), &format!("message {}", i));
+    }
+
+    // Only the newest entries are kept, and they keep their numbers
+    test!(log.next_seq() == 5);
+    let (lines, seq) = log.lines_since(0, LogLevel::Debug);
+    test!(seq == 5);
+    test!(lines == "[2.005] INFO message 2\n[3.005] INFO message 3\n[4.005] INFO message 4\n");
+
+    // Following the log returns only the new entries
+    let (lines, seq) = log.lines_since(seq, LogLevel::Debug);
+    test!(lines.is_empty() && seq == 5);
+    log.push(LogLevel::Error, Duration::new(10, 0), "failed");
+    log.push(LogLevel::Debug, Duration::new(11, 123000000), "detail");
+    let (lines, seq) = log.lines_since(seq, LogLevel::Debug);
+    test!(lines == "[10.000] ERROR failed\n[11.123] DEBUG detail\n" && seq == 7);
+
+    // Filtering by the least important level read
+    let (lines, _) = log.lines_since(0, LogLevel::Warning);
+    test!(lines == "[10.000] ERROR failed\n");
+
+    // Entries less important than the level of the log are not kept
+    log.level = LogLevel::Warning;
+    log.push(LogLevel::Info, Duration::new(12, 0), "ignored");
+    test!(log.next_seq() == 7);
+
+    log.clear();
+    test!(log.lines_since(0, LogLevel::Debug).0.is_empty());
+    test!(log.next_seq() == 7);
+
+    test!(LogLevel::from_name("warn") == Some(LogLevel::Warning));
+    test!(LogLevel::from_name("DEBUG") == Some(LogLevel::Debug));
+    test!(LogLevel::from_name("loud").is_none());
+    test!(LogLevel::Critical < LogLevel::Error);
+
+    succ!();
+}
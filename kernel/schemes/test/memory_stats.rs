@@ -0,0 +1,37 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use arch::memory::{self, CLUSTER_SIZE};
+    use syscall::{do_sys_close, do_sys_open, do_sys_read, O_RDONLY};
+
+    let before = memory::memory_stats();
+    test!(before.used + before.free == before.total);
+    test!(before.free == before.free_clusters * CLUSTER_SIZE);
+    test!(before.largest_free <= before.free);
+
+    let address = unsafe { memory::alloc(3 * CLUSTER_SIZE) };
+    test!(address > 0);
+    let during = memory::memory_stats();
+    unsafe { memory::unalloc(address) };
+    let after = memory::memory_stats();
+
+    test!(during.used == before.used + 3 * CLUSTER_SIZE);
+    test!(during.free_clusters + 3 == before.free_clusters);
+    test!(during.total == before.total);
+    test!(after.used == before.used && after.free == before.free);
+
+    for path in ["memory:\0", "memory:contexts\0", "cpu:\0"].iter() {
+        let fd = if let Ok(fd) = do_sys_open(path.as_ptr(), O_RDONLY) {
+            fd
+        } else {
+            fail!();
+        };
+        let mut buf = [0; 4096];
+        test!(do_sys_read(fd, buf.as_mut_ptr(), buf.len()).unwrap_or(0) > 0);
+        test!(do_sys_close(fd).is_ok());
+    }
+
+    succ!();
+}
@@ -0,0 +1,45 @@
+pub fn test() -> bool {
+    use syscall::{do_sys_mmap, do_sys_munmap, EINVAL, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, PROT_READ, PROT_WRITE};
+
+    let translate = |addr: usize| -> bool {
+        let contexts = ::env().contexts.lock();
+        match contexts.current() {
+            Ok(current) => current.translate(addr, 1).is_ok(),
+            Err(_) => false,
+        }
+    };
+
+    // File mappings are not supported
+    test!(do_sys_mmap(0, 4096, PROT_READ, MAP_PRIVATE, 0).is_err());
+    test!(do_sys_mmap(0, 0, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS, 0).is_err());
+    test!(do_sys_mmap(0x40000000, 4096, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED, 0).map_err(|err| err.errno) == Err(EINVAL));
+
+    // Mappings are zeroed and writeable
+    let addr = match do_sys_mmap(0, 3 * 4096, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, 0) {
+        Ok(addr) => addr,
+        Err(_) => fail!(),
+    };
+    test!(addr % 4096 == 0);
+    for i in 0..3 {
+        let ptr = (addr + i * 4096) as *mut u8;
+        test!(unsafe { *ptr } == 0);
+        unsafe { *ptr = i as u8 + 1 };
+    }
+
+    // Unmapping the middle page leaves the others mapped
+    test!(do_sys_munmap(addr + 4096, 4096).is_ok());
+    test!(translate(addr));
+    test!(! translate(addr + 4096));
+    test!(translate(addr + 2 * 4096));
+    test!(unsafe { *(addr as *const u8) } == 1);
+    test!(unsafe { *((addr + 2 * 4096) as *const u8) } == 3);
+
+    // The hole is reused by the next mapping
+    test!(do_sys_mmap(0, 4096, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS, 0).ok() == Some(addr + 4096));
+
+    test!(do_sys_munmap(addr, 3 * 4096).is_ok());
+    test!(! translate(addr));
+    test!(! translate(addr + 2 * 4096));
+    test!(do_sys_munmap(addr + 1, 4096).is_err());
+    succ!();
+}
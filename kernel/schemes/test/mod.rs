@@ -1,9 +1,16 @@
+use alloc::arc::Arc;
 use alloc::boxed::Box;
 
+use arch::context::{Context, ContextFile};
+
 use collections::string::{String, ToString};
 
 use fs::{KScheme, Resource, Url, VecResource};
 
+use sync::Intex;
+
+use syscall::do_sys_yield;
+
 use system::error::Result;
 
 #[macro_export]
@@ -29,9 +36,108 @@ macro_rules! fail {
     )
 }
 
+/// Run a test in a new kernel context, and wait for its result. Syscalls only accept buffers of
+/// userspace contexts that are inside of their memory, so tests that pass buffers on the kernel
+/// stack run in a context without a user stack
+pub fn in_kernel_context(test: fn() -> bool) -> bool {
+    let result = Arc::new(Intex::new(None));
+    let result_test = result.clone();
+    Context::spawn("ktest".to_string(), box move || {
+        let passed = test();
+        *result_test.lock() = Some(passed);
+    });
+
+    loop {
+        if let Some(passed) = *result.lock() {
+            return passed;
+        }
+        let _ = do_sys_yield();
+    }
+}
+
+/// Give the current context a descriptor for `resource`, opened with `flags`. This lets tests use
+/// files backed by memory, as there is no writable in-memory scheme to open them from
+pub fn install_file(resource: Box<Resource>, flags: usize) -> Option<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = match contexts.current() {
+        Ok(current) => current,
+        Err(_) => return None,
+    };
+
+    let fd = current.next_fd();
+    unsafe {
+        (*current.files.get()).push(ContextFile {
+            fd: fd,
+            flags: flags,
+            cloexec: false,
+            resource: resource,
+        });
+    }
+    Some(fd)
+}
+
 // Add your test here!
+pub mod access;
+pub mod ansi;
+pub mod append;
+pub mod arp_cache;
+pub mod backtrace;
+pub mod block_cache;
+pub mod canonicalize;
+pub mod chdir;
+pub mod clock;
+pub mod cloexec;
+pub mod console;
+pub mod cow;
+pub mod dhcp;
+pub mod directory;
+pub mod display_damage;
+pub mod dma;
+pub mod dns;
+pub mod dup2;
+pub mod flock;
 pub mod get_slice;
+pub mod getcwd;
+pub mod getppid;
+pub mod interrupt;
+pub mod intex;
+pub mod iovec;
+pub mod ip_fragment;
+pub mod ip_reassembly;
+pub mod keyboard_layout;
+pub mod klog;
+pub mod memory_stats;
 pub mod meta;
+pub mod mmap;
+pub mod monotonic;
+pub mod nanosleep;
+pub mod netstat;
+pub mod network_outbound;
+pub mod partition;
+pub mod pipe;
+pub mod pit;
+pub mod pread;
+pub mod ps2_mouse;
+pub mod rtl8139_ring;
+pub mod sched;
+pub mod scheme_calls;
+pub mod scheme_refcount;
+pub mod scrollback;
+pub mod sendfile;
+pub mod signal;
+pub mod stack_overflow;
+pub mod statvfs;
+pub mod symlink;
+pub mod tcp_send_queue;
+pub mod times;
+pub mod truncate;
+pub mod uaccess;
+pub mod url;
+pub mod user_buffers;
+pub mod utimens;
+pub mod vbe_modes;
+pub mod wait_queue;
+pub mod waitpid;
 
 pub struct TestScheme;
 
@@ -90,6 +196,70 @@ impl KScheme for TestScheme {
         reg_test!(meta::meta_test_woah, "Testing the testing (wut)");
         reg_test!(!meta::meta_test_woah_fail, "Testing the fail testing (wut)");
         reg_test!(get_slice::test, "GetSlice");
+        reg_test!(pipe::test, "Pipe");
+        reg_test!(symlink::test, "Symlink");
+        reg_test!(append::test, "Append");
+        reg_test!(cloexec::test, "Close on exec");
+        reg_test!(dup2::test, "Dup2");
+        reg_test!(mmap::test, "Mmap");
+        reg_test!(directory::test, "Directory");
+        reg_test!(pread::test, "Pread");
+        reg_test!(iovec::test, "Readv and writev");
+        reg_test!(statvfs::test, "Statvfs");
+        reg_test!(access::test, "Access");
+        reg_test!(truncate::test, "Truncate");
+        reg_test!(utimens::test, "Utimens");
+        reg_test!(sendfile::test, "Sendfile");
+        reg_test!(flock::test, "Flock");
+        reg_test!(console::test, "Console mode");
+        reg_test!(cow::test, "Copy on write");
+        reg_test!(signal::test, "Signal");
+        reg_test!(waitpid::test, "Waitpid");
+        reg_test!(getppid::test, "Getppid");
+        reg_test!(nanosleep::test, "Nanosleep");
+        reg_test!(canonicalize::test, "Canonicalize");
+        reg_test!(pit::test, "PIT period");
+        reg_test!(partition::test, "Disk partitions");
+        reg_test!(monotonic::test, "Monotonic clock");
+        reg_test!(block_cache::test, "Block cache");
+        reg_test!(times::test, "Times");
+        reg_test!(getcwd::test, "Getcwd");
+        reg_test!(memory_stats::test, "Memory statistics");
+        reg_test!(chdir::test, "Chdir");
+        reg_test!(url::test, "Url");
+        reg_test!(user_buffers::test, "User buffers");
+        reg_test!(interrupt::test, "Interrupt statistics");
+        reg_test!(uaccess::test, "User access");
+        reg_test!(keyboard_layout::test, "Keyboard layout");
+        reg_test!(ps2_mouse::test, "PS/2 mouse packets");
+        reg_test!(display_damage::test, "Display damage");
+        reg_test!(backtrace::test, "Backtrace symbols");
+        reg_test!(vbe_modes::test, "VBE modes");
+        reg_test!(scrollback::test, "Console scrollback");
+        reg_test!(rtl8139_ring::test, "RTL8139 receive ring");
+        reg_test!(ansi::test, "ANSI escape sequences");
+        reg_test!(klog::test, "Kernel log");
+        reg_test!(dma::test, "DMA addresses");
+        reg_test!(clock::test, "Clock resolution");
+        reg_test!(netstat::test, "Network statistics");
+        reg_test!(sched::test, "Scheduler fairness");
+        reg_test!(arp_cache::test, "ARP cache");
+        reg_test!(ip_reassembly::test, "IP reassembly");
+        reg_test!(wait_queue::test, "Wait queues");
+        reg_test!(ip_fragment::test, "IP fragmentation");
+        reg_test!(intex::test, "Intex try_lock");
+        reg_test!(dhcp::test, "DHCP");
+        reg_test!(scheme_refcount::test, "Scheme reference counting");
+        reg_test!(dns::test, "DNS scheme");
+        reg_test!(scheme_calls::test, "Scheme path calls");
+        reg_test!(tcp_send_queue::test, "TCP send queue");
+        reg_test!(network_outbound::test, "Network outbound queue");
+
+        // A double fault handler that does not work resets the machine, so overflowing the kernel
+        // stack is only tested by builds that ask for it
+        if option_env!("REDOX_TEST_STACK_OVERFLOW").is_some() {
+            reg_test!(stack_overflow::test, "Kernel stack overflow");
+        }
 
         Ok(box VecResource::new("test:".to_string(), string.into_bytes()))
     }
@@ -0,0 +1,20 @@
+pub fn test() -> bool {
+    use common::time::Duration;
+
+    let mut last = Duration::monotonic();
+    for _ in 0..1000 {
+        let now = Duration::monotonic();
+        test!(now >= last);
+        last = now;
+    }
+
+    // The HPET advances between reads, without waiting for a timer interrupt
+    if let Some(ref hpet) = ::env().hpet {
+        let counter = hpet.counter();
+        let start = hpet.monotonic();
+        while hpet.counter() == counter {}
+        test!(hpet.monotonic() > start);
+    }
+
+    succ!();
+}
@@ -0,0 +1,46 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use common::time::Duration;
+    use core::ptr;
+    use syscall::{do_sys_nanosleep, EINVAL, TimeSpec};
+
+    let req = TimeSpec {
+        tv_sec: 0,
+        tv_nsec: 10000000,
+    };
+    let mut rem = TimeSpec {
+        tv_sec: 1,
+        tv_nsec: 1,
+    };
+
+    // The context sleeps for at least the requested time
+    let start = Duration::monotonic();
+    test!(do_sys_nanosleep(&req, &mut rem).is_ok());
+    test!(Duration::monotonic() - start >= Duration::new(req.tv_sec, req.tv_nsec));
+
+    // A sleep shorter than a tick still lasts at least as long as requested
+    let short = TimeSpec {
+        tv_sec: 0,
+        tv_nsec: 1000,
+    };
+    let start = Duration::monotonic();
+    test!(do_sys_nanosleep(&short, ptr::null_mut()).is_ok());
+    test!(Duration::monotonic() - start >= Duration::new(short.tv_sec, short.tv_nsec));
+
+    // A zero duration only yields
+    let zero = TimeSpec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    test!(do_sys_nanosleep(&zero, ptr::null_mut()).is_ok());
+
+    let invalid = TimeSpec {
+        tv_sec: 0,
+        tv_nsec: 1000000000,
+    };
+    test!(do_sys_nanosleep(&invalid, ptr::null_mut()).map_err(|err| err.errno) == Err(EINVAL));
+    succ!();
+}
@@ -0,0 +1,47 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use collections::string::ToString;
+    use network::common::MacAddr;
+    use network::interface::{NetworkInterface, NETSTAT_HEADER};
+    use syscall::{do_sys_close, do_sys_open, do_sys_read, O_RDONLY};
+
+    let interface = NetworkInterface {
+        name: "eth9".to_string(),
+        mac: MacAddr { bytes: [0; 6] },
+        up: true,
+        rx_packets: 12,
+        tx_packets: 34,
+        rx_bytes: 5678,
+        tx_bytes: 9012,
+        rx_errors: 1,
+        rx_overflows: 2,
+        tx_timeouts: 3,
+    };
+
+    // The counters line up under the header
+    let line = interface.netstat();
+    test!(line.len() == NETSTAT_HEADER.len());
+    test!(line.starts_with("eth9 "));
+    test!(line.ends_with(" 1             2            3\n"));
+    test!(line.split_whitespace().count() == NETSTAT_HEADER.split_whitespace().count());
+
+    let stats = interface.stats();
+    test!(stats.contains("rx_overflows: 2\n") && stats.contains("tx_timeouts: 3\n"));
+
+    // The table starts with the header, followed by a line for each interface
+    let fd = if let Ok(fd) = do_sys_open("netstat:\0".as_ptr(), O_RDONLY) {
+        fd
+    } else {
+        fail!();
+    };
+    let mut buf = [0; 4096];
+    let count = do_sys_read(fd, buf.as_mut_ptr(), buf.len()).unwrap_or(0);
+    test!(do_sys_close(fd).is_ok());
+    test!(buf[.. count].starts_with(NETSTAT_HEADER.as_bytes()));
+    test!((count - NETSTAT_HEADER.len()) % NETSTAT_HEADER.len() == 0);
+
+    succ!();
+}
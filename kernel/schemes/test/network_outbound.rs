@@ -0,0 +1,77 @@
+use network::scheme::{NetworkResource, NetworkScheme};
+
+/// A card that never takes the frames of its resources
+struct StalledNic;
+
+impl NetworkScheme for StalledNic {
+    fn add(&mut self, _: *mut NetworkResource) {}
+    fn remove(&mut self, _: *mut NetworkResource) {}
+    fn sync(&mut self) {}
+}
+
+pub fn test() -> bool {
+    use alloc::arc::Arc;
+    use arch::context::Context;
+    use collections::string::ToString;
+    use common::time::Duration;
+    use fs::Resource;
+    use network::scheme::NETWORK_OUTBOUND_MAX;
+    use sync::Intex;
+    use syscall::do_sys_yield;
+    use system::error::EAGAIN;
+    use system::syscall::{O_NONBLOCK, POLLOUT};
+
+    let mut stalled = box StalledNic;
+    let nic: *mut NetworkScheme = &mut *stalled;
+
+    // A nonblocking resource fails once its queue is full, until a frame is taken
+    let mut resource = NetworkResource::new(nic, O_NONBLOCK);
+    for _ in 0..NETWORK_OUTBOUND_MAX {
+        test!(resource.write(b"frame").ok() == Some(5));
+    }
+    test!(resource.write(b"frame").map_err(|err| err.errno) == Err(EAGAIN));
+    test!(resource.poll() & POLLOUT == 0);
+    test!(resource.pop_outbound().is_some());
+    test!(resource.poll() & POLLOUT == POLLOUT);
+    test!(resource.write(b"frame").ok() == Some(5));
+    drop(resource);
+
+    // A blocking resource waits for a frame to be taken
+    let mut resource = NetworkResource::new(nic, 0);
+    for _ in 0..NETWORK_OUTBOUND_MAX {
+        test!(resource.write(b"frame").ok() == Some(5));
+    }
+
+    let written = Arc::new(Intex::new(None));
+    let written_writer = written.clone();
+    let ptr = resource.ptr as usize;
+    let writer = Context::spawn("ktest".to_string(), box move || {
+        let result = unsafe { (*(ptr as *mut NetworkResource)).write(b"last") };
+        *written_writer.lock() = Some(result.ok());
+    });
+
+    // Yield to the other contexts until `done`, for at most a few seconds
+    let settle = |done: &Fn() -> bool| {
+        let deadline = Duration::monotonic() + Duration::new(5, 0);
+        while ! done() && Duration::monotonic() < deadline {
+            let _ = do_sys_yield();
+        }
+        done()
+    };
+
+    // The writer has to be woken before the resource goes away, so the results are checked last
+    let blocked = settle(&|| {
+        ::env().contexts.lock().find(writer).map(|context| context.blocked).unwrap_or(false)
+    });
+    let waiting = written.lock().is_none();
+    let first = resource.pop_outbound();
+    let woken = settle(&|| written.lock().is_some());
+
+    test!(blocked && waiting);
+    test!(first.map_or(false, |frame| frame == b"frame"));
+    test!(woken && *written.lock() == Some(Some(4)));
+    test!(resource.outbound.lock().len() == NETWORK_OUTBOUND_MAX);
+    test!(resource.outbound.lock().back().map_or(false, |frame| frame == b"last"));
+
+    succ!();
+}
@@ -0,0 +1,163 @@
+use alloc::boxed::Box;
+
+use collections::string::String;
+use collections::vec::Vec;
+
+use disk::Disk;
+
+use system::error::Result;
+
+/// A disk backed by memory
+pub struct MemoryDisk {
+    pub data: Vec<u8>,
+}
+
+impl Disk for MemoryDisk {
+    fn name(&self) -> String {
+        String::from("Memory")
+    }
+
+    fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn read(&mut self, block: u64, buffer: &mut [u8]) -> Result<usize> {
+        let mut count = 0;
+        for (b, d) in buffer.iter_mut().zip(self.data.iter().skip(block as usize * 512)) {
+            *b = *d;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn write(&mut self, block: u64, buffer: &[u8]) -> Result<usize> {
+        let mut count = 0;
+        for (d, b) in self.data.iter_mut().skip(block as usize * 512).zip(buffer.iter()) {
+            *d = *b;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+fn mbr_entry(data: &mut [u8], i: usize, kind: u8, start: u32, blocks: u32) {
+    let entry = &mut data[446 + i * 16 .. 446 + (i + 1) * 16];
+    entry[4] = kind;
+    for j in 0..4 {
+        entry[8 + j] = (start >> (j * 8)) as u8;
+        entry[12 + j] = (blocks >> (j * 8)) as u8;
+    }
+}
+
+fn write_u32(bytes: &mut [u8], value: u32) {
+    for j in 0..4 {
+        bytes[j] = (value >> (j * 8)) as u8;
+    }
+}
+
+/// A disk of 16 blocks with a protective MBR and a GPT with room for `count` entries, of which
+/// the first is a partition in blocks 4 to 7
+fn gpt_disk(count: usize) -> Vec<u8> {
+    use core::cmp;
+    use disk::partition::crc32;
+
+    let mut data = vec![0; 16 * 512];
+    data[510] = 0x55;
+    data[511] = 0xAA;
+    mbr_entry(&mut data, 0, 0xEE, 1, 15);
+
+    data[2 * 512] = 1;
+    data[2 * 512 + 32] = 4;
+    data[2 * 512 + 40] = 7;
+    let table_end = cmp::min(2 * 512 + count * 128, data.len());
+    let table_crc = crc32(&data[2 * 512 .. table_end]);
+
+    {
+        let header = &mut data[512 .. 1024];
+        header[..8].clone_from_slice(b"EFI PART");
+        header[10] = 1;
+        write_u32(&mut header[12..], 92);
+        header[72] = 2;
+        write_u32(&mut header[80..], count as u32);
+        write_u32(&mut header[84..], 128);
+        write_u32(&mut header[88..], table_crc);
+        let header_crc = crc32(&header[.. 92]);
+        write_u32(&mut header[16..], header_crc);
+    }
+
+    data
+}
+
+pub fn test() -> bool {
+    use fs::{KScheme, ResourceSeek, Url};
+    use schemes::disk::DiskScheme;
+    use syscall::Stat;
+
+    let mut data = vec![0; 16 * 512];
+    for block in 0..16 {
+        data[block * 512] = block as u8;
+    }
+    data[510] = 0x55;
+    data[511] = 0xAA;
+    mbr_entry(&mut data, 0, 0x83, 2, 4);
+    // Extends past the end of the disk, so it is not exposed
+    mbr_entry(&mut data, 2, 0x83, 8, 16);
+
+    let disks: Vec<Box<Disk>> = vec![box MemoryDisk { data: data }, box MemoryDisk { data: vec![0; 4 * 512] }];
+    let mut scheme = DiskScheme::new(disks, 4);
+
+    let mut list = [0; 64];
+    let count = match scheme.open(Url::from_str("disk:/").unwrap(), 0) {
+        Ok(mut resource) => resource.read(&mut list).unwrap_or(0),
+        Err(_) => { fail!(); },
+    };
+    test!(&list[..count] == b"0\n1\n0p1");
+
+    let mut stat = Stat::default();
+    test!(scheme.stat(Url::from_str("disk:/0p1").unwrap(), &mut stat).is_ok());
+    test!(stat.st_size == 4 * 512);
+    test!(scheme.stat(Url::from_str("disk:/0p3").unwrap(), &mut stat).is_err());
+    test!(scheme.stat(Url::from_str("disk:/1p1").unwrap(), &mut stat).is_err());
+
+    let mut resource = match scheme.open(Url::from_str("disk:0p1").unwrap(), 0) {
+        Ok(resource) => resource,
+        Err(_) => { fail!(); },
+    };
+
+    // Reads are translated by the partition start and clamped at its end
+    let mut buf = [0; 8 * 512];
+    test!(resource.read(&mut buf).ok() == Some(4 * 512));
+    test!(buf[0] == 2 && buf[3 * 512] == 5);
+    test!(resource.read(&mut buf).ok() == Some(0));
+
+    test!(resource.seek(ResourceSeek::End(-512)).ok() == Some(3 * 512));
+    test!(resource.write(&[0xFF; 2 * 512]).ok() == Some(512));
+    test!(resource.read_at(3 * 512, &mut buf).ok() == Some(512));
+    test!(buf[0] == 0xFF);
+
+    // The block after the partition is untouched
+    let mut disk = match scheme.open(Url::from_str("disk:0").unwrap(), 0) {
+        Ok(resource) => resource,
+        Err(_) => { fail!(); },
+    };
+    test!(disk.read_at(6 * 512, &mut buf[.. 512]).ok() == Some(512));
+    test!(buf[0] == 6);
+
+    // A GPT is used behind a protective MBR, unless it does not match its checksums or has too
+    // many entries
+    let mut corrupt = gpt_disk(4);
+    corrupt[2 * 512 + 40] = 8;
+    let disks: Vec<Box<Disk>> = vec![box MemoryDisk { data: gpt_disk(4) }, box MemoryDisk { data: corrupt },
+                                     box MemoryDisk { data: gpt_disk(129) }];
+    let mut scheme = DiskScheme::new(disks, 4);
+
+    let count = match scheme.open(Url::from_str("disk:/").unwrap(), 0) {
+        Ok(mut resource) => resource.read(&mut list).unwrap_or(0),
+        Err(_) => { fail!(); },
+    };
+    test!(&list[..count] == b"0\n1\n2\n0p1");
+    test!(scheme.stat(Url::from_str("disk:/0p1").unwrap(), &mut stat).is_ok());
+    test!(stat.st_size == 4 * 512);
+
+    succ!();
+}
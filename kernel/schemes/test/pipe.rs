@@ -0,0 +1,104 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use alloc::arc::Arc;
+    use arch::context::Context;
+    use collections::string::ToString;
+    use common::time::Duration;
+    use core::usize;
+    use fs::Resource;
+    use schemes::pipe::{PipeRead, PipeWrite, PIPE_MAX};
+    use sync::Intex;
+    use syscall::{do_sys_close, do_sys_fcntl, do_sys_pipe2, do_sys_poll, do_sys_read, do_sys_write,
+                  do_sys_yield, PollFd, EAGAIN, EPIPE, F_GETFL, O_NONBLOCK, O_RDONLY, POLLHUP, POLLIN, POLLNVAL, POLLOUT};
+
+    let mut fds = [0; 2];
+    test!(do_sys_pipe2(fds.as_mut_ptr(), O_NONBLOCK).is_ok());
+    test!(do_sys_fcntl(fds[0], F_GETFL, 0).ok() == Some(O_RDONLY | O_NONBLOCK));
+
+    // An empty pipe does not block
+    let mut buf = [0; 4];
+    test!(do_sys_read(fds[0], buf.as_mut_ptr(), buf.len()).map_err(|err| err.errno) == Err(EAGAIN));
+
+    // Only the write side is ready
+    let mut pollfds = [PollFd { fd: fds[0], events: POLLIN, revents: 0 },
+                       PollFd { fd: fds[1], events: POLLOUT, revents: 0 }];
+    test!(do_sys_poll(pollfds.as_mut_ptr(), pollfds.len(), 0).ok() == Some(1));
+    test!(pollfds[0].revents == 0);
+    test!(pollfds[1].revents == POLLOUT);
+
+    test!(do_sys_write(fds[1], b"ab".as_ptr(), 2).ok() == Some(2));
+    test!(do_sys_poll(pollfds.as_mut_ptr(), 1, 0).ok() == Some(1));
+    test!(pollfds[0].revents == POLLIN);
+
+    // A ready descriptor returns at once, even when waiting forever
+    test!(do_sys_poll(pollfds.as_mut_ptr(), 1, usize::MAX as isize).ok() == Some(1));
+    test!(pollfds[0].revents == POLLIN);
+    test!(do_sys_read(fds[0], buf.as_mut_ptr(), buf.len()).ok() == Some(2));
+    test!(&buf[..2] == b"ab");
+    test!(do_sys_read(fds[0], buf.as_mut_ptr(), buf.len()).map_err(|err| err.errno) == Err(EAGAIN));
+
+    // Once the write side is closed, the end of the pipe is reported instead
+    test!(do_sys_close(fds[1]).is_ok());
+    test!(do_sys_poll(pollfds.as_mut_ptr(), 1, 0).ok() == Some(1));
+    test!(pollfds[0].revents == POLLIN | POLLHUP);
+    test!(do_sys_read(fds[0], buf.as_mut_ptr(), buf.len()).ok() == Some(0));
+    test!(do_sys_close(fds[0]).is_ok());
+
+    // Closed descriptors are reported without waiting
+    test!(do_sys_poll(pollfds.as_mut_ptr(), 1, -1).ok() == Some(1));
+    test!(pollfds[0].revents == POLLNVAL);
+
+    // A full pipe takes no more bytes, and is not writable until some are read
+    let big = vec![7; PIPE_MAX + 4];
+    test!(do_sys_pipe2(fds.as_mut_ptr(), O_NONBLOCK).is_ok());
+    test!(do_sys_write(fds[1], big.as_ptr(), big.len()).ok() == Some(PIPE_MAX));
+    test!(do_sys_write(fds[1], big.as_ptr(), 1).map_err(|err| err.errno) == Err(EAGAIN));
+    pollfds[1].fd = fds[1];
+    test!(do_sys_poll(pollfds[1..].as_mut_ptr(), 1, 0).ok() == Some(0));
+    test!(do_sys_read(fds[0], buf.as_mut_ptr(), buf.len()).ok() == Some(4));
+    test!(do_sys_poll(pollfds[1..].as_mut_ptr(), 1, 0).ok() == Some(1));
+    test!(do_sys_write(fds[1], big.as_ptr(), big.len()).ok() == Some(4));
+    test!(do_sys_close(fds[1]).is_ok());
+    test!(do_sys_close(fds[0]).is_ok());
+
+    // A blocking writer waits for a reader to make room, until all of its bytes are written
+    let mut read = PipeRead::new(0);
+    let mut write = PipeWrite::new(&read, 0);
+    let drained = Arc::new(Intex::new(0));
+    {
+        let drained = drained.clone();
+        Context::spawn("ktest".to_string(), box move || {
+            let mut buf = [0; 4096];
+            while let Ok(count) = read.read(&mut buf) {
+                if count == 0 {
+                    break;
+                }
+                *drained.lock() += count;
+            }
+        });
+    }
+    test!(write.write(&big).ok() == Some(big.len()));
+    drop(write);
+    let deadline = Duration::monotonic() + Duration::new(5, 0);
+    while *drained.lock() < big.len() && Duration::monotonic() < deadline {
+        let _ = do_sys_yield();
+    }
+    test!(*drained.lock() == big.len());
+
+    // A blocked writer stops once the last reader is gone
+    let mut read = PipeRead::new(0);
+    let mut write = PipeWrite::new(&read, 0);
+    Context::spawn("ktest".to_string(), box move || {
+        while read.poll() & POLLIN != POLLIN {
+            let _ = do_sys_yield();
+        }
+        drop(read);
+    });
+    test!(write.write(&big).ok() == Some(PIPE_MAX));
+    test!(write.write(&big).map_err(|err| err.errno) == Err(EPIPE));
+
+    succ!();
+}
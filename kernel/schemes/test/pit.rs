@@ -0,0 +1,21 @@
+pub fn test() -> bool {
+    use common::time::{Duration, TickPeriod, PIT_CLOCK, PIT_DIVISOR};
+
+    let mut period = TickPeriod::pit(PIT_DIVISOR);
+    let mut elapsed = Duration::new(0, 0);
+
+    // The fraction of a nanosecond is carried, so the error stays under a nanosecond
+    let ticks = 1000;
+    for _ in 0..ticks {
+        elapsed = elapsed + period.tick();
+    }
+    let expected = (ticks * PIT_DIVISOR * 3 * 1000000000 / PIT_CLOCK) as i64;
+    test!(elapsed.secs * 1000000000 + elapsed.nanos as i64 == expected);
+
+    // After a full cycle of the carried fraction there is no error at all
+    for _ in ticks..PIT_CLOCK {
+        elapsed = elapsed + period.tick();
+    }
+    test!(elapsed == Duration::new((PIT_DIVISOR * 3) as i64, 0));
+    succ!();
+}
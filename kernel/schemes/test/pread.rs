@@ -0,0 +1,38 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use collections::string::ToString;
+    use fs::VecResource;
+    use syscall::{do_sys_close, do_sys_lseek, do_sys_pipe2, do_sys_pread, do_sys_pwrite, do_sys_read,
+                  ESPIPE, O_RDWR, SEEK_CUR, SEEK_SET};
+
+    let file = box VecResource::new("test:pread".to_string(), b"abcdef".to_vec());
+    let fd = match super::install_file(file, O_RDWR) {
+        Some(fd) => fd,
+        None => { fail!(); }
+    };
+
+    // Positioned reads and writes leave the offset alone
+    let mut buf = [0; 3];
+    test!(do_sys_lseek(fd, 1, SEEK_SET).ok() == Some(1));
+    test!(do_sys_pread(fd, buf.as_mut_ptr(), buf.len(), 3).ok() == Some(3));
+    test!(&buf == b"def");
+    test!(do_sys_pwrite(fd, b"XY".as_ptr(), 2, 4).ok() == Some(2));
+    test!(do_sys_lseek(fd, 0, SEEK_CUR).ok() == Some(1));
+    test!(do_sys_read(fd, buf.as_mut_ptr(), buf.len()).ok() == Some(3));
+    test!(&buf == b"bcd");
+    test!(do_sys_pread(fd, buf.as_mut_ptr(), buf.len(), 4).ok() == Some(2));
+    test!(&buf[..2] == b"XY");
+    test!(do_sys_close(fd).is_ok());
+
+    // Pipes cannot seek
+    let mut fds = [0; 2];
+    test!(do_sys_pipe2(fds.as_mut_ptr(), 0).is_ok());
+    test!(do_sys_pread(fds[0], buf.as_mut_ptr(), buf.len(), 0).map_err(|err| err.errno) == Err(ESPIPE));
+    test!(do_sys_pwrite(fds[1], buf.as_ptr(), buf.len(), 0).map_err(|err| err.errno) == Err(ESPIPE));
+    test!(do_sys_close(fds[0]).is_ok());
+    test!(do_sys_close(fds[1]).is_ok());
+    succ!();
+}
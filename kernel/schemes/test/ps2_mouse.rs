@@ -0,0 +1,51 @@
+pub fn test() -> bool {
+    use common::event::MouseEvent;
+    use drivers::ps2::{mouse_packet, mouse_packet_len, MOUSE_ID_FIVE_BUTTONS, MOUSE_ID_STANDARD, MOUSE_ID_WHEEL};
+
+    test!(mouse_packet_len(MOUSE_ID_STANDARD) == 3);
+    test!(mouse_packet_len(MOUSE_ID_WHEEL) == 4);
+
+    // Left button, moved 5 right and 3 down
+    match mouse_packet(&[0x29, 0x05, 0xFD], MOUSE_ID_STANDARD) {
+        Some(packet) => {
+            test!(packet.dx == 5);
+            test!(packet.dy == 3);
+            test!(packet.buttons == 1);
+        },
+        None => { fail!(); }
+    }
+    test!(mouse_packet(&[0x08, 0x01, 0x00], MOUSE_ID_STANDARD).map(|packet| packet.dy) == Some(0));
+
+    // A byte without the sync bit cannot start a packet
+    test!(mouse_packet(&[0x01, 0x05, 0x03], MOUSE_ID_STANDARD).is_none());
+
+    test!(mouse_packet(&[0x08, 0x00, 0x00, 0xFF], MOUSE_ID_WHEEL).map(|packet| packet.dz) == Some(-1));
+
+    // Fourth and fifth buttons, scrolled one step towards the user
+    match mouse_packet(&[0x08, 0x00, 0x00, 0x31], MOUSE_ID_FIVE_BUTTONS) {
+        Some(packet) => {
+            test!(packet.dz == 1);
+            test!(packet.buttons == 0x18);
+        },
+        None => { fail!(); }
+    }
+    test!(mouse_packet(&[0x08, 0x00, 0x00, 0x0F], MOUSE_ID_FIVE_BUTTONS).map(|packet| packet.dz) == Some(-1));
+    test!(mouse_packet(&[0x08, 0x00, 0x00, 0x80], MOUSE_ID_FIVE_BUTTONS).is_none());
+
+    // The wheel and extra buttons survive the trip through an event
+    let event = MouseEvent {
+        x: 1,
+        y: 2,
+        left_button: true,
+        middle_button: false,
+        right_button: false,
+        fourth_button: false,
+        fifth_button: true,
+        wheel: -3,
+    }.to_event();
+    let mouse_event = MouseEvent::from_event(event);
+    test!(mouse_event.left_button && mouse_event.fifth_button && ! mouse_event.fourth_button);
+    test!(mouse_event.wheel == -3);
+
+    succ!();
+}
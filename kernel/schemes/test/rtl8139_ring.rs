@@ -0,0 +1,60 @@
+pub fn test() -> bool {
+    use collections::Vec;
+    use network::rtl8139::{rx_frame, RTL8139_RX_ALLOC, RTL8139_RX_SIZE};
+
+    let mut ring = Vec::new();
+    for _ in 0..RTL8139_RX_ALLOC {
+        ring.push(0u8);
+    }
+
+    // Write a frame the way the card does, with the header, the data and the CRC
+    fn put(ring: &mut Vec<u8>, capr: usize, data: &[u8]) {
+        let len = data.len() + 4;
+        ring[capr] = 1;
+        ring[capr + 1] = 0;
+        ring[capr + 2] = len as u8;
+        ring[capr + 3] = (len >> 8) as u8;
+        for (i, b) in data.iter().enumerate() {
+            ring[capr + 4 + i] = *b;
+        }
+        for i in 0..4 {
+            ring[capr + 4 + data.len() + i] = 0xCC;
+        }
+    }
+
+    let mut data = Vec::new();
+    for i in 0..61 {
+        data.push(i as u8);
+    }
+
+    // A frame at the start, the next one is aligned to 4 bytes
+    put(&mut ring, 0, &data);
+    match rx_frame(&ring, 0) {
+        Some((frame, next)) => {
+            test!(frame == &data[..]);
+            test!(next == 72);
+        },
+        None => { fail!(); }
+    }
+
+    // A frame that crosses the end of the ring comes back whole, and the next one is at the start
+    let capr = RTL8139_RX_SIZE - 8;
+    put(&mut ring, capr, &data);
+    match rx_frame(&ring, capr) {
+        Some((frame, next)) => {
+            test!(frame == &data[..]);
+            test!(next == 64);
+        },
+        None => { fail!(); }
+    }
+
+    // Bad status or length
+    ring[capr] = 0;
+    test!(rx_frame(&ring, capr).is_none());
+    ring[capr] = 1;
+    ring[capr + 2] = 0;
+    ring[capr + 3] = 0x10;
+    test!(rx_frame(&ring, capr).is_none());
+
+    succ!();
+}
@@ -0,0 +1,66 @@
+pub fn test() -> bool {
+    use alloc::arc::Arc;
+    use arch::context::{sched_cost, sched_time, Context, CONTEXT_ROOT_PID, PRIO_MAX, PRIO_MIN, SCHED_LAG,
+                        SCHED_UNIT};
+    use collections::string::ToString;
+    use core::ptr;
+    use sync::Intex;
+    use syscall::{do_sys_setpriority, do_sys_waitpid, do_sys_yield, EINVAL, EPERM};
+
+    // A context that was blocked or is new gets only SCHED_LAG slices ahead of the others
+    test!(sched_time(0, 1000) == 1000 - SCHED_LAG * SCHED_UNIT);
+    test!(sched_time(995, 1000) == 995);
+    test!(sched_time(2000, 1000) == 2000);
+    test!(sched_time(0, SCHED_LAG * SCHED_UNIT / 2) == 0);
+
+    // Each step of priority halves or doubles the cost of a slice
+    test!(sched_cost(0) == SCHED_UNIT);
+    test!(sched_cost(1) == SCHED_UNIT / 2);
+    test!(sched_cost(-1) == SCHED_UNIT * 2);
+    test!(sched_cost(PRIO_MAX) >= 1);
+    test!(sched_cost(PRIO_MAX + 5) == sched_cost(PRIO_MAX));
+    test!(sched_cost(PRIO_MIN - 5) == sched_cost(PRIO_MIN));
+
+    test!(do_sys_yield().is_ok());
+
+    test!(do_sys_setpriority(0, PRIO_MAX + 1).map_err(|err| err.errno) == Err(EINVAL));
+    test!(do_sys_setpriority(0, PRIO_MIN - 1).map_err(|err| err.errno) == Err(EINVAL));
+
+    let (pid, priority, privileged) = match ::env().contexts.lock().current() {
+        Ok(current) => (current.pid, current.priority,
+                        current.pid == CONTEXT_ROOT_PID || current.ppid == CONTEXT_ROOT_PID),
+        Err(_) => { fail!(); }
+    };
+
+    // Keeping the priority is always allowed, and only privileged contexts may raise it
+    test!(do_sys_setpriority(0, priority).is_ok());
+    if priority < PRIO_MAX {
+        if privileged {
+            test!(do_sys_setpriority(0, priority + 1).is_ok());
+            test!(do_sys_setpriority(0, priority).is_ok());
+        } else {
+            test!(do_sys_setpriority(0, priority + 1).map_err(|err| err.errno) == Err(EPERM));
+        }
+    }
+
+    // Other contexts can only be changed by their parent
+    let done = Arc::new(Intex::new(false));
+    let done_other = done.clone();
+    let other = Context::spawn("ktest".to_string(), box move || {
+        while ! *done_other.lock() {
+            let _ = do_sys_yield();
+        }
+    });
+    let unrelated = do_sys_setpriority(other, PRIO_MIN).map_err(|err| err.errno);
+    if let Ok(context) = ::env().contexts.lock().find_mut(other) {
+        context.ppid = pid;
+    }
+    let child = do_sys_setpriority(other, PRIO_MIN).is_ok();
+    *done.lock() = true;
+    let _ = do_sys_waitpid(other as isize, ptr::null_mut(), 0);
+
+    test!(unrelated == Err(EPERM));
+    test!(child);
+
+    succ!();
+}
@@ -0,0 +1,92 @@
+use alloc::boxed::Box;
+
+use fs::Resource;
+
+use core::ptr;
+
+use system::error::{Error, ENOENT, ENOSYS};
+use system::scheme::Packet;
+use system::syscall::{MODE_FILE, SYS_MKDIR, SYS_RMDIR, SYS_STAT, SYS_UNLINK, Stat, TimeSpec};
+
+/// Answer path calls like a userspace scheme server, and close the server without answering
+/// when asked to remove a directory. The buffers of a call are mapped into the context that
+/// created the scheme, so this has to run in the same context
+fn serve(mut server: Box<Resource>) {
+    loop {
+        let mut packet = Packet::default();
+        if server.read(&mut packet).is_err() {
+            return;
+        }
+
+        packet.a = Error::mux(match packet.a {
+            SYS_MKDIR => Ok(0),
+            SYS_UNLINK => Err(Error::new(ENOENT)),
+            SYS_STAT => {
+                let stat = Stat {
+                    st_mode: MODE_FILE | 0o640,
+                    st_size: 1234,
+                    st_atime: TimeSpec { tv_sec: 1, tv_nsec: 2 },
+                    st_mtime: TimeSpec { tv_sec: 3, tv_nsec: 4 },
+                    st_ctime: TimeSpec { tv_sec: 5, tv_nsec: 6 },
+                };
+                unsafe { ptr::write(packet.c as *mut Stat, stat) };
+                Ok(0)
+            },
+            SYS_RMDIR => return,
+            _ => Err(Error::new(ENOSYS)),
+        });
+
+        if server.write(&packet).is_err() {
+            return;
+        }
+    }
+}
+
+pub fn test() -> bool {
+    use arch::context::Context;
+    use collections::string::ToString;
+    use common::time::Duration;
+    use fs::Url;
+    use syscall::do_sys_yield;
+    use system::error::EIO;
+    use system::syscall::O_CREAT;
+
+    Context::spawn("ktest".to_string(), box move || {
+        if let Ok(server) = Url::from_str(":test_scheme_calls").and_then(|url| ::env().open(url, O_CREAT)) {
+            serve(server);
+        }
+    });
+
+    // Yield to the server until its scheme is registered, for at most a few seconds
+    let deadline = Duration::monotonic() + Duration::new(5, 0);
+    while ::env().schemes.lock().get("test_scheme_calls").is_none() && Duration::monotonic() < deadline {
+        let _ = do_sys_yield();
+    }
+
+    let url = |path| Url::from_str(path).unwrap();
+
+    // Path calls reach the server, and its errors reach the caller
+    test!(::env().mkdir(url("test_scheme_calls:/dir"), 0).is_ok());
+    test!(::env().unlink(url("test_scheme_calls:/file")).map_err(|err| err.errno) == Err(ENOENT));
+
+    // The server fills in the Stat on the kernel stack, which is not page aligned
+    let check = |stat: &Stat| {
+        let (mode, size, atime, mtime, ctime) = (stat.st_mode, stat.st_size, stat.st_atime, stat.st_mtime, stat.st_ctime);
+        mode == MODE_FILE | 0o640 && size == 1234 &&
+        atime.tv_sec == 1 && atime.tv_nsec == 2 &&
+        mtime.tv_sec == 3 && mtime.tv_nsec == 4 &&
+        ctime.tv_sec == 5 && ctime.tv_nsec == 6
+    };
+    let mut stat = Stat::default();
+    test!(::env().stat(url("test_scheme_calls:/file"), &mut stat).is_ok());
+    test!(check(&stat));
+    let mut stat = Stat::default();
+    test!(::env().lstat(url("test_scheme_calls:/file"), &mut stat).is_ok());
+    test!(check(&stat));
+
+    // A server that goes away while a call waits fails it
+    test!(::env().rmdir(url("test_scheme_calls:/dir")).map_err(|err| err.errno) == Err(EIO));
+    test!(::env().schemes.lock().get("test_scheme_calls").is_none());
+
+    succ!();
+}
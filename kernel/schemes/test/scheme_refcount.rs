@@ -0,0 +1,143 @@
+use alloc::boxed::Box;
+
+use fs::Resource;
+
+use system::error::{Error, ENOSYS};
+use system::scheme::Packet;
+use system::syscall::{SYS_CLOSE, SYS_FSYNC, SYS_LSEEK, SYS_OPEN};
+
+/// Answer calls like a userspace scheme server until asked to sync, then close the server
+/// without answering
+fn serve(mut server: Box<Resource>) {
+    let mut next_file = 1;
+    loop {
+        let mut packet = Packet::default();
+        if server.read(&mut packet).is_err() {
+            return;
+        }
+
+        packet.a = match packet.a {
+            SYS_OPEN => {
+                next_file += 1;
+                next_file
+            },
+            SYS_CLOSE => 0,
+            SYS_LSEEK => 42,
+            SYS_FSYNC => return,
+            _ => Error::mux(Err(Error::new(ENOSYS))),
+        };
+
+        if server.write(&packet).is_err() {
+            return;
+        }
+    }
+}
+
+pub fn test() -> bool {
+    use arch::context::Context;
+    use collections::string::ToString;
+    use common::time::Duration;
+    use fs::{ResourceSeek, Url};
+    use syscall::do_sys_yield;
+    use system::error::{EIO, ENODEV, ENOENT};
+    use system::syscall::{O_CREAT, O_RDWR};
+
+    // Yield to the other contexts until `done`, for at most a few seconds
+    let settle = |done: &Fn() -> bool| {
+        let deadline = Duration::monotonic() + Duration::new(5, 0);
+        while ! done() && Duration::monotonic() < deadline {
+            let _ = do_sys_yield();
+        }
+        done()
+    };
+
+    // Whether a scheme is registered, and whether it is draining
+    let state = |name: &str| ::env().schemes.lock().get(name).map(|scheme| scheme.draining());
+
+    let listed = |name: &str| -> bool {
+        let mut buf = [0; 4096];
+        let result = Url::from_str(":").and_then(|url| ::env().open(url, 0)).and_then(|mut list| list.read(&mut buf));
+        match result {
+            Ok(count) => buf[.. count].split(|&b| b == b'\n').any(|entry| entry == name.as_bytes()),
+            Err(_) => false,
+        }
+    };
+
+    let create = |name: &str| -> Option<Box<Resource>> {
+        Url::from_str(&format!(":{}", name)).and_then(|url| ::env().open(url, O_CREAT)).ok()
+    };
+
+    let open = |path: &str| Url::from_str(path).and_then(|url| ::env().open(url, O_RDWR));
+
+    // The server closes first: the scheme drains until its last resource is closed
+    let server = match create("test_refcount_a") {
+        Some(server) => server,
+        None => { fail!(); }
+    };
+    Context::spawn("ktest".to_string(), box move || serve(server));
+
+    let mut first = match open("test_refcount_a:first") {
+        Ok(resource) => resource,
+        Err(_) => { fail!(); }
+    };
+    let second = match open("test_refcount_a:second") {
+        Ok(resource) => resource,
+        Err(_) => { fail!(); }
+    };
+    test!(first.seek(ResourceSeek::Start(0)).ok() == Some(42));
+    test!(listed("test_refcount_a"));
+
+    // The call waiting when the server goes away fails instead of blocking
+    test!(first.sync().map_err(|err| err.errno) == Err(EIO));
+    test!(settle(&|| state("test_refcount_a") == Some(true)));
+    test!(! listed("test_refcount_a"));
+    test!(open("test_refcount_a:third").map(|_| ()).map_err(|err| err.errno) == Err(ENOENT));
+    test!(create("test_refcount_a").is_none());
+    test!(first.seek(ResourceSeek::Start(0)).map_err(|err| err.errno) == Err(ENODEV));
+
+    drop(first);
+    test!(state("test_refcount_a") == Some(true));
+    drop(second);
+    test!(state("test_refcount_a").is_none());
+
+    // The name can be taken again once the entry is gone
+    match create("test_refcount_a") {
+        Some(server) => drop(server),
+        None => { fail!(); }
+    }
+    test!(state("test_refcount_a").is_none());
+
+    // The resources close first: the scheme keeps working, and goes away with its server
+    let server = match create("test_refcount_b") {
+        Some(server) => server,
+        None => { fail!(); }
+    };
+    Context::spawn("ktest".to_string(), box move || serve(server));
+
+    let first = match open("test_refcount_b:first") {
+        Ok(resource) => resource,
+        Err(_) => { fail!(); }
+    };
+    drop(first);
+    test!(state("test_refcount_b") == Some(false));
+
+    let mut second = match open("test_refcount_b:second") {
+        Ok(resource) => resource,
+        Err(_) => { fail!(); }
+    };
+    test!(second.seek(ResourceSeek::Start(0)).ok() == Some(42));
+    drop(second);
+    test!(listed("test_refcount_b"));
+
+    // The sync closes the server, answered by the draining scheme itself
+    let mut last = match open("test_refcount_b:last") {
+        Ok(resource) => resource,
+        Err(_) => { fail!(); }
+    };
+    test!(last.sync().map_err(|err| err.errno) == Err(EIO));
+    drop(last);
+    test!(settle(&|| state("test_refcount_b").is_none()));
+    test!(! listed("test_refcount_b"));
+
+    succ!();
+}
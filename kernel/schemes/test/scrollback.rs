@@ -0,0 +1,45 @@
+pub fn test() -> bool {
+    use env::scrollback::{Cell, Scrollback};
+    use graphics::color::Color;
+
+    let white = Color::new(0xFF, 0xFF, 0xFF);
+    let black = Color::new(0, 0, 0);
+    let red = Color::new(0xFF, 0, 0);
+
+    let mut scrollback = Scrollback::new(4, 2, 3, white, black);
+    test!(scrollback.len() == 0);
+    test!(scrollback.line(0).len() == 4);
+    test!(scrollback.line(2).is_empty());
+
+    // Lines move from the screen into the history, keeping their colors
+    for i in 0..5 {
+        scrollback.set(0, 1, Cell {
+            c: (b'a' + i) as char,
+            foreground: red,
+            background: black,
+        });
+        scrollback.scroll(white, black);
+    }
+
+    // Only the last three lines are kept, oldest first, then the screen
+    test!(scrollback.len() == 3);
+    test!(scrollback.line(0)[0].c == 'b');
+    test!(scrollback.line(1)[0].c == 'c');
+    test!(scrollback.line(2)[0].c == 'd');
+    test!(scrollback.line(2)[0].foreground.data == red.data);
+    test!(scrollback.line(3)[0].c == 'e');
+    test!(scrollback.line(4)[0].c == ' ');
+    test!(scrollback.line(5).is_empty());
+
+    // Clearing the screen keeps the history
+    scrollback.set(3, 0, Cell::blank(white, red));
+    scrollback.clear(white, black);
+    test!(scrollback.line(3)[3].background.data == black.data);
+    test!(scrollback.len() == 3);
+
+    // Writes outside the screen are ignored
+    scrollback.set(4, 0, Cell::blank(white, red));
+    scrollback.set(0, 2, Cell::blank(white, red));
+
+    succ!();
+}
@@ -0,0 +1,42 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use collections::string::ToString;
+    use fs::VecResource;
+    use syscall::{do_sys_close, do_sys_lseek, do_sys_pipe2, do_sys_read, do_sys_sendfile, EINVAL, O_RDWR,
+                  SEEK_CUR, SEEK_SET};
+
+    let file = box VecResource::new("test:sendfile".to_string(), b"abcdef".to_vec());
+    let fd = match super::install_file(file, O_RDWR) {
+        Some(fd) => fd,
+        None => { fail!(); }
+    };
+
+    let mut fds = [0; 2];
+    test!(do_sys_pipe2(fds.as_mut_ptr(), 0).is_ok());
+
+    // With an offset, the file offset is left alone
+    let mut offset = 2;
+    let mut buf = [0; 8];
+    test!(do_sys_sendfile(fds[1], fd, &mut offset, 3).ok() == Some(3));
+    test!(offset == 5);
+    test!(do_sys_lseek(fd, 0, SEEK_CUR).ok() == Some(0));
+    test!(do_sys_read(fds[0], buf.as_mut_ptr(), buf.len()).ok() == Some(3));
+    test!(&buf[..3] == b"cde");
+
+    // Without one, the file offset moves, and the copy stops at the end of the file
+    test!(do_sys_lseek(fd, 4, SEEK_SET).ok() == Some(4));
+    test!(do_sys_sendfile(fds[1], fd, 0 as *mut usize, 100).ok() == Some(2));
+    test!(do_sys_lseek(fd, 0, SEEK_CUR).ok() == Some(6));
+    test!(do_sys_read(fds[0], buf.as_mut_ptr(), buf.len()).ok() == Some(2));
+    test!(&buf[..2] == b"ef");
+
+    test!(do_sys_sendfile(fd, fd, 0 as *mut usize, 1).map_err(|err| err.errno) == Err(EINVAL));
+
+    test!(do_sys_close(fds[0]).is_ok());
+    test!(do_sys_close(fds[1]).is_ok());
+    test!(do_sys_close(fd).is_ok());
+    succ!();
+}
@@ -0,0 +1,84 @@
+pub fn test() -> bool {
+    use arch::regs::Regs;
+    use core::usize;
+    use syscall::{do_sys_getpid, do_sys_kill, do_sys_signal, signal_fault, EINVAL, EPERM, ESRCH, NSIG, SIGCHLD,
+                  SIGINT, SIGKILL, SIGSEGV, SIG_DFL, SIG_IGN};
+
+    let pid = match do_sys_getpid() {
+        Ok(pid) => pid,
+        Err(_) => {
+            fail!();
+        },
+    };
+
+    let previous = {
+        let contexts = ::env().contexts.lock();
+        match contexts.current() {
+            Ok(current) => current.signal_handler(SIGINT),
+            Err(_) => {
+                fail!();
+            },
+        }
+    };
+
+    // A signal of 0 checks that the context exists
+    test!(do_sys_kill(pid, 0).is_ok());
+    test!(do_sys_kill(usize::MAX, 0).map_err(|err| err.errno) == Err(ESRCH));
+    test!(do_sys_kill(pid, NSIG).map_err(|err| err.errno) == Err(EINVAL));
+
+    // Kernel contexts can not be signalled
+    let kernel_pid = ::env().contexts.lock().iter().find(|context| context.stack.is_none()).map(|context| context.pid);
+    match kernel_pid {
+        Some(kernel_pid) => {
+            test!(do_sys_kill(kernel_pid, 0).map_err(|err| err.errno) == Err(EPERM));
+            test!(do_sys_kill(kernel_pid, SIGKILL).map_err(|err| err.errno) == Err(EPERM));
+        },
+        None => {
+            fail!();
+        },
+    }
+
+    // SIGKILL can not be caught or ignored
+    test!(do_sys_signal(SIGKILL, SIG_IGN, 0).map_err(|err| err.errno) == Err(EINVAL));
+    test!(do_sys_signal(0, SIG_IGN, 0).map_err(|err| err.errno) == Err(EINVAL));
+
+    // Ignored signals are discarded, and SIGCHLD is ignored by default
+    test!(do_sys_signal(SIGINT, SIG_IGN, 0).is_ok());
+    test!(do_sys_kill(pid, SIGINT).is_ok());
+    test!(do_sys_kill(pid, SIGCHLD).is_ok());
+    {
+        let contexts = ::env().contexts.lock();
+        match contexts.current() {
+            Ok(current) => test!(current.signals & (1 << SIGINT | 1 << SIGCHLD) == 0),
+            Err(_) => {
+                fail!();
+            },
+        }
+    }
+
+    let restored = match previous {
+        Some(handler) => do_sys_signal(SIGINT, handler.handler, handler.restorer),
+        None => do_sys_signal(SIGINT, SIG_DFL, 0),
+    };
+    test!(restored.ok() == Some(SIG_IGN));
+
+    // A fault with a handler is left pending for the handler, which runs on return to userspace
+    test!(do_sys_signal(SIGSEGV, 0x1000, 0).is_ok());
+    signal_fault(&mut Regs::default(), SIGSEGV);
+    let pending = {
+        let mut contexts = ::env().contexts.lock();
+        match contexts.current_mut() {
+            Ok(current) => {
+                let pending = current.signals & 1 << SIGSEGV != 0;
+                current.signals &= !(1 << SIGSEGV);
+                pending
+            },
+            Err(_) => {
+                fail!();
+            },
+        }
+    };
+    test!(do_sys_signal(SIGSEGV, SIG_DFL, 0).ok() == Some(0x1000));
+    test!(pending);
+    succ!();
+}
@@ -0,0 +1,32 @@
+use core::ptr;
+
+/// Recurse with a large frame until the kernel stack runs into its guard page
+#[inline(never)]
+fn recurse(depth: usize) -> usize {
+    let mut frame = [0u8; 1024];
+    unsafe { ptr::write_volatile(&mut frame[0], depth as u8); }
+    let deeper = recurse(depth + 1);
+    deeper + unsafe { ptr::read_volatile(&frame[0]) } as usize
+}
+
+/// Overflow the kernel stack of a new context, to exercise the double fault handler in a VM. The
+/// handler prints the diagnostics and exits the context
+pub fn test() -> bool {
+    use arch::context::Context;
+    use collections::string::ToString;
+    use common::time::Duration;
+    use syscall::do_sys_yield;
+
+    let pid = Context::spawn("ktest".to_string(), box || {
+        recurse(0);
+    });
+
+    let exited = || ::env().contexts.lock().find(pid).map(|context| context.exited).unwrap_or(true);
+
+    let deadline = Duration::monotonic() + Duration::new(5, 0);
+    while ! exited() && Duration::monotonic() < deadline {
+        let _ = do_sys_yield();
+    }
+    test!(exited());
+    succ!();
+}
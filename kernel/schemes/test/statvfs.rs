@@ -0,0 +1,26 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use syscall::{do_sys_close, do_sys_fstatvfs, do_sys_open, do_sys_statvfs, StatVfs, ENOSYS, O_RDONLY};
+
+    let mut stat = StatVfs::default();
+    test!(do_sys_statvfs("initfs:/\0".as_ptr(), &mut stat).is_ok());
+    test!(stat.f_bsize == 512);
+    test!(stat.f_blocks > 0);
+    test!(stat.f_files > 0);
+
+    let fd = if let Ok(fd) = do_sys_open("initfs:/bin/init\0".as_ptr(), O_RDONLY) {
+        fd
+    } else {
+        fail!();
+    };
+    let mut fstat = StatVfs::default();
+    test!(do_sys_fstatvfs(fd, &mut fstat).is_ok());
+    test!(fstat.f_blocks == stat.f_blocks && fstat.f_files == stat.f_files);
+    test!(do_sys_close(fd).is_ok());
+
+    test!(do_sys_statvfs("test:\0".as_ptr(), &mut stat).map_err(|err| err.errno) == Err(ENOSYS));
+    succ!();
+}
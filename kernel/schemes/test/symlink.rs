@@ -0,0 +1,43 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use syscall::{do_sys_close, do_sys_lstat, do_sys_open, do_sys_readlink, do_sys_stat,
+                  do_sys_symlink, do_sys_unlink, Stat, ELOOP, MODE_FILE, MODE_SYMLINK, MODE_TYPE,
+                  O_RDONLY};
+
+    let link = "initfs:/symlink_test\0".as_ptr();
+    let loop_a = "initfs:/symlink_test_a\0".as_ptr();
+    let loop_b = "initfs:/symlink_test_b\0".as_ptr();
+
+    test!(do_sys_symlink("bin/init\0".as_ptr(), link).is_ok());
+    test!(do_sys_symlink("bin/init\0".as_ptr(), link).is_err());
+
+    let mut buf = [0; 64];
+    test!(do_sys_readlink(link, buf.as_mut_ptr(), buf.len()).ok() == Some(8));
+    test!(&buf[..8] == b"bin/init");
+
+    let mut stat = Stat::default();
+    test!(do_sys_lstat(link, &mut stat).is_ok());
+    test!(stat.st_mode & MODE_TYPE == MODE_SYMLINK);
+    test!(do_sys_stat(link, &mut stat).is_ok());
+    test!(stat.st_mode & MODE_TYPE == MODE_FILE);
+
+    let fd = if let Ok(fd) = do_sys_open(link, O_RDONLY) {
+        fd
+    } else {
+        fail!();
+    };
+    test!(do_sys_close(fd).is_ok());
+
+    test!(do_sys_symlink("symlink_test_b\0".as_ptr(), loop_a).is_ok());
+    test!(do_sys_symlink("symlink_test_a\0".as_ptr(), loop_b).is_ok());
+    test!(do_sys_open(loop_a, O_RDONLY).map_err(|err| err.errno) == Err(ELOOP));
+
+    test!(do_sys_unlink(loop_b).is_ok());
+    test!(do_sys_unlink(loop_a).is_ok());
+    test!(do_sys_unlink(link).is_ok());
+    test!(do_sys_readlink(link, buf.as_mut_ptr(), buf.len()).is_err());
+    succ!();
+}
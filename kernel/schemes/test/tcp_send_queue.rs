@@ -0,0 +1,48 @@
+pub fn test() -> bool {
+    use common::time::Duration;
+    use network::common::Ipv4Addr;
+    use network::schemes::tcp::TcpSendQueue;
+
+    let mut queue = TcpSendQueue::new(Ipv4Addr { bytes: [10, 0, 2, 2] });
+    test!(queue.is_empty() && queue.deadline().is_none());
+
+    // Several segments can be in flight, wrapping around the sequence numbers
+    queue.push(0xFFFFFFF0, 0x10, vec![1]);
+    queue.push(0x10, 0x30, vec![2]);
+    test!(queue.in_flight() == 0x40);
+
+    // Segments are forgotten once all of their data is acknowledged
+    queue.ack(0x08);
+    test!(queue.in_flight() == 0x40);
+    queue.ack(0x10);
+    test!(queue.in_flight() == 0x20);
+
+    // Nothing is resent before the timeout, and the timeout doubles on every resend
+    let deadline = match queue.deadline() {
+        Some(deadline) => deadline,
+        None => { fail!(); }
+    };
+    test!(queue.expired(Duration::monotonic()).is_empty());
+    let segments = queue.expired(deadline);
+    test!(segments.len() == 1 && segments[0] == vec![2]);
+    match queue.deadline() {
+        Some(next) => test!(next - deadline >= Duration::new(2, 0)),
+        None => { fail!(); }
+    }
+
+    // A peer that stops acknowledging resets the connection
+    let mut resends = 1;
+    while ! queue.is_reset() && resends < 64 {
+        let deadline = match queue.deadline() {
+            Some(deadline) => deadline,
+            None => { fail!(); }
+        };
+        if ! queue.expired(deadline).is_empty() {
+            resends += 1;
+        }
+    }
+    test!(queue.is_reset() && queue.is_empty());
+    test!(resends == 8);
+
+    succ!();
+}
@@ -0,0 +1,35 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use common::time::{Duration, TickPeriod, PIT_CLOCK, PIT_DIVISOR};
+    use syscall::{do_sys_close, do_sys_open, do_sys_read, do_sys_times, Tms, O_RDONLY};
+
+    let mut period = TickPeriod::pit(PIT_DIVISOR);
+    test!(period.duration(PIT_CLOCK) == Duration::new((PIT_DIVISOR * 3) as i64, 0));
+    let mut elapsed = Duration::new(0, 0);
+    for _ in 0..1000 {
+        elapsed = elapsed + period.tick();
+    }
+    test!(period.duration(1000) == elapsed);
+
+    let mut tms = Tms::default();
+    test!(do_sys_times(&mut tms).is_ok());
+    test!(tms.tms_utime.tv_sec >= 0 && tms.tms_stime.tv_sec >= 0);
+    test!(tms.tms_cutime.tv_sec >= 0 && tms.tms_cstime.tv_sec >= 0);
+    test!(do_sys_times(0 as *mut Tms).is_err());
+
+    // The idle time is reported by the root context
+    let fd = if let Ok(fd) = do_sys_open("context:1/times\0".as_ptr(), O_RDONLY) {
+        fd
+    } else {
+        fail!();
+    };
+    let mut buf = [0; 4096];
+    let count = do_sys_read(fd, buf.as_mut_ptr(), buf.len()).unwrap_or(0);
+    test!(buf[..count].starts_with(b"UTIME: "));
+    test!(do_sys_close(fd).is_ok());
+
+    succ!();
+}
@@ -0,0 +1,75 @@
+use collections::vec::Vec;
+
+use fs::{KScheme, Url};
+
+use system::error::{Error, Result, ENOENT};
+use system::syscall::{MODE_FILE, Stat};
+
+/// A single writable file, at `test_truncate:file`
+struct TruncateScheme {
+    data: Vec<u8>,
+}
+
+impl KScheme for TruncateScheme {
+    fn scheme(&self) -> &str {
+        "test_truncate"
+    }
+
+    fn stat(&mut self, url: Url, stat: &mut Stat) -> Result<()> {
+        if url.reference() == "file" {
+            stat.st_mode = MODE_FILE | 0o644;
+            stat.st_size = self.data.len() as u64;
+            Ok(())
+        } else {
+            Err(Error::new(ENOENT))
+        }
+    }
+
+    fn truncate(&mut self, url: Url, len: usize) -> Result<()> {
+        if url.reference() == "file" {
+            self.data.resize(len, 0);
+            Ok(())
+        } else {
+            Err(Error::new(ENOENT))
+        }
+    }
+}
+
+/// The file grows and shrinks to the new length
+fn resize() -> bool {
+    use syscall::{do_sys_stat, do_sys_truncate};
+
+    let path = "test_truncate:file\0".as_ptr();
+    let size = |stat: &mut Stat| do_sys_stat(path, stat).ok().map(|_| stat.st_size);
+
+    ::env().register(box TruncateScheme { data: vec![1; 16] });
+    let mut stat = Stat::default();
+    let grown = do_sys_truncate(path, 4096).is_ok() && size(&mut stat) == Some(4096);
+    let shrunk = do_sys_truncate(path, 10).is_ok() && size(&mut stat) == Some(10);
+    let missing = do_sys_truncate("test_truncate:missing\0".as_ptr(), 0).map_err(|err| err.errno);
+    drop(::env().schemes.lock().remove("test_truncate"));
+
+    test!(grown);
+    test!(shrunk);
+    test!(missing == Err(ENOENT));
+    succ!();
+}
+
+pub fn test() -> bool {
+    use syscall::{do_sys_symlink, do_sys_truncate, do_sys_unlink, EACCES, EISDIR};
+
+    let link = "initfs:/truncate_test\0".as_ptr();
+
+    // The files of initfs are read only
+    test!(do_sys_truncate("initfs:/bin/init\0".as_ptr(), 0).map_err(|err| err.errno) == Err(EACCES));
+    test!(do_sys_truncate("initfs:/bin\0".as_ptr(), 0).map_err(|err| err.errno) == Err(EISDIR));
+    test!(do_sys_truncate("initfs:/truncate_missing\0".as_ptr(), 0).map_err(|err| err.errno) == Err(ENOENT));
+
+    // Links are followed to their target
+    test!(do_sys_symlink("bin\0".as_ptr(), link).is_ok());
+    test!(do_sys_truncate(link, 0).map_err(|err| err.errno) == Err(EISDIR));
+    test!(do_sys_unlink(link).is_ok());
+
+    test!(super::in_kernel_context(resize));
+    succ!();
+}
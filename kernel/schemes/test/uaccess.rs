@@ -0,0 +1,29 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use collections::Vec;
+    use syscall::EFAULT;
+    use syscall::uaccess::{copy_from_user, copy_slice_from_user, copy_slice_to_user, copy_to_user};
+
+    let mut value = 0usize;
+    test!(copy_to_user(&mut value as *mut usize, 42).is_ok());
+    test!(value == 42);
+    test!(copy_from_user(&value as *const usize).ok() == Some(42));
+
+    let mut values = [0u8; 4];
+    test!(copy_slice_to_user(values.as_mut_ptr(), &b"abcd"[..]).is_ok());
+    test!(&values == b"abcd");
+    test!(copy_slice_from_user(values.as_ptr(), 2).ok() == Some(b"ab".to_vec()));
+
+    // Null pointers fault, unless nothing is copied
+    test!(copy_from_user(0 as *const usize).map_err(|err| err.errno) == Err(EFAULT));
+    test!(copy_to_user(0 as *mut usize, 0).map_err(|err| err.errno) == Err(EFAULT));
+    test!(copy_slice_to_user(0 as *mut u8, &b"a"[..]).map_err(|err| err.errno) == Err(EFAULT));
+    test!(copy_slice_from_user(0 as *const u8, 0).ok() == Some(Vec::new()));
+
+    // A length that overflows faults
+    test!(copy_slice_from_user(values.as_ptr() as *const usize, usize::max_value()).map_err(|err| err.errno) == Err(EFAULT));
+    succ!();
+}
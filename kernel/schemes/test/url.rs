@@ -0,0 +1,33 @@
+pub fn test() -> bool {
+    use fs::Url;
+    use fs::url::percent_decode;
+
+    // URLs without encoding or a query are unchanged
+    let url = Url::from_str("initfs:/bin/init").unwrap_or(Url::new());
+    test!(url.scheme() == "initfs" && url.reference() == "/bin/init" && url.query() == None);
+    test!(url.decode().as_url() == url);
+    test!(url.to_string() == "initfs:/bin/init");
+
+    let url = Url::from_str("file:/a%20b%2Fc%2f").unwrap_or(Url::new());
+    test!(url.reference() == "/a%20b%2Fc%2f");
+    test!(url.decode().as_url().reference() == "/a b/c/");
+
+    // Malformed sequences are kept as they are
+    test!(percent_decode("100%") == "100%");
+    test!(percent_decode("%2") == "%2");
+    test!(percent_decode("%zz%41") == "%zzA");
+    test!(percent_decode("%FF") == "%FF");
+
+    // The query starts at the first '?', and may be empty
+    let url = Url::from_str("tcp:80?nonblock").unwrap_or(Url::new());
+    test!(url.reference() == "80" && url.query() == Some("nonblock"));
+    test!(url.to_string() == "tcp:80?nonblock");
+    let url = Url::from_str("tcp:80?").unwrap_or(Url::new());
+    test!(url.reference() == "80" && url.query() == Some(""));
+    test!(url.to_string() == "tcp:80?");
+    let url = Url::from_str("tcp:?a?b").unwrap_or(Url::new());
+    test!(url.reference() == "" && url.query() == Some("a?b"));
+    test!(url.decode().as_url().query() == Some("a?b"));
+
+    succ!();
+}
@@ -0,0 +1,57 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use arch::context::ContextMemory;
+    use core::usize;
+    use syscall::{do_sys_close, do_sys_fpath, do_sys_fstat, do_sys_open, do_sys_pipe2, do_sys_read,
+                  do_sys_stat, do_sys_write, Stat, EFAULT, O_RDONLY};
+
+    test!(do_sys_pipe2(0 as *mut usize, 0).map_err(|err| err.errno) == Err(EFAULT));
+
+    let mut fds = [0; 2];
+    test!(do_sys_pipe2(fds.as_mut_ptr(), 0).is_ok());
+
+    let read = do_sys_read(fds[0], 0 as *mut u8, 4).map_err(|err| err.errno);
+    let write = do_sys_write(fds[1], 0 as *const u8, 4).map_err(|err| err.errno);
+    let fpath = do_sys_fpath(fds[0], 0 as *mut u8, 4096).map_err(|err| err.errno);
+
+    // A buffer that wraps around the end of the address space is outside of every mapping
+    let wrapped = do_sys_write(fds[1], (usize::MAX - 1) as *const u8, 4).map_err(|err| err.errno);
+
+    // An empty buffer is never accessed
+    let empty = do_sys_write(fds[1], 0 as *const u8, 0).ok();
+
+    test!(do_sys_close(fds[0]).is_ok());
+    test!(do_sys_close(fds[1]).is_ok());
+
+    test!(read == Err(EFAULT));
+    test!(write == Err(EFAULT));
+    test!(fpath == Err(EFAULT));
+    test!(wrapped == Err(EFAULT));
+    test!(empty == Some(0));
+
+    let fd = if let Ok(fd) = do_sys_open("initfs:/bin/\0".as_ptr(), O_RDONLY) {
+        fd
+    } else {
+        fail!();
+    };
+    let fstat = do_sys_fstat(fd, 0 as *mut Stat).map_err(|err| err.errno);
+    test!(do_sys_close(fd).is_ok());
+    test!(fstat == Err(EFAULT));
+
+    test!(do_sys_stat("initfs:/bin\0".as_ptr(), 0 as *mut Stat).map_err(|err| err.errno) == Err(EFAULT));
+
+    let mem = ContextMemory {
+        physical_address: 0,
+        virtual_address: 0x80000000,
+        virtual_size: 4096,
+        writeable: true,
+        allocated: false,
+    };
+    test!(mem.contains(0x80000000, 4096));
+    test!(! mem.contains(0x80000001, 4096));
+    test!(! mem.contains(0x80000010, usize::MAX - 8));
+    succ!();
+}
@@ -0,0 +1,34 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use syscall::{do_sys_close, do_sys_futimens, do_sys_open, do_sys_stat, do_sys_utimens, Stat, TimeSpec,
+                  ENOENT, EPERM, O_RDONLY};
+
+    let path = "initfs:/bin/init\0".as_ptr();
+    let times = [TimeSpec { tv_sec: 100, tv_nsec: 1 }, TimeSpec { tv_sec: 200, tv_nsec: 2 }];
+
+    let mut stat = Stat::default();
+    test!(do_sys_utimens(path, times.as_ptr()).is_ok());
+    test!(do_sys_stat(path, &mut stat).is_ok());
+    test!(stat.st_atime.tv_sec == 100 && stat.st_atime.tv_nsec == 1);
+    test!(stat.st_mtime.tv_sec == 200 && stat.st_mtime.tv_nsec == 2);
+
+    // A null pointer sets both times to now
+    test!(do_sys_utimens(path, 0 as *const TimeSpec).is_ok());
+    test!(do_sys_stat(path, &mut stat).is_ok());
+    test!(stat.st_atime.tv_sec == stat.st_mtime.tv_sec && stat.st_mtime.tv_sec != 200);
+
+    test!(do_sys_utimens("initfs:/utimens_missing\0".as_ptr(), times.as_ptr()).map_err(|err| err.errno) == Err(ENOENT));
+
+    // The resources of initfs are copies, which have no timestamps
+    let fd = if let Ok(fd) = do_sys_open(path, O_RDONLY) {
+        fd
+    } else {
+        fail!();
+    };
+    test!(do_sys_futimens(fd, times.as_ptr()).map_err(|err| err.errno) == Err(EPERM));
+    test!(do_sys_close(fd).is_ok());
+    succ!();
+}
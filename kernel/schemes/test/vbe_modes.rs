@@ -0,0 +1,28 @@
+pub fn test() -> bool {
+    use graphics::display::{parse_resolution, vbe_find_mode, VBEMode};
+
+    test!(parse_resolution("1024x768") == Some((1024, 768)));
+    test!(parse_resolution(" 800x600\n") == Some((800, 600)));
+    test!(parse_resolution("1024").is_none());
+    test!(parse_resolution("0x768").is_none());
+    test!(parse_resolution("1024x768x32").is_none());
+
+    let modes = [
+        VBEMode::new(0x117, 1024, 768, 2048, 16, 0xE0000000),
+        VBEMode::new(0x118, 1024, 768, 4352, 32, 0xE0000000),
+        VBEMode::new(0x115, 800, 600, 3200, 32, 0xE0000000),
+    ];
+
+    // Only 32 bit modes are chosen, and padded scanlines are kept
+    match vbe_find_mode(&modes, 1024, 768) {
+        Some(mode) => {
+            let (number, bytesperscanline) = (mode.mode, mode.bytesperscanline);
+            test!(number == 0x118);
+            test!(bytesperscanline == 4352);
+        },
+        None => { fail!(); }
+    }
+    test!(vbe_find_mode(&modes, 1280, 1024).is_none());
+
+    succ!();
+}
@@ -0,0 +1,111 @@
+pub fn test() -> bool {
+    use alloc::arc::Arc;
+    use arch::context::Context;
+    use collections::string::ToString;
+    use common::time::Duration;
+    use sync::{Intex, WaitCondition, WaitEvent, WaitQueue};
+    use syscall::do_sys_yield;
+
+    const WORKERS: usize = 8;
+    const ROUNDS: usize = 64;
+
+    // Yield to the other contexts until `done`, for at most a few seconds
+    let settle = |done: &Fn() -> bool| {
+        let deadline = Duration::monotonic() + Duration::new(5, 0);
+        while ! done() && Duration::monotonic() < deadline {
+            let _ = do_sys_yield();
+        }
+        done()
+    };
+
+    // Waits time out when nothing is sent
+    let queue = WaitQueue::<usize>::new();
+    let start = Duration::monotonic();
+    test!(queue.receive_timeout(Duration::new(0, 10000000)).is_none());
+    test!(Duration::monotonic() - start >= Duration::new(0, 10000000));
+    test!(! queue.wait_timeout(Duration::new(0, 1000000)));
+    test!(queue.condition.len() == 0);
+    queue.send(1);
+    test!(queue.wait_timeout(Duration::new(1, 0)));
+    test!(queue.receive_timeout(Duration::new(1, 0)) == Some(1));
+
+    // Contexts ping-ponging through queues never miss a wakeup
+    let pings = Arc::new(WaitQueue::<usize>::new());
+    let pongs = Arc::new(WaitQueue::<usize>::new());
+    for _ in 0..WORKERS {
+        let pings = pings.clone();
+        let pongs = pongs.clone();
+        Context::spawn("ktest".to_string(), box move || {
+            for _ in 0..ROUNDS {
+                let value = pings.receive();
+                pongs.send(value + 1);
+            }
+        });
+    }
+
+    let mut sum = 0;
+    for round in 0..ROUNDS {
+        for worker in 0..WORKERS {
+            pings.send(round * WORKERS + worker);
+        }
+        for _ in 0..WORKERS {
+            match pongs.receive_timeout(Duration::new(5, 0)) {
+                Some(value) => sum += value,
+                None => { fail!(); }
+            }
+        }
+    }
+    let count = ROUNDS * WORKERS;
+    test!(sum == count * (count + 1) / 2);
+    test!(settle(&|| pings.condition.len() == 0));
+
+    // Notifying wakes only as many contexts as asked, the ones that waited the longest
+    let condition = Arc::new(WaitCondition::new());
+    let woken = Arc::new(Intex::new(0usize));
+    for _ in 0..3 {
+        let condition = condition.clone();
+        let woken = woken.clone();
+        Context::spawn("ktest".to_string(), box move || {
+            if unsafe { condition.wait_timeout(Duration::new(5, 0)) } {
+                *woken.lock() += 1;
+            }
+        });
+    }
+    test!(settle(&|| condition.len() == 3));
+    test!(unsafe { condition.notify_n(2) } == 2);
+    test!(settle(&|| *woken.lock() == 2));
+    test!(condition.len() == 1);
+    unsafe { condition.notify(); }
+    test!(settle(&|| *woken.lock() == 3));
+
+    // A context that timed out is no longer waiting
+    test!(! unsafe { condition.wait_timeout(Duration::new(0, 1000000)) });
+    test!(condition.len() == 0);
+
+    // A context can be forgotten without waking it
+    let context = unsafe { condition.block(None) };
+    test!(condition.remove(context));
+    test!(! condition.remove(context));
+    if let Ok(mut current) = ::env().contexts.lock().current_mut() {
+        current.blocked = false;
+    }
+    test!(unsafe { condition.notify_n(1) } == 0);
+
+    // Waiting for an event returns at once if one came since the count was read, and otherwise
+    // at the next one or at the deadline
+    let event = Arc::new(WaitEvent::new());
+    let seen = event.count();
+    event.notify();
+    test!(event.wait_until(seen, None));
+    let seen = event.count();
+    let start = Duration::monotonic();
+    test!(! event.wait_until(seen, Some(start + Duration::new(0, 10000000))));
+    test!(Duration::monotonic() - start >= Duration::new(0, 10000000));
+    {
+        let event = event.clone();
+        Context::spawn("ktest".to_string(), box move || event.notify());
+    }
+    test!(event.wait_until(seen, Some(Duration::monotonic() + Duration::new(5, 0))));
+
+    succ!();
+}
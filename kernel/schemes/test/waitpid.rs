@@ -0,0 +1,73 @@
+pub fn test() -> bool {
+    super::in_kernel_context(run)
+}
+
+fn run() -> bool {
+    use arch::context::Context;
+    use collections::string::ToString;
+    use core::ptr;
+    use syscall::{do_sys_exit, do_sys_waitpid, ECHILD, WNOHANG};
+
+    // Spawn a child, which can not run before it is adopted
+    let pid = {
+        let mut contexts = ::env().contexts.lock();
+        let ppid = match contexts.current() {
+            Ok(current) => current.pid,
+            Err(_) => {
+                fail!();
+            },
+        };
+
+        let pid = Context::spawn("ktest".to_string(), box || {
+            do_sys_exit(3);
+        });
+        match contexts.find_mut(pid) {
+            Ok(child) => child.ppid = ppid,
+            Err(_) => {
+                fail!();
+            },
+        }
+
+        // The child has not exited yet
+        let mut status = 0;
+        test!(do_sys_waitpid(pid as isize, &mut status, WNOHANG).ok() == Some(0));
+
+        pid
+    };
+
+    // Waiting blocks until the child exits, and reaps it
+    let mut status = 0;
+    test!(do_sys_waitpid(pid as isize, &mut status, 0).ok() == Some(pid));
+    test!(status == 3 << 8);
+    test!(::env().contexts.lock().find(pid).is_err());
+    test!(do_sys_waitpid(pid as isize, &mut status, WNOHANG).map_err(|err| err.errno) == Err(ECHILD));
+
+    // Any child can be waited for
+    let pid = {
+        let mut contexts = ::env().contexts.lock();
+        let ppid = match contexts.current() {
+            Ok(current) => current.pid,
+            Err(_) => {
+                fail!();
+            },
+        };
+
+        let pid = Context::spawn("ktest".to_string(), box || {
+            do_sys_exit(5);
+        });
+        match contexts.find_mut(pid) {
+            Ok(child) => child.ppid = ppid,
+            Err(_) => {
+                fail!();
+            },
+        }
+
+        pid
+    };
+    test!(do_sys_waitpid(-1, &mut status, 0).ok() == Some(pid));
+    test!(status == 5 << 8);
+
+    // Only children can be waited for
+    test!(do_sys_waitpid(1, ptr::null_mut(), WNOHANG).map_err(|err| err.errno) == Err(ECHILD));
+    succ!();
+}
@@ -0,0 +1,24 @@
+# Turn the output of nm -n into a table of the functions of the kernel, sorted by address, for
+# backtraces. Each entry is the address, a pointer to the name, and the length of the name.
+# Run with -v bits=32 or -v bits=64.
+
+BEGIN {
+    word = (bits == 64) ? "dq" : "dd";
+    print "SECTION .symbols progbits alloc noexec nowrite align=8";
+    n = 0;
+}
+
+$2 == "t" || $2 == "T" {
+    address[n] = $1;
+    name[n] = $3;
+    n++;
+}
+
+END {
+    for (i = 0; i < n; i++) {
+        printf "    %s 0x%s, symbol_%d, %d\n", word, address[i], i, length(name[i]);
+    }
+    for (i = 0; i < n; i++) {
+        printf "symbol_%d: db \"%s\"\n", i, name[i];
+    }
+}
@@ -2,11 +2,15 @@ use arch::context::{context_switch, Context};
 
 use collections::Vec;
 
-use core::mem;
+use common::time::Duration;
+
+use core::{cmp, mem};
 use core::ops::DerefMut;
 
 use super::Intex;
 
+use syscall::{context_exit, signal_terminating};
+
 pub struct WaitCondition {
     contexts: Intex<Vec<*mut Context>>
 }
@@ -18,21 +22,93 @@ impl WaitCondition {
         }
     }
 
+    /// The number of waiting contexts
+    pub fn len(&self) -> usize {
+        self.contexts.lock().len()
+    }
+
+    /// Wake all the waiting contexts
     pub unsafe fn notify(&self) {
         let mut contexts = Vec::new();
         mem::swap(self.contexts.lock().deref_mut(), &mut contexts);
         for &context in contexts.iter() {
             (*context).blocked = false;
+            (*context).wake = None;
+        }
+    }
+
+    /// Wake the `count` contexts that have waited the longest. Returns the number woken
+    pub unsafe fn notify_n(&self, count: usize) -> usize {
+        let mut contexts = self.contexts.lock();
+        let count = cmp::min(count, contexts.len());
+        for context in contexts.drain(.. count) {
+            (*context).blocked = false;
+            (*context).wake = None;
         }
+        count
+    }
+
+    /// Forget a waiting context without waking it, such as one that is gone. Returns true if it
+    /// was waiting
+    pub fn remove(&self, context_ptr: *const Context) -> bool {
+        let mut contexts = self.contexts.lock();
+        let len = contexts.len();
+        contexts.retain(|&context| context as *const Context != context_ptr);
+        contexts.len() != len
     }
 
+    /// Block the current context until notified. A context woken by a signal that terminates it
+    /// exits here instead of returning
     pub unsafe fn wait(&self) {
+        let context_ptr = self.block(None);
+        self.sleep(context_ptr);
+    }
+
+    /// Block the current context until notified, or until `timeout` has passed. Returns true if
+    /// it was notified
+    pub unsafe fn wait_timeout(&self, timeout: Duration) -> bool {
+        let context_ptr = self.block(Some(Duration::monotonic() + timeout));
+        self.sleep(context_ptr)
+    }
+
+    /// Add the current context to the waiting contexts and mark it blocked, waking it at
+    /// `deadline` if it is not notified before
+    ///
+    /// The context keeps running until it calls `sleep` with the returned context. Checking for
+    /// what is waited for and blocking under the lock of the notifier means a notification can not
+    /// be lost in between.
+    pub unsafe fn block(&self, deadline: Option<Duration>) -> *mut Context {
+        let mut context_ptr = 0 as *mut Context;
         if let Ok(mut context) = ::env().contexts.lock().current_mut() {
-            let mut contexts = self.contexts.lock();
-            contexts.push(context.deref_mut() as *mut Context);
-            (*context).blocked = true;
+            context_ptr = context.deref_mut() as *mut Context;
+            self.contexts.lock().push(context_ptr);
+            context.blocked = true;
+            if deadline.is_some() {
+                context.wake = deadline;
+            }
         }
+        context_ptr
+    }
+
+    /// Switch away from a context blocked by `block` until it is woken. Returns true if it was
+    /// notified
+    ///
+    /// A context woken by a signal that terminates it exits here instead of returning, passing a
+    /// notification it took on to the next waiting context.
+    pub unsafe fn sleep(&self, context_ptr: *mut Context) -> bool {
         context_switch();
+
+        // Forget the context if it was woken by something else, such as a timeout or a signal
+        let notified = ! self.remove(context_ptr);
+
+        if let Some(sig) = signal_terminating() {
+            if notified {
+                self.notify_n(1);
+            }
+            context_exit(sig);
+        }
+
+        notified
     }
 }
 
@@ -0,0 +1,50 @@
+use common::time::Duration;
+
+use super::Intex;
+use super::WaitCondition;
+
+/// A count of events that contexts can wait for the next of
+///
+/// A waiter reads the count before checking what it waits for, and blocks only if no event came
+/// since, so an event in between is not missed.
+pub struct WaitEvent {
+    count: Intex<u64>,
+    condition: WaitCondition,
+}
+
+impl WaitEvent {
+    pub fn new() -> WaitEvent {
+        WaitEvent {
+            count: Intex::new(0),
+            condition: WaitCondition::new(),
+        }
+    }
+
+    /// The number of events so far
+    pub fn count(&self) -> u64 {
+        *self.count.lock()
+    }
+
+    /// Count an event, waking all the waiting contexts
+    pub fn notify(&self) {
+        let mut count = self.count.lock();
+        *count = count.wrapping_add(1);
+        unsafe { self.condition.notify(); }
+    }
+
+    /// Block until there was an event since the count was `seen`, or until `deadline` if there is
+    /// one. Returns true if there was an event
+    pub fn wait_until(&self, seen: u64, deadline: Option<Duration>) -> bool {
+        let context = {
+            let count = self.count.lock();
+            if *count != seen {
+                return true;
+            }
+            if deadline.map_or(false, |deadline| Duration::monotonic() >= deadline) {
+                return false;
+            }
+            unsafe { self.condition.block(deadline) }
+        };
+        unsafe { self.condition.sleep(context) }
+    }
+}
@@ -1,11 +1,20 @@
+use arch::context::Context;
+
+use collections::vec::Vec;
 use collections::vec_deque::VecDeque;
 
+use common::time::Duration;
+
 use core::mem;
 use core::ops::DerefMut;
 
 use super::Intex;
 use super::WaitCondition;
 
+/// A queue that blocks receivers while it is empty
+///
+/// Each value sent wakes one receiver, and a receiver that leaves values behind wakes the next.
+/// Receivers check the queue and block under its lock, so a value sent in between is not missed.
 pub struct WaitQueue<T> {
     pub inner: Intex<VecDeque<T>>,
     pub condition: WaitCondition,
@@ -28,29 +37,115 @@ impl<T> WaitQueue<T> {
 
     pub fn receive(&self) -> T {
         loop {
-            if let Some(value) = self.inner.lock().pop_front() {
-                return value;
+            let context = {
+                let mut inner = self.inner.lock();
+                if let Some(value) = inner.pop_front() {
+                    if ! inner.is_empty() {
+                        unsafe { self.condition.notify_n(1); }
+                    }
+                    return value;
+                }
+                unsafe { self.condition.block(None) }
+            };
+            unsafe { self.condition.sleep(context); }
+        }
+    }
+
+    /// Receive a value, or `None` if none was sent before `timeout` passed
+    pub fn receive_timeout(&self, timeout: Duration) -> Option<T> {
+        let deadline = Duration::monotonic() + timeout;
+        loop {
+            let context = {
+                let mut inner = self.inner.lock();
+                if let Some(value) = inner.pop_front() {
+                    if ! inner.is_empty() {
+                        unsafe { self.condition.notify_n(1); }
+                    }
+                    return Some(value);
+                }
+                if Duration::monotonic() >= deadline {
+                    return None;
+                }
+                unsafe { self.condition.block(Some(deadline)) }
+            };
+            unsafe { self.condition.sleep(context); }
+        }
+    }
+
+    /// Receive at least one and at most `max` values
+    pub fn receive_max(&self, max: usize) -> Vec<T> {
+        let mut values = Vec::new();
+        values.push(self.receive());
+
+        let mut inner = self.inner.lock();
+        while values.len() < max {
+            match inner.pop_front() {
+                Some(value) => values.push(value),
+                None => break,
             }
-            unsafe { self.condition.wait(); }
         }
+        if ! inner.is_empty() {
+            unsafe { self.condition.notify_n(1); }
+        }
+
+        values
     }
 
     pub fn receive_all(&self) -> VecDeque<T> {
         loop {
-            {
+            let context = {
                 let mut inner = self.inner.lock();
                 if ! inner.is_empty() {
                     let mut swap_inner = VecDeque::new();
                     mem::swap(inner.deref_mut(), &mut swap_inner);
                     return swap_inner;
                 }
-            }
-            unsafe { self.condition.wait(); }
+                unsafe { self.condition.block(None) }
+            };
+            unsafe { self.condition.sleep(context); }
+        }
+    }
+
+    /// Block until a value is queued, without taking it, or until `timeout` has passed. Returns
+    /// true if a value is queued
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Duration::monotonic() + timeout;
+        loop {
+            let context = {
+                let inner = self.inner.lock();
+                if ! inner.is_empty() {
+                    return true;
+                }
+                if Duration::monotonic() >= deadline {
+                    return false;
+                }
+                unsafe { self.condition.block(Some(deadline)) }
+            };
+            unsafe { self.condition.sleep(context); }
         }
     }
 
     pub fn send(&self, value: T) {
         self.inner.lock().push_back(value);
-        unsafe { self.condition.notify(); }
+        unsafe { self.condition.notify_n(1); }
+    }
+
+    /// Send all the values of a slice, waking one receiver
+    pub fn send_slice(&self, values: &[T]) where T: Clone {
+        if ! values.is_empty() {
+            self.inner.lock().extend(values.iter().cloned());
+            unsafe { self.condition.notify_n(1); }
+        }
+    }
+
+    /// Wake up to `count` receivers without sending anything, so they check again what they wait
+    /// for. Returns the number woken
+    pub fn notify_n(&self, count: usize) -> usize {
+        unsafe { self.condition.notify_n(count) }
+    }
+
+    /// Forget a receiver that is gone, so it is not woken
+    pub fn remove(&self, context: *const Context) -> bool {
+        self.condition.remove(context)
     }
 }
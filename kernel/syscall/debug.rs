@@ -1,6 +1,6 @@
 use core::slice;
 
-use drivers::io::{Io, Pio};
+use drivers::serial::{self, COM1};
 
 use system::error::Result;
 
@@ -10,19 +10,11 @@ pub fn do_sys_debug(ptr: *const u8, len: usize) -> Result<usize> {
     if unsafe { ::ENV_PTR.is_some() } {
         ::env().console.lock().write(bytes);
     } else {
-        let serial_status = Pio::<u8>::new(0x3F8 + 5);
-        let mut serial_data = Pio::<u8>::new(0x3F8);
-
         for byte in bytes.iter() {
-            while !serial_status.readf(0x20) {}
-            serial_data.write(*byte);
+            serial::write(COM1, &[*byte]);
 
             if *byte == 8 {
-                while !serial_status.readf(0x20) {}
-                serial_data.write(0x20);
-
-                while !serial_status.readf(0x20) {}
-                serial_data.write(8);
+                serial::write(COM1, &[0x20, 8]);
             }
         }
     }
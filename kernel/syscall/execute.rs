@@ -7,6 +7,7 @@ use arch::elf::Elf;
 use arch::memory;
 use arch::regs::Regs;
 
+use collections::BTreeMap;
 use collections::string::{String, ToString};
 use collections::vec::Vec;
 
@@ -19,6 +20,7 @@ use core::{mem, ptr, slice, str};
 use fs::Url;
 
 use system::error::{Error, Result, ENOEXEC, ENOMEM};
+use system::syscall::{O_RDONLY, SIG_IGN};
 
 pub fn execute_thread(context_ptr: *mut Context, entry: usize, mut args: Vec<String>) -> ! {
     Context::spawn("kexec".to_string(), box move || {
@@ -111,12 +113,12 @@ pub fn execute(mut args: Vec<String>) -> Result<usize> {
     let path = current.canonicalize(args.get(0).map_or("", |p| &p));
     let mut url = try!(Url::from_str(&path)).to_cow();
     {
-        let mut resource = if let Ok(resource) = url.as_url().open() {
+        let mut resource = if let Ok(resource) = ::env().open(url.as_url(), O_RDONLY) {
             resource
         } else {
             let path = "file:/bin/".to_string() + args.get(0).map_or("", |p| &p);
             url = try!(Url::from_str(&path)).to_owned().into_cow();
-            try!(url.as_url().open())
+            try!(::env().open(url.as_url(), O_RDONLY))
         };
 
         // Hack to allow file scheme to find memory in context's memory space
@@ -216,6 +218,17 @@ pub fn execute(mut args: Vec<String>) -> Result<usize> {
                     context.name = url.as_url().to_string();
                     context.cwd = Arc::new(UnsafeCell::new(unsafe { (*context.cwd.get()).clone() }));
 
+                    // Close the files marked close-on-exec
+                    let pid = context.pid;
+                    unsafe {
+                        (*context.files.get()).retain(|file| {
+                            if file.cloexec {
+                                ::env().locks.close(pid, file.fd);
+                            }
+                            ! file.cloexec
+                        })
+                    };
+
                     unsafe { context.unmap() };
 
                     let mut image = ContextZone::new(CONTEXT_IMAGE_ADDR, CONTEXT_IMAGE_SIZE);
@@ -226,6 +239,16 @@ pub fn execute(mut args: Vec<String>) -> Result<usize> {
                     context.mmap = Arc::new(UnsafeCell::new(ContextZone::new(CONTEXT_MMAP_ADDR, CONTEXT_MMAP_SIZE)));
                     context.env_vars = Arc::new(UnsafeCell::new(unsafe { (*context.env_vars.get()).clone() }));
 
+                    // Handlers are not in the new image, but ignored signals stay ignored
+                    let mut signal_handlers = BTreeMap::new();
+                    for (&sig, &handler) in unsafe { (*context.signal_handlers.get()).iter() } {
+                        if handler.handler == SIG_IGN {
+                            signal_handlers.insert(sig, handler);
+                        }
+                    }
+                    context.signal_handlers = Arc::new(UnsafeCell::new(signal_handlers));
+                    context.signal_regs.clear();
+
                     unsafe { context.map() };
 
                     execute_thread(context.deref_mut(), entry, args);
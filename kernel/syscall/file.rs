@@ -1,16 +1,81 @@
 use arch::context::ContextFile;
 
-use core::slice;
+use collections::string::ToString;
+
+use common::time::{Duration, NANOS_PER_MILLI};
+
+use env::file_locks::LockOwner;
+
+use core::{cmp, mem, ptr, slice, str};
 
 use fs::{ResourceSeek, Url};
 
 use schemes::pipe::{PipeRead, PipeWrite};
 
+use super::uaccess::{copy_slice_to_user, copy_to_user};
+
 use system::c_string_to_str;
 
-use syscall::{Stat, SEEK_CUR, SEEK_END, SEEK_SET};
+use syscall::{IoVec, PollFd, Stat, StatVfs, TimeSpec, FD_CLOEXEC, F_DUPFD, F_GETFD, F_GETFL, F_SETFD, F_SETFL, IOV_MAX,
+              LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN, MODE_DIR, O_ACCMODE, O_APPEND, O_CLOEXEC, O_DIRECTORY,
+              O_NONBLOCK, O_RDONLY, O_WRONLY,
+              POLLERR, POLLHUP, POLLNVAL, R_OK, SEEK_CUR, SEEK_END, SEEK_SET, W_OK, X_OK};
+
+use system::error::{Error, Result, EACCES, EBADF, EFAULT, EINVAL, EISDIR, ENOTDIR, ERANGE};
+
+/** <!-- @MANSTART{sys_access} -->
+NAME
+    sys_access - check permissions for a file
+
+SYNOPSIS
+    sys_access(path: *const u8, mode: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_access checks whether path exists, following symbolic links, and whether it grants the
+    permissions in mode. mode is either F_OK, which only checks for existence, or a combination
+    of R_OK, W_OK and X_OK. As there are no users, the owner permission bits of the mode returned
+    by sys_stat are checked. A scheme that reports no permission bits at all does not restrict
+    access
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
+
+ERRORS
+    EACCES
+        One of the requested permissions is not granted
+
+    EINVAL
+        mode contains bits other than R_OK, W_OK and X_OK
+
+    ELOOP
+        Too many symbolic links were encountered in resolving path
+
+    ENOENT
+        path does not exist, or the scheme of path does not exist
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_access(path: *const u8, mode: usize) -> Result<usize> {
+    if mode & !(R_OK | W_OK | X_OK) != 0 {
+        return Err(Error::new(EINVAL));
+    }
+
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path = current.canonicalize(c_string_to_str(path));
+
+    let mut stat = Stat::default();
+    try!(::env().stat(try!(Url::from_str(&path)), &mut stat));
 
-use system::error::{Error, Result, EBADF, EFAULT, EINVAL};
+    let perm = (mode as u16) << 6;
+    if stat.st_mode & 0o777 == 0 || stat.st_mode & perm == perm {
+        Ok(0)
+    } else {
+        Err(Error::new(EACCES))
+    }
+}
 
 /** <!-- @MANSTART{sys_chdir} -->
 NAME
@@ -21,7 +86,7 @@ SYNOPSIS
 
 DESCRIPTION
     sys_chdir changes the current working directory of the calling process to the directory
-    specified in path
+    specified in path. The working directory always ends with a '/'
 
 RETURN VALUE
     On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
@@ -37,14 +102,14 @@ ERRORS
     EIO TODO
         An I/O error occured
 
-    ENOENT TODO
+    ENOENT
         path references a directory that does not exist
 
     ENOMEM TODO
         Insufficient kernel memory was available
 
-    ENOTDIR TODO
-        A component of path is not a directory
+    ENOTDIR
+        path is not a directory
 
     ESRCH
         Currently not running in a process context (rare, would only happen during kernel init)
@@ -52,8 +117,19 @@ ERRORS
 pub fn do_sys_chdir(path: *const u8) -> Result<usize> {
     let contexts = ::env().contexts.lock();
     let current = try!(contexts.current());
+    let mut path_string = current.canonicalize(c_string_to_str(path));
+
+    let mut stat = Stat::default();
+    try!(::env().stat(try!(Url::from_str(&path_string)), &mut stat));
+    if stat.st_mode & MODE_DIR != MODE_DIR {
+        return Err(Error::new(ENOTDIR));
+    }
+
+    if ! path_string.ends_with('/') {
+        path_string.push('/');
+    }
     unsafe {
-        *current.cwd.get() = current.canonicalize(c_string_to_str(path));
+        *current.cwd.get() = path_string;
     }
     Ok(0)
 }
@@ -99,6 +175,7 @@ pub fn do_sys_close(fd: usize) -> Result<usize> {
         if remove {
             if i < unsafe { (*current.files.get()).len() } {
                 drop(unsafe { (*current.files.get()).remove(i) });
+                ::env().locks.close(current.pid, fd);
 
                 return Ok(0);
             }
@@ -116,7 +193,8 @@ SYNOPSIS
     sys_dup(fd: usize) -> Result<usize>;
 
 DESCRIPTION
-    sys_dup creates a copy of fd, using the lowest unused descriptor for the new descriptor
+    sys_dup creates a copy of fd, using the lowest unused descriptor for the new descriptor. The
+    new descriptor has the same access mode as fd
 
 RETURN VALUE
     On success, Ok(new_fd) is returned, where new_fd is the new file descriptor. On error, Err(err)
@@ -132,8 +210,8 @@ ERRORS
 pub fn do_sys_dup(fd: usize) -> Result<usize> {
     let contexts = ::env().contexts.lock();
     let current = try!(contexts.current());
-    let resource = try!(current.get_file(fd));
-    let new_resource = try!(resource.dup());
+    let file = try!(current.get_context_file(fd));
+    let new_resource = try!(file.resource.dup());
     let new_fd = current.next_fd();
 
     //debugln!("{}: {}: dup {} as {}", current.pid, current.name, fd, new_fd);
@@ -141,28 +219,268 @@ pub fn do_sys_dup(fd: usize) -> Result<usize> {
     unsafe {
         (*current.files.get()).push(ContextFile {
             fd: new_fd,
+            flags: file.flags,
+            cloexec: false,
+            resource: new_resource,
+        });
+    }
+    Ok(new_fd)
+}
+
+/** <!-- @MANSTART{sys_dup2} -->
+NAME
+    sys_dup2 - duplicate a file descriptor onto a chosen descriptor
+
+SYNOPSIS
+    sys_dup2(fd: usize, new_fd: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_dup2 creates a copy of fd, using new_fd for the new descriptor. If new_fd is already open,
+    it is closed first. If fd and new_fd are the same, and fd is valid, nothing is done. The new
+    descriptor has the same access mode as fd
+
+RETURN VALUE
+    On success, Ok(new_fd) is returned. On error, Err(err) is returned where err is one of the
+    following errors
+
+ERRORS
+    EBADF
+        fd is not a valid open file decriptor
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_dup2(fd: usize, new_fd: usize) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let file = try!(current.get_context_file(fd));
+    if fd == new_fd {
+        return Ok(new_fd);
+    }
+
+    let flags = file.flags;
+    let new_resource = try!(file.resource.dup());
+
+    //debugln!("{}: {}: dup2 {} as {}", current.pid, current.name, fd, new_fd);
+
+    unsafe {
+        (*current.files.get()).retain(|file| file.fd != new_fd);
+        ::env().locks.close(current.pid, new_fd);
+        (*current.files.get()).push(ContextFile {
+            fd: new_fd,
+            flags: flags,
+            cloexec: false,
             resource: new_resource,
         });
     }
     Ok(new_fd)
 }
 
+/** <!-- @MANSTART{sys_fcntl} -->
+NAME
+    sys_fcntl - manipulate a file descriptor
+
+SYNOPSIS
+    sys_fcntl(fd: usize, cmd: usize, arg: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_fcntl performs the operation cmd on the file descriptor fd
+
+    F_DUPFD: 0
+        Duplicate fd, using the lowest available file descriptor greater than or equal to arg. The
+        new descriptor does not have FD_CLOEXEC set
+
+    F_GETFD: 1
+        Return the file descriptor flags of fd. FD_CLOEXEC is the only descriptor flag
+
+    F_SETFD: 2
+        Set the file descriptor flags of fd to arg
+
+    F_GETFL: 3
+        Return the file status flags of fd, including the access mode
+
+    F_SETFL: 4
+        Set the file status flags of fd to arg. Only O_APPEND and O_NONBLOCK can be changed, the
+        access mode and other flags in arg are ignored
+
+RETURN VALUE
+    On success, the return value depends on cmd. F_DUPFD returns Ok(new_fd), F_GETFD and F_GETFL
+    return Ok(flags), and the other commands return Ok(0). On error, Err(err) is returned where err
+    is one of the following errors
+
+ERRORS
+    EBADF
+        fd is not a valid open file decriptor
+
+    EINVAL
+        cmd is not a recognized command
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_fcntl(fd: usize, cmd: usize, arg: usize) -> Result<usize> {
+    let mut contexts = ::env().contexts.lock();
+    let mut current = try!(contexts.current_mut());
+    let file = try!(current.get_context_file_mut(fd));
+    match cmd {
+        F_DUPFD => {
+            let flags = file.flags;
+            let new_resource = try!(file.resource.dup());
+            let new_fd = current.next_fd_from(arg);
+            unsafe {
+                (*current.files.get()).push(ContextFile {
+                    fd: new_fd,
+                    flags: flags,
+                    cloexec: false,
+                    resource: new_resource,
+                });
+            }
+            Ok(new_fd)
+        },
+        F_GETFD => Ok(if file.cloexec { FD_CLOEXEC } else { 0 }),
+        F_SETFD => {
+            file.cloexec = arg & FD_CLOEXEC == FD_CLOEXEC;
+            Ok(0)
+        },
+        F_GETFL => Ok(file.flags),
+        F_SETFL => {
+            let mask = O_APPEND | O_NONBLOCK;
+            let flags = (file.flags & !mask) | (arg & mask);
+            try!(file.resource.set_flags(flags));
+            file.flags = flags;
+            Ok(0)
+        },
+        _ => Err(Error::new(EINVAL)),
+    }
+}
+
+/** <!-- @MANSTART{sys_flock} -->
+NAME
+    sys_flock - apply or remove an advisory lock on an open file
+
+SYNOPSIS
+    sys_flock(fd: usize, operation: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_flock takes or releases a lock on the file open as fd. Files are identified by their path.
+    Locks are advisory, reads and writes are not affected by them
+
+    LOCK_SH: 1
+        Take a shared lock. More than one file descriptor may hold a shared lock on a file
+
+    LOCK_EX: 2
+        Take an exclusive lock. Only one file descriptor may hold an exclusive lock on a file
+
+    LOCK_UN: 8
+        Release the lock held through fd
+
+    LOCK_NB: 4
+        Combined with LOCK_SH or LOCK_EX, fail instead of waiting for a conflicting lock to be
+        released
+
+    Taking a lock while fd already holds one converts it. Locks belong to the file descriptor, so
+    descriptors from dup or fork do not share them. Locks are released when fd is closed, and when
+    the context exits
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the
+    following errors
+
+ERRORS
+    EBADF
+        fd is not a valid open file decriptor
+
+    EINVAL
+        operation is not one of LOCK_SH, LOCK_EX and LOCK_UN, optionally with LOCK_NB
+
+    EWOULDBLOCK
+        LOCK_NB is set, and the file is locked in a conflicting way
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_flock(fd: usize, operation: usize) -> Result<usize> {
+    let (path, owner) = {
+        let contexts = ::env().contexts.lock();
+        let current = try!(contexts.current());
+        let resource = try!(current.get_file(fd));
+
+        let mut path = [0; 4096];
+        let count = try!(resource.path(&mut path));
+        let path = try!(str::from_utf8(&path[.. count]).or(Err(Error::new(EINVAL)))).to_string();
+
+        (path, LockOwner { pid: current.pid, fd: fd })
+    };
+
+    let nonblock = operation & LOCK_NB == LOCK_NB;
+    match operation & !LOCK_NB {
+        LOCK_SH => try!(::env().locks.lock(&path, owner, false, nonblock)),
+        LOCK_EX => try!(::env().locks.lock(&path, owner, true, nonblock)),
+        LOCK_UN => ::env().locks.unlock(&path, owner),
+        _ => return Err(Error::new(EINVAL)),
+    }
+
+    Ok(0)
+}
+
 pub fn do_sys_fpath(fd: usize, buf: *mut u8, count: usize) -> Result<usize> {
     let contexts = ::env().contexts.lock();
     let current = try!(contexts.current());
     let resource = try!(current.get_file(fd));
-    resource.path(unsafe { slice::from_raw_parts_mut(buf, count) })
+    let mut path = [0; 4096];
+    let len = cmp::min(count, path.len());
+    let len = try!(resource.path(&mut path[.. len]));
+    try!(copy_slice_to_user(buf, &path[.. len]));
+    Ok(len)
 }
 
 pub fn do_sys_fstat(fd: usize, stat: *mut Stat) -> Result<usize> {
     let contexts = ::env().contexts.lock();
     let current = try!(contexts.current());
     let resource = try!(current.get_file(fd));
-    if stat as usize > 0 {
-        resource.stat(unsafe { &mut *stat })
-    } else {
-        Err(Error::new(EFAULT))
-    }
+    let mut value = Stat::default();
+    let ret = try!(resource.stat(&mut value));
+    try!(copy_to_user(stat, value));
+    Ok(ret)
+}
+
+/** <!-- @MANSTART{sys_fstatvfs} -->
+NAME
+    sys_fstatvfs - get filesystem statistics of a file descriptor
+
+SYNOPSIS
+    sys_fstatvfs(fd: usize, stat: *mut StatVfs) -> Result<usize>;
+
+DESCRIPTION
+    sys_fstatvfs is identical to sys_statvfs, except that the filesystem is the one containing the
+    path of fd
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
+
+ERRORS
+    EBADF
+        fd is not a valid open file descriptor
+
+    EFAULT
+        stat points outside of the accessible address space of the process
+
+    ENOSYS
+        The scheme of fd does not support statvfs
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_fstatvfs(fd: usize, stat: *mut StatVfs) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let resource = try!(current.get_file(fd));
+    try!(current.validate_writeable(stat as usize, mem::size_of::<StatVfs>()));
+    let mut path = [0; 4096];
+    let count = try!(resource.path(&mut path));
+    let path = try!(str::from_utf8(&path[.. count]).or(Err(Error::new(EINVAL))));
+    ::env().statvfs(try!(Url::from_str(path)), unsafe { &mut *stat }).and(Ok(0))
 }
 
 /** <!-- @MANSTART{sys_fsync} -->
@@ -235,109 +553,337 @@ pub fn do_sys_ftruncate(fd: usize, length: usize) -> Result<usize> {
     resource.truncate(length).and(Ok(0))
 }
 
-//TODO: Link
-
-/** <!-- @MANSTART{sys_lseek} -->
+/** <!-- @MANSTART{sys_futimens} -->
 NAME
-    sys_lseek - reposition read/write file offset
+    sys_futimens - change the timestamps of a file descriptor
 
 SYNOPSIS
-    sys_lseek(fd: usize, offset: isize, whence: usize) -> Result<usize>;
+    sys_futimens(fd: usize, times: *const TimeSpec) -> Result<usize>;
 
 DESCRIPTION
-    sys_lseek repositions the offset of the file referenced by fd to the offset according to whence
+    sys_futimens is identical to sys_utimens, except that the file is the one referenced by fd
 
-    SEEK_SET: 0
-        The offset is set to offset bytes
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
 
-    SEEK_CUR: 1
-        The offset is set to its current location plus offset bytes
+ERRORS
+    EBADF
+        fd is not a valid open file descriptor
 
-    SEEK_END: 2
-        The offset is set to the size of the file plus offset bytes
+    EFAULT
+        times points outside of the accessible address space of the process
+
+    EPERM
+        fd does not support changing its timestamps
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_futimens(fd: usize, times: *const TimeSpec) -> Result<usize> {
+    let (atime, mtime) = try!(read_times(times));
+
+    let mut contexts = ::env().contexts.lock();
+    let mut current = try!(contexts.current_mut());
+    let mut resource = try!(current.get_file_mut(fd));
+    resource.set_times(atime, mtime).and(Ok(0))
+}
+
+/** <!-- @MANSTART{sys_getcwd} -->
+NAME
+    sys_getcwd - get current working directory
+
+SYNOPSIS
+    sys_getcwd(buf: *mut u8, count: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_getcwd copies the current working directory of the calling process into buf, which is count
+    bytes long. The result is not NUL terminated
 
 RETURN VALUE
-    On success, Ok(new_offset) is returned, where new_offset is the resulting offset location. On
+    On success, Ok(count) is returned, where count is the length of the working directory. On
     error, Err(err) is returned where err is one of the following errors
 
+ERRORS
+    EFAULT
+        buf points outside the accessible address space of the process
+
+    ERANGE
+        count is smaller than the length of the working directory
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_getcwd(buf: *mut u8, count: usize) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let cwd = unsafe { &*current.cwd.get() };
+    if buf as usize == 0 {
+        Err(Error::new(EFAULT))
+    } else if let Err(err) = current.validate_writeable(buf as usize, count) {
+        Err(err)
+    } else if cwd.len() > count {
+        Err(Error::new(ERANGE))
+    } else {
+        let buf = unsafe { slice::from_raw_parts_mut(buf, count) };
+        for (b, c) in buf.iter_mut().zip(cwd.bytes()) {
+            *b = c;
+        }
+        Ok(cwd.len())
+    }
+}
+
+/** <!-- @MANSTART{sys_getdents} -->
+NAME
+    sys_getdents - get directory entries
+
+SYNOPSIS
+    sys_getdents(fd: usize, buf: *mut u8, count: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_getdents reads as many directory entries as fit in count bytes from the directory referred
+    to by fd into the buffer starting at buf. Each entry is a Dirent header followed by the NUL
+    terminated name of the entry. The position in the directory is advanced past the entries read
+
+RETURN VALUE
+    On success, Ok(count) is returned, where count is the number of bytes read into buf. At the end
+    of the directory, Ok(0) is returned. On error, Err(err) is returned where err is one of the
+    following errors
+
 ERRORS
     EBADF
-        fd is not a valid open file decriptor
+        fd is not a valid open file decriptor, or is not open for reading
+
+    EFAULT
+        buf is outside of the accessible address space of the process
 
     EINVAL
-        whence or the offset is not valid
+        buf is too small to hold the next entry
 
-    ESPIPE
-        fd does not support seeking
+    ENOTDIR
+        fd does not refer to a directory
 
     ESRCH
         Currently not running in a process context (rare, would only happen during kernel init)
 <!-- @MANEND --> */
-pub fn do_sys_lseek(fd: usize, offset: isize, whence: usize) -> Result<usize> {
+pub fn do_sys_getdents(fd: usize, buf: *mut u8, count: usize) -> Result<usize> {
     let mut contexts = ::env().contexts.lock();
     let mut current = try!(contexts.current_mut());
-    let mut resource = try!(current.get_file_mut(fd));
-    match whence {
-        SEEK_SET => resource.seek(ResourceSeek::Start(offset as usize)),
-        SEEK_CUR => resource.seek(ResourceSeek::Current(offset)),
-        SEEK_END => resource.seek(ResourceSeek::End(offset)),
-        _ => Err(Error::new(EINVAL)),
+    try!(current.validate_writeable(buf as usize, count));
+    let file = try!(current.get_context_file_mut(fd));
+    if file.readable() {
+        file.resource.readdir(unsafe { slice::from_raw_parts_mut(buf, count) })
+    } else {
+        Err(Error::new(EBADF))
     }
 }
 
-/** <!-- @MANSTART{sys_mkdir} -->
+/** <!-- @MANSTART{sys_link} -->
 NAME
-    sys_mkdir - create a directory
+    sys_link - make a new name for a file
 
 SYNOPSIS
-    sys_mkdir(path: *const u8, flags: usize) -> Result<usize>;
+    sys_link(old: *const u8, new: *const u8) -> Result<usize>;
 
 DESCRIPTION
-    sys_mkdir attempts to create a directory named path
+    sys_link creates a new link, also known as a hard link, named new to the existing file old
 
 RETURN VALUE
     On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
     errors
 
 ERRORS
-    EACCES
-        This process does not have write permissions to the parent directory or search permissions
-        to other components in path
-
     EEXIST
-        path already exists
+        new already exists
 
     EFAULT
-        path points outside of the accessible address space of the process
+        old or new points outside of the accessible address space of the process
 
     ENOENT
-        A directory component in path does not exist
+        old does not exist, a directory component in new does not exist, or the scheme of old
+        does not exist
 
     EPERM
-        The filesystem containing path does not support the creation of directories
+        The filesystem containing old does not support the creation of hard links
 
-    EROFS
-        The filesystem containing path is read-only
+    EXDEV
+        old and new are not in the same scheme
 
     ESRCH
         Currently not running in a process context (rare, would only happen during kernel init)
 <!-- @MANEND --> */
-pub fn do_sys_mkdir(path: *const u8, flags: usize) -> Result<usize> {
+pub fn do_sys_link(old: *const u8, new: *const u8) -> Result<usize> {
     let contexts = ::env().contexts.lock();
     let current = try!(contexts.current());
-    let path_string = current.canonicalize(c_string_to_str(path));
-    ::env().mkdir(try!(Url::from_str(&path_string)), flags).and(Ok(0))
+    let old_string = current.canonicalize(c_string_to_str(old));
+    let new_string = current.canonicalize(c_string_to_str(new));
+    ::env().link(try!(Url::from_str(&old_string)), try!(Url::from_str(&new_string))).and(Ok(0))
 }
 
-/** <!-- @MANSTART{sys_open} -->
+/** <!-- @MANSTART{sys_lseek} -->
 NAME
-    sys_open - open and possibly create a file
+    sys_lseek - reposition read/write file offset
 
 SYNOPSIS
-    sys_open(path: *const u8, flags: usize) -> Result<usize>;
+    sys_lseek(fd: usize, offset: isize, whence: usize) -> Result<usize>;
 
 DESCRIPTION
-    sys_open returns a file descriptor referencing path, creating path if O_CREAT is provided
+    sys_lseek repositions the offset of the file referenced by fd to the offset according to whence
+
+    SEEK_SET: 0
+        The offset is set to offset bytes
+
+    SEEK_CUR: 1
+        The offset is set to its current location plus offset bytes
+
+    SEEK_END: 2
+        The offset is set to the size of the file plus offset bytes
+
+RETURN VALUE
+    On success, Ok(new_offset) is returned, where new_offset is the resulting offset location. On
+    error, Err(err) is returned where err is one of the following errors
+
+ERRORS
+    EBADF
+        fd is not a valid open file decriptor
+
+    EINVAL
+        whence or the offset is not valid
+
+    ESPIPE
+        fd does not support seeking
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_lseek(fd: usize, offset: isize, whence: usize) -> Result<usize> {
+    let mut contexts = ::env().contexts.lock();
+    let mut current = try!(contexts.current_mut());
+    let mut resource = try!(current.get_file_mut(fd));
+    match whence {
+        SEEK_SET => resource.seek(ResourceSeek::Start(offset as usize)),
+        SEEK_CUR => resource.seek(ResourceSeek::Current(offset)),
+        SEEK_END => resource.seek(ResourceSeek::End(offset)),
+        _ => Err(Error::new(EINVAL)),
+    }
+}
+
+/** <!-- @MANSTART{sys_lstat} -->
+NAME
+    sys_lstat - get file status without following symbolic links
+
+SYNOPSIS
+    sys_lstat(path: *const u8, stat: *mut Stat) -> Result<usize>;
+
+DESCRIPTION
+    sys_lstat is identical to sys_stat, except that if path is a symbolic link, the link itself is
+    described, with MODE_SYMLINK set in st_mode
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
+
+ERRORS
+    EFAULT
+        path or stat points outside of the accessible address space of the process
+
+    ENOENT
+        path does not exist, or the scheme of path does not exist
+
+    EPERM
+        The scheme of path does not support stat
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_lstat(path: *const u8, stat: *mut Stat) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path = current.canonicalize(c_string_to_str(path));
+    let url = try!(Url::from_str(&path));
+    let mut value = Stat::default();
+    try!(::env().lstat(url, &mut value));
+    try!(copy_to_user(stat, value));
+    Ok(0)
+}
+
+/** <!-- @MANSTART{sys_mkdir} -->
+NAME
+    sys_mkdir - create a directory
+
+SYNOPSIS
+    sys_mkdir(path: *const u8, flags: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_mkdir attempts to create a directory named path
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
+
+ERRORS
+    EACCES
+        This process does not have write permissions to the parent directory or search permissions
+        to other components in path
+
+    EEXIST
+        path already exists
+
+    EFAULT
+        path points outside of the accessible address space of the process
+
+    ENOENT
+        A directory component in path does not exist
+
+    EPERM
+        The filesystem containing path does not support the creation of directories
+
+    EROFS
+        The filesystem containing path is read-only
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_mkdir(path: *const u8, flags: usize) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path_string = current.canonicalize(c_string_to_str(path));
+    ::env().mkdir(try!(Url::from_str(&path_string)), flags).and(Ok(0))
+}
+
+/** <!-- @MANSTART{sys_open} -->
+NAME
+    sys_open - open and possibly create a file
+
+SYNOPSIS
+    sys_open(path: *const u8, flags: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_open returns a file descriptor referencing path, creating path if O_CREAT is provided
+
+    The access mode is one of the following. Reading from a file opened with O_WRONLY fails, but
+    writing to a file opened with O_RDONLY is still passed on to the file, as some programs open
+    files they write to this way
+
+    O_RDONLY: 0
+        The file is opened for reading only
+
+    O_WRONLY: 1
+        The file is opened for writing only
+
+    O_RDWR: 2
+        The file is opened for reading and writing
+
+    O_APPEND: 8
+        Every write is done at the end of the file. This is ignored for resources that cannot seek,
+        and can be changed later with F_SETFL
+
+    O_CLOEXEC: 0x1000
+        The file descriptor is closed when executing a new program. This can be changed later with
+        F_SETFD
+
+    O_DIRECTORY: 0x100000
+        Fail with ENOTDIR if path does not refer to a directory
 
     TODO: Open is very complicated, and has a lot of flags
 
@@ -358,7 +904,10 @@ ERRORS
         path points outside of the accessible address space of the process
 
     EISDIR
-        path refers to a directory and O_DIRECTORY was not provided
+        path refers to a directory, write access was requested, and O_DIRECTORY was not provided
+
+    ELOOP
+        Too many symbolic links were encountered in resolving path
 
     ENOENT
         A directory component in path does not exist
@@ -388,53 +937,200 @@ pub fn do_sys_open(path_c: *const u8, flags: usize) -> Result<usize> {
     //debugln!("{}: {}: open {}", current.pid, current.name, path);
     let url = try!(Url::from_str(&path));
     let resource = try!(::env().open(url, flags));
+
+    let mut stat = Stat::default();
+    let is_dir = resource.stat(&mut stat).is_ok() && stat.st_mode & MODE_DIR == MODE_DIR;
+    if flags & O_DIRECTORY == O_DIRECTORY {
+        if ! is_dir {
+            return Err(Error::new(ENOTDIR));
+        }
+    } else if is_dir && flags & O_ACCMODE != O_RDONLY {
+        return Err(Error::new(EISDIR));
+    }
+
     let fd = current.next_fd();
     unsafe {
         (*current.files.get()).push(ContextFile {
             fd: fd,
+            flags: flags & !(O_CLOEXEC | O_DIRECTORY),
+            cloexec: flags & O_CLOEXEC == O_CLOEXEC,
             resource: resource,
         });
     }
     Ok(fd)
 }
 
-pub fn do_sys_pipe2(fds: *mut usize, _flags: usize) -> Result<usize> {
+/** <!-- @MANSTART{sys_pipe2} -->
+NAME
+    sys_pipe2 - create a pipe
+
+SYNOPSIS
+    sys_pipe2(fds: *mut usize, flags: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_pipe2 creates a pipe, placing the file descriptor of the read end in fds[0] and the file
+    descriptor of the write end in fds[1]
+
+    The following flags are supported
+
+    O_NONBLOCK: 4
+        Reading from the empty pipe, or writing to the full pipe, returns EAGAIN instead of
+        blocking. This can be changed later with F_SETFL
+
+    O_CLOEXEC: 0x1000
+        Both descriptors are closed when executing a new program
+
+    The pipe holds up to PIPE_MAX (65536) bytes. Writing to the full pipe blocks until a reader
+    makes room, and a write returns once all of its bytes are in the pipe
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the
+    following errors
+
+ERRORS
+    EFAULT
+        fds is outside of the accessible address space of the process
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_pipe2(fds: *mut usize, flags: usize) -> Result<usize> {
     let contexts = ::env().contexts.lock();
     let current = try!(contexts.current());
-    if fds as usize > 0 {
-        let read = box PipeRead::new();
-        let write = box PipeWrite::new(&read);
-
-        unsafe {
-            *fds.offset(0) = current.next_fd();
-            (*current.files.get()).push(ContextFile {
-                fd: *fds.offset(0),
-                resource: read,
-            });
-
-            *fds.offset(1) = current.next_fd();
-            (*current.files.get()).push(ContextFile {
-                fd: *fds.offset(1),
-                resource: write,
-            });
-        }
+    let read_fd = current.next_fd();
+    let write_fd = current.next_fd_from(read_fd + 1);
+    try!(copy_slice_to_user(fds, &[read_fd, write_fd]));
 
-        Ok(0)
+    let read = box PipeRead::new(flags);
+    let write = box PipeWrite::new(&read, flags);
+
+    unsafe {
+        (*current.files.get()).push(ContextFile {
+            fd: read_fd,
+            flags: O_RDONLY | (flags & O_NONBLOCK),
+            cloexec: flags & O_CLOEXEC == O_CLOEXEC,
+            resource: read,
+        });
+
+        (*current.files.get()).push(ContextFile {
+            fd: write_fd,
+            flags: O_WRONLY | (flags & O_NONBLOCK),
+            cloexec: flags & O_CLOEXEC == O_CLOEXEC,
+            resource: write,
+        });
+    }
+
+    Ok(0)
+}
+
+/// How often sys_poll checks the descriptors again while waiting, for resources that do not count
+/// their input in `::env().input`
+const POLL_INTERVAL: i32 = 10 * NANOS_PER_MILLI;
+
+/** <!-- @MANSTART{sys_poll} -->
+NAME
+    sys_poll - wait for some event on a file descriptor
+
+SYNOPSIS
+    sys_poll(fds: *mut PollFd, nfds: usize, timeout: isize) -> Result<usize>;
+
+DESCRIPTION
+    sys_poll waits for one of the nfds file descriptors in fds to become ready. The events field
+    of each PollFd requests the events to wait for, and the revents field is set to the events that
+    occured
+
+    POLLIN: 0x1
+        There is data to read
+
+    POLLOUT: 0x4
+        Writing is possible
+
+    POLLERR: 0x8
+        An error condition, such as the read side of a pipe being closed. Always reported
+
+    POLLHUP: 0x10
+        The other side hung up, such as the write side of a pipe being closed. Always reported
+
+    POLLNVAL: 0x20
+        fd is not a valid open file descriptor. Always reported
+
+    timeout is the number of milliseconds to wait, measured with the monotonic clock. A timeout
+    of zero returns immediately, and a negative timeout, such as usize::MAX passed through the
+    syscall interface, waits forever. Resources that do not support polling are always reported
+    as readable and writable
+
+RETURN VALUE
+    On success, Ok(count) is returned, where count is the number of file descriptors with nonzero
+    revents, or zero if the timeout expired. On error, Err(err) is returned where err is one of
+    the following errors
+
+ERRORS
+    EFAULT
+        fds points outside the accessible address space of the process
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_poll(fds: *mut PollFd, nfds: usize, timeout: isize) -> Result<usize> {
+    {
+        let contexts = ::env().contexts.lock();
+        let current = try!(contexts.current());
+        try!(current.validate_writeable(fds as usize, nfds * mem::size_of::<PollFd>()));
+    }
+
+    let fds = unsafe { slice::from_raw_parts_mut(fds, nfds) };
+    let deadline = if timeout >= 0 {
+        Some(Duration::monotonic() + Duration::new(timeout as i64 / 1000, (timeout % 1000) as i32 * NANOS_PER_MILLI))
     } else {
-        Err(Error::new(EFAULT))
+        None
+    };
+
+    loop {
+        let seen = ::env().input.count();
+
+        let mut count = 0;
+        {
+            let mut contexts = ::env().contexts.lock();
+            let mut current = try!(contexts.current_mut());
+            for pollfd in fds.iter_mut() {
+                pollfd.revents = if let Ok(file) = current.get_context_file_mut(pollfd.fd) {
+                    file.resource.poll() & (pollfd.events | POLLERR | POLLHUP)
+                } else {
+                    POLLNVAL
+                };
+
+                if pollfd.revents != 0 {
+                    count += 1;
+                }
+            }
+        }
+
+        let now = Duration::monotonic();
+        if count > 0 || deadline.map_or(false, |deadline| deadline <= now) {
+            return Ok(count);
+        }
+
+        // Sleep until there is input, the next check, or the deadline if it comes first
+        let mut wake = now + Duration::new(0, POLL_INTERVAL);
+        if let Some(deadline) = deadline {
+            if deadline < wake {
+                wake = deadline;
+            }
+        }
+        ::env().input.wait_until(seen, Some(wake));
     }
 }
 
-/** <!-- @MANSTART{sys_read} -->
+/** <!-- @MANSTART{sys_pread} -->
 NAME
-    sys_read - read from a file descriptor
+    sys_pread - read from a file descriptor at a given offset
 
 SYNOPSIS
-    sys_read(fd: usize, buf: *mut u8, count: usize) -> Result<usize>;
+    sys_pread(fd: usize, buf: *mut u8, count: usize, offset: usize) -> Result<usize>;
 
 DESCRIPTION
-    sys_read attempts to read up to count bytes from file descriptor fd into the buffer starting at
-    buf
+    sys_pread attempts to read up to count bytes from file descriptor fd at offset into the buffer
+    starting at buf. The file offset of fd is not changed
 
 RETURN VALUE
     On success, Ok(count) is returned, where count is the number of bytes read into buf. On error,
@@ -442,67 +1138,49 @@ RETURN VALUE
 
 ERRORS
     EBADF
-        fd is not a valid open file decriptor
+        fd is not a valid open file decriptor, or is not open for reading
 
     EFAULT
         buf is outside of the accessible address space of the process
 
     EINVAL
-        fd refers to a ifle that does not support reading
+        fd refers to a disk, and offset is not a multiple of its block size of 512 bytes
 
     EIO
         I/O error
 
+    ESPIPE
+        fd refers to a pipe or another resource that does not support seeking
+
     ESRCH
         Currently not running in a process context (rare, would only happen during kernel init)
 <!-- @MANEND --> */
-pub fn do_sys_read(fd: usize, buf: *mut u8, count: usize) -> Result<usize> {
+pub fn do_sys_pread(fd: usize, buf: *mut u8, count: usize, offset: usize) -> Result<usize> {
     let mut contexts = ::env().contexts.lock();
     let mut current = try!(contexts.current_mut());
-    let mut resource = try!(current.get_file_mut(fd));
-    resource.read(unsafe { slice::from_raw_parts_mut(buf, count) })
-}
-
-pub fn do_sys_rmdir(path: *const u8) -> Result<usize> {
-    let contexts = ::env().contexts.lock();
-    let current = try!(contexts.current());
-    let path_string = current.canonicalize(c_string_to_str(path));
-    ::env().rmdir(try!(Url::from_str(&path_string))).and(Ok(0))
-}
-
-pub fn do_sys_stat(path: *const u8, stat: *mut Stat) -> Result<usize> {
-    let contexts = ::env().contexts.lock();
-    let current = try!(contexts.current());
-    let path = current.canonicalize(c_string_to_str(path));
-    let url = try!(Url::from_str(&path));
-    if stat as usize > 0 {
-        ::env().stat(url, unsafe { &mut *stat }).and(Ok(0))
+    try!(current.validate_writeable(buf as usize, count));
+    let file = try!(current.get_context_file_mut(fd));
+    if file.readable() {
+        file.resource.read_at(offset, unsafe { slice::from_raw_parts_mut(buf, count) })
     } else {
-        Err(Error::new(EFAULT))
+        Err(Error::new(EBADF))
     }
 }
 
-pub fn do_sys_unlink(path: *const u8) -> Result<usize> {
-    let contexts = ::env().contexts.lock();
-    let current = try!(contexts.current());
-    let path_string = current.canonicalize(c_string_to_str(path));
-    ::env().unlink(try!(Url::from_str(&path_string))).and(Ok(0))
-}
-
-/** <!-- @MANSTART{sys_write} -->
+/** <!-- @MANSTART{sys_pwrite} -->
 NAME
-    sys_write - read from a file descriptor
+    sys_pwrite - write to a file descriptor at a given offset
 
 SYNOPSIS
-    sys_write(fd: usize, buf: *mut u8, count: usize) -> Result<usize>;
+    sys_pwrite(fd: usize, buf: *const u8, count: usize, offset: usize) -> Result<usize>;
 
 DESCRIPTION
-    sys_write attempts to read up to count bytes from file descriptor fd into the buffer starting at
-    buf
+    sys_pwrite attempts to write up to count bytes from the buffer starting at buf to file
+    descriptor fd at offset. The file offset of fd is not changed
 
 RETURN VALUE
-    On success, Ok(count) is returned, where count is the number of bytes read into buf. On error,
-    Err(err) is returned where err is one of the following errors
+    On success, Ok(count) is returned, where count is the number of bytes written from buf. On
+    error, Err(err) is returned where err is one of the following errors
 
 ERRORS
     EBADF
@@ -512,7 +1190,7 @@ ERRORS
         buf is outside of the accessible address space of the process
 
     EINVAL
-        fd refers to a ifle that does not support writing
+        fd refers to a disk, and offset is not a multiple of its block size of 512 bytes
 
     EIO
         I/O error
@@ -520,15 +1198,712 @@ ERRORS
     ENOSPC
         The filesystem containing fd has no more space
 
-    EPIPE
-        fd is connected to a pipe or socket whose reading end is closed
+    ESPIPE
+        fd refers to a pipe or another resource that does not support seeking
 
     ESRCH
         Currently not running in a process context (rare, would only happen during kernel init)
 <!-- @MANEND --> */
-pub fn do_sys_write(fd: usize, buf: *const u8, count: usize) -> Result<usize> {
+pub fn do_sys_pwrite(fd: usize, buf: *const u8, count: usize, offset: usize) -> Result<usize> {
     let mut contexts = ::env().contexts.lock();
     let mut current = try!(contexts.current_mut());
-    let mut resource = try!(current.get_file_mut(fd));
-    resource.write(unsafe { slice::from_raw_parts(buf, count) })
+    try!(current.validate(buf as usize, count));
+    let file = try!(current.get_context_file_mut(fd));
+    if file.writeable() {
+        file.resource.write_at(offset, unsafe { slice::from_raw_parts(buf, count) })
+    } else {
+        Err(Error::new(EBADF))
+    }
+}
+
+/** <!-- @MANSTART{sys_read} -->
+NAME
+    sys_read - read from a file descriptor
+
+SYNOPSIS
+    sys_read(fd: usize, buf: *mut u8, count: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_read attempts to read up to count bytes from file descriptor fd into the buffer starting at
+    buf
+
+RETURN VALUE
+    On success, Ok(count) is returned, where count is the number of bytes read into buf. On error,
+    Err(err) is returned where err is one of the following errors
+
+ERRORS
+    EAGAIN
+        fd was opened with O_NONBLOCK, and no data is available
+
+    EBADF
+        fd is not a valid open file decriptor, or is not open for reading
+
+    EFAULT
+        buf is outside of the accessible address space of the process
+
+    EINVAL
+        fd refers to a ifle that does not support reading
+
+    EIO
+        I/O error
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_read(fd: usize, buf: *mut u8, count: usize) -> Result<usize> {
+    let mut contexts = ::env().contexts.lock();
+    let mut current = try!(contexts.current_mut());
+    try!(current.validate_writeable(buf as usize, count));
+    let file = try!(current.get_context_file_mut(fd));
+    if file.readable() {
+        file.resource.read(unsafe { slice::from_raw_parts_mut(buf, count) })
+    } else {
+        Err(Error::new(EBADF))
+    }
+}
+
+/** <!-- @MANSTART{sys_readlink} -->
+NAME
+    sys_readlink - read the target of a symbolic link
+
+SYNOPSIS
+    sys_readlink(path: *const u8, buf: *mut u8, count: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_readlink places the target of the symbolic link path in buf, truncating it to count bytes.
+    The target is not NUL terminated
+
+RETURN VALUE
+    On success, Ok(count) is returned, where count is the number of bytes placed in buf. On error,
+    Err(err) is returned where err is one of the following errors
+
+ERRORS
+    EFAULT
+        path or buf points outside of the accessible address space of the process
+
+    EINVAL
+        path is not a symbolic link, or its scheme does not support symbolic links
+
+    ENOENT
+        path does not exist, or the scheme of path does not exist
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_readlink(path: *const u8, buf: *mut u8, count: usize) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path_string = current.canonicalize(c_string_to_str(path));
+    let url = try!(Url::from_str(&path_string));
+    try!(current.validate_writeable(buf as usize, count));
+    ::env().readlink(url, unsafe { slice::from_raw_parts_mut(buf, count) })
+}
+
+/// How many bytes sys_readv reads at a time
+const READV_CHUNK: usize = 65536;
+
+/** <!-- @MANSTART{sys_readv} -->
+NAME
+    sys_readv - read from a file descriptor into multiple buffers
+
+SYNOPSIS
+    sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_readv reads from file descriptor fd into the iovcnt buffers described by iov, in order.
+    The data is read in pieces of up to READV_CHUNK (64 KiB), and each buffer is filled before the
+    next one is used. Reading stops at the first read that returns less than was asked for
+
+RETURN VALUE
+    On success, Ok(count) is returned, where count is the total number of bytes read. On error,
+    Err(err) is returned where err is one of the following errors
+
+ERRORS
+    EBADF
+        fd is not a valid open file decriptor, or is not open for reading
+
+    EFAULT
+        iov, or one of the buffers, is outside of the accessible address space of the process
+
+    EINVAL
+        iovcnt is greater than IOV_MAX, or the total length of the buffers overflows
+
+    EIO
+        I/O error
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> Result<usize> {
+    if iovcnt > IOV_MAX {
+        return Err(Error::new(EINVAL));
+    }
+
+    let mut contexts = ::env().contexts.lock();
+    let mut current = try!(contexts.current_mut());
+
+    try!(current.validate(iov as usize, iovcnt * mem::size_of::<IoVec>()));
+    let iovs = unsafe { slice::from_raw_parts(iov, iovcnt) };
+    for iov in iovs.iter() {
+        try!(current.validate_writeable(iov.iov_base, iov.iov_len));
+    }
+
+    let file = try!(current.get_context_file_mut(fd));
+    if ! file.readable() {
+        return Err(Error::new(EBADF));
+    }
+
+    let mut len = 0;
+    for iov in iovs.iter() {
+        len = try!(len.checked_add(iov.iov_len).ok_or(Error::new(EINVAL)));
+    }
+
+    // A read per buffer could block once the data filled the first ones, so the data is read in
+    // pieces as large as the buffers allow, and scattered. The buffers may overlap, so the size
+    // of the bounce buffer is bounded instead of following their total length
+    let mut bounce = vec![0; cmp::min(len, READV_CHUNK)];
+    let mut total = 0;
+    while total < len {
+        let chunk = cmp::min(len - total, bounce.len());
+        let count = match file.resource.read(&mut bounce[.. chunk]) {
+            Ok(count) => count,
+            Err(err) => if total > 0 {
+                break;
+            } else {
+                return Err(err);
+            },
+        };
+
+        scatter(iovs, total, &bounce[.. count]);
+        total += count;
+
+        if count < chunk {
+            break;
+        }
+    }
+
+    Ok(total)
+}
+
+/// Copy `data` into the buffers of `iovs`, starting `offset` bytes into them as if they were one
+fn scatter(iovs: &[IoVec], offset: usize, data: &[u8]) {
+    let mut start = 0;
+    let mut copied = 0;
+    for iov in iovs.iter() {
+        if copied >= data.len() {
+            break;
+        }
+
+        let position = offset + copied;
+        if position < start + iov.iov_len {
+            let within = position - start;
+            let count = cmp::min(iov.iov_len - within, data.len() - copied);
+            unsafe {
+                ptr::copy(data.as_ptr().offset(copied as isize), (iov.iov_base + within) as *mut u8, count);
+            }
+            copied += count;
+        }
+        start += iov.iov_len;
+    }
+}
+
+/** <!-- @MANSTART{sys_rename} -->
+NAME
+    sys_rename - change the name or location of a file
+
+SYNOPSIS
+    sys_rename(old: *const u8, new: *const u8) -> Result<usize>;
+
+DESCRIPTION
+    sys_rename renames old to new, replacing new if it already exists. Both paths must be in the
+    same scheme
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
+
+ERRORS
+    EFAULT
+        old or new points outside of the accessible address space of the process
+
+    ENOENT
+        old does not exist, or the scheme of old does not exist
+
+    EXDEV
+        old and new are not in the same scheme, or the scheme does not support renaming. Renaming
+        can be done by copying and unlinking in this case
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_rename(old: *const u8, new: *const u8) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let old_string = current.canonicalize(c_string_to_str(old));
+    let new_string = current.canonicalize(c_string_to_str(new));
+    ::env().rename(try!(Url::from_str(&old_string)), try!(Url::from_str(&new_string))).and(Ok(0))
+}
+
+pub fn do_sys_rmdir(path: *const u8) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path_string = current.canonicalize(c_string_to_str(path));
+    ::env().rmdir(try!(Url::from_str(&path_string))).and(Ok(0))
+}
+
+/// How many bytes sys_sendfile copies at a time
+const SENDFILE_CHUNK: usize = 65536;
+
+/** <!-- @MANSTART{sys_sendfile} -->
+NAME
+    sys_sendfile - transfer data between file descriptors
+
+SYNOPSIS
+    sys_sendfile(out_fd: usize, in_fd: usize, offset: *mut usize, count: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_sendfile copies up to count bytes from in_fd to out_fd within the kernel, without passing
+    them through a buffer in userspace. The copy stops early at the end of in_fd, or when a read
+    or write is short
+
+    If offset is null, reading starts at the file offset of in_fd, which is moved past the bytes
+    that were written to out_fd. Otherwise, reading starts at *offset, the file offset of in_fd is
+    not changed, and *offset is moved past the bytes that were written to out_fd
+
+RETURN VALUE
+    On success, Ok(count) is returned, where count is the number of bytes transferred. If an error
+    occurs after some bytes were transferred, the number of bytes transferred is returned.
+    Otherwise, Err(err) is returned where err is one of the following errors
+
+ERRORS
+    EBADF
+        in_fd or out_fd is not a valid open file descriptor, or in_fd is not open for reading
+
+    EFAULT
+        offset points outside of the accessible address space of the process
+
+    EINVAL
+        in_fd and out_fd are the same file descriptor
+
+    ESPIPE
+        offset is not null, and in_fd does not support seeking
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_sendfile(out_fd: usize, in_fd: usize, offset: *mut usize, count: usize) -> Result<usize> {
+    if out_fd == in_fd {
+        return Err(Error::new(EINVAL));
+    }
+
+    let mut contexts = ::env().contexts.lock();
+    let mut current = try!(contexts.current_mut());
+
+    if offset as usize > 0 {
+        try!(current.validate_writeable(offset as usize, mem::size_of::<usize>()));
+    }
+
+    let in_file = try!(current.get_context_file_mut(in_fd));
+    let out_file = try!(current.get_context_file_mut(out_fd));
+    if ! in_file.readable() || ! out_file.writeable() {
+        return Err(Error::new(EBADF));
+    }
+
+    if out_file.flags & O_APPEND == O_APPEND {
+        // Resources that cannot seek, such as pipes, ignore O_APPEND
+        let _ = out_file.resource.seek(ResourceSeek::End(0));
+    }
+
+    let mut position = if offset as usize > 0 {
+        Some(unsafe { *offset })
+    } else {
+        None
+    };
+
+    let mut buf = vec![0; cmp::min(count, SENDFILE_CHUNK)];
+    let mut total = 0;
+    while total < count {
+        let len = cmp::min(buf.len(), count - total);
+        let read_result = match position {
+            Some(position) => in_file.resource.read_at(position, &mut buf[.. len]),
+            None => in_file.resource.read(&mut buf[.. len]),
+        };
+        let read = match read_result {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) => if total > 0 {
+                break;
+            } else {
+                return Err(err);
+            },
+        };
+
+        let written = match out_file.resource.write(&buf[.. read]) {
+            Ok(written) => written,
+            Err(err) => {
+                if position.is_none() {
+                    let _ = in_file.resource.seek(ResourceSeek::Current(- (read as isize)));
+                }
+                if total > 0 {
+                    break;
+                } else {
+                    return Err(err);
+                }
+            },
+        };
+
+        total += written;
+        match position {
+            Some(ref mut position) => *position += written,
+            None => if written < read {
+                // Leave the bytes that were not written to be read again
+                let _ = in_file.resource.seek(ResourceSeek::Current(written as isize - read as isize));
+            },
+        }
+
+        if written < read || read < len {
+            break;
+        }
+    }
+
+    if let Some(position) = position {
+        unsafe { *offset = position };
+    }
+
+    Ok(total)
+}
+
+pub fn do_sys_stat(path: *const u8, stat: *mut Stat) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path = current.canonicalize(c_string_to_str(path));
+    let url = try!(Url::from_str(&path));
+    let mut value = Stat::default();
+    try!(::env().stat(url, &mut value));
+    try!(copy_to_user(stat, value));
+    Ok(0)
+}
+
+/** <!-- @MANSTART{sys_statvfs} -->
+NAME
+    sys_statvfs - get filesystem statistics
+
+SYNOPSIS
+    sys_statvfs(path: *const u8, stat: *mut StatVfs) -> Result<usize>;
+
+DESCRIPTION
+    sys_statvfs places the block size, the total and free block counts, and the total and free
+    file counts of the filesystem containing path in stat. Symbolic links are followed
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
+
+ERRORS
+    EFAULT
+        path or stat points outside of the accessible address space of the process
+
+    ELOOP
+        Too many symbolic links were encountered in resolving path
+
+    ENOENT
+        path does not exist, or the scheme of path does not exist
+
+    ENOSYS
+        The scheme of path does not support statvfs
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_statvfs(path: *const u8, stat: *mut StatVfs) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path = current.canonicalize(c_string_to_str(path));
+    let url = try!(Url::from_str(&path));
+    try!(current.validate_writeable(stat as usize, mem::size_of::<StatVfs>()));
+    ::env().statvfs(url, unsafe { &mut *stat }).and(Ok(0))
+}
+
+/** <!-- @MANSTART{sys_symlink} -->
+NAME
+    sys_symlink - make a symbolic link
+
+SYNOPSIS
+    sys_symlink(target: *const u8, path: *const u8) -> Result<usize>;
+
+DESCRIPTION
+    sys_symlink creates a symbolic link named path which contains the string target. target is
+    stored as given, and is resolved relative to the directory of path when the link is followed
+
+    Opening a path follows up to 8 symbolic links, after which ELOOP is returned
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
+
+ERRORS
+    EEXIST
+        path already exists
+
+    EFAULT
+        target or path points outside of the accessible address space of the process
+
+    ENOENT
+        A directory component in path does not exist, or the scheme of path does not exist
+
+    EPERM
+        The filesystem containing path does not support the creation of symbolic links
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_symlink(target: *const u8, path: *const u8) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path_string = current.canonicalize(c_string_to_str(path));
+    ::env().symlink(c_string_to_str(target), try!(Url::from_str(&path_string))).and(Ok(0))
+}
+
+/** <!-- @MANSTART{sys_truncate} -->
+NAME
+    sys_truncate - truncate a file to a specified length by path
+
+SYNOPSIS
+    sys_truncate(path: *const u8, length: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_truncate causes the file at path to be truncated to a size of precisely length bytes.
+    Symbolic links are followed
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
+
+ERRORS
+    EACCES
+        The file at path is read only, such as the files of initfs
+
+    EFAULT
+        path points outside of the accessible address space of the process
+
+    EISDIR
+        path is a directory
+
+    ELOOP
+        Too many symbolic links were encountered in resolving path
+
+    ENOENT
+        path does not exist, or the scheme of path does not exist
+
+    EROFS
+        path is on a read only filesystem
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_truncate(path: *const u8, length: usize) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path_string = current.canonicalize(c_string_to_str(path));
+    ::env().truncate(try!(Url::from_str(&path_string)), length).and(Ok(0))
+}
+
+pub fn do_sys_unlink(path: *const u8) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path_string = current.canonicalize(c_string_to_str(path));
+    ::env().unlink(try!(Url::from_str(&path_string))).and(Ok(0))
+}
+
+/// Read the access and modification times passed to sys_utimens and sys_futimens, which are
+/// both the current time if times is null
+fn read_times(times: *const TimeSpec) -> Result<(TimeSpec, TimeSpec)> {
+    if times as usize > 0 {
+        let contexts = ::env().contexts.lock();
+        let current = try!(contexts.current());
+        try!(current.validate(times as usize, 2 * mem::size_of::<TimeSpec>()));
+        Ok(unsafe { (*times, *times.offset(1)) })
+    } else {
+        let now = Duration::realtime();
+        let time = TimeSpec {
+            tv_sec: now.secs,
+            tv_nsec: now.nanos,
+        };
+        Ok((time, time))
+    }
+}
+
+/** <!-- @MANSTART{sys_utimens} -->
+NAME
+    sys_utimens - change the timestamps of a file
+
+SYNOPSIS
+    sys_utimens(path: *const u8, times: *const TimeSpec) -> Result<usize>;
+
+DESCRIPTION
+    sys_utimens sets the access time of the file at path to times[0], and its modification time
+    to times[1]. If times is null, both are set to the current time. Symbolic links are followed
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
+
+ERRORS
+    EFAULT
+        path or times points outside of the accessible address space of the process
+
+    ELOOP
+        Too many symbolic links were encountered in resolving path
+
+    ENOENT
+        path does not exist, or the scheme of path does not exist
+
+    EPERM
+        The scheme of path does not support changing timestamps
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_utimens(path: *const u8, times: *const TimeSpec) -> Result<usize> {
+    let (atime, mtime) = try!(read_times(times));
+
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    let path_string = current.canonicalize(c_string_to_str(path));
+    ::env().set_times(try!(Url::from_str(&path_string)), atime, mtime).and(Ok(0))
+}
+
+/** <!-- @MANSTART{sys_write} -->
+NAME
+    sys_write - read from a file descriptor
+
+SYNOPSIS
+    sys_write(fd: usize, buf: *mut u8, count: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_write attempts to read up to count bytes from file descriptor fd into the buffer starting at
+    buf
+
+RETURN VALUE
+    On success, Ok(count) is returned, where count is the number of bytes read into buf. On error,
+    Err(err) is returned where err is one of the following errors
+
+ERRORS
+    EBADF
+        fd is not a valid open file decriptor
+
+    EFAULT
+        buf is outside of the accessible address space of the process
+
+    EINVAL
+        fd refers to a ifle that does not support writing
+
+    EIO
+        I/O error
+
+    ENOSPC
+        The filesystem containing fd has no more space
+
+    EPIPE
+        fd is connected to a pipe or socket whose reading end is closed
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_write(fd: usize, buf: *const u8, count: usize) -> Result<usize> {
+    let mut contexts = ::env().contexts.lock();
+    let mut current = try!(contexts.current_mut());
+    try!(current.validate(buf as usize, count));
+    let file = try!(current.get_context_file_mut(fd));
+    if file.writeable() {
+        if file.flags & O_APPEND == O_APPEND {
+            // Resources that cannot seek, such as pipes, ignore O_APPEND
+            let _ = file.resource.seek(ResourceSeek::End(0));
+        }
+        file.resource.write(unsafe { slice::from_raw_parts(buf, count) })
+    } else {
+        Err(Error::new(EBADF))
+    }
+}
+
+/** <!-- @MANSTART{sys_writev} -->
+NAME
+    sys_writev - write to a file descriptor from multiple buffers
+
+SYNOPSIS
+    sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_writev writes the iovcnt buffers described by iov to file descriptor fd, in order. Writing
+    stops at the first buffer that is not written completely
+
+RETURN VALUE
+    On success, Ok(count) is returned, where count is the total number of bytes written. If an
+    error occurs after some bytes were written, the number of bytes written is returned.
+    Otherwise, Err(err) is returned where err is one of the following errors
+
+ERRORS
+    EBADF
+        fd is not a valid open file decriptor
+
+    EFAULT
+        iov, or one of the buffers, is outside of the accessible address space of the process
+
+    EINVAL
+        iovcnt is greater than IOV_MAX
+
+    EIO
+        I/O error
+
+    ENOSPC
+        The filesystem containing fd has no more space
+
+    EPIPE
+        fd is connected to a pipe or socket whose reading end is closed
+
+    ESRCH
+        Currently not running in a process context (rare, would only happen during kernel init)
+<!-- @MANEND --> */
+pub fn do_sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> Result<usize> {
+    if iovcnt > IOV_MAX {
+        return Err(Error::new(EINVAL));
+    }
+
+    let mut contexts = ::env().contexts.lock();
+    let mut current = try!(contexts.current_mut());
+
+    try!(current.validate(iov as usize, iovcnt * mem::size_of::<IoVec>()));
+    let iovs = unsafe { slice::from_raw_parts(iov, iovcnt) };
+    for iov in iovs.iter() {
+        try!(current.validate(iov.iov_base, iov.iov_len));
+    }
+
+    let file = try!(current.get_context_file_mut(fd));
+    if ! file.writeable() {
+        return Err(Error::new(EBADF));
+    }
+
+    if file.flags & O_APPEND == O_APPEND {
+        // Resources that cannot seek, such as pipes, ignore O_APPEND
+        let _ = file.resource.seek(ResourceSeek::End(0));
+    }
+
+    let mut total = 0;
+    for iov in iovs.iter() {
+        match file.resource.write(unsafe { slice::from_raw_parts(iov.iov_base as *const u8, iov.iov_len) }) {
+            Ok(count) => {
+                total += count;
+                if count < iov.iov_len {
+                    break;
+                }
+            },
+            Err(err) => if total > 0 {
+                break;
+            } else {
+                return Err(err);
+            }
+        }
+    }
+
+    Ok(total)
 }
@@ -1,7 +1,8 @@
 use arch::context::ContextMemory;
 use arch::memory;
 
-use system::error::Result;
+use system::error::{Error, Result, EINVAL, ENODEV, ENOMEM};
+use system::syscall::{MAP_ANONYMOUS, MAP_FIXED, PROT_WRITE};
 
 //TODO: Refactor file to propogate results
 
@@ -15,7 +16,8 @@ pub fn do_sys_brk(addr: usize) -> Result<usize> {
         // TODO: Make this smarter, currently it attempt to resize the entire data segment
         if let Some(mut mem) = unsafe { (*current.heap.get()).memory.last_mut() } {
             if mem.writeable && mem.allocated {
-                if addr >= mem.virtual_address {
+                // Shared memory is copied, so that it can be resized
+                if addr >= mem.virtual_address && unsafe { mem.unshare() } {
                     unsafe { mem.unmap() };
 
                     let size = addr - mem.virtual_address;
@@ -61,3 +63,108 @@ pub fn do_sys_brk(addr: usize) -> Result<usize> {
 
     Ok(ret)
 }
+
+/** <!-- @MANSTART{sys_mmap} -->
+NAME
+    sys_mmap - map memory
+
+SYNOPSIS
+    sys_mmap(addr: usize, len: usize, prot: usize, flags: usize, fd: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_mmap maps len bytes, rounded up to whole pages, of zeroed memory into the address space of
+    the calling process, and returns the address of the mapping. The mapping is shared with
+    threads, shared copy-on-write with forked processes, and freed when the process exits or calls
+    sys_execve
+
+    addr is only a hint, and is ignored. The memory is writeable if prot contains PROT_WRITE, and
+    is otherwise read only, as PROT_NONE and PROT_EXEC are not enforced
+
+    Only MAP_ANONYMOUS mappings are supported, in which case fd is ignored
+
+RETURN VALUE
+    On success, Ok(addr) is returned. On error, Err(err) is returned where err is one of the
+    following errors
+
+ERRORS
+    EBADF
+        MAP_ANONYMOUS was not given and fd is not a valid file descriptor
+
+    EINVAL
+        len is 0, or MAP_FIXED was given
+
+    ENODEV
+        MAP_ANONYMOUS was not given, as mapping files is not supported
+
+    ENOMEM
+        No free range of addresses or physical memory is available
+<!-- @MANEND --> */
+pub fn do_sys_mmap(_addr: usize, len: usize, prot: usize, flags: usize, fd: usize) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+
+    if flags & MAP_ANONYMOUS != MAP_ANONYMOUS {
+        try!(current.get_file(fd));
+        return Err(Error::new(ENODEV));
+    }
+
+    if len == 0 || flags & MAP_FIXED == MAP_FIXED {
+        return Err(Error::new(EINVAL));
+    }
+
+    let size = (len + 4095) / 4096 * 4096;
+    let mmap = unsafe { &mut *current.mmap.get() };
+    let virtual_address = try!(mmap.next_free(size).ok_or(Error::new(ENOMEM)));
+
+    let physical_address = unsafe { memory::alloc_aligned(size, 4096) };
+    if physical_address == 0 {
+        return Err(Error::new(ENOMEM));
+    }
+
+    let mut mem = ContextMemory {
+        physical_address: physical_address,
+        virtual_address: virtual_address,
+        virtual_size: size,
+        writeable: prot & PROT_WRITE == PROT_WRITE,
+        allocated: true
+    };
+
+    unsafe {
+        mem.map();
+        mmap.memory.push(mem);
+    }
+
+    Ok(virtual_address)
+}
+
+/** <!-- @MANSTART{sys_munmap} -->
+NAME
+    sys_munmap - unmap memory
+
+SYNOPSIS
+    sys_munmap(addr: usize, len: usize) -> Result<usize>;
+
+DESCRIPTION
+    sys_munmap unmaps and frees the pages mapped by sys_mmap in the range from addr to addr + len,
+    rounded up to whole pages. Mappings that are only partially inside of the range are split, and
+    the rest of them stays mapped. It is not an error if no pages are mapped in the range
+
+RETURN VALUE
+    On success, Ok(0) is returned. On error, Err(err) is returned where err is one of the following
+    errors
+
+ERRORS
+    EINVAL
+        addr is not page aligned, or len is 0
+<!-- @MANEND --> */
+pub fn do_sys_munmap(addr: usize, len: usize) -> Result<usize> {
+    if addr % 4096 != 0 || len == 0 {
+        return Err(Error::new(EINVAL));
+    }
+
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    unsafe { (*current.mmap.get()).unmap_range(addr, len) };
+
+    Ok(0)
+}
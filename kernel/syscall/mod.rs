@@ -5,6 +5,7 @@ pub use self::debug::*;
 pub use self::file::*;
 pub use self::memory::*;
 pub use self::process::*;
+pub use self::signal::*;
 pub use self::time::*;
 
 use arch::regs::Regs;
@@ -15,7 +16,9 @@ pub mod execute;
 pub mod file;
 pub mod memory;
 pub mod process;
+pub mod signal;
 pub mod time;
+pub mod uaccess;
 
 pub fn syscall_handle(regs: &mut Regs) {
     {
@@ -46,35 +49,68 @@ pub fn syscall_handle(regs: &mut Regs) {
         SYS_SUPERVISE => do_sys_supervise(regs.bx),
 
         // Unix
+        SYS_ACCESS => do_sys_access(regs.bx as *const u8, regs.cx),
         SYS_BRK => do_sys_brk(regs.bx),
         SYS_CHDIR => do_sys_chdir(regs.bx as *const u8),
         SYS_CLONE => do_sys_clone(regs),
         SYS_CLOSE => do_sys_close(regs.bx),
+        SYS_CLOCK_GETRES => do_sys_clock_getres(regs.bx, regs.cx as *mut TimeSpec),
         SYS_CLOCK_GETTIME => do_sys_clock_gettime(regs.bx, regs.cx as *mut TimeSpec),
         SYS_DUP => do_sys_dup(regs.bx),
+        SYS_DUP2 => do_sys_dup2(regs.bx, regs.cx),
         SYS_EXECVE => do_sys_execve(regs.bx as *const u8, regs.cx as *const *const u8),
         SYS_EXIT => do_sys_exit(regs.bx),
+        SYS_FCNTL => do_sys_fcntl(regs.bx, regs.cx, regs.dx),
+        SYS_FLOCK => do_sys_flock(regs.bx, regs.cx),
+        SYS_FORK => do_sys_fork(regs),
         SYS_FPATH => do_sys_fpath(regs.bx, regs.cx as *mut u8, regs.dx),
         SYS_FSTAT => do_sys_fstat(regs.bx, regs.cx as *mut Stat),
+        SYS_FSTATVFS => do_sys_fstatvfs(regs.bx, regs.cx as *mut StatVfs),
         SYS_FSYNC => do_sys_fsync(regs.bx),
         SYS_FTRUNCATE => do_sys_ftruncate(regs.bx, regs.cx),
+        SYS_FUTIMENS => do_sys_futimens(regs.bx, regs.cx as *const TimeSpec),
+        SYS_GETCWD => do_sys_getcwd(regs.bx as *mut u8, regs.cx),
+        SYS_GETDENTS => do_sys_getdents(regs.bx, regs.cx as *mut u8, regs.dx),
         SYS_GETPID => do_sys_getpid(),
+        SYS_GETPPID => do_sys_getppid(),
         SYS_IOPL => do_sys_iopl(regs),
-        // TODO: link
+        SYS_KILL => do_sys_kill(regs.bx, regs.cx),
+        SYS_LINK => do_sys_link(regs.bx as *const u8, regs.cx as *const u8),
         SYS_LSEEK => do_sys_lseek(regs.bx, regs.cx as isize, regs.dx),
+        SYS_LSTAT => do_sys_lstat(regs.bx as *const u8, regs.cx as *mut Stat),
         SYS_MKDIR => do_sys_mkdir(regs.bx as *const u8, regs.cx),
+        SYS_MMAP => do_sys_mmap(regs.bx, regs.cx, regs.dx, regs.si, regs.di),
+        SYS_MUNMAP => do_sys_munmap(regs.bx, regs.cx),
         SYS_NANOSLEEP => do_sys_nanosleep(regs.bx as *const TimeSpec, regs.cx as *mut TimeSpec),
         SYS_OPEN => do_sys_open(regs.bx as *const u8, regs.cx),
         SYS_PIPE2 => do_sys_pipe2(regs.bx as *mut usize, regs.cx),
+        SYS_POLL => do_sys_poll(regs.bx as *mut PollFd, regs.cx, regs.dx as isize),
+        SYS_PREAD => do_sys_pread(regs.bx, regs.cx as *mut u8, regs.dx, regs.si),
+        SYS_PWRITE => do_sys_pwrite(regs.bx, regs.cx as *const u8, regs.dx, regs.si),
         SYS_READ => do_sys_read(regs.bx, regs.cx as *mut u8, regs.dx),
+        SYS_READLINK => do_sys_readlink(regs.bx as *const u8, regs.cx as *mut u8, regs.dx),
+        SYS_READV => do_sys_readv(regs.bx, regs.cx as *const IoVec, regs.dx),
+        SYS_RENAME => do_sys_rename(regs.bx as *const u8, regs.cx as *const u8),
         SYS_RMDIR => do_sys_rmdir(regs.bx as *const u8),
+        SYS_SENDFILE => do_sys_sendfile(regs.bx, regs.cx, regs.dx as *mut usize, regs.si),
+        SYS_SETPRIORITY => do_sys_setpriority(regs.bx, regs.cx as isize),
+        SYS_SIGNAL => do_sys_signal(regs.bx, regs.cx, regs.dx),
+        SYS_SIGRETURN => do_sys_sigreturn(regs),
         SYS_STAT => do_sys_stat(regs.bx as *const u8, regs.cx as *mut Stat),
+        SYS_STATVFS => do_sys_statvfs(regs.bx as *const u8, regs.cx as *mut StatVfs),
+        SYS_SYMLINK => do_sys_symlink(regs.bx as *const u8, regs.cx as *const u8),
+        SYS_TIMES => do_sys_times(regs.bx as *mut Tms),
+        SYS_TRUNCATE => do_sys_truncate(regs.bx as *const u8, regs.cx),
         SYS_UNLINK => do_sys_unlink(regs.bx as *const u8),
+        SYS_UTIMENS => do_sys_utimens(regs.bx as *const u8, regs.cx as *const TimeSpec),
         SYS_WAITPID => do_sys_waitpid(regs.bx as isize, regs.cx as *mut usize, regs.dx),
         SYS_WRITE => do_sys_write(regs.bx, regs.cx as *mut u8, regs.dx),
+        SYS_WRITEV => do_sys_writev(regs.bx, regs.cx as *const IoVec, regs.dx),
         SYS_YIELD => do_sys_yield(),
 
         _ => Err(Error::new(ENOSYS)),
     });
     //debugln!("={:X}", regs.ax);
+
+    signal_handle(regs);
 }
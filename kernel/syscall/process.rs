@@ -1,22 +1,30 @@
-use arch::context::{context_clone, context_switch, ContextFile};
+use alloc::arc::Arc;
+
+use arch::context::{context_clone, context_switch, ContextFile, ContextZone, CONTEXT_IMAGE_ADDR,
+                    CONTEXT_IMAGE_SIZE, CONTEXT_HEAP_ADDR, CONTEXT_HEAP_SIZE, CONTEXT_MMAP_ADDR,
+                    CONTEXT_MMAP_SIZE, CONTEXT_ROOT_PID, PRIO_MAX, PRIO_MIN};
 use arch::regs::Regs;
 
-use collections::{BTreeMap, Vec};
+use collections::Vec;
 use collections::string::ToString;
 
+use core::cell::UnsafeCell;
 use core::{mem, ptr};
-use core::ops::DerefMut;
+
+use sync::WaitCondition;
 
 use system::{c_array_to_slice, c_string_to_str};
 
-use system::error::{Error, Result, ECHILD, EINVAL, EACCES};
+use system::error::{Error, Result, ECHILD, EINVAL, EACCES, EPERM};
+use system::syscall::{O_RDWR, SIGCHLD, WNOHANG};
 
 use super::execute::execute;
+use super::signal::signal_send;
 
 use fs::SupervisorResource;
 
 pub fn do_sys_clone(regs: &Regs) -> Result<usize> {
-    unsafe { context_clone(regs) }
+    unsafe { context_clone(regs, regs.bx) }
 }
 
 pub fn do_sys_execve(path: *const u8, args: *const *const u8) -> Result<usize> {
@@ -29,16 +37,31 @@ pub fn do_sys_execve(path: *const u8, args: *const *const u8) -> Result<usize> {
     execute(args_vec)
 }
 
-/// Exit context
-pub fn do_sys_exit(status: usize) -> ! {
+/// Exit the current context with a wait status, which holds the exit code in the second byte,
+/// or the number of the signal that terminated the context in the first byte
+///
+/// The files and memory of the context are released, and it stays as a zombie holding the status
+/// until its parent waits for it. Its children are adopted by the root context.
+pub fn context_exit(status: usize) -> ! {
     {
         let mut contexts = ::env().contexts.lock();
 
-        let mut statuses = BTreeMap::new();
         let (pid, ppid) = {
             if let Ok(mut current) = contexts.current_mut() {
                 current.exited = true;
-                mem::swap(&mut statuses, &mut current.statuses.inner.lock().deref_mut());
+                current.status = status;
+
+                if let Some(vfork) = current.vfork.take() {
+                    unsafe { (*vfork).blocked = false; }
+                }
+
+                ::env().locks.exit(current.pid);
+                unsafe { current.unmap(); }
+                current.image = Arc::new(UnsafeCell::new(ContextZone::new(CONTEXT_IMAGE_ADDR, CONTEXT_IMAGE_SIZE)));
+                current.heap = Arc::new(UnsafeCell::new(ContextZone::new(CONTEXT_HEAP_ADDR, CONTEXT_HEAP_SIZE)));
+                current.mmap = Arc::new(UnsafeCell::new(ContextZone::new(CONTEXT_MMAP_ADDR, CONTEXT_MMAP_SIZE)));
+                current.files = Arc::new(UnsafeCell::new(Vec::new()));
+
                 (current.pid, current.ppid)
             } else {
                 (0, 0)
@@ -46,19 +69,24 @@ pub fn do_sys_exit(status: usize) -> ! {
         };
 
         for mut context in contexts.iter_mut() {
-            // Add exit status to parent
+            // Wake the parent if it is waiting
             if context.pid == ppid {
-                context.statuses.send(pid, status);
-                for (pid, status) in statuses.iter() {
-                    context.statuses.send(*pid, *status);
-                }
+                unsafe { context.child_exit.notify(); }
             }
 
-            // Move children to parent
+            // Move children to the root context
             if context.ppid == pid {
-                context.ppid = ppid;
+                context.ppid = CONTEXT_ROOT_PID;
             }
         }
+
+        let _ = signal_send(ppid, SIGCHLD);
+
+        // The parent returns to the foreground
+        let mut console = ::env().console.lock();
+        if console.foreground_pid == pid {
+            console.foreground_pid = ppid;
+        }
     }
 
     loop {
@@ -68,12 +96,30 @@ pub fn do_sys_exit(status: usize) -> ! {
     }
 }
 
+/// Exit context
+pub fn do_sys_exit(code: usize) -> ! {
+    context_exit((code & 0xFF) << 8)
+}
+
+/// Create a child process, with a copy of the file descriptors, and memory that is shared with
+/// the parent until one of them writes to it. Returns the pid of the child to the parent, and 0
+/// to the child
+pub fn do_sys_fork(regs: &Regs) -> Result<usize> {
+    unsafe { context_clone(regs, 0) }
+}
+
 pub fn do_sys_getpid() -> Result<usize> {
     let contexts = ::env().contexts.lock();
     let current = try!(contexts.current());
     Ok(current.pid)
 }
 
+pub fn do_sys_getppid() -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    Ok(current.ppid)
+}
+
 #[cfg(target_arch = "x86")]
 pub fn do_sys_iopl(regs: &mut Regs) -> Result<usize> {
     let level = regs.bx;
@@ -108,24 +154,93 @@ pub fn do_sys_iopl(regs: &mut Regs) -> Result<usize> {
     }
 }
 
-//TODO: Finish implementation, add more functions to WaitMap so that matching any or using WNOHANG works
-pub fn do_sys_waitpid(pid: isize, status_ptr: *mut usize, _options: usize) -> Result<usize> {
-    let mut contexts = ::env().contexts.lock();
-    let current = try!(contexts.current_mut());
+/// Wait for a child to exit, and write its wait status to status_ptr. A pid of 0 or -1 waits for
+/// any child, as there are no process groups. Returns the pid of the child, which is removed, or 0
+/// if `WNOHANG` is set and no child has exited yet
+pub fn do_sys_waitpid(pid: isize, status_ptr: *mut usize, options: usize) -> Result<usize> {
+    if pid < -1 {
+        return Err(Error::new(ECHILD));
+    }
 
-    if pid > 0 {
-        let status = current.statuses.receive(&(pid as usize));
+    loop {
+        let child_exit = {
+            let mut contexts = ::env().contexts.lock();
 
-        if status_ptr as usize > 0 {
-            unsafe {
-                ptr::write(status_ptr, status);
+            let (ppid, child_exit) = {
+                let current = try!(contexts.current());
+                if ! status_ptr.is_null() {
+                    try!(current.validate_writeable(status_ptr as usize, mem::size_of::<usize>()));
+                }
+                (current.pid, &current.child_exit as *const WaitCondition)
+            };
+
+            let mut children = 0;
+            let mut exited = None;
+            for child in contexts.iter() {
+                if child.ppid == ppid && (pid <= 0 || child.pid == pid as usize) {
+                    children += 1;
+                    if child.exited {
+                        exited = Some((child.pid, child.status, child.time + child.child_time,
+                                       child.user_time + child.child_user_time));
+                        break;
+                    }
+                }
             }
-        }
 
-        Ok(pid as usize)
-    } else {
-        Err(Error::new(ECHILD))
+            if children == 0 {
+                return Err(Error::new(ECHILD));
+            }
+
+            if let Some((child_pid, status, time, user_time)) = exited {
+                unsafe { drop(contexts.remove(child_pid)); }
+
+                if let Ok(mut current) = contexts.current_mut() {
+                    current.child_time += time;
+                    current.child_user_time += user_time;
+                }
+
+                if ! status_ptr.is_null() {
+                    unsafe { ptr::write(status_ptr, status); }
+                }
+
+                return Ok(child_pid);
+            }
+
+            if options & WNOHANG == WNOHANG {
+                return Ok(0);
+            }
+
+            child_exit
+        };
+
+        unsafe { (*child_exit).wait(); }
+    }
+}
+
+/// Set the priority of the current context or one of its children, or of the current context if
+/// `pid` is 0. Only the kernel and the contexts it started, like init, may raise a priority; the
+/// others may only lower it
+pub fn do_sys_setpriority(pid: usize, priority: isize) -> Result<usize> {
+    if priority < PRIO_MIN || priority > PRIO_MAX {
+        return Err(Error::new(EINVAL));
     }
+
+    let mut contexts = ::env().contexts.lock();
+    let (current_pid, privileged) = {
+        let current = try!(contexts.current());
+        (current.pid, current.pid == CONTEXT_ROOT_PID || current.ppid == CONTEXT_ROOT_PID)
+    };
+
+    let mut context = try!(contexts.find_mut(if pid == 0 { current_pid } else { pid }));
+    if context.pid != current_pid && context.ppid != current_pid {
+        return Err(Error::new(EPERM));
+    }
+    if priority > context.priority && ! privileged {
+        return Err(Error::new(EPERM));
+    }
+    context.priority = priority;
+
+    Ok(0)
 }
 
 pub fn do_sys_yield() -> Result<usize> {
@@ -171,6 +286,8 @@ pub fn do_sys_supervise(pid: usize) -> Result<usize> {
     unsafe {
         (*current.files.get()).push(ContextFile {
             fd: fd,
+            flags: O_RDWR,
+            cloexec: false,
             resource: box try!(SupervisorResource::new(procc)),
         });
     }
@@ -0,0 +1,205 @@
+use arch::context::{Context, SignalHandler};
+use arch::regs::Regs;
+
+use core::{mem, ptr};
+use core::ops::DerefMut;
+
+use super::context_exit;
+
+use system::error::{Error, Result, EINVAL, EPERM, ESRCH};
+use system::syscall::{NSIG, SIGCHLD, SIGKILL, SIG_DFL, SIG_IGN};
+
+/// Check if a signal is ignored by a context. Signals without a handler terminate the context,
+/// except for SIGCHLD, which is ignored
+fn signal_ignored(context: &Context, sig: usize) -> bool {
+    match context.signal_handler(sig) {
+        Some(handler) => handler.handler == SIG_IGN,
+        None => sig == SIGCHLD,
+    }
+}
+
+/// Send a signal to a context. A signal of 0 only checks that the context exists and can be
+/// signalled
+pub fn do_sys_kill(pid: usize, sig: usize) -> Result<usize> {
+    try!(signal_send(pid, sig));
+    Ok(0)
+}
+
+/// Set the handler of a signal, returning the previous one. SIGKILL can not be caught or ignored
+pub fn do_sys_signal(sig: usize, handler: usize, restorer: usize) -> Result<usize> {
+    if sig == 0 || sig >= NSIG || sig == SIGKILL {
+        return Err(Error::new(EINVAL));
+    }
+
+    let mut contexts = ::env().contexts.lock();
+    let mut current = try!(contexts.current_mut());
+
+    let previous = {
+        let handlers = unsafe { &mut *current.signal_handlers.get() };
+        if handler == SIG_DFL {
+            handlers.remove(&sig)
+        } else {
+            handlers.insert(sig, SignalHandler {
+                handler: handler,
+                restorer: restorer,
+            })
+        }
+    };
+
+    if handler == SIG_IGN {
+        current.signals &= !(1 << sig);
+    }
+
+    Ok(previous.map_or(SIG_DFL, |previous| previous.handler))
+}
+
+/// Return from a signal handler, restoring the registers of the code it interrupted
+pub fn do_sys_sigreturn(regs: &mut Regs) -> Result<usize> {
+    let mut contexts = ::env().contexts.lock();
+    let mut current = try!(contexts.current_mut());
+
+    match current.signal_regs.pop() {
+        Some(saved) => {
+            *regs = saved;
+            Ok(regs.ax)
+        },
+        None => Err(Error::new(EINVAL)),
+    }
+}
+
+/// Make a signal pending in a context, and wake the context if it is blocked so that it can act
+/// on it. Ignored signals are discarded. Kernel contexts, which have no user stack to run a
+/// handler on, can not be signalled
+pub fn signal_send(pid: usize, sig: usize) -> Result<()> {
+    if sig >= NSIG {
+        return Err(Error::new(EINVAL));
+    }
+
+    let mut contexts = ::env().contexts.lock();
+
+    let context_ptr = match contexts.iter_mut().find(|context| context.pid == pid) {
+        Some(context) => context.deref_mut() as *mut Context,
+        None => return Err(Error::new(ESRCH)),
+    };
+
+    if unsafe { (*context_ptr).stack.is_none() } {
+        return Err(Error::new(EPERM));
+    }
+
+    // A parent waiting for a vfork child stays blocked, as the child is running on its memory
+    let vforked = contexts.iter().any(|context| context.vfork == Some(context_ptr));
+
+    let context = unsafe { &mut *context_ptr };
+    if sig > 0 && ! signal_ignored(context, sig) {
+        context.signals |= 1 << sig;
+        if context.blocked && ! context.blocked_syscall && ! vforked {
+            context.blocked = false;
+            context.wake = None;
+        }
+    }
+
+    Ok(())
+}
+
+/// Act on a pending signal of the current context when it returns to userspace
+///
+/// A signal with a handler calls it on the user stack, with the signal number as argument and the
+/// restorer as return address. A signal with the default disposition terminates the context, as
+/// does a handler that does not fit on the stack.
+pub fn signal_handle(regs: &mut Regs) {
+    if regs.cs & 3 != 3 {
+        return;
+    }
+
+    let sig = {
+        let mut contexts = ::env().contexts.lock();
+        let mut current = match contexts.current_mut() {
+            Ok(current) => current,
+            Err(_) => return,
+        };
+
+        if current.signals == 0 {
+            return;
+        }
+
+        let sig = current.signals.trailing_zeros() as usize;
+        current.signals &= !(1 << sig);
+
+        if signal_ignored(current, sig) {
+            return;
+        }
+
+        if let Some(handler) = current.signal_handler(sig) {
+            let size = mem::size_of::<usize>();
+            let sp = regs.sp - 2 * size;
+            if current.validate_writeable(sp, 2 * size).is_ok() {
+                current.signal_regs.push(*regs);
+                unsafe {
+                    ptr::write((sp + size) as *mut usize, sig);
+                    ptr::write(sp as *mut usize, handler.restorer);
+                }
+
+                regs.sp = sp;
+                regs.ip = handler.handler;
+                regs.di = sig;
+                return;
+            }
+        }
+
+        sig
+    };
+
+    context_exit(sig);
+}
+
+/// Deliver a signal for a fault in the userspace code of the current context
+///
+/// Returning would run the faulting instruction again, so the signal terminates the context unless
+/// it has a handler, even if it is ignored.
+pub fn signal_fault(regs: &mut Regs, sig: usize) {
+    let handled = {
+        let mut contexts = ::env().contexts.lock();
+        match contexts.current_mut() {
+            Ok(current) => match current.signal_handler(sig) {
+                Some(handler) if handler.handler != SIG_IGN => {
+                    current.signals |= 1 << sig;
+                    true
+                },
+                _ => false,
+            },
+            Err(_) => false,
+        }
+    };
+
+    if handled {
+        signal_handle(regs);
+    } else {
+        context_exit(sig);
+    }
+}
+
+/// Get the pending signal of the current context that terminates it, if any
+pub fn signal_terminating() -> Option<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = match contexts.current() {
+        Ok(current) => current,
+        Err(_) => return None,
+    };
+
+    let terminates = |sig: usize| {
+        current.signals & (1 << sig) != 0 && current.signal_handler(sig).is_none()
+            && ! signal_ignored(current, sig)
+    };
+
+    (1..NSIG).find(|&sig| terminates(sig))
+}
+
+/// Exit the current context if it has a pending signal that terminates it
+///
+/// This is checked by blocking kernel code when the context is woken, as it would otherwise block
+/// again before returning to userspace, where signals are acted on.
+pub fn signal_exit() {
+    if let Some(sig) = signal_terminating() {
+        context_exit(sig);
+    }
+}
@@ -2,9 +2,11 @@ use arch::context::context_switch;
 
 use common::time::Duration;
 
-use syscall::{CLOCK_MONOTONIC, CLOCK_REALTIME, TimeSpec};
+use core::mem;
 
-use system::error::{Error, Result, EFAULT, EINVAL};
+use syscall::{CLOCK_MONOTONIC, CLOCK_REALTIME, TimeSpec, Tms};
+
+use system::error::{Error, Result, EFAULT, EINTR, EINVAL};
 
 pub fn do_sys_clock_gettime(clock: usize, tp: *mut TimeSpec) -> Result<usize> {
     if tp as usize > 0 {
@@ -18,7 +20,7 @@ pub fn do_sys_clock_gettime(clock: usize, tp: *mut TimeSpec) -> Result<usize> {
                 Ok(0)
             }
             CLOCK_MONOTONIC => {
-                let clock_monotonic = ::env().clock_monotonic.lock();
+                let clock_monotonic = Duration::monotonic();
                 unsafe {
                     (*tp).tv_sec = clock_monotonic.secs;
                     (*tp).tv_nsec = clock_monotonic.nanos;
@@ -32,29 +34,126 @@ pub fn do_sys_clock_gettime(clock: usize, tp: *mut TimeSpec) -> Result<usize> {
     }
 }
 
+/// Get the resolution of a clock. The real time is advanced by the PIT, and so is the monotonic
+/// time unless the HPET is present
+pub fn do_sys_clock_getres(clock: usize, res: *mut TimeSpec) -> Result<usize> {
+    if res as usize > 0 {
+        let resolution = match clock {
+            CLOCK_REALTIME => ::env().pit.lock().resolution(),
+            CLOCK_MONOTONIC => match ::env().hpet {
+                Some(ref hpet) => hpet.resolution(),
+                None => ::env().pit.lock().resolution(),
+            },
+            _ => return Err(Error::new(EINVAL)),
+        };
+
+        unsafe {
+            (*res).tv_sec = resolution.secs;
+            (*res).tv_nsec = resolution.nanos;
+        }
+        Ok(0)
+    } else {
+        Err(Error::new(EFAULT))
+    }
+}
+
+/// Sleep until the requested time has passed. The timer interrupt wakes the context when it is
+/// due. If it is woken earlier by a signal, the remaining time is written to rem, if it is not
+/// null, and `EINTR` is returned. A zero duration only yields to the other contexts
 pub fn do_sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec) -> Result<usize> {
-    if req as usize > 0 {
+    let wake = {
         let mut contexts = ::env().contexts.lock();
-        let mut context = try!(contexts.current_mut());
+        let mut current = try!(contexts.current_mut());
+
+        try!(current.validate(req as usize, mem::size_of::<TimeSpec>()));
+        if ! rem.is_null() {
+            try!(current.validate_writeable(rem as usize, mem::size_of::<TimeSpec>()));
+        }
+
+        let req = unsafe { *req };
+        if req.tv_sec < 0 || req.tv_nsec < 0 || req.tv_nsec >= 1000000000 {
+            return Err(Error::new(EINVAL));
+        }
+
+        if req.tv_sec == 0 && req.tv_nsec == 0 {
+            None
+        } else {
+            // Without the HPET, the monotonic clock is only advanced on each tick, so it may be
+            // up to a tick behind. Sleeping a tick longer keeps even a sleep shorter than a tick
+            // from returning early
+            let requested = Duration::new(req.tv_sec, req.tv_nsec);
+            let mut wake = Duration::monotonic() + requested;
+            if ::env().hpet.is_none() {
+                wake = wake + ::env().pit.lock().resolution();
+            }
+            current.blocked = true;
+            current.wake = Some(wake);
+            Some((wake, requested))
+        }
+    };
 
-        context.blocked = true;
-        context.wake = Some(
-            Duration::monotonic() + Duration::new(unsafe { (*req).tv_sec }, unsafe { (*req).tv_nsec })
-        );
+    let (wake, requested) = match wake {
+        Some(wake) => wake,
+        None => {
+            unsafe { context_switch(); }
+            return Ok(0);
+        }
+    };
 
+    loop {
+        let blocked = match ::env().contexts.lock().current() {
+            Ok(current) => current.blocked,
+            Err(_) => false,
+        };
+        if ! blocked {
+            break;
+        }
         unsafe { context_switch(); }
+    }
 
-        if rem as usize > 0 {
-            unsafe {
-                (*rem).tv_sec = 0;
+    let now = Duration::monotonic();
+    if now < wake {
+        if ! rem.is_null() {
+            let mut remaining = wake - now;
+            if remaining > requested {
+                remaining = requested;
             }
             unsafe {
-                (*rem).tv_nsec = 0;
+                (*rem).tv_sec = remaining.secs;
+                (*rem).tv_nsec = remaining.nanos;
             }
         }
 
-        Ok(0)
+        Err(Error::new(EINTR))
     } else {
-        Err(Error::new(EFAULT))
+        Ok(0)
     }
 }
+
+/// Get the CPU time used by the current context, and by its children that were waited for. Time
+/// slices are converted to durations using the period of the PIT
+pub fn do_sys_times(buf: *mut Tms) -> Result<usize> {
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    try!(current.validate_writeable(buf as usize, mem::size_of::<Tms>()));
+
+    let pit = ::env().pit.lock();
+    let timespec = |ticks: usize| {
+        let duration = pit.duration(ticks as u64);
+        TimeSpec {
+            tv_sec: duration.secs,
+            tv_nsec: duration.nanos,
+        }
+    };
+
+    unsafe {
+        *buf = Tms {
+            tms_utime: timespec(current.user_time),
+            tms_stime: timespec(current.time - current.user_time),
+            tms_cutime: timespec(current.child_user_time),
+            tms_cstime: timespec(current.child_time - current.child_user_time),
+        };
+    }
+
+    Ok(0)
+}
@@ -0,0 +1,55 @@
+//! Copying between the kernel and the memory of the current context
+//!
+//! Syscalls use these instead of dereferencing pointers from userspace, so that a pointer outside
+//! of the memory of the context returns `EFAULT` instead of accessing kernel memory.
+
+use collections::Vec;
+
+use core::{mem, ptr, slice};
+
+use system::error::{Error, Result, EFAULT};
+
+/// Check that `count` values of `T` at `ptr` are inside of the memory of the current context, and
+/// if they are to be written, that the memory is writeable
+fn validate<T>(ptr: usize, count: usize, write: bool) -> Result<()> {
+    let len = try!(count.checked_mul(mem::size_of::<T>()).ok_or(Error::new(EFAULT)));
+    let contexts = ::env().contexts.lock();
+    let current = try!(contexts.current());
+    if write {
+        current.validate_writeable(ptr, len)
+    } else {
+        current.validate(ptr, len)
+    }
+}
+
+/// Copy a value from userspace
+pub fn copy_from_user<T: Copy>(src: *const T) -> Result<T> {
+    try!(validate::<T>(src as usize, 1, false));
+    Ok(unsafe { ptr::read(src) })
+}
+
+/// Copy a value to userspace
+pub fn copy_to_user<T: Copy>(dst: *mut T, value: T) -> Result<()> {
+    try!(validate::<T>(dst as usize, 1, true));
+    unsafe { ptr::write(dst, value) };
+    Ok(())
+}
+
+/// Copy `count` values from userspace
+pub fn copy_slice_from_user<T: Copy>(src: *const T, count: usize) -> Result<Vec<T>> {
+    try!(validate::<T>(src as usize, count, false));
+    if count > 0 {
+        Ok(unsafe { slice::from_raw_parts(src, count) }.to_vec())
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Copy a slice of values to userspace
+pub fn copy_slice_to_user<T: Copy>(dst: *mut T, values: &[T]) -> Result<()> {
+    try!(validate::<T>(dst as usize, values.len(), true));
+    if ! values.is_empty() {
+        unsafe { ptr::copy(values.as_ptr(), dst, values.len()) };
+    }
+    Ok(())
+}
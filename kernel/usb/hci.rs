@@ -157,6 +157,9 @@ pub trait Hci {
                                                 left_button: buttons & 1 == 1,
                                                 middle_button: buttons & 4 == 4,
                                                 right_button: buttons & 2 == 2,
+                                                fourth_button: false,
+                                                fifth_button: false,
+                                                wheel: 0,
                                             };
 
                                             if ::env().console.lock().draw {
@@ -5,7 +5,7 @@ use alloc::boxed::Box;
 use core_collections::borrow::ToOwned;
 
 use ffi::{OsString, OsStr};
-use fs::{self, File};
+use fs::{self, File, OpenOptions};
 use path::{Path, PathBuf};
 use string::{String, ToString};
 use sys_common::AsInner;
@@ -14,7 +14,7 @@ use error;
 use fmt;
 
 use system::error::ENOENT;
-use system::syscall::sys_chdir;
+use system::syscall::{sys_chdir, sys_getcwd};
 
 use io::{Error, Result, Read, Write};
 
@@ -85,8 +85,11 @@ fn get_path_from(location : &str) -> Result<PathBuf> {
 
 /// Method to return the current directory
 pub fn current_dir() -> Result<PathBuf> {
-    // Return the current path
-    get_path_from("./")
+    let mut buf = [0; 4096];
+    match sys_getcwd(&mut buf) {
+        Ok(count) => Ok(PathBuf::from(unsafe { String::from_utf8_unchecked(Vec::from(&buf[0..count])) })),
+        Err(err) => Err(Error::from_sys(err)),
+    }
 }
 
 /// Method to return the home directory
@@ -184,7 +187,7 @@ pub fn var_os<K: AsRef<OsStr>>(key: K) -> Option<OsString> {
 /// Sets the environment variable `key` to the value `value` for the current process
 pub fn set_var<K: AsRef<OsStr>, V: AsRef<OsStr>>(key: K, value: V) {
     if let (Some(key_str), Some(value_str)) = (key.as_ref().to_str(), value.as_ref().to_str()) {
-        if let Ok(mut file) = File::open(&("env:".to_owned() + key_str)) {
+        if let Ok(mut file) = OpenOptions::new().write(true).open(&("env:".to_owned() + key_str)) {
             let _ = file.write_all(value_str.as_bytes());
         }
     }
@@ -9,7 +9,7 @@ use core_collections::borrow::ToOwned;
 use vec::Vec;
 
 use io::Error;
-use system::syscall::{sys_clone, sys_close, sys_dup, sys_execve, sys_exit, sys_pipe2, sys_read, sys_write, sys_waitpid, CLONE_VM, CLONE_VFORK, CLONE_SUPERVISE};
+use system::syscall::{sys_clone, sys_close, sys_dup2, sys_execve, sys_exit, sys_pipe2, sys_read, sys_write, sys_waitpid, CLONE_VM, CLONE_VFORK, CLONE_SUPERVISE};
 use system::error::Error as SysError;
 
 pub struct ExitStatus {
@@ -185,14 +185,16 @@ impl Command {
             match child_stderr {
                 StdioType::Piped(read, write) => {
                     try!(sys_close(read).map_err(|x| Error::from_sys(x)));
-                    try!(sys_close(2).map_err(|x| Error::from_sys(x)));
-                    try!(sys_dup(write).map_err(|x| Error::from_sys(x)));
-                    try!(sys_close(write).map_err(|x| Error::from_sys(x)));
+                    try!(sys_dup2(write, 2).map_err(|x| Error::from_sys(x)));
+                    if write != 2 {
+                        try!(sys_close(write).map_err(|x| Error::from_sys(x)));
+                    }
                 },
                 StdioType::Raw(fd) => {
-                    try!(sys_close(2).map_err(|x| Error::from_sys(x)));
-                    try!(sys_dup(fd).map_err(|x| Error::from_sys(x)));
-                    try!(sys_close(fd).map_err(|x| Error::from_sys(x)));
+                    try!(sys_dup2(fd, 2).map_err(|x| Error::from_sys(x)));
+                    if fd != 2 {
+                        try!(sys_close(fd).map_err(|x| Error::from_sys(x)));
+                    }
                 },
                 StdioType::Null => {
                     try!(sys_close(2).map_err(|x| Error::from_sys(x)));
@@ -203,14 +205,16 @@ impl Command {
             match child_stdout {
                 StdioType::Piped(read, write) => {
                     try!(sys_close(read).map_err(|x| Error::from_sys(x)));
-                    try!(sys_close(1).map_err(|x| Error::from_sys(x)));
-                    try!(sys_dup(write).map_err(|x| Error::from_sys(x)));
-                    try!(sys_close(write).map_err(|x| Error::from_sys(x)));
+                    try!(sys_dup2(write, 1).map_err(|x| Error::from_sys(x)));
+                    if write != 1 {
+                        try!(sys_close(write).map_err(|x| Error::from_sys(x)));
+                    }
                 },
                 StdioType::Raw(fd) => {
-                    try!(sys_close(1).map_err(|x| Error::from_sys(x)));
-                    try!(sys_dup(fd).map_err(|x| Error::from_sys(x)));
-                    try!(sys_close(fd).map_err(|x| Error::from_sys(x)));
+                    try!(sys_dup2(fd, 1).map_err(|x| Error::from_sys(x)));
+                    if fd != 1 {
+                        try!(sys_close(fd).map_err(|x| Error::from_sys(x)));
+                    }
                 },
                 StdioType::Null => {
                     try!(sys_close(1).map_err(|x| Error::from_sys(x)));
@@ -221,14 +225,16 @@ impl Command {
             match child_stdin {
                 StdioType::Piped(read, write) => {
                     try!(sys_close(write).map_err(|x| Error::from_sys(x)));
-                    try!(sys_close(0).map_err(|x| Error::from_sys(x)));
-                    try!(sys_dup(read).map_err(|x| Error::from_sys(x)));
-                    try!(sys_close(read).map_err(|x| Error::from_sys(x)));
+                    try!(sys_dup2(read, 0).map_err(|x| Error::from_sys(x)));
+                    if read != 0 {
+                        try!(sys_close(read).map_err(|x| Error::from_sys(x)));
+                    }
                 },
                 StdioType::Raw(fd) => {
-                    try!(sys_close(0).map_err(|x| Error::from_sys(x)));
-                    try!(sys_dup(fd).map_err(|x| Error::from_sys(x)));
-                    try!(sys_close(fd).map_err(|x| Error::from_sys(x)));
+                    try!(sys_dup2(fd, 0).map_err(|x| Error::from_sys(x)));
+                    if fd != 0 {
+                        try!(sys_close(fd).map_err(|x| Error::from_sys(x)));
+                    }
                 },
                 StdioType::Null => {
                     try!(sys_close(0).map_err(|x| Error::from_sys(x)));
@@ -1,5 +1,5 @@
 use cell::UnsafeCell;
-use fs::File;
+use fs::{File, OpenOptions};
 use io::{Error, ErrorKind, Result, Read, Write};
 use iter::Iterator;
 use net::{SocketAddr, Shutdown};
@@ -26,7 +26,7 @@ impl TcpStream {
     pub fn connect(addr: &SocketAddr) -> Result<TcpStream> {
         let path = format!("tcp:{}", addr);
         println!("Open {}", path);
-        Ok(TcpStream(UnsafeCell::new(try!(File::open(path)))))
+        Ok(TcpStream(UnsafeCell::new(try!(OpenOptions::new().read(true).write(true).open(path)))))
     }
 
     pub fn duplicate(&self) -> Result<TcpStream> {
@@ -166,7 +166,7 @@ impl UdpSocket {
     pub fn bind(addr: &SocketAddr) -> Result<UdpSocket> {
         let path = format!("udp:{}", addr);
         println!("Open {}", path);
-        Ok(UdpSocket(UnsafeCell::new(try!(File::open(path)))))
+        Ok(UdpSocket(UnsafeCell::new(try!(OpenOptions::new().read(true).write(true).open(path)))))
     }
 
     pub fn duplicate(&self) -> Result<UdpSocket> {